@@ -74,6 +74,39 @@ macro_rules! impl_dot_cli_option {
     };
 }
 
+/// Define a new MSBuild property that is passed as `-p:Name=true` or
+/// `-p:Name=false`.
+macro_rules! impl_dot_cli_property {
+    ($name:ident, $property:literal) => {
+        impl DotNetCommandLineOption for $name {
+            fn value(&self) -> &str {
+                if self.0 {
+                    "true"
+                } else {
+                    "false"
+                }
+            }
+            fn flag() -> &'static str {
+                "-p"
+            }
+            fn args(&self) -> [Option<&str>; 2] {
+                let arg = if self.0 {
+                    concat!("-p:", $property, "=true")
+                } else {
+                    concat!("-p:", $property, "=false")
+                };
+                [Some(arg), None]
+            }
+        }
+
+        impl From<bool> for $name {
+            fn from(value: bool) -> Self {
+                Self(value)
+            }
+        }
+    };
+}
+
 /// Define a new subcommand for `dotnet`.
 macro_rules! define_command {
     ($dest_name:ident From() $value_name:ident => $value:expr ) => {};
@@ -114,6 +147,9 @@ macro_rules! define_command {
             fn get_args<'a, R>(&'a self, f: impl FnOnce(&mut dyn Iterator<Item = &'a str>) -> R) -> R {
                 f(&mut self.args_iter())
             }
+            fn supports_flag(flag: &str) -> bool {
+                [$( <$field_type as DotNetCommandLineOption>::flag() ),*].contains(&flag)
+            }
         }
         $(
             impl AsMut<Option<$field_type>> for $name {
@@ -25,7 +25,7 @@ use std::convert::AsMut;
 use std::iter;
 use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Child, Command, ExitStatus};
 
 #[macro_use]
 mod helper_macros;
@@ -33,6 +33,11 @@ mod helper_macros;
 pub trait DotNetCommandLineOption {
     fn value(&self) -> &str;
     fn flag() -> &'static str;
+    /// The arguments that are passed to `dotnet` for this option. Defaults to
+    /// the flag followed by the value.
+    fn args(&self) -> [Option<&str>; 2] {
+        [Some(Self::flag()), Some(self.value())]
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -207,50 +212,38 @@ impl From<bool> for DotNetSelfContained {
     }
 }
 
+/// Bundle the app and its dependencies into a single executable when
+/// publishing. Requires a [`DotNetRuntimeIdentifier`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DotNetPublishSingleFile(pub bool);
+impl_dot_cli_property!(DotNetPublishSingleFile, "PublishSingleFile");
+
+/// Remove unused code from the app and its dependencies when publishing. Only
+/// supported for self contained apps, see [`DotNetSelfContained`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DotNetPublishTrimmed(pub bool);
+impl_dot_cli_property!(DotNetPublishTrimmed, "PublishTrimmed");
+
 /// The directory to restore packages to.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DotNetRestorePackagesDir(pub Cow<'static, str>);
 impl_dot_cli_option!(DotNetRestorePackagesDir, "--packages");
 
-fn create_arg_iter<'a>(
-    command: &'a str,
-    mut value: Option<Cow<'a, str>>,
-) -> impl Iterator<Item = Cow<'a, str>> {
-    let mut counter = 0;
-    iter::from_fn(move || {
-        if value.is_none() {
-            None
-        } else {
-            let value = match counter {
-                0 => Some(Cow::from(command)),
-                1 => value.take(),
-                _ => return None,
-            };
-            counter += 1;
-            value
-        }
-    })
-}
-
 fn create_arg_iter_from_cli_option<O>(option: Option<&O>) -> impl Iterator<Item = &str>
 where
     O: DotNetCommandLineOption,
 {
-    create_arg_iter(
-        O::flag(),
-        option.map(DotNetCommandLineOption::value).map(Cow::from),
-    )
-    .filter_map(|v| {
-        if let Cow::Borrowed(v) = v {
-            Some(v)
-        } else {
-            None
-        }
-    })
+    option
+        .into_iter()
+        .flat_map(DotNetCommandLineOption::args)
+        .flatten()
 }
 
 pub trait DotNetCommand {
     fn get_args<'a, R>(&'a self, f: impl FnOnce(&mut dyn Iterator<Item = &'a str>) -> R) -> R;
+    /// `true` if the command accepts the option with the specified
+    /// [`flag`](DotNetCommandLineOption::flag).
+    fn supports_flag(flag: &str) -> bool;
 }
 
 define_command!(
@@ -264,6 +257,8 @@ define_command!(
         output: DotNetOutput,
         artifacts_dir: DotNetArtifactsDir,
         verbosity: DotNetVerbosity,
+        single_file: DotNetPublishSingleFile,
+        trimmed: DotNetPublishTrimmed,
     },
     From(Build, Restore, Clean)
 );
@@ -314,12 +309,20 @@ define_command!(
 pub struct DotNetInvoker<C> {
     command_data: C,
     project_path: Option<PathBuf>,
+    /// One command is created for each of these, see
+    /// [`DotNetInvoker::runtimes`].
+    runtimes: Vec<DotNetRuntimeIdentifier>,
+    /// One command is created for each of these, see
+    /// [`DotNetInvoker::frameworks`].
+    frameworks: Vec<DotNetFrameWork>,
 }
 impl DotNetInvoker<()> {
     pub fn new() -> Self {
         Self {
             command_data: (),
             project_path: None,
+            runtimes: Vec::new(),
+            frameworks: Vec::new(),
         }
     }
 
@@ -327,24 +330,32 @@ impl DotNetInvoker<()> {
         DotNetInvoker {
             command_data: Default::default(),
             project_path: self.project_path,
+            runtimes: self.runtimes,
+            frameworks: self.frameworks,
         }
     }
     pub fn build(self) -> DotNetInvoker<Build> {
         DotNetInvoker {
             command_data: Default::default(),
             project_path: self.project_path,
+            runtimes: self.runtimes,
+            frameworks: self.frameworks,
         }
     }
     pub fn restore(self) -> DotNetInvoker<Restore> {
         DotNetInvoker {
             command_data: Default::default(),
             project_path: self.project_path,
+            runtimes: self.runtimes,
+            frameworks: self.frameworks,
         }
     }
     pub fn clean(self) -> DotNetInvoker<Clean> {
         DotNetInvoker {
             command_data: Default::default(),
             project_path: self.project_path,
+            runtimes: self.runtimes,
+            frameworks: self.frameworks,
         }
     }
 }
@@ -367,29 +378,136 @@ impl<C> DotNetInvoker<C> {
         DotNetInvoker {
             command_data: self.command_data.into(),
             project_path: self.project_path,
+            runtimes: self.runtimes,
+            frameworks: self.frameworks,
         }
     }
 }
 impl<C> DotNetInvoker<C>
+where
+    C: AsMut<Option<DotNetRuntimeIdentifier>>,
+{
+    /// Create one command for each of these runtimes when using
+    /// [`get_commands`](Self::get_commands) or
+    /// [`invoke_all`](Self::invoke_all), since `dotnet` only accepts a single
+    /// runtime per invocation. Takes precedence over
+    /// [`runtime`](Self::runtime).
+    pub fn runtimes<I>(mut self, runtimes: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<DotNetRuntimeIdentifier>,
+    {
+        self.runtimes = runtimes.into_iter().map(Into::into).collect();
+        self
+    }
+}
+impl<C> DotNetInvoker<C>
+where
+    C: AsMut<Option<DotNetFrameWork>>,
+{
+    /// Create one command for each of these target frameworks when using
+    /// [`get_commands`](Self::get_commands) or
+    /// [`invoke_all`](Self::invoke_all). Combined with
+    /// [`runtimes`](Self::runtimes) a command is created for every pair of
+    /// framework and runtime. Takes precedence over
+    /// [`framework`](Self::framework).
+    pub fn frameworks<I>(mut self, frameworks: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<DotNetFrameWork>,
+    {
+        self.frameworks = frameworks.into_iter().map(Into::into).collect();
+        self
+    }
+}
+impl<C> DotNetInvoker<C>
 where
     C: DotNetCommand,
 {
+    /// The command for a single `dotnet` invocation. Ignores the lists from
+    /// [`runtimes`](Self::runtimes) and [`frameworks`](Self::frameworks), use
+    /// [`get_commands`](Self::get_commands) to handle those.
     pub fn get_command(&self) -> Command {
+        self.command_with(None, None)
+    }
+    /// One command for each runtime and framework combination, see
+    /// [`runtimes`](Self::runtimes) and [`frameworks`](Self::frameworks).
+    /// Returns a single command if neither list was specified.
+    ///
+    /// Lists are ignored if the command doesn't support that option, for
+    /// example `restore` doesn't have a framework option.
+    pub fn get_commands(&self) -> Vec<Command> {
+        fn or_none<C: DotNetCommand, T: DotNetCommandLineOption>(values: &[T]) -> Vec<Option<&T>> {
+            if values.is_empty() || !C::supports_flag(T::flag()) {
+                vec![None]
+            } else {
+                values.iter().map(Some).collect()
+            }
+        }
+        let runtimes = or_none::<C, _>(&self.runtimes);
+        or_none::<C, _>(&self.frameworks)
+            .into_iter()
+            .flat_map(|framework| {
+                runtimes
+                    .iter()
+                    .map(move |&runtime| self.command_with(runtime, framework))
+            })
+            .collect()
+    }
+    /// Create a command where the runtime and framework options are replaced
+    /// by the specified values.
+    fn command_with(
+        &self,
+        runtime: Option<&DotNetRuntimeIdentifier>,
+        framework: Option<&DotNetFrameWork>,
+    ) -> Command {
         let mut command = Command::new("dotnet");
         command.stdout(std::io::stderr());
         if let Some(path) = self.project_path.as_ref() {
             // Start with the project's path as the current working directory:
             command.current_dir(path);
         }
+        let replaced = [
+            runtime.map(|_| DotNetRuntimeIdentifier::flag()),
+            framework.map(|_| DotNetFrameWork::flag()),
+        ];
         self.command_data.get_args(|args| {
             // Apply command's arguments:
-            command.args(args);
+            while let Some(arg) = args.next() {
+                if replaced.contains(&Some(arg)) {
+                    // Skip the value as well:
+                    args.next();
+                } else {
+                    command.arg(arg);
+                }
+            }
         });
+        command.args(create_arg_iter_from_cli_option(runtime));
+        command.args(create_arg_iter_from_cli_option(framework));
         command
     }
     pub fn invoke(&self) -> std::io::Result<std::process::ExitStatus> {
         self.get_command().status()
     }
+    /// Run each command from [`get_commands`](Self::get_commands) one after
+    /// another. Stops at the first command that fails, so the last status is
+    /// unsuccessful if any of them failed.
+    pub fn invoke_all(&self) -> std::io::Result<Vec<ExitStatus>> {
+        let mut statuses = Vec::new();
+        for mut command in self.get_commands() {
+            let status = command.status()?;
+            statuses.push(status);
+            if !status.success() {
+                break;
+            }
+        }
+        Ok(statuses)
+    }
+    /// Start `dotnet` without waiting for it to exit. The returned handle can
+    /// be used to wait for or cancel the build.
+    pub fn spawn(&self) -> std::io::Result<DotNetBuild> {
+        self.get_command().spawn().map(DotNetBuild::from)
+    }
 }
 /// Allow calling methods that are implemented on the command struct.
 impl<C> Deref for DotNetInvoker<C> {
@@ -403,6 +521,54 @@ impl<C> DerefMut for DotNetInvoker<C> {
         &mut self.command_data
     }
 }
+/// A running `dotnet` process started by [`DotNetInvoker::spawn`].
+///
+/// The process is killed if this handle is dropped before it has exited.
+#[derive(Debug)]
+pub struct DotNetBuild {
+    child: Child,
+    exited: bool,
+}
+impl DotNetBuild {
+    /// The OS-assigned process identifier of the `dotnet` process.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+    /// Wait for the process to exit.
+    pub fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        let status = self.child.wait()?;
+        self.exited = true;
+        Ok(status)
+    }
+    /// Check if the process has exited without blocking.
+    pub fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        let status = self.child.try_wait()?;
+        self.exited |= status.is_some();
+        Ok(status)
+    }
+    /// Stop the process. Use [`wait`](Self::wait) afterwards to get its exit
+    /// status.
+    pub fn kill(&mut self) -> std::io::Result<()> {
+        self.child.kill()
+    }
+}
+impl From<Child> for DotNetBuild {
+    fn from(child: Child) -> Self {
+        Self {
+            child,
+            exited: false,
+        }
+    }
+}
+impl Drop for DotNetBuild {
+    fn drop(&mut self) {
+        if !self.exited {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
 setter!(packages, DotNetRestorePackagesDir);
 setter!(framework, DotNetFrameWork);
 setter!(runtime, DotNetRuntimeIdentifier);
@@ -411,3 +577,86 @@ setter!(verbosity, DotNetVerbosity);
 setter!(self_contained, DotNetSelfContained);
 setter!(output_dir, DotNetOutput);
 setter!(artifacts_dir, DotNetArtifactsDir);
+setter!(publish_single_file, DotNetPublishSingleFile);
+setter!(publish_trimmed, DotNetPublishTrimmed);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publish_single_file_and_trimmed_args() {
+        let invoker = DotNetInvoker::new()
+            .publish()
+            .runtime("linux-x64")
+            .self_contained(true)
+            .publish_single_file(true)
+            .publish_trimmed(true);
+        assert_eq!(
+            invoker.args_iter().collect::<Vec<_>>(),
+            [
+                "publish",
+                "--runtime",
+                "linux-x64",
+                "--self-contained",
+                "true",
+                "-p:PublishSingleFile=true",
+                "-p:PublishTrimmed=true",
+            ]
+        );
+
+        let invoker = invoker.publish_trimmed(false);
+        let args = invoker.args_iter().collect::<Vec<_>>();
+        assert!(args.contains(&"-p:PublishTrimmed=false"));
+        // Not used by other commands:
+        let build = invoker.into_command::<Build>();
+        assert!(!build.args_iter().any(|arg| arg.starts_with("-p:")));
+    }
+
+    #[test]
+    fn one_command_per_runtime() {
+        let invoker = DotNetInvoker::new()
+            .publish()
+            .configuration("Release")
+            .runtime("win-x64")
+            .runtimes(["linux-x64", "osx-arm64"]);
+        let commands = invoker.get_commands();
+        assert_eq!(commands.len(), 2);
+        for (command, runtime) in commands.iter().zip(["linux-x64", "osx-arm64"]) {
+            let args = command.get_args().collect::<Vec<_>>();
+            assert_eq!(
+                args,
+                [
+                    "publish",
+                    "--configuration",
+                    "Release",
+                    "--runtime",
+                    runtime
+                ]
+            );
+        }
+
+        // Each framework is combined with each runtime:
+        let invoker = invoker.frameworks(["net6.0", "net8.0"]);
+        assert_eq!(invoker.get_commands().len(), 4);
+        // Restore doesn't have a framework option:
+        let commands = invoker.into_command::<Restore>().get_commands();
+        assert_eq!(commands.len(), 2);
+        assert!(!commands[0].get_args().any(|arg| arg == "--framework"));
+    }
+
+    #[test]
+    fn kill_stops_build() {
+        let child = if cfg!(windows) {
+            Command::new("ping").args(["-n", "60", "127.0.0.1"]).spawn()
+        } else {
+            Command::new("sleep").arg("60").spawn()
+        };
+        let mut build = DotNetBuild::from(child.unwrap());
+        assert!(build.try_wait().unwrap().is_none());
+
+        build.kill().unwrap();
+        let status = build.wait().unwrap();
+        assert!(!status.success());
+    }
+}
@@ -1,13 +1,20 @@
 use clap::{Parser, Subcommand};
 use color_eyre::Section;
 use eyre::{bail, Result, WrapErr};
-use html_to_pdf::{HtmlSink, HtmlToPdfConverter, PdfScope, WriteBuilder, WriteBuilderSimple};
+use html_to_pdf::{
+    HtmlSink, HtmlToPdfConverter, HtmlTooLargeError, Margin, PageOrientation, PageRanges, Paper,
+    PdfAConformance, PdfScope, SelectPagesConverter, WriteBuilder, WriteBuilderCounting,
+    WriteBuilderFile, WriteBuilderSimple,
+};
 
 use std::ffi::OsString;
 use std::fs::{File, OpenOptions};
-use std::io::{self, BufReader, BufWriter, Read, Write};
-use std::path::PathBuf;
+use std::io::{self, BufReader, Read, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::thread;
+use std::time::Instant;
 
 /// Convert a HTML file to a PDF file.
 #[derive(Parser)]
@@ -15,6 +22,14 @@ use std::thread;
 struct Cli {
     #[arg(long, conflicts_with = "input", help_heading = "INPUT")]
     stdin: bool,
+    /// The HTML file to convert, or a glob pattern like "reports/2024-*.html"
+    /// to convert every matching file. With a pattern the output path is a
+    /// directory and each PDF is placed at the same path relative to it as
+    /// the HTML file has relative to the part of the pattern before the first
+    /// wildcard.
+    ///
+    /// Quote patterns ('reports/*.html') so that the shell passes them on
+    /// instead of expanding them itself.
     #[arg(
         short,
         long,
@@ -23,6 +38,20 @@ struct Cli {
         required_unless_present = "stdin"
     )]
     input: Option<PathBuf>,
+    /// Abort the conversion if the HTML input is larger than this many bytes.
+    /// The program then exits with code 3 instead of 1 so that services can
+    /// tell this apart from other failures. Unlimited by default.
+    #[arg(long, value_name = "BYTES", help_heading = "INPUT")]
+    max_input_size: Option<u64>,
+    /// Stop at the first file that fails to convert when the input is a glob
+    /// pattern.
+    #[arg(long, overrides_with = "keep_going", help_heading = "INPUT")]
+    fail_fast: bool,
+    /// Convert every file that matches a glob pattern even if some of them
+    /// fail and list the failures at the end. The program still exits with an
+    /// error if any file failed. This is the default.
+    #[arg(long, overrides_with = "fail_fast", help_heading = "INPUT")]
+    keep_going: bool,
 
     #[arg(long, conflicts_with = "output", help_heading = "OUTPUT")]
     stdout: bool,
@@ -42,6 +71,53 @@ struct Cli {
         help_heading = "OUTPUT"
     )]
     overwrite: bool,
+    /// Set the permissions of the output file after a successful conversion,
+    /// specified as an octal number (for example "640").
+    ///
+    /// This is only supported on Unix, on other platforms the option is
+    /// ignored.
+    #[arg(
+        long,
+        value_name = "OCTAL_MODE",
+        value_parser = parse_octal_mode,
+        requires = "output",
+        help_heading = "OUTPUT"
+    )]
+    chmod: Option<u32>,
+    /// Split the generated PDF into multiple files that each have at most
+    /// this many pages. The files are named after the output path with an
+    /// index added before the extension, for example "out.1.pdf" and
+    /// "out.2.pdf".
+    #[arg(
+        long,
+        value_name = "PAGES",
+        requires = "output",
+        help_heading = "OUTPUT"
+    )]
+    split_every: Option<NonZeroU32>,
+
+    /// The paper to print on, either a standard size like "a4" or "letter"
+    /// or a custom size like "210mm x 297mm". Lengths support the units mm,
+    /// cm, in, pt and px. Uses the engine's default if not specified.
+    #[arg(long, value_name = "SIZE", help_heading = "PAGE")]
+    paper: Option<Paper>,
+    /// Turn the paper to "portrait" or "landscape".
+    #[arg(long, value_name = "ORIENTATION", help_heading = "PAGE")]
+    orientation: Option<PageOrientation>,
+    /// Space between the edges of the paper and the content, specified like
+    /// the CSS margin property, for example "1cm" or "10mm 20mm". Not
+    /// supported by dot-net-itext and pdf-min, use a CSS @page rule instead.
+    #[arg(long, value_name = "MARGIN", help_heading = "PAGE")]
+    margin: Option<Margin>,
+    /// Only keep these pages, for example "1-3,5" or "4-" for all pages from
+    /// the fourth. Pages are kept in the order of the document and it is an
+    /// error to select pages that the document doesn't have.
+    ///
+    /// Chrome only renders the selected pages but ignores selected pages past
+    /// the end of the document, as long as some of them exist. The other
+    /// engines render all pages and the rest are removed afterwards.
+    #[arg(long, value_name = "PAGES", help_heading = "PAGE")]
+    pages: Option<PageRanges>,
 
     /// Specify where extra files will be stored. Defaults to the user's global
     /// temp folder.
@@ -56,10 +132,136 @@ struct Cli {
     )]
     extract_at: ExtraFileLocation,
 
+    /// How to report the result of the conversion. With "json" a JSON object
+    /// describing the conversion is printed to stdout as a single line when
+    /// the program finishes, even if the conversion failed. If the input is a
+    /// glob pattern then there is one line for each matched file (JSON Lines).
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ReportFormat::Text,
+        conflicts_with = "stdout"
+    )]
+    format: ReportFormat,
+
+    /// Pass a setting directly to the conversion engine, can be specified
+    /// multiple times. Keys that the selected engine doesn't know about are
+    /// ignored with a warning.
+    ///
+    /// - wkhtml: a wkhtmltopdf setting, for example "load.jsdelay=1000".
+    ///
+    /// - chromiumoxide: a DevTools Protocol "Page.printToPDF" parameter, for
+    ///   example "scale=0.8" or "landscape=true".
+    ///
+    /// - dot-net-itext: "pdf-a", "icc-profile" or "font" (a font file that
+    ///   the HTML can use, can be specified multiple times).
+    ///
+    /// - dot-net-itext-framework: "custom-page-break" or "font".
+    #[arg(
+        long = "engine-arg",
+        value_name = "KEY=VALUE",
+        value_parser = parse_engine_arg,
+        help_heading = "ENGINE"
+    )]
+    engine_args: Vec<EngineArg>,
+    /// Print the options that are passed to the conversion engine before it
+    /// starts, including the resolved page setup and engine settings. Useful
+    /// when reporting issues.
+    #[arg(short, long, help_heading = "ENGINE")]
+    verbose: bool,
+
     #[command(subcommand)]
     command: PdfConversionMethod,
 }
 
+fn parse_octal_mode(mode: &str) -> Result<u32, String> {
+    u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|&mode| mode <= 0o7777)
+        .ok_or_else(|| format!("{mode:?} isn't an octal file mode like \"644\""))
+}
+
+fn parse_pdf_a(conformance: &str) -> Result<PdfAConformance, String> {
+    let conformance = conformance
+        .trim_start_matches("PDF/A-")
+        .trim_start_matches("pdf/a-");
+    let mut chars = conformance.chars();
+    match (
+        chars.next().and_then(|c| c.to_digit(10)),
+        chars.next(),
+        chars.next(),
+    ) {
+        (Some(part), Some(level), None) => PdfAConformance::from_part_and_level(part as u8, level),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        let supported = PdfAConformance::ALL
+            .iter()
+            .map(|v| format!("{}{}", v.part(), v.level().to_ascii_lowercase()))
+            .collect::<Vec<_>>();
+        format!(
+            "{conformance:?} isn't a supported PDF/A conformance level, use one of: {}",
+            supported.join(", ")
+        )
+    })
+}
+
+/// A setting that is passed to the conversion engine with `--engine-arg`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineArg {
+    pub key: String,
+    pub value: String,
+}
+impl EngineArg {
+    /// Inform the user that the engine couldn't use this setting.
+    fn warn_ignored(&self, engine: &str, reason: impl std::fmt::Display) {
+        eprintln!(
+            "Warning: ignoring --engine-arg {}={} for {engine}: {reason}",
+            self.key, self.value
+        );
+    }
+}
+
+/// Page setup from the `--paper`, `--orientation` and `--margin` options.
+#[derive(Debug, Clone, Copy, Default)]
+struct PageSetup {
+    paper: Option<Paper>,
+    orientation: Option<PageOrientation>,
+    margin: Option<Margin>,
+}
+impl PageSetup {
+    /// Inform the user that the engine can't use some of the options.
+    fn warn_unsupported(&self, engine: &str, paper: bool, orientation: bool, margin: bool) {
+        for (option, specified) in [
+            ("--paper", paper && self.paper.is_some()),
+            ("--orientation", orientation && self.orientation.is_some()),
+            ("--margin", margin && self.margin.is_some()),
+        ] {
+            if specified {
+                eprintln!("Warning: ignoring {option} since {engine} doesn't support it");
+            }
+        }
+    }
+    fn page_options(&self) -> html_to_pdf::PageOptions {
+        html_to_pdf::PageOptions {
+            page_size: self.paper,
+            orientation: self.orientation,
+            margins: self.margin,
+            dpi: None,
+        }
+    }
+}
+
+fn parse_engine_arg(arg: &str) -> Result<EngineArg, String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok(EngineArg {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        }),
+        _ => Err(format!("{arg:?} isn't formatted as KEY=VALUE")),
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
 pub enum ExtraFileLocation {
     LocalPersist,
@@ -68,6 +270,65 @@ pub enum ExtraFileLocation {
     GlobalTemp,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+/// Information about the conversion of one file that is printed when using
+/// `--format json`.
+#[derive(Debug, Clone, serde::Serialize)]
+struct ConversionReport {
+    engine: &'static str,
+    /// The input file, or `None` if reading from stdin. This is the pattern
+    /// if the conversion failed before any file that matched it was converted.
+    input: Option<String>,
+    /// The written output files, more than one if `--split-every` was used.
+    output: Vec<String>,
+    /// Size of the generated PDFs.
+    bytes: u64,
+    duration_secs: f64,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+impl ConversionReport {
+    fn new(cli: &Cli, input: Option<&Path>) -> Self {
+        Self {
+            engine: cli.command.name(),
+            input: input.map(|path| path.display().to_string()),
+            output: Vec::new(),
+            bytes: 0,
+            duration_secs: 0.0,
+            success: false,
+            error: None,
+        }
+    }
+
+    /// Run a conversion that fills in this report and record how long it took
+    /// and whether it succeeded.
+    fn run(mut self, f: impl FnOnce(&mut Self) -> Result<()>) -> (Self, Result<()>) {
+        let started = Instant::now();
+        let result = f(&mut self);
+        self.duration_secs = started.elapsed().as_secs_f64();
+        self.success = result.is_ok();
+        if let Err(e) = &result {
+            self.error = Some(format!("{e:#}"));
+        }
+        (self, result)
+    }
+}
+
+/// Write each report as a line of JSON (JSON Lines).
+fn write_json_reports(mut out: impl Write, reports: &[ConversionReport]) -> Result<()> {
+    for report in reports {
+        serde_json::to_writer(&mut out, report).context("Failed to serialize conversion report")?;
+        writeln!(out).context("Failed to write conversion report")?;
+    }
+    Ok(())
+}
+
 /// Configuration for different HTML to PDF converters.
 #[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
 pub enum PdfConversionMethod {
@@ -83,10 +344,25 @@ pub enum PdfConversionMethod {
     /// Use the iText .Net library via a small C# program. This is slower than
     /// the older .Net Framework iText library but has more accurate results
     /// (for example some japanese characters are only correctly shown with this
-    /// option).
+    /// option, unless a font that has them is passed to the other engine with
+    /// --engine-arg font=PATH).
     ///
     /// - No PDF Table of Contents.
-    DotNetItext,
+    DotNetItext {
+        /// Create a PDF/A document for archival, for example "2b". Requires
+        /// --icc-profile.
+        #[arg(
+            long,
+            value_name = "CONFORMANCE",
+            value_parser = parse_pdf_a,
+            requires = "icc_profile"
+        )]
+        pdf_a: Option<PdfAConformance>,
+        /// An ICC color profile (usually sRGB) that is embedded as the output
+        /// intent of a PDF/A document.
+        #[arg(long, value_name = "ICC_PATH", requires = "pdf_a")]
+        icc_profile: Option<PathBuf>,
+    },
     /// Use "wkhtmltopdf" to handle the conversion.
     Wkhtml {
         /// Shell out to the "wkhtmltopdf" executable. If this is `false` we will
@@ -95,6 +371,11 @@ pub enum PdfConversionMethod {
         /// NOTE: not implemented yet.
         #[arg(long)]
         shelled: bool,
+        /// Extract the wkhtml runner program and library into a temporary
+        /// folder inside this folder instead of the system's temp folder, for
+        /// example if the system's temp folder doesn't allow running programs.
+        #[arg(long, value_name = "DIR")]
+        temp_dir: Option<PathBuf>,
     },
     /// Use the Rust library "pdf-min" to handle the conversion.
     ///
@@ -111,6 +392,19 @@ pub enum PdfConversionMethod {
     Chromiumoxide,
 }
 
+impl PdfConversionMethod {
+    /// The name of the subcommand that selects this converter.
+    fn name(&self) -> &'static str {
+        match self {
+            PdfConversionMethod::DotNetItextFramework { .. } => "dot-net-itext-framework",
+            PdfConversionMethod::DotNetItext { .. } => "dot-net-itext",
+            PdfConversionMethod::Wkhtml { .. } => "wkhtml",
+            PdfConversionMethod::PdfMin => "pdf-min",
+            PdfConversionMethod::Chromiumoxide => "chromiumoxide",
+        }
+    }
+}
+
 #[derive(Parser, Debug, Clone, PartialEq, Eq)]
 pub enum DotNetFrameworkItextMode {
     /// A C# HTML to PDF converter using its older legacy implementation.
@@ -151,25 +445,78 @@ impl DotNetFrameworkItextMode {
     }
     fn into_converter(
         self,
+        page_setup: PageSetup,
     ) -> html_to_pdf_adapter_dotnet_framework_itext::DotNetFrameworkPdfConverter {
-        html_to_pdf_adapter_dotnet_framework_itext::DotNetFrameworkPdfConverter {
-            mode: self.mode(),
-            custom_page_break: if let DotNetFrameworkItextMode::PdfLegacy { custom_page_break } =
-                self
-            {
-                custom_page_break
-            } else {
-                None
-            },
-            #[cfg(feature = "dotnet_framework_conversion_include_exe")]
-            extract_included_exe_at: Some(std::env::temp_dir().join("HtmlToPdf_Framework")),
-            #[cfg(not(feature = "dotnet_framework_conversion_include_exe"))]
-            extract_included_exe_at: None,
+        let mut converter = html_to_pdf_engines::dotnet_framework_itext_converter(
+            self.mode(),
+            &page_setup.page_options(),
+        );
+        if let DotNetFrameworkItextMode::PdfLegacy { custom_page_break } = self {
+            converter.custom_page_break = custom_page_break;
         }
+        converter
     }
 }
 
-impl<'scope, W> HtmlToPdfConverter<'scope, W> for PdfConversionMethod
+#[cfg(feature = "wk_html_to_pdf")]
+fn wkhtml_converter(
+    temp_dir: Option<PathBuf>,
+    page_setup: PageSetup,
+    engine_args: &[EngineArg],
+) -> html_to_pdf_adapter_wkhtml::WkHtmlPdfConverter {
+    let mut converter = html_to_pdf_engines::wkhtml_converter(&page_setup.page_options());
+    converter.temp_dir = temp_dir;
+    // Engine args are applied last so that they can override the page setup:
+    for arg in engine_args {
+        if let Err(e) = converter.set(&arg.key, arg.value.as_str()) {
+            arg.warn_ignored("wkhtml", e);
+        }
+    }
+    converter
+}
+
+#[cfg(feature = "chromiumoxide_conversion")]
+fn chromiumoxide_converter(
+    page_setup: PageSetup,
+    pages: Option<&PageRanges>,
+    engine_args: &[EngineArg],
+) -> html_to_pdf_adapter_chromiumoxide::ChromiumoxideConverter {
+    let mut builder = html_to_pdf_engines::chromiumoxide_converter(&page_setup.page_options());
+    if let Some(pages) = pages {
+        builder = builder.page_ranges(pages);
+    }
+    // Engine args are applied last so that they can override the page setup:
+    for arg in engine_args {
+        if let Err(e) = builder.set_pdf_option(&arg.key, &arg.value) {
+            arg.warn_ignored("chromiumoxide", e);
+        }
+    }
+    builder.build()
+}
+
+/// The message that `--verbose` prints before an engine is started.
+fn options_message(engine: &str, converter: &impl std::fmt::Debug) -> String {
+    format!("Starting {engine} with options: {converter:#?}")
+}
+
+fn log_options(verbose: bool, engine: &str, converter: &impl std::fmt::Debug) {
+    if verbose {
+        eprintln!("{}", options_message(engine, converter));
+    }
+}
+
+/// A conversion method together with the page setup, the selected pages and
+/// the settings from `--engine-arg`.
+struct Converter {
+    method: PdfConversionMethod,
+    page_setup: PageSetup,
+    pages: Option<PageRanges>,
+    engine_args: Vec<EngineArg>,
+    /// Print the engine's options before starting it.
+    verbose: bool,
+}
+
+impl<'scope, W> HtmlToPdfConverter<'scope, W> for Converter
 where
     W: WriteBuilder + Send + 'scope,
 {
@@ -177,49 +524,101 @@ where
     type Error = eyre::Error;
 
     fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink> {
-        Ok(match self {
+        let Converter {
+            method,
+            page_setup,
+            pages,
+            engine_args,
+            verbose,
+        } = self;
+        // Chrome only renders the selected pages, for the other engines the
+        // rest are removed afterwards:
+        if pages.is_some() && method != PdfConversionMethod::Chromiumoxide {
+            let converter = SelectPagesConverter {
+                converter: Converter {
+                    method,
+                    page_setup,
+                    pages: None,
+                    engine_args,
+                    verbose,
+                },
+                pages,
+            };
+            return Ok(Box::new(
+                converter
+                    .start(scope, output)?
+                    .map_completion_err(eyre::Report::from),
+            ));
+        }
+        Ok(match method {
             PdfConversionMethod::DotNetItextFramework { mode } => {
                 #[cfg(feature = "dotnet_framework_conversion")]
                 {
-                    Box::new(mode.into_converter().start(scope, output)?)
+                    let mut converter = mode.into_converter(page_setup);
+                    for arg in &engine_args {
+                        match arg.key.as_str() {
+                            "custom-page-break" => {
+                                converter.custom_page_break = Some(arg.value.clone().into())
+                            }
+                            "font" => converter.fonts.push(arg.value.clone().into()),
+                            _ => arg.warn_ignored("dot-net-itext-framework", "unknown key"),
+                        }
+                    }
+                    log_options(verbose, "dot-net-itext-framework", &converter);
+                    Box::new(converter.start(scope, output)?)
                 }
                 #[cfg(not(feature = "dotnet_framework_conversion"))]
                 {
+                    let _ = (page_setup, verbose);
                     bail!(
                         r#"The C# .Net Framework PDF conversion program wasn't included when this program was created."#
                     );
                 }
             }
-            PdfConversionMethod::DotNetItext => {
+            PdfConversionMethod::DotNetItext { pdf_a, icc_profile } => {
                 #[cfg(feature = "dotnet_conversion")]
                 {
-                    Box::new(
-                        html_to_pdf_adapter_dotnet_itext::DotNetPdfConverter {
-                            #[cfg(feature = "dotnet_conversion_include_exe")]
-                            extract_included_exe_at: Some(std::env::temp_dir().join("HtmlToPdf")),
-                            #[cfg(not(feature = "dotnet_conversion_include_exe"))]
-                            extract_included_exe_at: None,
+                    let mut converter = html_to_pdf_adapter_dotnet_itext::DotNetPdfConverter {
+                        pdf_a,
+                        icc_profile,
+                        ..html_to_pdf_engines::dotnet_itext_converter(&page_setup.page_options())
+                    };
+                    page_setup.warn_unsupported("dot-net-itext", false, false, true);
+                    for arg in &engine_args {
+                        match arg.key.as_str() {
+                            "pdf-a" => match parse_pdf_a(&arg.value) {
+                                Ok(pdf_a) => converter.pdf_a = Some(pdf_a),
+                                Err(e) => arg.warn_ignored("dot-net-itext", e),
+                            },
+                            "icc-profile" => converter.icc_profile = Some(arg.value.clone().into()),
+                            "font" => converter.fonts.push(arg.value.clone().into()),
+                            _ => arg.warn_ignored("dot-net-itext", "unknown key"),
                         }
-                        .start(scope, output)?,
-                    )
+                    }
+                    log_options(verbose, "dot-net-itext", &converter);
+                    Box::new(converter.start(scope, output)?)
                 }
                 #[cfg(not(feature = "dotnet_conversion"))]
                 {
+                    let _ = (pdf_a, icc_profile, page_setup, verbose);
                     bail!(
                         r#"The C# .Net PDF conversion program wasn't included when this program was created."#
                     );
                 }
             }
-            PdfConversionMethod::Wkhtml { shelled } => {
+            PdfConversionMethod::Wkhtml { shelled, temp_dir } => {
                 if shelled {
                     bail!("Shell out to wkhtml for PDF conversion is not supported yet.");
                 }
                 #[cfg(feature = "wk_html_to_pdf")]
                 {
-                    Box::new(html_to_pdf_adapter_wkhtml::WkHtmlPdfConverter.start(scope, output)?)
+                    let converter = wkhtml_converter(temp_dir, page_setup, &engine_args);
+                    log_options(verbose, "wkhtml", &converter);
+                    Box::new(converter.start(scope, output)?)
                 }
                 #[cfg(not(feature = "wk_html_to_pdf"))]
                 {
+                    let _ = (temp_dir, page_setup, verbose);
                     bail!(
                         r#"The WKHtmlToPdf PDF conversion program wasn't included when this program was created."#
                     );
@@ -228,14 +627,21 @@ where
             PdfConversionMethod::PdfMin => {
                 #[cfg(not(feature = "pdf_min_conversion"))]
                 {
+                    let _ = (page_setup, verbose);
                     bail!(
                         r#"The "pdf-min" Rust library wasn't built when this program was created."#
                     );
                 }
                 #[cfg(feature = "pdf_min_conversion")]
                 {
+                    for arg in &engine_args {
+                        arg.warn_ignored("pdf-min", "this engine doesn't have any settings");
+                    }
+                    let converter =
+                        html_to_pdf_engines::pdf_min_converter(&page_setup.page_options());
+                    log_options(verbose, "pdf-min", &converter);
                     Box::new(
-                        html_to_pdf_adapter_pdf_min::PdfMinConverter
+                        converter
                             .start(scope, output)
                             .map_err(|e| eyre::eyre!(e))?
                             .map_completion_err(|e| eyre::eyre!(e)),
@@ -245,19 +651,21 @@ where
             PdfConversionMethod::Chromiumoxide => {
                 #[cfg(not(feature = "chromiumoxide_conversion"))]
                 {
+                    let _ = (page_setup, pages, verbose);
                     bail!(
                         r#"The "chromiumoxide" Rust library wasn't built when this program was created."#
                     );
                 }
                 #[cfg(feature = "chromiumoxide_conversion")]
                 {
+                    let converter =
+                        chromiumoxide_converter(page_setup, pages.as_ref(), &engine_args);
+                    log_options(verbose, "chromiumoxide", &converter);
                     Box::new(
-                        html_to_pdf_adapter_chromiumoxide::ChromiumoxideConverter {
-                            pdf_options: Default::default(),
-                        }
-                        .start(scope, output)
-                        .map_err(|e| eyre::eyre!(e))?
-                        .map_completion_err(|e| eyre::eyre!(e)),
+                        converter
+                            .start(scope, output)
+                            .map_err(|e| eyre::eyre!(e))?
+                            .map_completion_err(|e| eyre::eyre!(e)),
                     )
                 }
             }
@@ -265,11 +673,148 @@ where
     }
 }
 
-fn main() -> Result<()> {
+/// Options that create the output file. If `overwrite` is `false` then
+/// opening fails if the file already exists, otherwise an existing file is
+/// truncated.
+fn output_file_options(overwrite: bool) -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if overwrite {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    options
+}
+
+/// Add context to an error from creating the output file at `path`.
+fn explain_output_file_error<T>(result: io::Result<T>, path: &Path, overwrite: bool) -> Result<T> {
+    match result {
+        Err(e) if !overwrite && e.kind() == io::ErrorKind::AlreadyExists => Err(e)
+            .with_context(|| format!("Output file already exists at: {}", path.display()))
+            .suggestion("pass the --overwrite flag if the output file should be overwritten"),
+        result => {
+            result.with_context(|| format!("Failed to create output file at: {}", path.display()))
+        }
+    }
+}
+
+/// Create the output file, see [`output_file_options`].
+fn create_output_file(path: &Path, overwrite: bool) -> Result<File> {
+    explain_output_file_error(output_file_options(overwrite).open(path), path, overwrite)
+}
+
+/// Add an index before the extension of a path, so `out.pdf` becomes
+/// `out.1.pdf`.
+fn indexed_output_path(path: &Path, index: usize) -> PathBuf {
+    let mut file_name = path.file_stem().unwrap_or_default().to_owned();
+    file_name.push(format!(".{index}"));
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    path.with_file_name(file_name)
+}
+
+fn set_output_permissions(path: &Path, mode: u32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).with_context(
+            || {
+                format!(
+                    "Failed to change permissions of output file at: {}",
+                    path.display()
+                )
+            },
+        )?;
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        eprintln!("Ignoring the --chmod option since it is only supported on Unix");
+    }
+    Ok(())
+}
+
+/// Exit code used when the input is larger than `--max-input-size`.
+const EXIT_INPUT_TOO_LARGE: u8 = 3;
+
+/// Check if an error was caused by input that was larger than
+/// `--max-input-size`.
+fn is_input_too_large(error: &eyre::Report) -> bool {
+    error
+        .chain()
+        .filter_map(|e| e.downcast_ref::<io::Error>())
+        .any(|e| HtmlTooLargeError::from_io(e).is_some())
+}
+
+fn main() -> Result<ExitCode> {
     let cli = Cli::parse();
 
     color_eyre::install()?;
 
+    let format = cli.format;
+    let mut reports = Vec::new();
+    let run_report = ConversionReport::new(&cli, cli.input.as_deref());
+    let (run_report, result) = run_report.run(|_| convert(cli, &mut reports));
+    if reports.is_empty() {
+        // Failed before any file was converted:
+        reports.push(run_report);
+    }
+
+    if format == ReportFormat::Json {
+        write_json_reports(io::stdout().lock(), &reports)?;
+    }
+    match result {
+        Err(e) if is_input_too_large(&e) => {
+            eprintln!("Error: {e:?}");
+            Ok(ExitCode::from(EXIT_INPUT_TOO_LARGE))
+        }
+        result => result.map(|()| ExitCode::SUCCESS),
+    }
+}
+
+/// Check if an input path uses any of the wildcards that [`glob`] supports.
+fn is_glob_pattern(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Find the files that match `pattern` and the path of each file relative to
+/// the part of the pattern before the first wildcard.
+fn expand_input_pattern(pattern: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let base = pattern
+        .components()
+        .take_while(|component| !is_glob_pattern(component.as_os_str().as_ref()))
+        .collect::<PathBuf>();
+    let pattern_str = pattern
+        .to_str()
+        .ok_or_else(|| eyre::eyre!("Input patterns must be valid UTF-8"))?;
+    let paths =
+        glob::glob(pattern_str).with_context(|| format!("Invalid input pattern: {pattern_str}"))?;
+
+    let mut inputs = Vec::new();
+    for path in paths {
+        let path = path.context("Failed to read a path that matched the input pattern")?;
+        if !path.is_file() {
+            continue;
+        }
+        let relative = path.strip_prefix(&base).unwrap_or(&path).to_owned();
+        inputs.push((path, relative));
+    }
+    if inputs.is_empty() {
+        return Err(eyre::eyre!(
+            "No files matched the input pattern: {pattern_str}"
+        ))
+        .suggestion("check the pattern, it is relative to the current directory");
+    }
+    Ok(inputs)
+}
+
+/// Convert the input file or all files that match the input pattern, a report
+/// is added to `reports` for each file.
+fn convert(cli: Cli, reports: &mut Vec<ConversionReport>) -> Result<()> {
     if cli.extract_at != ExtraFileLocation::GlobalPersist {
         bail!(
             "Locations of extra files can't be configured yet \
@@ -277,51 +822,87 @@ fn main() -> Result<()> {
         )
     }
 
-    let mut input: Box<dyn Read> = if let Some(input) = cli.input {
-        eprintln!("Reading input from file at: {}", input.display());
-        Box::new(BufReader::new(File::open(&input).with_context(|| {
-            format!("Failed to open input file at: {}", input.display())
-        })?))
-    } else {
-        eprintln!("Reading input from stdin");
-        Box::new(io::stdin())
+    let pattern = cli.input.as_deref().filter(|input| is_glob_pattern(input));
+    let Some(pattern) = pattern else {
+        let report = ConversionReport::new(&cli, cli.input.as_deref());
+        let (report, result) = report
+            .run(|report| convert_file(&cli, cli.input.as_deref(), cli.output.as_deref(), report));
+        reports.push(report);
+        result?;
+        eprintln!("Successfully converted HTML to PDF");
+        return Ok(());
     };
 
-    let mut output: Box<dyn Write + Send> = if let Some(output) = cli.output {
-        eprintln!("Writing output to file at: {}", output.display());
-        Box::new(BufWriter::new({
-            let result = OpenOptions::new()
-                .truncate(true)
-                .write(true)
-                .create(true)
-                .create_new(!cli.overwrite)
-                .open(&output);
-
-            let should_overwrite =
-                matches!(&result, Err(e) if e.kind() == io::ErrorKind::AlreadyExists);
-
-            let result = result
-                .with_context(|| format!("Failed to create output file at: {}", output.display()));
-            if should_overwrite && !cli.overwrite {
-                result.suggestion(
-                    "pass the --overwrite flag if the output file should be overwritten",
-                )?
-            } else {
-                result?
-            }
-        }))
-    } else {
-        eprintln!("Writing output to stdout");
-        Box::new(io::stdout())
+    let Some(output_dir) = &cli.output else {
+        bail!("An output directory is required when the input is a glob pattern, --stdout can't be used");
     };
+    let inputs = expand_input_pattern(pattern)?;
+    eprintln!(
+        "Converting {} files that matched the input pattern: {}",
+        inputs.len(),
+        pattern.display()
+    );
+    let fail_fast = cli.fail_fast && !cli.keep_going;
+    let mut failures = Vec::new();
+    for (input, relative) in &inputs {
+        let output = output_dir.join(relative).with_extension("pdf");
+        let report = ConversionReport::new(&cli, Some(input));
+        let (report, result) = report.run(|report| {
+            if let Some(parent) = output.parent() {
+                std::fs::create_dir_all(parent).with_context(|| {
+                    format!("Failed to create output directory at: {}", parent.display())
+                })?;
+            }
+            convert_file(&cli, Some(input.as_path()), Some(output.as_path()), report)
+        });
+        reports.push(report);
+        let result = result.with_context(|| format!("Failed to convert: {}", input.display()));
 
-    let pdf_method = cli.command;
+        if let Err(e) = result {
+            if fail_fast {
+                return Err(e);
+            }
+            eprintln!("Error: {e:#}");
+            eprintln!("Continuing with the remaining files...");
+            failures.push((input, e));
+        }
+    }
+    if !failures.is_empty() {
+        let mut message = format!(
+            "{} of {} files failed to convert:",
+            failures.len(),
+            inputs.len()
+        );
+        for (input, e) in &failures {
+            message.push_str(&format!("\n- {}: {e:#}", input.display()));
+        }
+        return Err(eyre::eyre!(message))
+            .suggestion("pass --fail-fast to stop at the first file that fails");
+    }
+    eprintln!("Successfully converted {} HTML files to PDF", inputs.len());
+
+    Ok(())
+}
+
+/// Write all HTML from `input` to the converter and wait until it has written
+/// the PDF to `output`.
+fn run_converter<W>(
+    cli: &Cli,
+    converter: Converter,
+    mut input: Box<dyn Read + '_>,
+    output: W,
+) -> Result<WriteBuilderCounting<W>>
+where
+    W: WriteBuilder + Send,
+{
+    let mut output = WriteBuilderCounting::new(output);
     thread::scope(|s| -> Result<()> {
-        eprintln!("Opened input and output, starting PDF converter...");
+        eprintln!("Opened input, starting PDF converter...");
 
-        let mut html_sink = pdf_method
-            .start(PdfScope::scoped(s), WriteBuilderSimple(&mut output))
-            .context("Failed to start PDF converter")?;
+        let mut html_sink = converter
+            .start(PdfScope::scoped(s), &mut output)
+            .context("Failed to start PDF converter")?
+            .limit(cli.max_input_size.unwrap_or(u64::MAX));
 
         eprintln!("Started PDF converter, reading HTML from input...");
 
@@ -335,8 +916,564 @@ fn main() -> Result<()> {
 
         Ok(())
     })?;
+    Ok(output)
+}
 
-    eprintln!("Successfully converted HTML to PDF");
+/// Convert a single HTML file, or stdin if `input` is `None`, and add the
+/// written files to `report`.
+fn convert_file(
+    cli: &Cli,
+    input: Option<&Path>,
+    output_path: Option<&Path>,
+    report: &mut ConversionReport,
+) -> Result<()> {
+    let input: Box<dyn Read> = if let Some(input) = input {
+        eprintln!("Reading input from file at: {}", input.display());
+        Box::new(BufReader::new(File::open(input).with_context(|| {
+            format!("Failed to open input file at: {}", input.display())
+        })?))
+    } else {
+        eprintln!("Reading input from stdin");
+        Box::new(io::stdin())
+    };
+
+    let pdf_method = Converter {
+        method: cli.command.clone(),
+        page_setup: PageSetup {
+            paper: cli.paper,
+            orientation: cli.orientation,
+            margin: cli.margin,
+        },
+        pages: cli.pages.clone(),
+        engine_args: cli.engine_args.clone(),
+        verbose: cli.verbose,
+    };
+
+    // The whole PDF is needed before it can be split into multiple files:
+    let mut pdf_buffer = Vec::new();
+    if cli.split_every.is_some() {
+        let output = run_converter(cli, pdf_method, input, WriteBuilderSimple(&mut pdf_buffer))?;
+        report.bytes += output.bytes_written();
+    } else if let Some(output_path) = output_path {
+        eprintln!("Writing output to file at: {}", output_path.display());
+        if !cli.overwrite && output_path.exists() {
+            let exists = Err(io::Error::from(io::ErrorKind::AlreadyExists));
+            return explain_output_file_error(exists, output_path, cli.overwrite);
+        }
+        // The file is created when the converter starts writing the PDF, so a
+        // failed conversion doesn't leave an empty file behind:
+        let output = WriteBuilderFile::new(output_path, output_file_options(cli.overwrite));
+        let output = run_converter(cli, pdf_method, input, output)?;
+        report.bytes += output.bytes_written();
+        let file = output.into_inner().into_file().with_context(|| {
+            format!("Failed to write output file at: {}", output_path.display())
+        })?;
+        if file.is_none() {
+            // The converter succeeded without writing anything:
+            create_output_file(output_path, cli.overwrite)?;
+        }
+    } else {
+        eprintln!("Writing output to stdout");
+        let output = run_converter(cli, pdf_method, input, WriteBuilderSimple(io::stdout()))?;
+        report.bytes += output.bytes_written();
+        output
+            .into_inner()
+            .0
+            .flush()
+            .context("Failed to flush output")?;
+    }
+
+    let mut output_files = Vec::new();
+    if let (Some(pages_per_file), Some(output)) = (cli.split_every, output_path) {
+        let parts = html_to_pdf::pdf::split_every(&pdf_buffer, pages_per_file)
+            .context("Failed to split the PDF into multiple files")?;
+        for (index, part) in parts.iter().enumerate() {
+            let path = indexed_output_path(output, index + 1);
+            eprintln!("Writing part {} to file at: {}", index + 1, path.display());
+            create_output_file(&path, cli.overwrite)?
+                .write_all(part)
+                .with_context(|| format!("Failed to write output file at: {}", path.display()))?;
+            output_files.push(path);
+        }
+    } else if let Some(output) = output_path {
+        output_files.push(output.to_owned());
+    }
+
+    report
+        .output
+        .extend(output_files.iter().map(|path| path.display().to_string()));
+
+    if let Some(mode) = cli.chmod {
+        for path in &output_files {
+            set_output_permissions(path, mode)?;
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_output_file_respects_overwrite() {
+        let dir = std::env::temp_dir().join(format!("html-to-pdf-cli-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for overwrite in [false, true] {
+            let path = dir.join(format!("overwrite-{overwrite}.pdf"));
+            let _ = std::fs::remove_file(&path);
+
+            // The file doesn't exist:
+            create_output_file(&path, overwrite)
+                .unwrap()
+                .write_all(b"first")
+                .unwrap();
+            assert_eq!(std::fs::read(&path).unwrap(), b"first");
+
+            // The file exists:
+            let result = create_output_file(&path, overwrite);
+            if overwrite {
+                result.unwrap().write_all(b"new").unwrap();
+                assert_eq!(std::fs::read(&path).unwrap(), b"new");
+            } else {
+                let error = result.unwrap_err();
+                assert_eq!(
+                    error.downcast_ref::<io::Error>().map(io::Error::kind),
+                    Some(io::ErrorKind::AlreadyExists)
+                );
+                assert_eq!(std::fs::read(&path).unwrap(), b"first");
+            }
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn json_report_contains_expected_fields() {
+        let cli = Cli::parse_from([
+            "html-to-pdf",
+            "--input",
+            "in.html",
+            "--output",
+            "out.pdf",
+            "--format",
+            "json",
+            "pdf-min",
+        ]);
+        let mut report = ConversionReport::new(&cli, cli.input.as_deref());
+        report.output.push("out.pdf".to_owned());
+        report.bytes = 42;
+        report.success = true;
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&report).unwrap()).unwrap();
+        assert_eq!(json["engine"], "pdf-min");
+        assert_eq!(json["input"], "in.html");
+        assert_eq!(json["output"], serde_json::json!(["out.pdf"]));
+        assert_eq!(json["bytes"], 42);
+        assert_eq!(json["success"], true);
+        assert!(json["duration_secs"].is_number());
+        assert!(json.get("error").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "wk_html_to_pdf")]
+    fn engine_arg_reaches_wkhtml_settings() {
+        let cli = Cli::parse_from([
+            "html-to-pdf",
+            "--stdin",
+            "--stdout",
+            "--engine-arg",
+            "load.jsdelay=1000",
+            "--engine-arg",
+            "not.a.setting=1",
+            "wkhtml",
+        ]);
+        assert_eq!(cli.engine_args.len(), 2);
+
+        let converter = wkhtml_converter(None, PageSetup::default(), &cli.engine_args);
+        assert_eq!(
+            converter.settings().collect::<Vec<_>>(),
+            [("load.jsdelay", "1000")]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "pdf_min_conversion")]
+    fn input_over_max_size_is_rejected() {
+        let dir = std::env::temp_dir().join(format!("html-to-pdf-cli-max-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.html");
+        let output = dir.join("out.pdf");
+        let _ = std::fs::remove_file(&output);
+        std::fs::write(&input, "<p>Hello world</p>".repeat(100)).unwrap();
+
+        let cli = Cli::parse_from([
+            "html-to-pdf",
+            "--input",
+            input.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+            "--overwrite",
+            "--max-input-size",
+            "1024",
+            "pdf-min",
+        ]);
+        let error = convert(cli, &mut Vec::new()).unwrap_err();
+        assert!(is_input_too_large(&error), "{error:?}");
+        // No PDF was generated, so the output file wasn't created:
+        assert!(!output.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "pdf_min_conversion"))]
+    fn chmod_sets_output_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir =
+            std::env::temp_dir().join(format!("html-to-pdf-cli-chmod-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.html");
+        let output = dir.join("out.pdf");
+        std::fs::write(&input, "<p>Hello world</p>").unwrap();
+
+        let cli = Cli::parse_from([
+            "html-to-pdf",
+            "--input",
+            input.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+            "--overwrite",
+            "--chmod",
+            "600",
+            "pdf-min",
+        ]);
+        convert(cli, &mut Vec::new()).unwrap();
+        let mode = std::fs::metadata(&output).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn chmod_rejects_invalid_octal_modes() {
+        for mode in ["8", "rw-r--r--", "77777"] {
+            let result = Cli::try_parse_from([
+                "html-to-pdf",
+                "--stdin",
+                "--output",
+                "out.pdf",
+                "--chmod",
+                mode,
+                "pdf-min",
+            ]);
+            let error = result.err().expect("invalid modes should be rejected");
+            assert_eq!(
+                error.kind(),
+                clap::error::ErrorKind::ValueValidation,
+                "{mode:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "pdf_min_conversion")]
+    fn glob_input_converts_matching_files() {
+        let dir = std::env::temp_dir().join(format!("html-to-pdf-cli-glob-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for file in ["a/2024-1.html", "b/2024-2.html", "b/2023-3.html"] {
+            let path = dir.join("reports").join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, "<p>Report</p>").unwrap();
+        }
+        let output = dir.join("out");
+
+        let run = |pattern: &str| {
+            let cli = Cli::parse_from([
+                "html-to-pdf",
+                "--input",
+                dir.join("reports").join(pattern).to_str().unwrap(),
+                "--output",
+                output.to_str().unwrap(),
+                "pdf-min",
+            ]);
+            let mut reports = Vec::new();
+            convert(cli, &mut reports).map(|()| reports)
+        };
+
+        let reports = run("*/2024-*.html").unwrap();
+        let mut outputs = reports
+            .iter()
+            .flat_map(|report| report.output.clone())
+            .collect::<Vec<_>>();
+        outputs.sort();
+        assert_eq!(
+            outputs,
+            [
+                output.join("a").join("2024-1.pdf").display().to_string(),
+                output.join("b").join("2024-2.pdf").display().to_string(),
+            ]
+        );
+        assert!(!output.join("b").join("2023-3.pdf").exists());
+        for path in &outputs {
+            assert!(std::fs::read(path).unwrap().starts_with(b"%PDF-"));
+        }
+
+        let error = run("*/2025-*.html").unwrap_err();
+        assert!(error.to_string().contains("No files matched"), "{error:?}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "pdf_min_conversion")]
+    fn batch_json_report_has_a_line_per_file() {
+        let dir = std::env::temp_dir().join(format!("html-to-pdf-cli-json-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("in")).unwrap();
+        for file in ["a.html", "b.html"] {
+            std::fs::write(dir.join("in").join(file), "<p>Report</p>").unwrap();
+        }
+
+        let cli = Cli::parse_from([
+            "html-to-pdf",
+            "--input",
+            dir.join("in").join("*.html").to_str().unwrap(),
+            "--output",
+            dir.join("out").to_str().unwrap(),
+            "--format",
+            "json",
+            "pdf-min",
+        ]);
+        let mut reports = Vec::new();
+        convert(cli, &mut reports).unwrap();
+        let mut json = Vec::new();
+        write_json_reports(&mut json, &reports).unwrap();
+
+        let lines = String::from_utf8(json).unwrap();
+        let mut inputs = Vec::new();
+        for line in lines.lines() {
+            let json: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(json["engine"], "pdf-min");
+            assert_eq!(json["success"], true);
+            assert_eq!(json["output"].as_array().unwrap().len(), 1);
+            assert!(json["bytes"].as_u64().unwrap() > 0);
+            inputs.push(json["input"].as_str().unwrap().to_owned());
+        }
+        inputs.sort();
+        assert_eq!(
+            inputs,
+            ["a.html", "b.html"].map(|file| dir.join("in").join(file).display().to_string())
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "pdf_min_conversion")]
+    fn batch_failures_stop_or_continue() {
+        let dir =
+            std::env::temp_dir().join(format!("html-to-pdf-cli-batch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("in")).unwrap();
+        std::fs::write(dir.join("in").join("a.html"), "<p>A</p>").unwrap();
+        // Larger than --max-input-size so that it fails to convert:
+        std::fs::write(dir.join("in").join("b.html"), "<p>B</p>".repeat(1000)).unwrap();
+        std::fs::write(dir.join("in").join("c.html"), "<p>C</p>").unwrap();
+
+        let run = |mode: &str| {
+            let output = dir.join(mode.trim_start_matches('-'));
+            let cli = Cli::parse_from([
+                "html-to-pdf",
+                "--input",
+                dir.join("in").join("*.html").to_str().unwrap(),
+                "--output",
+                output.to_str().unwrap(),
+                "--max-input-size",
+                "1024",
+                mode,
+                "pdf-min",
+            ]);
+            let error = convert(cli, &mut Vec::new()).unwrap_err();
+            let converted = ["a.pdf", "c.pdf"].map(|file| {
+                std::fs::read(output.join(file)).is_ok_and(|pdf| pdf.starts_with(b"%PDF-"))
+            });
+            (error, converted)
+        };
+
+        let (error, converted) = run("--fail-fast");
+        assert!(is_input_too_large(&error), "{error:?}");
+        assert_eq!(converted, [true, false]);
+
+        let (error, converted) = run("--keep-going");
+        assert!(
+            error.to_string().starts_with("1 of 3 files failed"),
+            "{error:?}"
+        );
+        assert!(error.to_string().contains("b.html"), "{error:?}");
+        assert_eq!(converted, [true, true]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn page_setup_is_parsed() {
+        let cli = Cli::parse_from([
+            "html-to-pdf",
+            "--stdin",
+            "--stdout",
+            "--paper",
+            "210mm x 297mm",
+            "--orientation",
+            "landscape",
+            "--margin",
+            "1cm 2cm",
+            "chromiumoxide",
+        ]);
+        assert_eq!(cli.paper, Some("210mm x 297mm".parse().unwrap()));
+        assert_eq!(cli.orientation, Some(PageOrientation::Landscape));
+        assert_eq!(cli.margin, Some("1cm 2cm 1cm 2cm".parse().unwrap()));
+
+        let result = Cli::try_parse_from([
+            "html-to-pdf",
+            "--stdin",
+            "--stdout",
+            "--paper",
+            "B5",
+            "chromiumoxide",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "chromiumoxide_conversion")]
+    fn verbose_options_include_paper_size() {
+        let cli = Cli::parse_from([
+            "html-to-pdf",
+            "--stdin",
+            "--stdout",
+            "--verbose",
+            "--paper",
+            "a4",
+            "chromiumoxide",
+        ]);
+        assert!(cli.verbose);
+
+        let page_setup = PageSetup {
+            paper: cli.paper,
+            ..PageSetup::default()
+        };
+        let converter = chromiumoxide_converter(page_setup, None, &cli.engine_args);
+        let message = options_message("chromiumoxide", &converter);
+        assert!(message.starts_with("Starting chromiumoxide with options:"));
+        for expected in ["paper_width", "8.27", "paper_height", "11.69"] {
+            assert!(message.contains(expected), "{message}");
+        }
+    }
+
+    /// Convert a document with several pages and keep only its second page.
+    #[cfg(any(feature = "pdf_min_conversion", feature = "chromiumoxide_conversion"))]
+    fn convert_second_page(engine: &str) -> Vec<u8> {
+        let dir = std::env::temp_dir().join(format!(
+            "html-to-pdf-cli-pages-{engine}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.html");
+        let output = dir.join("out.pdf");
+        std::fs::write(
+            &input,
+            "<p style=\"break-after: page\">Lorem ipsum dolor sit amet.</p>".repeat(200),
+        )
+        .unwrap();
+
+        let cli = Cli::parse_from([
+            "html-to-pdf",
+            "--input",
+            input.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+            "--pages",
+            "2",
+            engine,
+        ]);
+        convert(cli, &mut Vec::new()).unwrap();
+        let pdf = std::fs::read(&output).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        pdf
+    }
+
+    #[test]
+    #[cfg(feature = "pdf_min_conversion")]
+    fn pages_selects_single_page_with_pdf_min() {
+        let pdf = convert_second_page("pdf-min");
+        assert_eq!(html_to_pdf::pdf::page_count(&pdf).unwrap(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "chromiumoxide_conversion")]
+    #[ignore = "requires Chrome to be installed"]
+    fn pages_selects_single_page_with_chromiumoxide() {
+        let pdf = convert_second_page("chromiumoxide");
+        assert_eq!(html_to_pdf::pdf::page_count(&pdf).unwrap(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "pdf_min_conversion")]
+    fn pages_past_the_end_are_rejected() {
+        let cli = Cli::parse_from([
+            "html-to-pdf",
+            "--stdin",
+            "--stdout",
+            "--pages",
+            "1-3",
+            "pdf-min",
+        ]);
+        let converter = Converter {
+            method: cli.command,
+            page_setup: PageSetup::default(),
+            pages: cli.pages,
+            engine_args: Vec::new(),
+            verbose: false,
+        };
+        let mut html_sink = converter
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        let error = html_sink
+            .complete()
+            .err()
+            .expect("pages past the end should fail");
+        assert!(
+            error
+                .chain()
+                .any(|e| e.is::<html_to_pdf::PagesOutOfRangeError>()),
+            "{error:?}"
+        );
+    }
+
+    #[test]
+    fn wkhtml_temp_dir_is_parsed() {
+        let cli = Cli::parse_from([
+            "html-to-pdf",
+            "--stdin",
+            "--stdout",
+            "wkhtml",
+            "--temp-dir",
+            "/mnt/exec/tmp",
+        ]);
+        assert_eq!(
+            cli.command,
+            PdfConversionMethod::Wkhtml {
+                shelled: false,
+                temp_dir: Some(PathBuf::from("/mnt/exec/tmp")),
+            }
+        );
+    }
+}
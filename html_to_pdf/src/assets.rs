@@ -0,0 +1,192 @@
+//! Resources that the HTML references, supplied up front so that converters
+//! can render the page without fetching anything over the network.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use bytes::Bytes;
+use mime::Mime;
+
+/// Assets referenced by the HTML, keyed by their path.
+///
+/// # Path mapping
+///
+/// Each key is the URL path that the HTML uses to reference the asset,
+/// relative to the document:
+///
+/// - Segments are separated by `/`, for example `images/logo.png`. A single
+///   leading `/` is ignored so `/images/logo.png` is the same asset.
+/// - Segments can't be empty, `.` or `..`, and keys can't contain `\`, `:`,
+///   `?`, `#`, `%` or control characters. Keys are matched as is, so they
+///   should only use characters that URLs don't need to percent-encode.
+/// - The HTML should reference assets using relative URLs like
+///   `<img src="images/logo.png">`. Converters that write the assets to a
+///   folder resolve root-relative URLs like `/images/logo.png` against the
+///   file system root, so those only work with converters that serve the
+///   assets from a local server.
+pub type Assets = HashMap<String, (Bytes, Mime)>;
+
+/// The key of an asset doesn't follow the path mapping rules of [`Assets`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidAssetPathError(pub String);
+impl fmt::Display for InvalidAssetPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, r#""{}" is not a valid asset path"#, self.0)
+    }
+}
+impl Error for InvalidAssetPathError {}
+
+/// Check that `key` is a valid asset path and return it without its leading
+/// `/`.
+pub fn asset_path(key: &str) -> Result<&str, InvalidAssetPathError> {
+    let path = key.strip_prefix('/').unwrap_or(key);
+    let valid_segments = !path.is_empty()
+        && path
+            .split('/')
+            .all(|segment| !matches!(segment, "" | "." | ".."));
+    let valid_chars = !path
+        .chars()
+        .any(|c| matches!(c, '\\' | ':' | '?' | '#' | '%') || c.is_control());
+    if valid_segments && valid_chars {
+        Ok(path)
+    } else {
+        Err(InvalidAssetPathError(key.to_owned()))
+    }
+}
+
+/// A temporary folder that contains [`Assets`], for converters that load
+/// resources from the file system. The folder is deleted when this is
+/// dropped.
+#[derive(Debug)]
+pub struct AssetDir {
+    path: PathBuf,
+}
+impl AssetDir {
+    /// Write `assets` into a new folder inside the system's temp folder.
+    pub fn create(assets: &Assets) -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let temp_dir = std::env::temp_dir();
+        let dir = loop {
+            let path = temp_dir.join(format!(
+                "html_to_pdf-assets-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            match fs::create_dir(&path) {
+                Ok(()) => break Self { path },
+                // Left behind by an earlier process with the same id:
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        for (key, (data, _)) in assets {
+            let path =
+                asset_path(key).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let path = dir.path.join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, data)?;
+        }
+        Ok(dir)
+    }
+    /// The folder that contains the assets.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// A `file:` URL for the folder that ends with a `/`, so that relative
+    /// asset paths can be resolved against it.
+    pub fn base_url(&self) -> String {
+        let path = self.path.to_string_lossy().replace('\\', "/");
+        let mut url = String::from(if path.starts_with('/') {
+            "file://"
+        } else {
+            "file:///"
+        });
+        for byte in path.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                    url.push(byte as char)
+                }
+                // Keep the colon after a Windows drive letter:
+                b':' => url.push(':'),
+                _ => url.push_str(&format!("%{byte:02X}")),
+            }
+        }
+        if !url.ends_with('/') {
+            url.push('/');
+        }
+        url
+    }
+}
+impl Drop for AssetDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.path).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asset_paths_are_validated() {
+        assert_eq!(asset_path("images/logo.png"), Ok("images/logo.png"));
+        assert_eq!(asset_path("/images/logo.png"), Ok("images/logo.png"));
+        for invalid in [
+            "",
+            "/",
+            "//logo.png",
+            "images//logo.png",
+            "../logo.png",
+            "images/./logo.png",
+            "images\\logo.png",
+            "C:/logo.png",
+            "logo.png?v=1",
+            "logo%20big.png",
+        ] {
+            assert!(asset_path(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn asset_dir_contains_assets_until_dropped() {
+        let assets = Assets::from([
+            (
+                "/images/logo.png".to_owned(),
+                (Bytes::from_static(b"png data"), mime::IMAGE_PNG),
+            ),
+            (
+                "style.css".to_owned(),
+                (Bytes::from_static(b"p {}"), mime::TEXT_CSS),
+            ),
+        ]);
+        let dir = AssetDir::create(&assets).unwrap();
+        let path = dir.path().to_owned();
+        assert_eq!(
+            fs::read(path.join("images").join("logo.png")).unwrap(),
+            b"png data"
+        );
+        assert_eq!(fs::read(path.join("style.css")).unwrap(), b"p {}");
+
+        let base_url = dir.base_url();
+        assert!(base_url.starts_with("file:///"), "{base_url}");
+        assert!(base_url.ends_with('/'), "{base_url}");
+
+        drop(dir);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn invalid_asset_path_is_an_error() {
+        let assets = Assets::from([("../escape.png".to_owned(), (Bytes::new(), mime::IMAGE_PNG))]);
+        let error = AssetDir::create(&assets).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
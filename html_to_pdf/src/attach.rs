@@ -0,0 +1,157 @@
+//! Embed the source HTML in the generated PDF.
+
+use std::{
+    borrow::Cow,
+    error::Error,
+    io::{self, Write},
+};
+
+use crate::{
+    pdf, post_process::start_post_processed, ConverterCapabilities, Diagnostic, HtmlSink,
+    HtmlToPdfConverter, HtmlToPdfError, PdfPostProcessor, PdfScope, PostProcessedHtmlSink,
+    WriteBuilder, WriteBuilderSimple,
+};
+
+/// Wraps a converter and attaches the HTML that was converted to the PDF as an
+/// embedded file called `file_name`, for example to archive a document
+/// together with its source or to reproduce a conversion while debugging. See
+/// [`pdf::attach_file`].
+///
+/// The attachment is added after the conversion, so the wrapped converter can
+/// be anything but the PDF is [buffered](crate::HtmlSinkPostProcessed#buffering)
+/// and so is the HTML.
+///
+/// # Size
+///
+/// The PDF grows by about the compressed size of the HTML. Resources that the
+/// HTML references, like images and fonts, are not attached.
+#[derive(Debug, Clone)]
+pub struct AttachHtmlConverter<C> {
+    pub converter: C,
+    pub file_name: String,
+}
+impl<C> AttachHtmlConverter<C> {
+    pub fn new(converter: C, file_name: impl Into<String>) -> Self {
+        Self {
+            converter,
+            file_name: file_name.into(),
+        }
+    }
+}
+impl<'scope, W, C> HtmlToPdfConverter<'scope, W> for AttachHtmlConverter<C>
+where
+    W: WriteBuilder + Send + 'scope,
+    C: HtmlToPdfConverter<'scope, WriteBuilderSimple<Vec<u8>>>,
+    C::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    type HtmlSink = AttachHtmlSink<C::HtmlSink, W, C::Error>;
+    type Error = HtmlToPdfError;
+
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        let processor = AttachHtmlPostProcessor {
+            file_name: self.file_name,
+            html: Vec::new(),
+        };
+        Ok(AttachHtmlSink {
+            inner: start_post_processed(self.converter, scope, output, processor)?,
+        })
+    }
+
+    fn name(&self) -> Cow<'static, str> {
+        format!("attach_html({})", self.converter.name()).into()
+    }
+    fn capabilities(&self) -> ConverterCapabilities {
+        self.converter.capabilities()
+    }
+}
+
+/// The [`PdfPostProcessor`] of [`AttachHtmlConverter`], holds the HTML that
+/// has been written so far.
+#[derive(Debug, Clone)]
+pub struct AttachHtmlPostProcessor {
+    file_name: String,
+    html: Vec<u8>,
+}
+impl PdfPostProcessor for AttachHtmlPostProcessor {
+    type Error = HtmlToPdfError;
+
+    fn process(
+        self,
+        pdf: Vec<u8>,
+        _diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Vec<u8>, HtmlToPdfError> {
+        Ok(pdf::attach_file(
+            &pdf,
+            &self.file_name,
+            "text/html",
+            &self.html,
+        )?)
+    }
+}
+
+/// The [`HtmlSink`] for [`AttachHtmlConverter`].
+pub struct AttachHtmlSink<S, W, E> {
+    inner: PostProcessedHtmlSink<S, W, E, AttachHtmlPostProcessor>,
+}
+impl<S, W, E> HtmlSink<W, HtmlToPdfError> for AttachHtmlSink<S, W, E>
+where
+    S: HtmlSink<WriteBuilderSimple<Vec<u8>>, E>,
+    W: WriteBuilder,
+{
+    fn complete(self) -> Result<W, HtmlToPdfError>
+    where
+        Self: Sized,
+    {
+        self.inner.complete()
+    }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), HtmlToPdfError>
+    where
+        Self: Sized,
+    {
+        self.inner.complete_with_diagnostics()
+    }
+}
+impl<S, W, E> Write for AttachHtmlSink<S, W, E>
+where
+    S: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.inner
+            .processor_mut()
+            .html
+            .extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::BlankPdfConverter;
+
+    #[test]
+    fn html_is_embedded_in_pdf() {
+        let html = b"<h1>Title</h1><p>Hello</p>";
+        let converter = AttachHtmlConverter::new(BlankPdfConverter::new(1), "source.html");
+        assert_eq!(
+            HtmlToPdfConverter::<WriteBuilderSimple<Vec<u8>>>::name(&converter),
+            "attach_html(blank)"
+        );
+
+        let mut html_sink = converter
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        html_sink.write_all(html).unwrap();
+        let pdf = html_sink.complete().unwrap().0;
+
+        assert_eq!(pdf::page_count(&pdf).unwrap(), 1);
+        assert_eq!(
+            pdf::attachments(&pdf).unwrap(),
+            [("source.html".to_owned(), html.to_vec())]
+        );
+    }
+}
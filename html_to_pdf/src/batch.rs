@@ -0,0 +1,50 @@
+//! Convert many HTML documents in one go.
+
+use std::{
+    error::Error,
+    io::{self, Read},
+    thread,
+};
+
+use crate::{HtmlSink, HtmlToPdfConverter, HtmlToPdfError, PdfScope, WriteBuilder};
+
+/// Convert each HTML document in `inputs` and write the PDF to the output that
+/// it is paired with.
+///
+/// `converter_factory` is called once for each input to create a fresh
+/// converter, use `|| converter.clone()` to reuse the same configuration. The
+/// conversions run one after another inside a single [`thread::scope`] and a
+/// failed conversion doesn't prevent the remaining inputs from being
+/// converted.
+///
+/// The converter must work with any thread scope, so in practice the output
+/// writers need to be `'static` (for example [`std::fs::File`] or an owned
+/// buffer).
+///
+/// Returns one result per input in the same order as the inputs.
+pub fn convert_all<C, F, I, R, W>(
+    mut converter_factory: F,
+    inputs: I,
+) -> Vec<Result<W, HtmlToPdfError>>
+where
+    F: FnMut() -> C,
+    C: for<'scope> HtmlToPdfConverter<'scope, W>,
+    for<'scope> <C as HtmlToPdfConverter<'scope, W>>::Error:
+        Into<Box<dyn Error + Send + Sync + 'static>>,
+    I: IntoIterator<Item = (R, W)>,
+    R: Read,
+    W: WriteBuilder + Send,
+{
+    thread::scope(|s| {
+        inputs
+            .into_iter()
+            .map(|(mut html, output)| -> Result<W, HtmlToPdfError> {
+                let mut html_sink = converter_factory()
+                    .start(PdfScope::scoped(s), output)
+                    .map_err(HtmlToPdfError::engine)?;
+                io::copy(&mut html, &mut html_sink)?;
+                html_sink.complete().map_err(HtmlToPdfError::engine)
+            })
+            .collect()
+    })
+}
@@ -0,0 +1,200 @@
+//! Compare how fast different converters are.
+
+use std::{
+    fmt,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use crate::{HtmlSink, HtmlToPdfConverter, PdfScope, WriteBuilderSimple};
+
+/// Measurements for one converter from [`run_benchmark`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConverterBenchmark {
+    /// See [`HtmlToPdfConverter::name`].
+    pub name: String,
+    /// Wall time of each measured conversion, from
+    /// [`HtmlToPdfConverter::start`] until [`HtmlSink::complete`] returned, in
+    /// the order they were run.
+    pub durations: Vec<Duration>,
+    /// Size of the output of each measured conversion.
+    pub sizes: Vec<u64>,
+    /// The error of the conversion that failed, later iterations are skipped
+    /// for this converter.
+    pub error: Option<String>,
+}
+impl ConverterBenchmark {
+    pub fn mean_duration(&self) -> Option<Duration> {
+        let len = u32::try_from(self.durations.len())
+            .ok()
+            .filter(|&n| n > 0)?;
+        Some(self.durations.iter().sum::<Duration>() / len)
+    }
+    pub fn median_duration(&self) -> Option<Duration> {
+        let mut durations = self.durations.clone();
+        durations.sort_unstable();
+        let middle = durations.len() / 2;
+        match durations.len() {
+            0 => None,
+            len if len % 2 == 1 => Some(durations[middle]),
+            _ => Some((durations[middle - 1] + durations[middle]) / 2),
+        }
+    }
+    pub fn min_duration(&self) -> Option<Duration> {
+        self.durations.iter().min().copied()
+    }
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.durations.iter().max().copied()
+    }
+    /// Conversions per second based on the mean duration.
+    pub fn throughput(&self) -> Option<f64> {
+        Some(1.0 / self.mean_duration()?.as_secs_f64())
+    }
+    pub fn mean_size(&self) -> Option<u64> {
+        let len = u64::try_from(self.sizes.len()).ok().filter(|&n| n > 0)?;
+        Some(self.sizes.iter().sum::<u64>() / len)
+    }
+    pub fn min_size(&self) -> Option<u64> {
+        self.sizes.iter().min().copied()
+    }
+    pub fn max_size(&self) -> Option<u64> {
+        self.sizes.iter().max().copied()
+    }
+}
+
+/// The result of [`run_benchmark`]. Formats as a summary with one line for
+/// each converter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchReport {
+    /// Number of measured conversions that each converter was asked to do.
+    pub iterations: usize,
+    /// Size of the HTML that was converted.
+    pub html_bytes: u64,
+    /// One entry for each converter, in the order they were given.
+    pub converters: Vec<ConverterBenchmark>,
+}
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} iterations converting {} bytes of HTML:",
+            self.iterations, self.html_bytes
+        )?;
+        for converter in &self.converters {
+            write!(f, "{}: ", converter.name)?;
+            match (converter.mean_duration(), converter.median_duration()) {
+                (Some(mean), Some(median)) => write!(
+                    f,
+                    "mean {mean:.2?}, median {median:.2?}, {:.2} conversions/s, mean size {} bytes",
+                    converter.throughput().unwrap_or_default(),
+                    converter.mean_size().unwrap_or_default(),
+                )?,
+                _ => write!(f, "no successful conversions")?,
+            }
+            if let Some(error) = &converter.error {
+                write!(f, " (failed: {error})")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Convert `html` `iterations` times with each converter and measure how long
+/// the conversions take and how large the outputs are. Use something like an
+/// engine enum to compare different kinds of converters.
+///
+/// Each converter first does one conversion that isn't measured, since the
+/// first conversion often includes one-time costs like starting a program or
+/// loading fonts. After that the converters take turns for each iteration so
+/// that changes in system load affect all of them equally. The conversions
+/// run one at a time on the current thread and the output is kept in memory,
+/// so disk speed doesn't affect the result.
+///
+/// A converter that fails is skipped for the rest of the benchmark and its
+/// error is stored in the report.
+pub fn run_benchmark<C>(html: &[u8], converters: &[C], iterations: usize) -> BenchReport
+where
+    C: HtmlToPdfConverter<'static, WriteBuilderSimple<Vec<u8>>> + Clone,
+    C::Error: fmt::Display,
+{
+    let mut results = converters
+        .iter()
+        .map(|converter| {
+            let mut result = ConverterBenchmark {
+                name: converter.name().into_owned(),
+                durations: Vec::with_capacity(iterations),
+                sizes: Vec::with_capacity(iterations),
+                error: None,
+            };
+            // Warmup:
+            if let Err(e) = convert_once(converter.clone(), html) {
+                result.error = Some(e);
+            }
+            result
+        })
+        .collect::<Vec<_>>();
+
+    for _ in 0..iterations {
+        for (converter, result) in converters.iter().zip(&mut results) {
+            if result.error.is_some() {
+                continue;
+            }
+            let converter = converter.clone();
+            let started = Instant::now();
+            match convert_once(converter, html) {
+                Ok(pdf) => {
+                    result.durations.push(started.elapsed());
+                    result.sizes.push(pdf.len() as u64);
+                }
+                Err(e) => result.error = Some(e),
+            }
+        }
+    }
+
+    BenchReport {
+        iterations,
+        html_bytes: html.len() as u64,
+        converters: results,
+    }
+}
+
+/// Returns the output or the error message.
+fn convert_once<C>(converter: C, html: &[u8]) -> Result<Vec<u8>, String>
+where
+    C: HtmlToPdfConverter<'static, WriteBuilderSimple<Vec<u8>>>,
+    C::Error: fmt::Display,
+{
+    let mut html_sink = converter
+        .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+        .map_err(|e| e.to_string())?;
+    html_sink.write_all(html).map_err(|e| e.to_string())?;
+    let WriteBuilderSimple(pdf) = html_sink.complete().map_err(|e| e.to_string())?;
+    Ok(pdf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockConverter;
+
+    #[test]
+    fn every_iteration_is_measured() {
+        let converter = MockConverter::new();
+        let report = run_benchmark(b"<p>Hello</p>", std::slice::from_ref(&converter), 5);
+
+        // One warmup conversion:
+        assert_eq!(converter.captured_html().len(), 6);
+        assert_eq!(report.iterations, 5);
+        assert_eq!(report.html_bytes, 12);
+        let [result] = &report.converters[..] else {
+            panic!("expected one converter: {report:?}");
+        };
+        assert_eq!(result.name, "mock");
+        assert_eq!(result.durations.len(), 5);
+        assert_eq!(result.sizes, [MockConverter::fake_pdf(12).len() as u64; 5]);
+        assert_eq!(result.error, None);
+        assert!(result.median_duration() <= result.max_duration());
+        assert!(report.to_string().contains("mock: mean"), "{report}");
+    }
+}
@@ -0,0 +1,149 @@
+//! A pipe that limits how much data can be in flight between its ends, used by
+//! [`WriteStream::stream_bounded`](crate::WriteStream::stream_bounded).
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, Read, Write},
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+struct State {
+    /// Data that the reader hasn't taken yet.
+    buffer: VecDeque<u8>,
+    /// Bytes that have been written but not yet consumed by the reader, this
+    /// includes data that the reader has taken but not consumed.
+    in_flight: usize,
+    writer_closed: bool,
+    reader_closed: bool,
+}
+
+struct Shared {
+    capacity: usize,
+    state: Mutex<State>,
+    changed: Condvar,
+}
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    fn wait<'a>(&self, guard: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.changed
+            .wait(guard)
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Create a pipe where writes block while `capacity` bytes are waiting to be
+/// consumed by the reader.
+pub(crate) fn bounded_pipe(capacity: usize) -> (BoundedPipeReader, BoundedPipeWriter) {
+    assert!(
+        capacity > 0,
+        "a pipe must be able to hold at least one byte"
+    );
+    let shared = Arc::new(Shared {
+        capacity,
+        state: Mutex::new(State {
+            buffer: VecDeque::new(),
+            in_flight: 0,
+            writer_closed: false,
+            reader_closed: false,
+        }),
+        changed: Condvar::new(),
+    });
+    (
+        BoundedPipeReader {
+            shared: shared.clone(),
+            taken: Vec::new(),
+            position: 0,
+        },
+        BoundedPipeWriter { shared },
+    )
+}
+
+/// Reads data written to a [`BoundedPipeWriter`]. Reaching the end of the data
+/// means that the writer was dropped.
+pub(crate) struct BoundedPipeReader {
+    shared: Arc<Shared>,
+    /// Data taken from the shared buffer.
+    taken: Vec<u8>,
+    /// How much of `taken` has been consumed.
+    position: usize,
+}
+impl BufRead for BoundedPipeReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position == self.taken.len() {
+            let mut state = self.shared.lock();
+            while state.buffer.is_empty() && !state.writer_closed {
+                state = self.shared.wait(state);
+            }
+            self.taken.clear();
+            self.taken.extend(state.buffer.drain(..));
+            self.position = 0;
+        }
+        Ok(&self.taken[self.position..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = amt.min(self.taken.len() - self.position);
+        if amt == 0 {
+            return;
+        }
+        self.position += amt;
+        self.shared.lock().in_flight -= amt;
+        self.shared.changed.notify_all();
+    }
+}
+impl Read for BoundedPipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+impl Drop for BoundedPipeReader {
+    fn drop(&mut self) {
+        self.shared.lock().reader_closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+/// Writes data to a [`BoundedPipeReader`], writes fail with
+/// [`io::ErrorKind::BrokenPipe`] once the reader has been dropped.
+pub(crate) struct BoundedPipeWriter {
+    shared: Arc<Shared>,
+}
+impl Write for BoundedPipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut state = self.shared.lock();
+        while state.in_flight >= self.shared.capacity && !state.reader_closed {
+            state = self.shared.wait(state);
+        }
+        if state.reader_closed {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the pipe's reader was dropped",
+            ));
+        }
+        let len = buf.len().min(self.shared.capacity - state.in_flight);
+        state.buffer.extend(&buf[..len]);
+        state.in_flight += len;
+        drop(state);
+        self.shared.changed.notify_all();
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+impl Drop for BoundedPipeWriter {
+    fn drop(&mut self) {
+        self.shared.lock().writer_closed = true;
+        self.shared.changed.notify_all();
+    }
+}
@@ -0,0 +1,412 @@
+//! Reuse the output of earlier conversions of the same HTML.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, SystemTime},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    ConverterCapabilities, Diagnostic, HtmlSink, HtmlToPdfConverter, PdfScope, WriteBuilder,
+    WriteBuilderSimple,
+};
+
+/// Error returned by [`CachingConverter`].
+#[derive(Debug)]
+pub enum CacheError<E> {
+    /// Failed to read or write the cache directory.
+    Io(io::Error),
+    /// The wrapped converter failed.
+    Converter(E),
+}
+impl<E: fmt::Display> fmt::Display for CacheError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::Io(e) => write!(f, "failed to access the PDF cache: {e}"),
+            CacheError::Converter(e) => e.fmt(f),
+        }
+    }
+}
+impl<E: Error + 'static> Error for CacheError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CacheError::Io(e) => Some(e),
+            CacheError::Converter(e) => Some(e),
+        }
+    }
+}
+impl<E> From<io::Error> for CacheError<E> {
+    fn from(e: io::Error) -> Self {
+        CacheError::Io(e)
+    }
+}
+
+/// A directory with converted documents, named after the SHA-256 hash of the
+/// HTML they were converted from. Clones share the same locks, so
+/// conversions of the same HTML that run at the same time only invoke the
+/// converter once.
+///
+/// The cache doesn't know how the documents were converted, so use a separate
+/// directory for each converter configuration.
+#[derive(Debug, Clone)]
+pub struct PdfCache {
+    dir: PathBuf,
+    max_bytes: Option<u64>,
+    ttl: Option<Duration>,
+    /// One lock per key that is currently being looked up or converted.
+    locks: Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>,
+}
+impl PdfCache {
+    /// Cache documents in `dir`, it is created if it doesn't exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes: None,
+            ttl: None,
+            locks: Arc::default(),
+        }
+    }
+    /// Remove the least recently used documents when the cached documents
+    /// take up more than this many bytes.
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+    /// Convert the HTML again if the cached document was last used longer ago
+    /// than this.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+    /// The directory that documents are cached in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &str, extension: &str) -> PathBuf {
+        self.dir.join(format!("{key}.{extension}"))
+    }
+    fn is_expired(&self, modified: SystemTime) -> bool {
+        match (self.ttl, modified.elapsed()) {
+            (Some(ttl), Ok(age)) => age > ttl,
+            _ => false,
+        }
+    }
+
+    /// Run `f` while no other conversion with a clone of this cache uses the
+    /// same key.
+    fn with_key_locked<T>(&self, key: &str, f: impl FnOnce() -> T) -> T {
+        let lock = Arc::clone(
+            self.locks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(key.to_owned())
+                .or_default(),
+        );
+        let result = {
+            let _guard = lock.lock().unwrap_or_else(PoisonError::into_inner);
+            f()
+        };
+        let mut locks = self.locks.lock().unwrap_or_else(PoisonError::into_inner);
+        // Only the map and this function hold the lock, so nobody is waiting:
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(key);
+        }
+        result
+    }
+
+    /// Open the cached document for `key` and mark it as recently used, or
+    /// return `None` if there is no document or it has expired.
+    fn open(&self, key: &str, extension: &str) -> io::Result<Option<File>> {
+        let file = match File::options()
+            .read(true)
+            .write(true)
+            .open(self.path(key, extension))
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if self.is_expired(file.metadata()?.modified()?) {
+            return Ok(None);
+        }
+        // The modified time is used to find the least recently used documents:
+        file.set_modified(SystemTime::now())?;
+        Ok(Some(file))
+    }
+
+    /// Remove expired documents and then the least recently used ones until
+    /// the cache is small enough.
+    fn evict(&self, extension: &str) -> io::Result<()> {
+        if self.ttl.is_none() && self.max_bytes.is_none() {
+            return Ok(());
+        }
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let is_cached_document = path.extension().is_some_and(|ext| ext == extension)
+                && path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .is_some_and(is_key);
+            if !is_cached_document {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            entries.push((metadata.modified()?, metadata.len(), path));
+        }
+        // Most recently used first:
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.0));
+
+        let mut total = 0;
+        for (modified, len, path) in entries {
+            total += len;
+            let too_large = self.max_bytes.is_some_and(|max| total > max);
+            if too_large || self.is_expired(modified) {
+                match fs::remove_file(&path) {
+                    // Removed by another process:
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    result => result?,
+                }
+                total -= len;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `true` for the hex encoded SHA-256 hashes that documents are named after.
+fn is_key(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Wraps a converter and reuses the documents that it created earlier for
+/// identical HTML, see [`PdfCache`].
+///
+/// The HTML is hashed and buffered in memory while it is written, so the
+/// wrapped converter is only started once the [`HtmlSink`] is completed and
+/// the cache didn't have a document for the HTML. Since that happens after
+/// [`HtmlToPdfConverter::start`] has returned this is only implemented for
+/// the `'static` scope and the conversion spawns threads with
+/// [`PdfScope::owned`].
+///
+/// A new document is written to a temporary file that is renamed once it is
+/// complete, so other processes that share the directory never see partial
+/// documents.
+#[derive(Debug, Clone)]
+pub struct CachingConverter<C> {
+    pub converter: C,
+    pub cache: PdfCache,
+}
+impl<C> CachingConverter<C> {
+    pub fn new(converter: C, cache: PdfCache) -> Self {
+        Self { converter, cache }
+    }
+}
+impl<W, C> HtmlToPdfConverter<'static, W> for CachingConverter<C>
+where
+    W: WriteBuilder + Send + 'static,
+    C: HtmlToPdfConverter<'static, WriteBuilderSimple<File>>,
+{
+    type HtmlSink = CachingHtmlSink<C, W>;
+    type Error = CacheError<C::Error>;
+
+    fn start(
+        self,
+        _scope: PdfScope<'static, '_>,
+        output: W,
+    ) -> Result<Self::HtmlSink, Self::Error> {
+        Ok(CachingHtmlSink {
+            converter: self.converter,
+            cache: self.cache,
+            hasher: Sha256::new(),
+            html: Vec::new(),
+            output,
+        })
+    }
+
+    fn output_content_type(&self) -> &'static str {
+        self.converter.output_content_type()
+    }
+    fn output_extension(&self) -> &'static str {
+        self.converter.output_extension()
+    }
+    fn name(&self) -> Cow<'static, str> {
+        format!("cache({})", self.converter.name()).into()
+    }
+    /// The HTML is buffered to find its cache entry, so it is never streamed.
+    fn capabilities(&self) -> ConverterCapabilities {
+        ConverterCapabilities {
+            streaming_input: false,
+            ..self.converter.capabilities()
+        }
+    }
+}
+
+/// The [`HtmlSink`] for [`CachingConverter`].
+pub struct CachingHtmlSink<C, W> {
+    converter: C,
+    cache: PdfCache,
+    hasher: Sha256,
+    html: Vec<u8>,
+    output: W,
+}
+impl<C, W> CachingHtmlSink<C, W>
+where
+    C: HtmlToPdfConverter<'static, WriteBuilderSimple<File>>,
+{
+    /// Convert the HTML into a new cache entry at `path`.
+    fn convert(self, path: &Path) -> Result<(W, File, Vec<Diagnostic>), CacheError<C::Error>> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let temp_path = path.with_extension(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = (|| -> Result<_, CacheError<C::Error>> {
+            let file = File::create(&temp_path)?;
+            let mut html_sink = self
+                .converter
+                .start(PdfScope::owned(), WriteBuilderSimple(file))
+                .map_err(CacheError::Converter)?;
+            html_sink.write_all(&self.html)?;
+            let (WriteBuilderSimple(mut file), diagnostics) = html_sink
+                .complete_with_diagnostics()
+                .map_err(CacheError::Converter)?;
+            file.flush()?;
+            drop(file);
+            fs::rename(&temp_path, path)?;
+            Ok(diagnostics)
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        Ok((self.output, File::open(path)?, result?))
+    }
+}
+impl<C, W> HtmlSink<W, CacheError<C::Error>> for CachingHtmlSink<C, W>
+where
+    W: WriteBuilder,
+    C: HtmlToPdfConverter<'static, WriteBuilderSimple<File>>,
+{
+    fn complete(self) -> Result<W, CacheError<C::Error>>
+    where
+        Self: Sized,
+    {
+        self.complete_with_diagnostics().map(|(writer, _)| writer)
+    }
+    /// Diagnostics are only returned when the wrapped converter was invoked,
+    /// they aren't cached.
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), CacheError<C::Error>>
+    where
+        Self: Sized,
+    {
+        let key = self
+            .hasher
+            .clone()
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        let extension = self.converter.output_extension();
+        let cache = self.cache.clone();
+        let path = cache.path(&key, extension);
+
+        let (mut output, mut file, diagnostics) = cache.with_key_locked(&key, || {
+            fs::create_dir_all(cache.dir())?;
+            match cache.open(&key, extension)? {
+                Some(file) => Ok((self.output, file, Vec::new())),
+                None => self.convert(&path),
+            }
+        })?;
+        io::copy(&mut file, &mut output.get_writer()?)?;
+        cache.evict(extension)?;
+        Ok((output, diagnostics))
+    }
+}
+impl<C, W> Write for CachingHtmlSink<C, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.hasher.update(buf);
+        self.html.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockConverter;
+
+    fn convert(converter: &CachingConverter<MockConverter>, html: &[u8]) -> Vec<u8> {
+        let mut html_sink = converter
+            .clone()
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        html_sink.write_all(html).unwrap();
+        html_sink.complete().unwrap().0
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("html_to_pdf-cache-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn second_conversion_is_served_from_cache() {
+        let dir = test_dir("hit");
+        let mock = MockConverter::new();
+        let converter = CachingConverter::new(mock.clone(), PdfCache::new(&dir));
+
+        let miss = convert(&converter, b"<p>Hello</p>");
+        let hit = convert(&converter, b"<p>Hello</p>");
+        assert_eq!(miss, MockConverter::fake_pdf(12));
+        assert_eq!(hit, miss);
+        assert_eq!(mock.captured_html().len(), 1, "converted only once");
+
+        convert(&converter, b"<p>Other</p>");
+        assert_eq!(mock.captured_html().len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn least_recently_used_documents_are_evicted() {
+        let dir = test_dir("evict");
+        let mock = MockConverter::new();
+        // Room for two of the fake PDFs:
+        let max_bytes = 2 * MockConverter::fake_pdf(3).len() as u64;
+        let converter =
+            CachingConverter::new(mock.clone(), PdfCache::new(&dir).max_bytes(max_bytes));
+
+        for html in [b"<a>", b"<b>", b"<c>"] {
+            convert(&converter, html);
+            // Make sure that the documents have different modified times:
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // "<a>" was evicted so it is converted again:
+        convert(&converter, b"<a>");
+        assert_eq!(mock.captured_html().len(), 4);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
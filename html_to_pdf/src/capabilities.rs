@@ -0,0 +1,83 @@
+//! Describe which features a converter supports.
+
+/// Features that a converter supports, see
+/// [`HtmlToPdfConverter::capabilities`](crate::HtmlToPdfConverter::capabilities).
+///
+/// Use [`ConverterCapabilities::supports`] to pick a converter that has the
+/// features that are needed:
+///
+/// ```
+/// # use html_to_pdf::ConverterCapabilities;
+/// let required = ConverterCapabilities {
+///     clickable_links: true,
+///     ..Default::default()
+/// };
+/// let pdf_min = ConverterCapabilities::default();
+/// assert!(!pdf_min.supports(&required));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ConverterCapabilities {
+    /// The PDF has an outline (bookmarks) for the HTML's headings that PDF
+    /// viewers show as a table of contents.
+    pub table_of_contents: bool,
+    /// `<a>` tags become links that can be clicked in the PDF.
+    pub clickable_links: bool,
+    /// HTML is passed on to the engine while it is written to the
+    /// [`HtmlSink`](crate::HtmlSink) instead of being buffered until the sink
+    /// is completed.
+    pub streaming_input: bool,
+    /// Fonts are embedded in the PDF, so it looks the same on computers that
+    /// don't have the fonts installed.
+    pub embedded_fonts: bool,
+}
+impl ConverterCapabilities {
+    /// `true` if every capability in `required` is supported.
+    pub fn supports(&self, required: &Self) -> bool {
+        self.intersection(required) == *required
+    }
+    /// The capabilities that both `self` and `other` have, for example for a
+    /// converter that might use either of two converters.
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self {
+            table_of_contents: self.table_of_contents && other.table_of_contents,
+            clickable_links: self.clickable_links && other.clickable_links,
+            streaming_input: self.streaming_input && other.streaming_input,
+            embedded_fonts: self.embedded_fonts && other.embedded_fonts,
+        }
+    }
+    /// The capabilities that `self` or `other` have.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            table_of_contents: self.table_of_contents || other.table_of_contents,
+            clickable_links: self.clickable_links || other.clickable_links,
+            streaming_input: self.streaming_input || other.streaming_input,
+            embedded_fonts: self.embedded_fonts || other.embedded_fonts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supports_checks_every_required_capability() {
+        let chrome_like = ConverterCapabilities {
+            clickable_links: true,
+            embedded_fonts: true,
+            ..Default::default()
+        };
+        let links = ConverterCapabilities {
+            clickable_links: true,
+            ..Default::default()
+        };
+        let links_and_toc = ConverterCapabilities {
+            table_of_contents: true,
+            ..links
+        };
+        assert!(chrome_like.supports(&links));
+        assert!(chrome_like.supports(&ConverterCapabilities::default()));
+        assert!(!chrome_like.supports(&links_and_toc));
+        assert_eq!(chrome_like.intersection(&links_and_toc), links);
+    }
+}
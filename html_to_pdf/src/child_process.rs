@@ -0,0 +1,238 @@
+//! Options for engines that run a separate program.
+
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+    process::{Child, Command, ExitStatus},
+};
+
+/// Windows process creation flag that starts a console program without a
+/// console window. Unlike `DETACHED_PROCESS` it is also inherited by any
+/// programs that the child starts itself, see
+/// <https://learn.microsoft.com/en-us/windows/win32/procthread/process-creation-flags>.
+pub const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// The Windows process creation flags for a child program that should
+/// (`hide_window = true`) or shouldn't have a console window.
+pub fn console_creation_flags(hide_window: bool) -> u32 {
+    if hide_window {
+        CREATE_NO_WINDOW
+    } else {
+        0
+    }
+}
+
+/// Hide the console window of a child program on Windows, which otherwise
+/// pops up when the current program is a GUI program without a console. Pass
+/// `false` to keep it, for example to see the child's output while
+/// debugging. Does nothing on other platforms.
+pub fn hide_console_window(command: &mut Command, hide_window: bool) {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+
+        command.creation_flags(console_creation_flags(hide_window));
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = (command, hide_window);
+    }
+}
+
+/// A running child program that is killed and waited for if this is dropped
+/// before the program has exited, so that cancelled conversions don't leave
+/// programs running, see the "Dropping" section of [`HtmlSink`](crate::HtmlSink).
+#[derive(Debug)]
+pub struct ChildGuard {
+    child: Child,
+    exited: bool,
+}
+impl ChildGuard {
+    /// The OS-assigned process identifier of the program.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+    /// Wait for the program to exit.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        let status = self.child.wait()?;
+        self.exited = true;
+        Ok(status)
+    }
+    /// Check if the program has exited without blocking.
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        let status = self.child.try_wait()?;
+        self.exited |= status.is_some();
+        Ok(status)
+    }
+    /// Stop the program. Use [`wait`](Self::wait) afterwards to get its exit
+    /// status.
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()
+    }
+}
+impl From<Child> for ChildGuard {
+    fn from(child: Child) -> Self {
+        Self {
+            child,
+            exited: false,
+        }
+    }
+}
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if !self.exited {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// A separate program that an engine ran exited with an error.
+///
+/// Adapters wrap this in their own error type, use
+/// [`Error::source`] or `eyre::Report::downcast_ref` to find it, for example
+/// to handle specific exit codes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessError {
+    /// The name of the program that failed.
+    pub program: String,
+    /// The program's exit code, `None` if it was terminated by a signal.
+    pub code: Option<i32>,
+    /// The signal that terminated the program, always `None` on Windows.
+    pub signal: Option<i32>,
+    /// The end of what the program wrote to stderr, at most
+    /// [`STDERR_TAIL_LEN`](Self::STDERR_TAIL_LEN) bytes. Empty if stderr wasn't
+    /// captured.
+    pub stderr: String,
+}
+impl ProcessError {
+    /// The number of bytes from the end of stderr that are kept by
+    /// [`forward_stderr`].
+    pub const STDERR_TAIL_LEN: usize = 4096;
+
+    pub fn new(program: impl Into<String>, status: ExitStatus) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+        #[cfg(not(unix))]
+        let signal = None;
+        Self {
+            program: program.into(),
+            code: status.code(),
+            signal,
+            stderr: String::new(),
+        }
+    }
+    /// Include the end of the program's stderr, see [`forward_stderr`].
+    pub fn with_stderr(mut self, stderr: impl Into<String>) -> Self {
+        self.stderr = stderr.into();
+        self
+    }
+}
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, r#""{}" exited with an error"#, self.program)?;
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, " (code: {code})")?,
+            (None, Some(signal)) => write!(f, " (signal: {signal})")?,
+            (None, None) => write!(f, " (no exit code)")?,
+        }
+        let stderr = self.stderr.trim();
+        if !stderr.is_empty() {
+            write!(f, ", stderr:\n{stderr}")?;
+        }
+        Ok(())
+    }
+}
+impl Error for ProcessError {}
+
+/// Copy everything that a child program writes to stderr to `forward_to`,
+/// usually the current program's stderr so that it is still visible to users,
+/// and return the last [`ProcessError::STDERR_TAIL_LEN`] bytes of it once the
+/// program closes its stderr. Usually called on a separate thread.
+pub fn forward_stderr(mut stderr: impl Read, mut forward_to: impl Write) -> String {
+    let mut tail = Vec::new();
+    let mut buffer = [0; 1024];
+    loop {
+        let len = match stderr.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        let _ = forward_to.write_all(&buffer[..len]);
+        tail.extend_from_slice(&buffer[..len]);
+        if tail.len() > ProcessError::STDERR_TAIL_LEN {
+            tail.drain(..tail.len() - ProcessError::STDERR_TAIL_LEN);
+        }
+    }
+    String::from_utf8_lossy(&tail).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hide_window_toggles_creation_flags() {
+        assert_eq!(console_creation_flags(true), CREATE_NO_WINDOW);
+        assert_eq!(console_creation_flags(false), 0);
+
+        // Only observable on Windows, but should work everywhere:
+        let mut command = Command::new("program");
+        hide_console_window(&mut command, true);
+        hide_console_window(&mut command, false);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn dropped_guard_stops_program() {
+        let is_running = |id: u32| {
+            Command::new("kill")
+                .args(["-0", &id.to_string()])
+                .stderr(std::process::Stdio::null())
+                .status()
+                .unwrap()
+                .success()
+        };
+        let guard = ChildGuard::from(Command::new("sleep").arg("30").spawn().unwrap());
+        let id = guard.id();
+        assert!(is_running(id));
+
+        drop(guard);
+        assert!(!is_running(id));
+    }
+
+    #[test]
+    fn failed_program_reports_exit_code_and_stderr() {
+        let mut command = if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.args(["/C", "echo oops 1>&2 & exit 3"]);
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.args(["-c", "echo oops >&2; exit 3"]);
+            command
+        };
+        let mut child = command
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stderr = forward_stderr(child.stderr.take().unwrap(), io::sink());
+        let error = ProcessError::new("shell", child.wait().unwrap()).with_stderr(stderr);
+
+        assert_eq!(error.code, Some(3));
+        assert_eq!(error.signal, None);
+        assert_eq!(error.stderr.trim(), "oops");
+        assert!(error.to_string().contains("(code: 3)"), "{error}");
+    }
+
+    #[test]
+    fn stderr_tail_is_limited() {
+        let stderr = vec![b'a'; ProcessError::STDERR_TAIL_LEN * 3];
+        assert_eq!(
+            forward_stderr(&stderr[..], io::sink()).len(),
+            ProcessError::STDERR_TAIL_LEN
+        );
+    }
+}
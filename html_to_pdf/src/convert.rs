@@ -0,0 +1,84 @@
+//! Convert a single HTML document without setting up the conversion by hand.
+
+use std::io::{self, Read, Write};
+
+use crate::{HtmlSink, HtmlToPdfConverter, PdfScope, WriteBuilderSimple};
+
+/// Convert all HTML from `html` and write the PDF to `output`, which is
+/// returned once the conversion has completed:
+///
+/// ```no_run
+/// # use html_to_pdf::HtmlToPdfConverter;
+/// # fn f<C>(converter: C) -> Result<(), C::Error>
+/// # where
+/// #     C: HtmlToPdfConverter<'static, html_to_pdf::WriteBuilderSimple<std::fs::File>>,
+/// #     C::Error: From<std::io::Error>,
+/// # {
+/// let html = std::fs::File::open("input.html")?;
+/// let output = std::fs::File::create("output.pdf")?;
+/// html_to_pdf::convert(converter, html, output)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Threads are spawned using [`PdfScope::owned`], so `output` must be
+/// `'static`. Use [`convert_scoped`] to write to borrowed data. Failures to
+/// read the HTML are converted into the converter's error type.
+pub fn convert<C, R, W>(converter: C, html: R, output: W) -> Result<W, C::Error>
+where
+    C: HtmlToPdfConverter<'static, WriteBuilderSimple<W>>,
+    C::Error: From<io::Error>,
+    R: Read,
+    W: Write + Send + 'static,
+{
+    convert_scoped(PdfScope::owned(), converter, html, output)
+}
+
+/// Like [`convert`] but spawns threads using `scope`, for example one created
+/// with [`PdfScope::scoped`] so that `output` can borrow local data.
+pub fn convert_scoped<'scope, C, R, W>(
+    scope: PdfScope<'scope, '_>,
+    converter: C,
+    mut html: R,
+    output: W,
+) -> Result<W, C::Error>
+where
+    C: HtmlToPdfConverter<'scope, WriteBuilderSimple<W>>,
+    C::Error: From<io::Error>,
+    R: Read,
+    W: Write + Send + 'scope,
+{
+    let mut html_sink = converter.start(scope, WriteBuilderSimple(output))?;
+    io::copy(&mut html, &mut html_sink)?;
+    Ok(html_sink.complete()?.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockConverter;
+    use std::thread;
+
+    #[test]
+    fn converts_html_from_reader() {
+        let converter = MockConverter::new();
+        let pdf = convert(converter.clone(), &b"<p>Hello</p>"[..], Vec::new()).unwrap();
+        assert_eq!(pdf, MockConverter::fake_pdf(12));
+        assert_eq!(converter.last_html().unwrap(), b"<p>Hello</p>");
+    }
+
+    #[test]
+    fn scoped_conversion_can_borrow_output() {
+        let mut pdf = Vec::new();
+        thread::scope(|s| {
+            convert_scoped(
+                PdfScope::scoped(s),
+                MockConverter::new(),
+                &b"<p>Hi</p>"[..],
+                &mut pdf,
+            )
+            .unwrap();
+        });
+        assert_eq!(pdf, MockConverter::fake_pdf(9));
+    }
+}
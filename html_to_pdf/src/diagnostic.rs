@@ -0,0 +1,49 @@
+//! Non-fatal problems that converters report while converting.
+
+use std::fmt;
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DiagnosticLevel {
+    Warning,
+    /// Something failed, for example a resource couldn't be loaded, but a PDF
+    /// was still generated.
+    Error,
+}
+impl fmt::Display for DiagnosticLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DiagnosticLevel::Warning => "warning",
+            DiagnosticLevel::Error => "error",
+        })
+    }
+}
+
+/// A problem that a conversion engine reported without failing the
+/// conversion, for example a missing image. Returned by
+/// [`HtmlSink::complete_with_diagnostics`](crate::HtmlSink::complete_with_diagnostics).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub level: DiagnosticLevel,
+    /// The message as reported by the engine.
+    pub message: String,
+}
+impl Diagnostic {
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            level: DiagnosticLevel::Warning,
+            message: message.into(),
+        }
+    }
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            level: DiagnosticLevel::Error,
+            message: message.into(),
+        }
+    }
+}
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.level, self.message)
+    }
+}
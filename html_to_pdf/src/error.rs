@@ -0,0 +1,198 @@
+//! An error type that can represent failures from any converter.
+
+use std::{any::Any, error::Error, fmt, io};
+
+/// An error that can be returned by any HTML to PDF converter.
+///
+/// Generic code that works with many different converters can map their
+/// errors into this type, for example by using
+/// [`HtmlSink::map_completion_err`](crate::HtmlSink::map_completion_err) with
+/// [`HtmlToPdfError::engine`]. The original error is preserved and can be
+/// retrieved using [`Error::source`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum HtmlToPdfError {
+    /// Failed to read HTML data or to write PDF data.
+    Io(io::Error),
+    /// The engine that generated the PDF reported an error.
+    Engine(Box<dyn Error + Send + Sync + 'static>),
+    /// The conversion was stopped using a [`CancelToken`](crate::CancelToken).
+    Cancelled,
+}
+impl HtmlToPdfError {
+    /// Wrap an error that was reported by a PDF conversion engine. Errors
+    /// that were caused by [`Cancelled`] become [`HtmlToPdfError::Cancelled`].
+    pub fn engine(error: impl Into<Box<dyn Error + Send + Sync + 'static>>) -> Self {
+        let error = error.into();
+        if is_cancellation(&*error) {
+            Self::Cancelled
+        } else {
+            Self::Engine(error)
+        }
+    }
+}
+/// `true` if `error` or one of its sources is [`Cancelled`], also when it is
+/// wrapped inside an [`io::Error`].
+fn is_cancellation(error: &(dyn Error + 'static)) -> bool {
+    let mut next = Some(error);
+    while let Some(error) = next {
+        let io_inner = error
+            .downcast_ref::<io::Error>()
+            .and_then(io::Error::get_ref);
+        if error.is::<Cancelled>()
+            || io_inner.is_some_and(|inner| inner.is::<Cancelled>())
+            || matches!(error.downcast_ref(), Some(HtmlToPdfError::Cancelled))
+        {
+            return true;
+        }
+        next = error.source();
+    }
+    false
+}
+impl fmt::Display for HtmlToPdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HtmlToPdfError::Io(_) => write!(f, "failed to read HTML or to write PDF data"),
+            HtmlToPdfError::Engine(_) => write!(f, "the HTML to PDF conversion failed"),
+            HtmlToPdfError::Cancelled => write!(f, "the HTML to PDF conversion was cancelled"),
+        }
+    }
+}
+impl Error for HtmlToPdfError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HtmlToPdfError::Io(e) => Some(e),
+            #[cfg(feature = "eyre")]
+            HtmlToPdfError::Engine(e) if e.is::<EyreError>() => {
+                e.downcast_ref::<EyreError>().map(EyreError::inner)
+            }
+            HtmlToPdfError::Engine(e) => Some(&**e),
+            HtmlToPdfError::Cancelled => None,
+        }
+    }
+}
+impl From<io::Error> for HtmlToPdfError {
+    fn from(value: io::Error) -> Self {
+        if is_cancellation(&value) {
+            Self::Cancelled
+        } else {
+            Self::Io(value)
+        }
+    }
+}
+impl From<Cancelled> for HtmlToPdfError {
+    fn from(_: Cancelled) -> Self {
+        Self::Cancelled
+    }
+}
+#[cfg(feature = "eyre")]
+impl From<eyre::Report> for HtmlToPdfError {
+    fn from(value: eyre::Report) -> Self {
+        let error = EyreError(value);
+        if is_cancellation(error.inner()) {
+            Self::Cancelled
+        } else {
+            Self::Engine(Box::new(error))
+        }
+    }
+}
+
+/// Keeps an [`eyre::Report`] inside [`HtmlToPdfError::Engine`]. Converting a
+/// report into a boxed error hides the error that it wraps from downcasting,
+/// so [`HtmlToPdfError::source`](Error::source) returns that error instead.
+#[cfg(feature = "eyre")]
+struct EyreError(eyre::Report);
+#[cfg(feature = "eyre")]
+impl EyreError {
+    fn inner(&self) -> &(dyn Error + 'static) {
+        &*self.0
+    }
+}
+#[cfg(feature = "eyre")]
+impl fmt::Debug for EyreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+#[cfg(feature = "eyre")]
+impl fmt::Display for EyreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+#[cfg(feature = "eyre")]
+impl Error for EyreError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.inner())
+    }
+}
+#[cfg(feature = "chromiumoxide")]
+impl From<chromiumoxide::error::CdpError> for HtmlToPdfError {
+    fn from(value: chromiumoxide::error::CdpError) -> Self {
+        Self::engine(value)
+    }
+}
+#[cfg(feature = "lopdf")]
+impl From<lopdf::Error> for HtmlToPdfError {
+    fn from(value: lopdf::Error) -> Self {
+        Self::engine(value)
+    }
+}
+
+/// A [`CancelToken`](crate::CancelToken) was cancelled while a conversion
+/// was running. Converters whose error type is [`io::Error`] return this
+/// wrapped inside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the conversion was cancelled")
+    }
+}
+impl Error for Cancelled {}
+impl From<Cancelled> for io::Error {
+    fn from(value: Cancelled) -> Self {
+        io::Error::other(value)
+    }
+}
+
+/// A thread that a converter spawned panicked, for example while it was
+/// generating the PDF. Returned by [`WriteStream::join_result`](crate::WriteStream::join_result)
+/// and [`PdfScopedJoinHandle::join_result`](crate::PdfScopedJoinHandle::join_result).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerPanicked {
+    message: Option<String>,
+}
+impl WorkerPanicked {
+    /// Create an error from the payload that a panicking thread was joined
+    /// with, see [`panic_message`](crate::panic_message).
+    pub fn new(payload: &(dyn Any + Send)) -> Self {
+        Self {
+            message: crate::panic_message(payload).map(str::to_owned),
+        }
+    }
+    /// The message that the thread panicked with, if it was created by
+    /// `panic!` with a string message.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+impl fmt::Display for WorkerPanicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "a worker thread panicked: {message}"),
+            None => write!(f, "a worker thread panicked"),
+        }
+    }
+}
+impl Error for WorkerPanicked {}
+impl From<WorkerPanicked> for io::Error {
+    fn from(value: WorkerPanicked) -> Self {
+        io::Error::other(value)
+    }
+}
+impl From<WorkerPanicked> for HtmlToPdfError {
+    fn from(value: WorkerPanicked) -> Self {
+        Self::engine(value)
+    }
+}
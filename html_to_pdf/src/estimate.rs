@@ -0,0 +1,208 @@
+//! Estimate how many pages a conversion will produce.
+
+use std::{
+    error::Error,
+    io::{self, Write},
+};
+
+use crate::{HtmlToPdfConverter, HtmlToPdfError, WriteBuilderSimple};
+
+/// Estimate how many pages the PDF for `html` would have, see
+/// [`HtmlToPdfConverter::estimate_pages`].
+///
+/// This picks the output type that the estimate is done with, which the trait
+/// method can't infer by itself.
+pub fn estimate_pages<C>(converter: C, html: &[u8]) -> Result<u32, HtmlToPdfError>
+where
+    C: HtmlToPdfConverter<'static, WriteBuilderSimple<PdfPageCounter>>,
+    C::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    converter.estimate_pages(html)
+}
+
+/// Counts the pages of PDF data that is written to it without keeping the
+/// data around.
+///
+/// Pages are found by looking for `/Type /Page` in the document. Pages inside
+/// compressed object streams can't be seen, so documents that use those are
+/// counted as having fewer pages than they have.
+#[derive(Debug, Clone, Default)]
+pub struct PdfPageCounter {
+    pages: u32,
+    state: CounterState,
+}
+#[derive(Debug, Clone, Copy, Default)]
+enum CounterState {
+    /// No match in progress.
+    #[default]
+    Start,
+    /// Matched this many bytes of `/Type`.
+    Type(usize),
+    /// Found `/Type`, skipping whitespace before its value.
+    Value,
+    /// Matched this many bytes of `/Page`.
+    Page(usize),
+    /// Found `/Type /Page`, but the next byte could continue the name, for
+    /// example `/Type /Pages`.
+    Found,
+}
+impl PdfPageCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// The number of pages that were found in the data written so far.
+    pub fn pages(&self) -> u32 {
+        self.pages
+    }
+
+    fn push(&mut self, byte: u8) {
+        const TYPE: &[u8] = b"/Type";
+        const PAGE: &[u8] = b"/Page";
+
+        self.state = match self.state {
+            CounterState::Type(i) if byte == TYPE[i] => {
+                if i + 1 == TYPE.len() {
+                    CounterState::Value
+                } else {
+                    CounterState::Type(i + 1)
+                }
+            }
+            CounterState::Value if byte.is_ascii_whitespace() => CounterState::Value,
+            CounterState::Value if byte == b'/' => CounterState::Page(1),
+            CounterState::Page(i) if byte == PAGE[i] => {
+                if i + 1 == PAGE.len() {
+                    CounterState::Found
+                } else {
+                    CounterState::Page(i + 1)
+                }
+            }
+            CounterState::Found if is_regular_char(byte) => CounterState::Start,
+            CounterState::Found => {
+                self.pages += 1;
+                CounterState::restart(byte)
+            }
+            _ => CounterState::restart(byte),
+        };
+    }
+}
+impl CounterState {
+    /// The state after `byte` when no match is in progress.
+    fn restart(byte: u8) -> Self {
+        if byte == b'/' {
+            CounterState::Type(1)
+        } else {
+            CounterState::Start
+        }
+    }
+}
+impl Write for PdfPageCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.push(byte);
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Bytes that aren't whitespace or delimiters in PDF syntax and can therefore
+/// be part of a name.
+fn is_regular_char(byte: u8) -> bool {
+    !byte.is_ascii_whitespace()
+        && !matches!(
+            byte,
+            b'\0' | b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HtmlSink, PdfScope, WriteBuilder};
+
+    /// A minimal PDF with the specified number of pages.
+    fn fake_pdf(pages: usize) -> Vec<u8> {
+        let mut pdf = b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n\
+            2 0 obj\n<</Type/Pages/Count 0/Kids[]>>\nendobj\n"
+            .to_vec();
+        for page in 0..pages {
+            write!(
+                pdf,
+                "{} 0 obj\n<</Type/Page/Parent 2 0 R>>\nendobj\n",
+                page + 3
+            )
+            .unwrap();
+        }
+        pdf.extend_from_slice(b"%%EOF\n");
+        pdf
+    }
+
+    #[test]
+    fn pages_are_counted_across_writes() {
+        let pdf = fake_pdf(3);
+        for chunk_size in [1, 2, 7, pdf.len()] {
+            let mut counter = PdfPageCounter::new();
+            for chunk in pdf.chunks(chunk_size) {
+                counter.write_all(chunk).unwrap();
+            }
+            assert_eq!(counter.pages(), 3, "chunk size {chunk_size}");
+        }
+    }
+
+    #[test]
+    fn estimate_is_close_to_actual_page_count() {
+        /// Creates one page per paragraph.
+        struct ParagraphConverter;
+        impl<'scope, W> HtmlToPdfConverter<'scope, W> for ParagraphConverter
+        where
+            W: WriteBuilder + Send + 'scope,
+        {
+            type HtmlSink = ParagraphHtmlSink<W>;
+            type Error = io::Error;
+
+            fn start(self, _scope: PdfScope<'scope, '_>, output: W) -> io::Result<Self::HtmlSink> {
+                Ok(ParagraphHtmlSink(Vec::new(), output))
+            }
+        }
+        struct ParagraphHtmlSink<W>(Vec<u8>, W);
+        crate::forward_write!(impl[W] for ParagraphHtmlSink<W>, |this| &mut this.0);
+        impl<W: WriteBuilder> HtmlSink<W, io::Error> for ParagraphHtmlSink<W> {
+            fn complete(mut self) -> io::Result<W> {
+                let pages = self.0.windows(3).filter(|w| w == b"<p>").count();
+                self.1.get_writer()?.write_all(&fake_pdf(pages))?;
+                Ok(self.1)
+            }
+        }
+
+        let html = b"<h1>Title</h1><p>One</p><p>Two</p><p>Three</p><p>Four</p>";
+        let actual = {
+            let mut html_sink = ParagraphConverter
+                .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+                .unwrap();
+            html_sink.write_all(html).unwrap();
+            let pdf = html_sink.complete().unwrap().0;
+            let mut counter = PdfPageCounter::new();
+            counter.write_all(&pdf).unwrap();
+            counter.pages()
+        };
+        assert_eq!(actual, 4);
+
+        let estimate = estimate_pages(ParagraphConverter, html).unwrap();
+        assert!(estimate.abs_diff(actual) <= 1, "{estimate} vs {actual}");
+    }
+
+    #[test]
+    fn estimate_errors_keep_their_source() {
+        use crate::testing::FailingConverter;
+
+        let error = estimate_pages(FailingConverter::new(), b"<p>Hello</p>").unwrap_err();
+        assert!(matches!(error, HtmlToPdfError::Engine(_)), "{error:?}");
+        let source = error.source().and_then(|e| e.downcast_ref::<io::Error>());
+        assert_eq!(
+            source.map(ToString::to_string).as_deref(),
+            Some(FailingConverter::ERROR_MESSAGE)
+        );
+    }
+}
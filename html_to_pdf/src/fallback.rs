@@ -0,0 +1,321 @@
+//! Try another converter if the first one fails.
+
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt,
+    io::{self, Write},
+    marker::PhantomData,
+    panic,
+    sync::mpsc,
+};
+
+use crate::{
+    ConverterCapabilities, Diagnostic, HtmlSink, HtmlToPdfConverter, HtmlToPdfError, PdfScope,
+    PdfScopedJoinHandle, WriteBuilder, WriteBuilderSimple,
+};
+
+/// A converter that failed while [`FallbackConverter`] tried it.
+#[derive(Debug)]
+pub struct FailedAttempt {
+    /// The [name](HtmlToPdfConverter::name) of the converter.
+    pub converter: Cow<'static, str>,
+    pub error: HtmlToPdfError,
+}
+
+/// Error returned by [`FallbackConverter`].
+#[derive(Debug)]
+pub enum FallbackError {
+    /// Every converter failed. The errors are in the order that the converters
+    /// were tried.
+    AllFailed(Vec<FailedAttempt>),
+    /// A converter succeeded but the PDF couldn't be written to the output.
+    Io(io::Error),
+}
+impl fmt::Display for FallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FallbackError::AllFailed(attempts) => {
+                write!(f, "all {} converters failed", attempts.len())?;
+                for attempt in attempts {
+                    write!(f, "; {}: {}", attempt.converter, attempt.error)?;
+                    if let Some(source) = attempt.error.source() {
+                        write!(f, ": {source}")?;
+                    }
+                }
+                Ok(())
+            }
+            FallbackError::Io(_) => write!(f, "failed to write PDF data"),
+        }
+    }
+}
+impl Error for FallbackError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FallbackError::AllFailed(attempts) => {
+                attempts.last().map(|attempt| &attempt.error as _)
+            }
+            FallbackError::Io(e) => Some(e),
+        }
+    }
+}
+
+/// Converts using `first` and if that fails then converts the same HTML again
+/// using `fallback`, for example to use Chrome when it is installed and
+/// otherwise a simpler converter.
+///
+/// The HTML is buffered in memory until the conversion is completed so that it
+/// can be written to the fallback converter, which is only started if the
+/// first converter fails either when it is started, while HTML is written to
+/// it or when it is completed. Both converters write to an in-memory buffer
+/// that is copied to the output once one of them succeeds, so a failed attempt
+/// never leaves a partial PDF in the output.
+///
+/// The fallback converter is started on a thread that is spawned from the
+/// [`PdfScope`] when the conversion starts. If the first converter succeeds
+/// then that thread exits without doing anything.
+///
+/// Nest this converter to try more than two converters, the errors of the
+/// inner converters are then the source of the outer [`FallbackError`].
+#[derive(Debug, Clone)]
+pub struct FallbackConverter<A, B> {
+    pub first: A,
+    pub fallback: B,
+}
+impl<A, B> FallbackConverter<A, B> {
+    pub fn new(first: A, fallback: B) -> Self {
+        Self { first, fallback }
+    }
+}
+impl<'scope, W, A, B> HtmlToPdfConverter<'scope, W> for FallbackConverter<A, B>
+where
+    W: WriteBuilder + Send + 'scope,
+    A: HtmlToPdfConverter<'scope, WriteBuilderSimple<Vec<u8>>>,
+    A::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+    B: HtmlToPdfConverter<'scope, WriteBuilderSimple<Vec<u8>>> + Send + 'scope,
+    B::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    type HtmlSink = FallbackHtmlSink<'scope, A::HtmlSink, W, A::Error>;
+    type Error = FallbackError;
+
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        let first_name = self.first.name();
+        let (first, failed) = match self
+            .first
+            .start(scope.clone(), WriteBuilderSimple(Vec::new()))
+        {
+            Ok(sink) => (Some(sink), Vec::new()),
+            Err(e) => (
+                None,
+                vec![FailedAttempt {
+                    converter: first_name.clone(),
+                    error: HtmlToPdfError::engine(e),
+                }],
+            ),
+        };
+
+        let fallback_name = self.fallback.name();
+        let (html_tx, html_rx) = mpsc::channel::<Vec<u8>>();
+        let fallback = scope.clone().spawn(move || {
+            // The sender is dropped without any HTML if the first converter
+            // succeeded.
+            let html = html_rx.recv().ok()?;
+            let convert = || {
+                let mut sink = self
+                    .fallback
+                    .start(scope, WriteBuilderSimple(Vec::new()))
+                    .map_err(HtmlToPdfError::engine)?;
+                sink.write_all(&html)?;
+                let (WriteBuilderSimple(pdf), diagnostics) = sink
+                    .complete_with_diagnostics()
+                    .map_err(HtmlToPdfError::engine)?;
+                Ok((pdf, diagnostics))
+            };
+            Some(convert())
+        });
+
+        Ok(FallbackHtmlSink {
+            first,
+            first_name,
+            fallback_name,
+            failed,
+            html: Vec::new(),
+            html_tx,
+            fallback,
+            output,
+            _error: PhantomData,
+        })
+    }
+
+    fn output_content_type(&self) -> &'static str {
+        self.first.output_content_type()
+    }
+    fn output_extension(&self) -> &'static str {
+        self.first.output_extension()
+    }
+    fn name(&self) -> Cow<'static, str> {
+        format!("fallback({}, {})", self.first.name(), self.fallback.name()).into()
+    }
+    /// Only the capabilities that both converters have, since either of them
+    /// might produce the PDF. The HTML is kept so that it can be given to the
+    /// fallback, so it is never streamed.
+    fn capabilities(&self) -> ConverterCapabilities {
+        ConverterCapabilities {
+            streaming_input: false,
+            ..self
+                .first
+                .capabilities()
+                .intersection(&self.fallback.capabilities())
+        }
+    }
+}
+
+type FallbackResult = Option<Result<(Vec<u8>, Vec<Diagnostic>), HtmlToPdfError>>;
+
+/// The [`HtmlSink`] for [`FallbackConverter`]. Writes the HTML to the first
+/// converter and keeps a copy in case the fallback converter is needed.
+pub struct FallbackHtmlSink<'scope, S, W, E> {
+    /// `None` if the first converter failed.
+    first: Option<S>,
+    first_name: Cow<'static, str>,
+    fallback_name: Cow<'static, str>,
+    failed: Vec<FailedAttempt>,
+    html: Vec<u8>,
+    html_tx: mpsc::Sender<Vec<u8>>,
+    fallback: PdfScopedJoinHandle<'scope, FallbackResult>,
+    output: W,
+    _error: PhantomData<fn() -> E>,
+}
+impl<'scope, S, W, E> FallbackHtmlSink<'scope, S, W, E> {
+    fn first_failed(&mut self, error: HtmlToPdfError) {
+        self.first = None;
+        self.failed.push(FailedAttempt {
+            converter: self.first_name.clone(),
+            error,
+        });
+    }
+}
+impl<'scope, S, W, E> HtmlSink<W, FallbackError> for FallbackHtmlSink<'scope, S, W, E>
+where
+    S: HtmlSink<WriteBuilderSimple<Vec<u8>>, E>,
+    W: WriteBuilder,
+    E: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    fn complete(self) -> Result<W, FallbackError>
+    where
+        Self: Sized,
+    {
+        self.complete_with_diagnostics().map(|(writer, _)| writer)
+    }
+    fn complete_with_diagnostics(mut self) -> Result<(W, Vec<Diagnostic>), FallbackError>
+    where
+        Self: Sized,
+    {
+        let mut result = None;
+        if let Some(first) = self.first.take() {
+            match first.complete_with_diagnostics() {
+                Ok((WriteBuilderSimple(pdf), diagnostics)) => result = Some((pdf, diagnostics)),
+                Err(e) => self.first_failed(HtmlToPdfError::engine(e)),
+            }
+        }
+        if result.is_none() {
+            // The fallback thread is still waiting for HTML.
+            let _ = self.html_tx.send(std::mem::take(&mut self.html));
+        }
+        drop(self.html_tx);
+        let fallback = self
+            .fallback
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload));
+        match fallback {
+            Some(Ok(converted)) => result = Some(converted),
+            Some(Err(error)) => self.failed.push(FailedAttempt {
+                converter: self.fallback_name,
+                error,
+            }),
+            None => {}
+        }
+
+        let (pdf, diagnostics) = result.ok_or(FallbackError::AllFailed(self.failed))?;
+        let mut output = self.output;
+        output
+            .get_writer()
+            .and_then(|mut writer| writer.write_all(&pdf))
+            .map_err(FallbackError::Io)?;
+        Ok((output, diagnostics))
+    }
+}
+impl<'scope, S, W, E> Write for FallbackHtmlSink<'scope, S, W, E>
+where
+    S: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.html.extend_from_slice(buf);
+        if let Some(first) = &mut self.first {
+            if let Err(e) = first.write_all(buf) {
+                self.first_failed(e.into());
+            }
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(first) = &mut self.first {
+            if let Err(e) = first.flush() {
+                self.first_failed(e.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FailingConverter, MockConverter};
+
+    fn convert<A, B>(converter: FallbackConverter<A, B>) -> Result<Vec<u8>, FallbackError>
+    where
+        A: HtmlToPdfConverter<'static, WriteBuilderSimple<Vec<u8>>>,
+        A::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+        B: HtmlToPdfConverter<'static, WriteBuilderSimple<Vec<u8>>> + Send + 'static,
+        B::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+    {
+        let mut sink = converter.start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))?;
+        sink.write_all(b"<p>Hello</p>").unwrap();
+        sink.complete().map(|output| output.0)
+    }
+
+    #[test]
+    fn uses_first_converter_that_succeeds() {
+        let first = MockConverter::new();
+        let fallback = MockConverter::new();
+        let pdf = convert(FallbackConverter::new(first.clone(), fallback.clone())).unwrap();
+        assert_eq!(pdf, MockConverter::fake_pdf(12));
+        assert_eq!(first.last_html().unwrap(), b"<p>Hello</p>");
+        assert!(fallback.captured_html().is_empty());
+
+        let fallback = MockConverter::new();
+        let pdf = convert(FallbackConverter::new(
+            FailingConverter::on_start(),
+            fallback.clone(),
+        ))
+        .unwrap();
+        assert_eq!(pdf, MockConverter::fake_pdf(12));
+        assert_eq!(fallback.last_html().unwrap(), b"<p>Hello</p>");
+    }
+
+    #[test]
+    fn collects_errors_when_all_converters_fail() {
+        let Err(FallbackError::AllFailed(attempts)) = convert(FallbackConverter::new(
+            FailingConverter::on_start(),
+            FailingConverter::new(),
+        )) else {
+            panic!("expected all converters to fail");
+        };
+        assert_eq!(attempts.len(), 2);
+        assert!(attempts
+            .iter()
+            .all(|attempt| attempt.converter == "failing"));
+        assert!(matches!(attempts[0].error, HtmlToPdfError::Engine(_)));
+    }
+}
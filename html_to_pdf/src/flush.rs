@@ -0,0 +1,121 @@
+//! Forward PDF data from an engine and flush it to the output regularly.
+
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    time::{Duration, Instant},
+};
+
+/// Copy all data from `reader` to `writer`, like [`io::copy`], but flush
+/// `writer` whenever data arrives and `flush_interval` has passed since it was
+/// last flushed. With `None` the writer is never flushed, so buffered writers
+/// only pass the data on once their buffer is full.
+///
+/// Adapters that read the PDF from a child process use this to implement
+/// their `flush_interval` option. Short intervals let clients start reading
+/// the PDF sooner, but every flush can mean a small write or network packet,
+/// which lowers throughput for large documents.
+pub fn copy_with_flush_interval<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    flush_interval: Option<Duration>,
+) -> io::Result<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    let Some(flush_interval) = flush_interval else {
+        return io::copy(reader, writer);
+    };
+    let mut buffer = [0; 8 * 1024];
+    let mut copied = 0;
+    let mut last_flush = Instant::now();
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(copied),
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+        if last_flush.elapsed() >= flush_interval {
+            writer.flush()?;
+            last_flush = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::BufWriter, thread};
+
+    /// Returns one chunk per read and waits before each chunk after the first,
+    /// like an engine that produces the PDF slowly.
+    struct SlowReader {
+        chunks: Vec<&'static [u8]>,
+        started: bool,
+    }
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.chunks.is_empty() {
+                return Ok(0);
+            }
+            if self.started {
+                thread::sleep(Duration::from_millis(30));
+            }
+            self.started = true;
+            let chunk = self.chunks.remove(0);
+            buf[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    /// Records when the first bytes were written.
+    struct Sniffer {
+        first_write: Option<Instant>,
+        data: Vec<u8>,
+    }
+    impl Write for Sniffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.first_write.get_or_insert_with(Instant::now);
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// How long it took until the first bytes reached the sniffer.
+    fn time_to_first_byte(flush_interval: Option<Duration>) -> Duration {
+        let chunks: Vec<&[u8]> = vec![b"%PDF-1.7\n", b"1 0 obj\n", b"endobj\n", b"%%EOF\n"];
+        let start = Instant::now();
+        let mut writer = BufWriter::with_capacity(
+            1024,
+            Sniffer {
+                first_write: None,
+                data: Vec::new(),
+            },
+        );
+        let mut reader = SlowReader {
+            chunks,
+            started: false,
+        };
+        let copied = copy_with_flush_interval(&mut reader, &mut writer, flush_interval).unwrap();
+        let sniffer = writer.into_inner().map_err(|e| e.into_error()).unwrap();
+        assert_eq!(copied, sniffer.data.len() as u64);
+        assert_eq!(sniffer.data, b"%PDF-1.7\n1 0 obj\nendobj\n%%EOF\n");
+        sniffer.first_write.unwrap() - start
+    }
+
+    #[test]
+    fn flush_interval_delivers_early_bytes_sooner() {
+        let buffered = time_to_first_byte(None);
+        let flushed = time_to_first_byte(Some(Duration::from_millis(1)));
+        assert!(
+            flushed < buffered,
+            "first byte after {flushed:?} with flushing vs {buffered:?} without"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Declare the natural language of generated PDFs.
+
+use std::{borrow::Cow, error::Error};
+
+use crate::{
+    pdf, post_process::start_post_processed, ConverterCapabilities, Diagnostic, HtmlToPdfConverter,
+    HtmlToPdfError, PdfPostProcessor, PdfScope, PostProcessedHtmlSink, WriteBuilder,
+    WriteBuilderSimple,
+};
+
+/// Wraps a converter and stores `lang` as the `/Lang` of the generated PDF,
+/// see [`pdf::set_language`]. Accessibility checkers require it and screen
+/// readers use it to pick a voice. If `lang` is `None` the PDF is passed
+/// through unchanged.
+///
+/// The language is set after the conversion, so the wrapped converter can be
+/// anything but the PDF is [buffered](crate::HtmlSinkPostProcessed#buffering).
+/// The chromiumoxide adapter can also set the page's language itself, which Chrome
+/// then stores in the tagged PDFs that it creates.
+///
+/// # Reading order
+///
+/// Only tagged PDFs describe the order in which their content should be read.
+/// Chrome creates tagged PDFs that follow the order of the HTML elements. The
+/// iText, wkhtml and `pdf-min` engines create untagged PDFs, so readers guess
+/// the order from the layout of each page. This converter doesn't change the
+/// reading order.
+#[derive(Debug, Clone)]
+pub struct LanguageConverter<C> {
+    pub converter: C,
+    pub lang: Option<String>,
+}
+impl<C> LanguageConverter<C> {
+    pub fn new(converter: C, lang: impl Into<String>) -> Self {
+        Self {
+            converter,
+            lang: Some(lang.into()),
+        }
+    }
+}
+impl<'scope, W, C> HtmlToPdfConverter<'scope, W> for LanguageConverter<C>
+where
+    W: WriteBuilder + Send + 'scope,
+    C: HtmlToPdfConverter<'scope, WriteBuilderSimple<Vec<u8>>>,
+    C::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    type HtmlSink = LanguageHtmlSink<C::HtmlSink, W, C::Error>;
+    type Error = HtmlToPdfError;
+
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        let processor = LanguagePostProcessor { lang: self.lang };
+        start_post_processed(self.converter, scope, output, processor)
+    }
+
+    fn name(&self) -> Cow<'static, str> {
+        format!("lang({})", self.converter.name()).into()
+    }
+    fn capabilities(&self) -> ConverterCapabilities {
+        self.converter.capabilities()
+    }
+}
+
+/// The [`PdfPostProcessor`] of [`LanguageConverter`].
+#[derive(Debug, Clone)]
+pub struct LanguagePostProcessor {
+    lang: Option<String>,
+}
+impl PdfPostProcessor for LanguagePostProcessor {
+    type Error = HtmlToPdfError;
+
+    fn process(
+        self,
+        pdf: Vec<u8>,
+        _diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Vec<u8>, HtmlToPdfError> {
+        match &self.lang {
+            Some(lang) => Ok(pdf::set_language(&pdf, lang)?),
+            None => Ok(pdf),
+        }
+    }
+}
+
+/// The [`HtmlSink`](crate::HtmlSink) for [`LanguageConverter`].
+pub type LanguageHtmlSink<S, W, E> = PostProcessedHtmlSink<S, W, E, LanguagePostProcessor>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::BlankPdfConverter, HtmlSink};
+    use std::io::Write;
+
+    #[test]
+    fn lang_is_stored_in_catalog() {
+        let converter = LanguageConverter::new(BlankPdfConverter::new(1), "sv-SE");
+        let mut html_sink = converter
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        html_sink.write_all(b"<p>Hej</p>").unwrap();
+        let pdf = html_sink.complete().unwrap().0;
+
+        assert_eq!(pdf::language(&pdf).unwrap().as_deref(), Some("sv-SE"));
+        assert_eq!(
+            pdf::language(&BlankPdfConverter::blank_pdf(1)).unwrap(),
+            None
+        );
+    }
+}
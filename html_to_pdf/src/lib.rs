@@ -1,6 +1,15 @@
 //! Provides an interface for HTML to PDF conversions.
 
-use std::{fmt, io::Write, marker::PhantomData};
+use std::{borrow::Cow, fmt, io::Write, marker::PhantomData};
+
+mod error;
+pub use error::*;
+
+mod diagnostic;
+pub use diagnostic::*;
+
+mod capabilities;
+pub use capabilities::*;
 
 mod thread_scope {
     //! A scope that can spawn either `'static` "owned" threads or limited
@@ -8,19 +17,29 @@ mod thread_scope {
 
     use std::{
         any::Any,
+        io,
+        sync::{Arc, Condvar, Mutex, PoisonError},
         thread::{self, JoinHandle, ScopedJoinHandle},
     };
 
+    use crate::{thread_pool::TaskSlot, Cancelled, WorkerPanicked};
+
     enum PdfScopedJoinHandleState<'scope, T> {
         Static(
             JoinHandle<Box<dyn AsAny + Send + 'static>>,
             &'scope StaticThread<'scope>,
         ),
         Scoped(ScopedJoinHandle<'scope, T>),
+        Pooled(Arc<TaskSlot<T>>),
     }
-    /// A thread join handle that internally can be either [`JoinHandle`] or
-    /// [`ScopedJoinHandle`].
+    /// A thread join handle that internally can be either [`JoinHandle`],
+    /// [`ScopedJoinHandle`] or a task on a [`PdfThreadPool`](crate::PdfThreadPool).
     pub struct PdfScopedJoinHandle<'scope, T>(PdfScopedJoinHandleState<'scope, T>);
+    impl<'scope, T> PdfScopedJoinHandle<'scope, T> {
+        pub(crate) fn pooled(slot: Arc<TaskSlot<T>>) -> Self {
+            Self(PdfScopedJoinHandleState::Pooled(slot))
+        }
+    }
     impl<'scope, T: 'scope> PdfScopedJoinHandle<'scope, T> {
         pub fn join(self) -> thread::Result<T> {
             match self.0 {
@@ -32,9 +51,27 @@ mod thread_scope {
                         .expect("failed to downcast type returned from spawned thread")
                 }),
                 PdfScopedJoinHandleState::Scoped(v) => v.join(),
+                PdfScopedJoinHandleState::Pooled(slot) => slot.wait(),
             }
         }
     }
+    impl<'scope, T: 'scope> PdfScopedJoinHandle<'scope, T> {
+        /// Wait for the thread to finish and turn a panic inside it into a
+        /// [`WorkerPanicked`] error.
+        pub fn join_result(self) -> Result<T, WorkerPanicked> {
+            self.join()
+                .map_err(|payload| WorkerPanicked::new(&*payload))
+        }
+    }
+
+    /// Get the message of a panic payload returned from a join handle if it was
+    /// created by `panic!` with a string message.
+    pub fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+        payload
+            .downcast_ref::<&'static str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+    }
 
     /// A trait that allows downcasts for a type `T` stored inside `Self` if we
     /// can prove that `T: 'static` using [`StaticThread`].
@@ -76,8 +113,9 @@ mod thread_scope {
     #[allow(clippy::type_complexity)]
     struct StaticThread<'scope> {
         spawn: fn(
+            builder: thread::Builder,
             f: Box<dyn FnOnce() -> Box<dyn AsAny + Send + 'scope> + Send + 'scope>,
-        ) -> JoinHandle<Box<dyn AsAny + Send + 'static>>,
+        ) -> io::Result<JoinHandle<Box<dyn AsAny + Send + 'static>>>,
         static_dyn_downcast: for<'a> fn(
             downcast: &'a mut (dyn DynDowncast + 'scope),
         ) -> &'a mut (dyn DynDowncast + 'static),
@@ -85,12 +123,75 @@ mod thread_scope {
     impl StaticThread<'static> {
         fn new() -> &'static Self {
             &Self {
-                spawn: thread::spawn,
+                spawn: thread::Builder::spawn,
                 static_dyn_downcast: |downcast| downcast,
             }
         }
     }
 
+    /// Lets conversions be cancelled from another thread, for example when a
+    /// page takes too long to load. Pass it to converters using
+    /// [`PdfScope::owned_with_cancel`] or [`PdfScope::scoped_with_cancel`].
+    ///
+    /// Converters check the token where they can stop and then fail with a
+    /// [`Cancelled`] error, which [`HtmlToPdfError`](crate::HtmlToPdfError)
+    /// represents as [`HtmlToPdfError::Cancelled`](crate::HtmlToPdfError::Cancelled).
+    /// Clones share the same state and only [`cancel`](Self::cancel) cancels,
+    /// dropping a token doesn't.
+    #[derive(Debug, Clone, Default)]
+    pub struct CancelToken(Arc<CancelState>);
+    #[derive(Debug, Default)]
+    struct CancelState {
+        cancelled: Mutex<bool>,
+        changed: Condvar,
+    }
+    impl CancelToken {
+        pub fn new() -> Self {
+            Self::default()
+        }
+        /// Cancel all conversions that use this token.
+        pub fn cancel(&self) {
+            *self
+                .0
+                .cancelled
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = true;
+            self.0.changed.notify_all();
+        }
+        pub fn is_cancelled(&self) -> bool {
+            *self
+                .0
+                .cancelled
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+        }
+        /// Block until the token is cancelled, for example on a thread that
+        /// then stops a child program.
+        pub fn cancelled(&self) {
+            let mut cancelled = self
+                .0
+                .cancelled
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            while !*cancelled {
+                cancelled = self
+                    .0
+                    .changed
+                    .wait(cancelled)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+        /// Returns [`Cancelled`] if the token has been cancelled, so that
+        /// converters can check it with `?`.
+        pub fn check(&self) -> Result<(), Cancelled> {
+            if self.is_cancelled() {
+                Err(Cancelled)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
     #[derive(Clone, Copy)]
     enum PdfScopeInner<'scope, 'env> {
         Static(&'scope StaticThread<'scope>),
@@ -101,13 +202,58 @@ mod thread_scope {
     /// then it wraps [`thread::Scope`], otherwise it can be constructed using
     /// [`PdfScope::owned`] in which case it will spawn normal `'static` threads
     /// using [`thread::spawn`].
-    #[derive(Clone, Copy)]
-    pub struct PdfScope<'scope, 'env>(PdfScopeInner<'scope, 'env>);
+    ///
+    /// The scope can also carry a [`CancelToken`] that converters check, and
+    /// a way to configure the threads it spawns, for example their stack size.
+    ///
+    /// The scope used to be [`Copy`] but the token and the thread builder are
+    /// reference counted, so it is now only [`Clone`]. This is a breaking
+    /// change: converters that start more than one conversion or spawn more
+    /// than one thread with the same scope must [`clone`](Clone::clone) it.
+    #[derive(Clone)]
+    pub struct PdfScope<'scope, 'env> {
+        inner: PdfScopeInner<'scope, 'env>,
+        cancel: Option<CancelToken>,
+        thread_builder: Option<Arc<ThreadBuilderFn>>,
+    }
+    type ThreadBuilderFn = dyn Fn() -> thread::Builder + Send + Sync;
     impl PdfScope<'static, 'static> {
         /// Create a scope that has no max lifetime, this means only `'static`
         /// data can be used by spawned threads.
         pub fn owned() -> Self {
-            Self(PdfScopeInner::Static(StaticThread::new()))
+            Self {
+                inner: PdfScopeInner::Static(StaticThread::new()),
+                cancel: None,
+                thread_builder: None,
+            }
+        }
+        /// Like [`PdfScope::owned`] but threads are spawned using the
+        /// [`thread::Builder`] returned by `builder`, for example to give them
+        /// a larger stack size than the default:
+        ///
+        /// ```
+        /// # use html_to_pdf::PdfScope;
+        /// # use std::thread;
+        /// let scope = PdfScope::owned_with_builder(|| thread::Builder::new().stack_size(16 << 20));
+        /// ```
+        ///
+        /// `builder` is called once for each thread since a conversion can
+        /// spawn more than one and a [`thread::Builder`] can only be used once.
+        pub fn owned_with_builder(
+            builder: impl Fn() -> thread::Builder + Send + Sync + 'static,
+        ) -> Self {
+            Self {
+                thread_builder: Some(Arc::new(builder)),
+                ..Self::owned()
+            }
+        }
+        /// Like [`PdfScope::owned`] but conversions stop once `token` is
+        /// cancelled.
+        pub fn owned_with_cancel(token: CancelToken) -> Self {
+            Self {
+                cancel: Some(token),
+                ..Self::owned()
+            }
         }
     }
     impl<'scope, 'env> PdfScope<'scope, 'env> {
@@ -115,29 +261,184 @@ mod thread_scope {
         /// the provide scope ends. Spawned threads can make use of data that
         /// outlives the `'scope` lifetime.
         pub fn scoped(scope: &'scope thread::Scope<'scope, 'env>) -> Self {
-            Self(PdfScopeInner::Scoped(scope))
+            Self {
+                inner: PdfScopeInner::Scoped(scope),
+                cancel: None,
+                thread_builder: None,
+            }
+        }
+        /// Like [`PdfScope::scoped`] but threads are spawned using the
+        /// [`thread::Builder`] returned by `builder`, see
+        /// [`PdfScope::owned_with_builder`].
+        pub fn scoped_with_builder(
+            scope: &'scope thread::Scope<'scope, 'env>,
+            builder: impl Fn() -> thread::Builder + Send + Sync + 'static,
+        ) -> Self {
+            Self {
+                thread_builder: Some(Arc::new(builder)),
+                ..Self::scoped(scope)
+            }
+        }
+        /// Like [`PdfScope::scoped`] but conversions stop once `token` is
+        /// cancelled.
+        pub fn scoped_with_cancel(
+            scope: &'scope thread::Scope<'scope, 'env>,
+            token: CancelToken,
+        ) -> Self {
+            Self {
+                cancel: Some(token),
+                ..Self::scoped(scope)
+            }
+        }
+        /// The token that cancels conversions started with this scope, if any.
+        /// Converters should clone it before spawning threads.
+        pub fn cancel_token(&self) -> Option<&CancelToken> {
+            self.cancel.as_ref()
+        }
+        /// `true` if the scope's [`CancelToken`] has been cancelled.
+        pub fn is_cancelled(&self) -> bool {
+            self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
         }
         /// Spawn a thread that might be limited to a scope created by
         /// [`thread::scoped`].
+        ///
+        /// # Panics
+        ///
+        /// Like [`thread::spawn`] this panics if the operating system fails to
+        /// create the thread.
         pub fn spawn<F, T>(self, f: F) -> PdfScopedJoinHandle<'scope, T>
         where
             F: FnOnce() -> T + Send + 'scope,
             T: Send + 'scope,
         {
-            PdfScopedJoinHandle(match self.0 {
+            let builder = self.thread_builder();
+            self.spawn_with(builder, f)
+        }
+        /// Like [`spawn`](Self::spawn) but the thread is given a name, which
+        /// is included in panic messages and shown by debuggers and profilers.
+        /// Converters should use a name that identifies them, for example
+        /// `"dotnet-itext reader"`.
+        ///
+        /// The name replaces any name set by the scope's
+        /// [`thread::Builder`].
+        pub fn spawn_named<F, T>(
+            self,
+            name: impl Into<String>,
+            f: F,
+        ) -> PdfScopedJoinHandle<'scope, T>
+        where
+            F: FnOnce() -> T + Send + 'scope,
+            T: Send + 'scope,
+        {
+            let builder = self.thread_builder().name(name.into());
+            self.spawn_with(builder, f)
+        }
+        fn thread_builder(&self) -> thread::Builder {
+            match &self.thread_builder {
+                Some(builder) => builder(),
+                None => thread::Builder::new(),
+            }
+        }
+        fn spawn_with<F, T>(self, builder: thread::Builder, f: F) -> PdfScopedJoinHandle<'scope, T>
+        where
+            F: FnOnce() -> T + Send + 'scope,
+            T: Send + 'scope,
+        {
+            PdfScopedJoinHandle(match self.inner {
                 PdfScopeInner::Static(dyn_static) => PdfScopedJoinHandleState::Static(
-                    (dyn_static.spawn)(Box::new(move || Box::new(f()))),
+                    (dyn_static.spawn)(builder, Box::new(move || Box::new(f())))
+                        .expect("failed to spawn thread"),
                     dyn_static,
                 ),
-                PdfScopeInner::Scoped(scope) => PdfScopedJoinHandleState::Scoped(scope.spawn(f)),
+                PdfScopeInner::Scoped(scope) => PdfScopedJoinHandleState::Scoped(
+                    builder
+                        .spawn_scoped(scope, f)
+                        .expect("failed to spawn thread"),
+                ),
             })
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn join_result_returns_thread_result() {
+            let handle = PdfScope::owned().spawn(|| Ok::<_, String>(5));
+            assert_eq!(handle.join_result(), Ok(Ok(5)));
+
+            thread::scope(|s| {
+                let handle = PdfScope::scoped(s).spawn(|| Err::<(), _>("failed".to_owned()));
+                assert_eq!(handle.join_result(), Ok(Err("failed".to_owned())));
+            });
+        }
+
+        #[test]
+        fn join_result_maps_panic_to_error() {
+            let handle =
+                PdfScope::owned().spawn(|| -> Result<(), String> { panic!("worker failed") });
+            let error = handle.join_result().unwrap_err();
+            assert_eq!(error.message(), Some("worker failed"));
+        }
+
+        #[test]
+        fn threads_are_spawned_with_builder() {
+            let builder = || thread::Builder::new().name("converter".to_owned());
+            let name = || thread::current().name().map(str::to_owned);
+
+            let handle = PdfScope::owned_with_builder(builder).spawn(name);
+            assert_eq!(handle.join().unwrap().as_deref(), Some("converter"));
+            thread::scope(|s| {
+                let handle = PdfScope::scoped_with_builder(s, builder).spawn(name);
+                assert_eq!(handle.join().unwrap().as_deref(), Some("converter"));
+            });
+            assert_eq!(PdfScope::owned().spawn(name).join().unwrap(), None);
+        }
+
+        #[test]
+        fn named_threads_are_named() {
+            let name = || thread::current().name().map(str::to_owned);
+
+            let handle = PdfScope::owned().spawn_named("worker", name);
+            assert_eq!(handle.join().unwrap().as_deref(), Some("worker"));
+            thread::scope(|s| {
+                let builder = || thread::Builder::new().name("converter".to_owned());
+                let handle = PdfScope::scoped_with_builder(s, builder).spawn_named("worker", name);
+                assert_eq!(handle.join().unwrap().as_deref(), Some("worker"));
+            });
+        }
+
+        #[test]
+        fn cancel_token_is_only_cancelled_explicitly() {
+            let token = CancelToken::new();
+            drop(token.clone());
+            let scope = PdfScope::owned_with_cancel(token.clone());
+            assert!(!scope.is_cancelled());
+
+            let waiter = scope.spawn({
+                let token = token.clone();
+                move || token.cancelled()
+            });
+            token.cancel();
+            waiter.join().unwrap();
+            assert_eq!(token.check(), Err(Cancelled));
+            assert!(!PdfScope::owned().is_cancelled());
+        }
+    }
 }
 pub use thread_scope::*;
 
 mod write_builder {
-    use std::io::{self, Write};
+    use std::{
+        fs::{File, OpenOptions},
+        io::{self, BufWriter, Write},
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+    };
 
     mod sealed_lifetime {
         //! For more info see:
@@ -226,33 +527,378 @@ mod write_builder {
             (self.0)()
         }
     }
+
+    /// A write builder that opens a file the first time it is used, so a
+    /// conversion that fails before it writes any PDF data doesn't leave an
+    /// empty file behind. The opened file is reused if the builder is asked
+    /// for more writers.
+    ///
+    /// Writes are buffered, use [`into_file`](Self::into_file) to flush them
+    /// and find out if that failed.
+    #[derive(Debug)]
+    pub struct WriteBuilderFile {
+        path: PathBuf,
+        options: OpenOptions,
+        file: Option<BufWriter<File>>,
+    }
+    impl WriteBuilderFile {
+        /// Open the file at `path` using `options` once it is needed.
+        pub fn new(path: impl Into<PathBuf>, options: OpenOptions) -> Self {
+            Self {
+                path: path.into(),
+                options,
+                file: None,
+            }
+        }
+        /// Create the file at `path` once it is needed, truncating it if it
+        /// already exists.
+        pub fn create(path: impl Into<PathBuf>) -> Self {
+            let mut options = OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            Self::new(path, options)
+        }
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+        /// The file if it has been opened. Some of the written data might
+        /// still be buffered.
+        pub fn file(&self) -> Option<&File> {
+            self.file.as_ref().map(BufWriter::get_ref)
+        }
+        /// Write any buffered data and return the file if it has been opened.
+        pub fn into_file(self) -> io::Result<Option<File>> {
+            self.file
+                .map(|file| file.into_inner().map_err(io::IntoInnerError::into_error))
+                .transpose()
+        }
+    }
+    impl<'a> WriteBuilderLifetime<'a> for WriteBuilderFile {
+        type Writer = &'a mut BufWriter<File>;
+    }
+    impl WriteBuilder for WriteBuilderFile {
+        fn get_writer(&mut self) -> io::Result<<Self as WriteBuilderLifetime<'_>>::Writer> {
+            if self.file.is_none() {
+                self.file = Some(BufWriter::new(self.options.open(&self.path)?));
+            }
+            Ok(self.file.as_mut().expect("file was opened above"))
+        }
+    }
+
+    /// A write builder that writes all PDF data to two builders, for example to
+    /// save the PDF to a file while also hashing it.
+    pub struct WriteBuilderTee<A, B>(pub A, pub B);
+    impl<'a, A, B> WriteBuilderLifetime<'a> for WriteBuilderTee<A, B>
+    where
+        A: WriteBuilderLifetime<'a>,
+        B: WriteBuilderLifetime<'a>,
+    {
+        type Writer = TeeWriter<A::Writer, B::Writer>;
+    }
+    impl<A, B> WriteBuilder for WriteBuilderTee<A, B>
+    where
+        A: WriteBuilder,
+        B: WriteBuilder,
+    {
+        fn get_writer(&mut self) -> io::Result<<Self as WriteBuilderLifetime<'_>>::Writer> {
+            Ok(TeeWriter(self.0.get_writer()?, self.1.get_writer()?))
+        }
+    }
+
+    /// The writer returned by [`WriteBuilderTee`], it forwards all data to
+    /// both writers.
+    ///
+    /// A write only reports the bytes that both writers accepted: if the first
+    /// writer does a short write then the second writer is given the same
+    /// bytes and the rest is left for the next call.
+    pub struct TeeWriter<A, B>(pub A, pub B);
+    impl<A, B> Write for TeeWriter<A, B>
+    where
+        A: Write,
+        B: Write,
+    {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let written = self.0.write(buf)?;
+            self.1.write_all(&buf[..written])?;
+            Ok(written)
+        }
+        fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+            self.0.write_all(buf)?;
+            self.1.write_all(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()?;
+            self.1.flush()
+        }
+    }
+
+    /// A write builder that counts the bytes written to the wrapped builder, for
+    /// example to log the size of the PDF. It can wrap or be wrapped by other
+    /// builders such as [`WriteBuilderTee`].
+    #[derive(Debug)]
+    pub struct WriteBuilderCounting<W> {
+        inner: W,
+        pub(crate) bytes: Arc<AtomicU64>,
+    }
+    impl<W> WriteBuilderCounting<W> {
+        pub fn new(inner: W) -> Self {
+            Self {
+                inner,
+                bytes: Arc::new(AtomicU64::new(0)),
+            }
+        }
+        /// The number of bytes that have been written so far.
+        pub fn bytes_written(&self) -> u64 {
+            self.bytes.load(Ordering::Relaxed)
+        }
+        pub fn into_inner(self) -> W {
+            self.inner
+        }
+    }
+    impl<'a, W> WriteBuilderLifetime<'a> for WriteBuilderCounting<W>
+    where
+        W: WriteBuilderLifetime<'a>,
+    {
+        type Writer = CountingWriter<W::Writer>;
+    }
+    impl<W> WriteBuilder for WriteBuilderCounting<W>
+    where
+        W: WriteBuilder,
+    {
+        fn get_writer(&mut self) -> io::Result<<Self as WriteBuilderLifetime<'_>>::Writer> {
+            Ok(CountingWriter {
+                inner: self.inner.get_writer()?,
+                bytes: Arc::clone(&self.bytes),
+            })
+        }
+    }
+
+    /// The writer returned by [`WriteBuilderCounting`].
+    #[derive(Debug)]
+    pub struct CountingWriter<W> {
+        inner: W,
+        bytes: Arc<AtomicU64>,
+    }
+    impl<W: Write> Write for CountingWriter<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let written = self.inner.write(buf)?;
+            self.bytes.fetch_add(written as u64, Ordering::Relaxed);
+            Ok(written)
+        }
+
+        fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+            let written = self.inner.write_vectored(bufs)?;
+            self.bytes.fetch_add(written as u64, Ordering::Relaxed);
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    /// A write builder that hashes all PDF data while it is written to the
+    /// wrapped builder, for example to deduplicate generated documents without
+    /// reading them a second time.
+    ///
+    /// Use [`WriteBuilderHashing::finish`] after the conversion has completed
+    /// to get the digest:
+    ///
+    /// ```no_run
+    /// # use std::{fs::File, io::Write};
+    /// # use html_to_pdf::{digest::{Digest, Output}, HtmlSink, HtmlToPdfConverter, PdfScope, WriteBuilderHashing, WriteBuilderSimple};
+    /// fn convert_and_hash<C, H>(
+    ///     converter: C,
+    ///     html: &[u8],
+    ///     file: File,
+    /// ) -> Result<(File, Output<H>), C::Error>
+    /// where
+    ///     C: HtmlToPdfConverter<'static, WriteBuilderHashing<WriteBuilderSimple<File>, H>>,
+    ///     H: Digest + Send + 'static,
+    /// {
+    ///     let output = WriteBuilderHashing::new(WriteBuilderSimple(file));
+    ///     let mut html_sink = converter.start(PdfScope::owned(), output)?;
+    ///     html_sink.write_all(html).expect("failed to write HTML");
+    ///     let (WriteBuilderSimple(file), digest) = html_sink.complete()?.finish();
+    ///     Ok((file, digest))
+    /// }
+    /// ```
+    #[cfg(feature = "hash")]
+    pub struct WriteBuilderHashing<W, H> {
+        builder: W,
+        hasher: H,
+    }
+    #[cfg(feature = "hash")]
+    impl<W, H: digest::Digest> WriteBuilderHashing<W, H> {
+        pub fn new(builder: W) -> Self {
+            Self::with_hasher(builder, H::new())
+        }
+        /// Continue hashing using an existing `hasher`, for example one that
+        /// has already been given a key or a prefix.
+        pub fn with_hasher(builder: W, hasher: H) -> Self {
+            Self { builder, hasher }
+        }
+        /// Return the wrapped builder and the digest of all data that was
+        /// written.
+        pub fn finish(self) -> (W, digest::Output<H>) {
+            (self.builder, self.hasher.finalize())
+        }
+    }
+    #[cfg(feature = "hash")]
+    impl<'a, W, H> WriteBuilderLifetime<'a> for WriteBuilderHashing<W, H>
+    where
+        W: WriteBuilderLifetime<'a>,
+        H: digest::Digest,
+    {
+        type Writer = HashingWriter<'a, W::Writer, H>;
+    }
+    #[cfg(feature = "hash")]
+    impl<W, H> WriteBuilder for WriteBuilderHashing<W, H>
+    where
+        W: WriteBuilder,
+        H: digest::Digest,
+    {
+        fn get_writer(&mut self) -> io::Result<<Self as WriteBuilderLifetime<'_>>::Writer> {
+            Ok(HashingWriter {
+                writer: self.builder.get_writer()?,
+                hasher: &mut self.hasher,
+            })
+        }
+    }
+
+    /// The writer returned by [`WriteBuilderHashing`], only the bytes that the
+    /// wrapped writer accepted are hashed.
+    #[cfg(feature = "hash")]
+    pub struct HashingWriter<'a, W, H> {
+        writer: W,
+        hasher: &'a mut H,
+    }
+    #[cfg(feature = "hash")]
+    impl<W, H> Write for HashingWriter<'_, W, H>
+    where
+        W: Write,
+        H: digest::Digest,
+    {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let written = self.writer.write(buf)?;
+            self.hasher.update(&buf[..written]);
+            Ok(written)
+        }
+        fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+            self.writer.write_all(buf)?;
+            self.hasher.update(buf);
+            Ok(())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.writer.flush()
+        }
+    }
+
+    /// A write builder that gzip compresses all PDF data before it is written
+    /// to the wrapped writer.
+    ///
+    /// Use [`HtmlSink::gzip`](crate::HtmlSink::gzip) on the converter's sink
+    /// to finalize the gzip stream when the conversion completes.
+    ///
+    /// If the builder is dropped without being finished then the gzip stream
+    /// will be finalized on a best effort basis and any error will be ignored.
+    #[cfg(feature = "gzip")]
+    pub struct WriteBuilderGzip<W: Write>(flate2::write::GzEncoder<W>);
+    #[cfg(feature = "gzip")]
+    impl<W: Write> WriteBuilderGzip<W> {
+        /// Compress data using the specified compression `level`, which should
+        /// be in the range `0..=9` where `0` means no compression and `9` means
+        /// the best (but slowest) compression.
+        pub fn new(writer: W, level: u32) -> Self {
+            Self(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::new(level),
+            ))
+        }
+        /// Write the gzip trailer and return the wrapped writer.
+        pub fn finish(self) -> io::Result<W> {
+            self.0.finish()
+        }
+    }
+    #[cfg(feature = "gzip")]
+    impl<'a, W> WriteBuilderLifetime<'a> for WriteBuilderGzip<W>
+    where
+        W: Write,
+    {
+        type Writer = &'a mut flate2::write::GzEncoder<W>;
+    }
+    #[cfg(feature = "gzip")]
+    impl<W> WriteBuilder for WriteBuilderGzip<W>
+    where
+        W: Write,
+    {
+        fn get_writer(&mut self) -> io::Result<<Self as WriteBuilderLifetime<'_>>::Writer> {
+            Ok(&mut self.0)
+        }
+    }
 }
 pub use write_builder::*;
 
+mod bounded_pipe;
+
 mod io_stream {
     //! Utility that is useful to implement a lot of converters.
     use std::{
         io::{self, BufRead, Read, Write},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
         thread,
     };
 
-    use crate::{PdfScope, PdfScopedJoinHandle};
+    use crate::{
+        bounded_pipe::bounded_pipe, CancelToken, PdfScope, PdfScopedJoinHandle, WorkerPanicked,
+    };
 
     /// Reads data from another thread.
-    pub struct ReadStream(pipe::PipeReader);
+    ///
+    /// If the [`WriteStream`] is dropped without being joined then reaching
+    /// the end of the data is an [`io::ErrorKind::UnexpectedEof`] error so
+    /// that the partially written data isn't mistaken for the whole document.
+    /// Reads fail with [`Cancelled`](crate::Cancelled) once the scope's
+    /// [`CancelToken`] is cancelled.
+    pub struct ReadStream {
+        reader: Box<dyn BufRead + Send>,
+        /// Set when the [`WriteStream`] is joined.
+        completed: Arc<AtomicBool>,
+        cancel: Option<CancelToken>,
+    }
+    impl ReadStream {
+        fn check_end(&self, read: usize) -> io::Result<usize> {
+            if let Some(cancel) = &self.cancel {
+                cancel.check()?;
+            }
+            if read == 0 && !self.completed.load(Ordering::Acquire) {
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the HTML sink was dropped without being completed",
+                ))
+            } else {
+                Ok(read)
+            }
+        }
+    }
     impl BufRead for ReadStream {
         fn fill_buf(&mut self) -> io::Result<&[u8]> {
-            self.0.fill_buf()
+            let read = self.reader.fill_buf()?.len();
+            self.check_end(read)?;
+            self.reader.fill_buf()
         }
 
         fn consume(&mut self, amt: usize) {
-            self.0.consume(amt)
+            self.reader.consume(amt)
         }
     }
 
     impl Read for ReadStream {
         fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-            self.0.read(buf)
+            let read = self.reader.read(buf)?;
+            self.check_end(read)
         }
     }
 
@@ -263,21 +909,80 @@ mod io_stream {
         reader_thread: PdfScopedJoinHandle<'scope, R>,
         /// A pipe through which HTML data can be written so that the spawned thread
         /// can read it and use it to generate the PDF.
-        writer: pipe::PipeWriter,
+        writer: Box<dyn Write + Send>,
+        /// `true` if a write failed because the spawned thread dropped its end
+        /// of the pipe.
+        reader_closed_early: bool,
+        /// Shared with the [`ReadStream`].
+        completed: Arc<AtomicBool>,
+        cancel: Option<CancelToken>,
+    }
+    /// The outcome of [`WriteStream::join_result`].
+    #[derive(Debug)]
+    pub struct StreamJoin<R> {
+        /// The value that the spawned thread returned, or a [`WorkerPanicked`]
+        /// error if it panicked.
+        pub result: Result<R, WorkerPanicked>,
+        /// `true` if the spawned thread stopped reading before all data that
+        /// was written to the [`WriteStream`] had been consumed, so that a
+        /// write failed with [`io::ErrorKind::BrokenPipe`]. Converters can use
+        /// this to explain errors, for example that a child process exited
+        /// before it read all of the HTML.
+        pub reader_closed_early: bool,
     }
     impl<'scope, R> WriteStream<'scope, R>
     where
         R: Send + 'scope,
     {
         /// Preform the PDF generation on a background thread.
+        ///
+        /// Dropping the returned stream without joining it cancels the
+        /// generation, see [`ReadStream`]. Both the stream and the
+        /// [`ReadStream`] fail with [`Cancelled`](crate::Cancelled) once the
+        /// scope's [`CancelToken`] is cancelled.
         pub fn stream(
             scope: PdfScope<'scope, '_>,
             f: impl FnOnce(ReadStream) -> R + Send + 'scope,
         ) -> Self {
             let (reader, writer) = pipe::pipe();
+            Self::with_pipe(scope, Box::new(reader), Box::new(writer), f)
+        }
+        /// Like [`WriteStream::stream`] but at most `capacity` bytes that the
+        /// spawned thread hasn't read yet are kept in memory. Writes block
+        /// until the spawned thread has read enough data to make room, so
+        /// writing a large document to a slow converter doesn't buffer all of
+        /// it.
+        ///
+        /// # Panics
+        ///
+        /// If `capacity` is zero.
+        pub fn stream_bounded(
+            scope: PdfScope<'scope, '_>,
+            capacity: usize,
+            f: impl FnOnce(ReadStream) -> R + Send + 'scope,
+        ) -> Self {
+            let (reader, writer) = bounded_pipe(capacity);
+            Self::with_pipe(scope, Box::new(reader), Box::new(writer), f)
+        }
+        fn with_pipe(
+            scope: PdfScope<'scope, '_>,
+            reader: Box<dyn BufRead + Send>,
+            writer: Box<dyn Write + Send>,
+            f: impl FnOnce(ReadStream) -> R + Send + 'scope,
+        ) -> Self {
+            let completed = Arc::new(AtomicBool::new(false));
+            let cancel = scope.cancel_token().cloned();
+            let reader = ReadStream {
+                reader,
+                completed: completed.clone(),
+                cancel: cancel.clone(),
+            };
             WriteStream {
-                reader_thread: scope.spawn(move || f(ReadStream(reader))),
+                reader_thread: scope.spawn(move || f(reader)),
                 writer,
+                reader_closed_early: false,
+                completed,
+                cancel,
             }
         }
     }
@@ -285,29 +990,181 @@ mod io_stream {
     where
         R: 'scope,
     {
+        /// `true` if a write has failed because the spawned thread stopped
+        /// reading, see [`StreamJoin::reader_closed_early`].
+        pub fn reader_closed_early(&self) -> bool {
+            self.reader_closed_early
+        }
+
         /// Wait for the spawned thread to finish.
         pub fn join(self) -> thread::Result<R> {
+            self.finish().join()
+        }
+        /// Like [`join`](Self::join) but a panic in the spawned thread is
+        /// returned as a [`WorkerPanicked`] error, so that it can be reported
+        /// to the caller like any other error, and it is also reported if the
+        /// spawned thread stopped reading early, see [`StreamJoin`].
+        pub fn join_result(self) -> StreamJoin<R> {
+            let reader_closed_early = self.reader_closed_early;
+            StreamJoin {
+                result: self.finish().join_result(),
+                reader_closed_early,
+            }
+        }
+        fn finish(self) -> PdfScopedJoinHandle<'scope, R> {
+            self.completed.store(true, Ordering::Release);
             // Drop the writer first so that the background thread doesn't
             // deadlock trying to read more data:
             drop(self.writer);
-            // Then wait for the background thread to finish:
-            self.reader_thread.join()
+            // Then the caller can wait for the background thread to finish:
+            self.reader_thread
+        }
+    }
+    impl<R> WriteStream<'_, R> {
+        fn check_cancelled(&self) -> io::Result<()> {
+            match &self.cancel {
+                Some(cancel) => Ok(cancel.check()?),
+                None => Ok(()),
+            }
+        }
+        fn track<T>(&mut self, result: io::Result<T>) -> io::Result<T> {
+            if let Err(e) = &result {
+                if e.kind() == io::ErrorKind::BrokenPipe {
+                    self.reader_closed_early = true;
+                }
+            }
+            result
         }
     }
     impl<R> Write for WriteStream<'_, R> {
         #[inline]
         fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-            self.writer.write(buf)
+            self.check_cancelled()?;
+            let result = self.writer.write(buf);
+            self.track(result)
         }
 
         #[inline]
         fn flush(&mut self) -> io::Result<()> {
-            self.writer.flush()
+            self.check_cancelled()?;
+            let result = self.writer.flush();
+            self.track(result)
         }
     }
 }
 pub use io_stream::*;
 
+mod semaphore;
+pub use semaphore::*;
+
+mod pdf_a;
+pub use pdf_a::*;
+
+mod page_setup;
+pub use page_setup::*;
+
+mod batch;
+pub use batch::*;
+
+mod convert;
+pub use convert::*;
+
+mod timing;
+pub use timing::*;
+
+mod thread_pool;
+pub use thread_pool::*;
+
+mod sanitize;
+pub use sanitize::*;
+
+mod estimate;
+pub use estimate::*;
+
+mod limit;
+pub use limit::*;
+
+mod progress;
+pub use progress::*;
+
+mod validate;
+pub use validate::*;
+
+mod outline;
+pub use outline::*;
+
+mod reader;
+pub use reader::*;
+
+mod flush;
+pub use flush::*;
+
+mod child_process;
+pub use child_process::*;
+
+mod temp_file;
+pub use temp_file::*;
+
+mod rotating;
+pub use rotating::*;
+
+mod post_process;
+pub use post_process::*;
+
+mod sequence;
+pub use sequence::*;
+
+mod timeout;
+pub use timeout::*;
+
+mod fallback;
+pub use fallback::*;
+
+#[cfg(feature = "cache")]
+mod cache;
+#[cfg(feature = "cache")]
+pub use cache::*;
+
+#[cfg(feature = "bench")]
+mod benchmark;
+#[cfg(feature = "bench")]
+pub use benchmark::*;
+
+#[cfg(feature = "assets")]
+mod assets;
+#[cfg(feature = "assets")]
+pub use assets::*;
+#[cfg(feature = "assets")]
+pub use {bytes, mime};
+#[cfg(feature = "hash")]
+pub use digest;
+
+#[cfg(feature = "lopdf")]
+pub mod pdf;
+#[cfg(feature = "lopdf")]
+mod attach;
+#[cfg(feature = "lopdf")]
+pub use attach::*;
+#[cfg(feature = "lopdf")]
+mod page_limit;
+#[cfg(feature = "lopdf")]
+pub use page_limit::*;
+#[cfg(feature = "lopdf")]
+mod language;
+#[cfg(feature = "lopdf")]
+pub use language::*;
+#[cfg(feature = "lopdf")]
+mod select_pages;
+#[cfg(feature = "lopdf")]
+pub use select_pages::*;
+#[cfg(feature = "sign")]
+mod sign;
+#[cfg(feature = "sign")]
+pub use sign::*;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
 /// Specifies a way to convert HTML to a PDF.
 ///
 /// # Type parameters
@@ -334,26 +1191,268 @@ where
         scope: PdfScope<'scope, '_>,
         output: W,
     ) -> Result<Self::HtmlSink, Self::Error>;
-}
 
-/// Automatically implemented for all [`HtmlSink`] types. Used by blanket
-/// implementation for `Box<dyn HtmlSink>`.
-///
-/// For more info about this pattern, see: [Call consuming method for dyn trait
-/// object? - help - The Rust Programming Language
-/// Forum](https://users.rust-lang.org/t/call-consuming-method-for-dyn-trait-object/69596/7)
-pub trait HtmlSinkBoxed<W, E>: Write {
-    fn complete_boxed(self: Box<Self>) -> Result<W, E>;
-}
-impl<W, E, T> HtmlSinkBoxed<W, E> for T
-where
-    T: HtmlSink<W, E>,
-{
-    fn complete_boxed(self: Box<Self>) -> Result<W, E> {
-        T::complete(*self)
+    /// The MIME type of the data that is written to the output, for example to
+    /// use as the `Content-Type` header when serving it over HTTP.
+    ///
+    /// Defaults to `application/pdf`. Converters that produce another format
+    /// should override this and [`output_extension`](Self::output_extension).
+    fn output_content_type(&self) -> &'static str {
+        "application/pdf"
+    }
+    /// Suggested file extension (without a leading `.`) for the data that is
+    /// written to the output. Defaults to `pdf`.
+    fn output_extension(&self) -> &'static str {
+        "pdf"
+    }
+    /// A short name that identifies the converter in logs and error
+    /// messages, for example `chromiumoxide`. Wrappers include the name of
+    /// the converter they wrap, for example `semaphore(chromiumoxide)` for
+    /// [`SemaphoreConverter`].
+    ///
+    /// Defaults to `unknown`.
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("unknown")
+    }
+    /// Features that the converter supports with its current options, for
+    /// example to choose a converter at runtime. Wrappers report the
+    /// capabilities of the converter they wrap.
+    ///
+    /// Defaults to no capabilities.
+    fn capabilities(&self) -> ConverterCapabilities {
+        ConverterCapabilities::default()
+    }
+
+    /// Estimate how many pages the PDF for `html` would have, for example to
+    /// plan a UI before committing to a full conversion. Call this through
+    /// [`estimate_pages`](crate::estimate_pages) which selects the output type.
+    ///
+    /// By default this does a full conversion and counts the pages of the
+    /// result using [`PdfPageCounter`] without keeping the PDF in memory.
+    /// Converters can override this with a cheaper approach.
+    ///
+    /// # Accuracy
+    ///
+    /// The result is only an estimate. Overrides might render the page with
+    /// lower fidelity, which can move page breaks, and PDFs that store their
+    /// pages in compressed object streams are counted as having fewer pages
+    /// than they have, see [`PdfPageCounter`].
+    fn estimate_pages(self, html: &[u8]) -> Result<u32, HtmlToPdfError>
+    where
+        Self: Sized + HtmlToPdfConverter<'static, WriteBuilderSimple<PdfPageCounter>>,
+        <Self as HtmlToPdfConverter<'static, WriteBuilderSimple<PdfPageCounter>>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    {
+        type Output = WriteBuilderSimple<PdfPageCounter>;
+        let output = WriteBuilderSimple(PdfPageCounter::new());
+        let mut html_sink =
+            <Self as HtmlToPdfConverter<'static, Output>>::start(self, PdfScope::owned(), output)
+                .map_err(HtmlToPdfError::engine)?;
+        html_sink.write_all(html)?;
+        let counter = html_sink.complete().map_err(HtmlToPdfError::engine)?.0;
+        Ok(counter.pages())
+    }
+
+    /// Convert the HTML from `html` on a background thread and return a
+    /// reader that yields the PDF data as it is generated, for example to
+    /// forward it to an HTTP response body. Call this through
+    /// [`into_reader`](crate::into_reader) which selects the output type.
+    ///
+    /// Errors from the conversion are returned by the reader once it has read
+    /// all PDF data that was generated, see [`PdfReader`].
+    fn into_reader<R>(self, mut html: R) -> PdfReader
+    where
+        Self:
+            Sized + HtmlToPdfConverter<'static, WriteBuilderSimple<PdfPipeWriter>> + Send + 'static,
+        <Self as HtmlToPdfConverter<'static, WriteBuilderSimple<PdfPipeWriter>>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
+        R: std::io::Read + Send + 'static,
+    {
+        PdfReader::spawn(move |output| {
+            type Output = WriteBuilderSimple<PdfPipeWriter>;
+            let mut html_sink = <Self as HtmlToPdfConverter<'static, Output>>::start(
+                self,
+                PdfScope::owned(),
+                output,
+            )
+            .map_err(HtmlToPdfError::engine)?;
+            std::io::copy(&mut html, &mut html_sink)?;
+            html_sink.complete().map_err(HtmlToPdfError::engine)?;
+            Ok(())
+        })
     }
-}
 
+    /// Convert `html` and return the PDF together with its outline, for
+    /// example to build a navigation pane. Call this through
+    /// [`convert_with_outline`](crate::convert_with_outline) which selects the
+    /// output type.
+    ///
+    /// The outline that the engine stored in the PDF is used if there is one,
+    /// see [`pdf::outline`]. Otherwise it is derived from the headings in
+    /// `html`, see [`pdf::outline_from_html`].
+    #[cfg(feature = "lopdf")]
+    fn convert_with_outline(
+        self,
+        html: &[u8],
+    ) -> Result<(Vec<u8>, Vec<OutlineItem>), HtmlToPdfError>
+    where
+        Self: Sized + HtmlToPdfConverter<'static, WriteBuilderSimple<Vec<u8>>>,
+        <Self as HtmlToPdfConverter<'static, WriteBuilderSimple<Vec<u8>>>>::Error:
+            Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    {
+        type Output = WriteBuilderSimple<Vec<u8>>;
+        let output = WriteBuilderSimple(Vec::new());
+        let mut html_sink =
+            <Self as HtmlToPdfConverter<'static, Output>>::start(self, PdfScope::owned(), output)
+                .map_err(HtmlToPdfError::engine)?;
+        html_sink.write_all(html)?;
+        let pdf = html_sink.complete().map_err(HtmlToPdfError::engine)?.0;
+
+        let mut outline = pdf::outline(&pdf)?;
+        if outline.is_empty() {
+            outline = pdf::outline_from_html(&pdf, html)?;
+        }
+        Ok((pdf, outline))
+    }
+}
+
+/// Object safe version of [`HtmlToPdfConverter`] that is automatically
+/// implemented for all converters whose errors can be converted into `E`.
+/// `Box<dyn DynHtmlToPdfConverter>` implements [`HtmlToPdfConverter`] with a
+/// boxed [`HtmlSink`], so converters can be chosen at runtime, for example
+/// from a list of different converters:
+///
+/// ```
+/// # use html_to_pdf::{DynHtmlToPdfConverter, HtmlToPdfConverter, HtmlSink, PdfScope, WriteBuilderSimple};
+/// # use std::io::Write;
+/// # fn convert(converters: Vec<Box<dyn DynHtmlToPdfConverter<'static, WriteBuilderSimple<Vec<u8>>>>>) -> Result<(), html_to_pdf::HtmlToPdfError> {
+/// for converter in converters {
+///     let mut html_sink = converter.start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))?;
+///     html_sink.write_all(b"<p>Hello</p>")?;
+///     let pdf = html_sink.complete()?.0;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub trait DynHtmlToPdfConverter<'scope, W, E = HtmlToPdfError>
+where
+    W: WriteBuilder + Send + 'scope,
+{
+    /// Start the conversion, see [`HtmlToPdfConverter::start`].
+    fn start_boxed(
+        self: Box<Self>,
+        scope: PdfScope<'scope, '_>,
+        output: W,
+    ) -> Result<Box<dyn HtmlSink<W, E> + 'scope>, E>;
+    /// See [`HtmlToPdfConverter::output_content_type`].
+    fn dyn_output_content_type(&self) -> &'static str;
+    /// See [`HtmlToPdfConverter::output_extension`].
+    fn dyn_output_extension(&self) -> &'static str;
+    /// See [`HtmlToPdfConverter::name`].
+    fn dyn_name(&self) -> Cow<'static, str>;
+    /// See [`HtmlToPdfConverter::capabilities`].
+    fn dyn_capabilities(&self) -> ConverterCapabilities;
+}
+impl<'scope, W, E, T> DynHtmlToPdfConverter<'scope, W, E> for T
+where
+    W: WriteBuilder + Send + 'scope,
+    E: 'scope,
+    T: HtmlToPdfConverter<'scope, W>,
+    T::HtmlSink: 'scope,
+    T::Error: Into<E> + 'scope,
+{
+    fn start_boxed(
+        self: Box<Self>,
+        scope: PdfScope<'scope, '_>,
+        output: W,
+    ) -> Result<Box<dyn HtmlSink<W, E> + 'scope>, E> {
+        let html_sink = T::start(*self, scope, output).map_err(Into::into)?;
+        Ok(Box::new(html_sink.map_completion_err(Into::into)))
+    }
+    fn dyn_output_content_type(&self) -> &'static str {
+        self.output_content_type()
+    }
+    fn dyn_output_extension(&self) -> &'static str {
+        self.output_extension()
+    }
+    fn dyn_name(&self) -> Cow<'static, str> {
+        self.name()
+    }
+    fn dyn_capabilities(&self) -> ConverterCapabilities {
+        self.capabilities()
+    }
+}
+macro_rules! impl_converter_for_boxed_dyn {
+    ($($bounds:tt)*) => {
+        impl<'scope, W, E> HtmlToPdfConverter<'scope, W>
+            for Box<dyn DynHtmlToPdfConverter<'scope, W, E> $($bounds)* + 'scope>
+        where
+            W: WriteBuilder + Send + 'scope,
+            E: fmt::Debug + fmt::Display,
+        {
+            type HtmlSink = Box<dyn HtmlSink<W, E> + 'scope>;
+            type Error = E;
+
+            fn start(
+                self,
+                scope: PdfScope<'scope, '_>,
+                output: W,
+            ) -> Result<Self::HtmlSink, Self::Error> {
+                self.start_boxed(scope, output)
+            }
+            fn output_content_type(&self) -> &'static str {
+                (**self).dyn_output_content_type()
+            }
+            fn output_extension(&self) -> &'static str {
+                (**self).dyn_output_extension()
+            }
+            fn name(&self) -> Cow<'static, str> {
+                (**self).dyn_name()
+            }
+            fn capabilities(&self) -> ConverterCapabilities {
+                (**self).dyn_capabilities()
+            }
+        }
+    };
+}
+impl_converter_for_boxed_dyn!();
+impl_converter_for_boxed_dyn!(+ Send);
+
+/// Automatically implemented for all [`HtmlSink`] types. Used by blanket
+/// implementation for `Box<dyn HtmlSink>`.
+///
+/// For more info about this pattern, see: [Call consuming method for dyn trait
+/// object? - help - The Rust Programming Language
+/// Forum](https://users.rust-lang.org/t/call-consuming-method-for-dyn-trait-object/69596/7)
+pub trait HtmlSinkBoxed<W, E>: Write {
+    fn complete_boxed(self: Box<Self>) -> Result<W, E>;
+    fn complete_with_diagnostics_boxed(self: Box<Self>) -> Result<(W, Vec<Diagnostic>), E>;
+}
+impl<W, E, T> HtmlSinkBoxed<W, E> for T
+where
+    T: HtmlSink<W, E>,
+{
+    fn complete_boxed(self: Box<Self>) -> Result<W, E> {
+        T::complete(*self)
+    }
+    fn complete_with_diagnostics_boxed(self: Box<Self>) -> Result<(W, Vec<Diagnostic>), E> {
+        T::complete_with_diagnostics(*self)
+    }
+}
+
+/// HTML is written to a sink and the PDF is produced once the sink is
+/// [completed](HtmlSink::complete).
+///
+/// # Dropping
+///
+/// Dropping a sink without completing it cancels the conversion: HTML that was
+/// written is discarded, child programs that the converter started are killed
+/// and waited for (see [`ChildGuard`]) and temporary files are deleted. No
+/// more PDF data is written to the output, but converters that stream the PDF
+/// might already have written the start of it, so an output file can be left
+/// with part of a document. This is the same for every sink, including sinks
+/// that wrap other sinks, so returning early with `?` while writing HTML never
+/// produces a PDF of the partially written HTML.
 pub trait HtmlSink<W, E>: HtmlSinkBoxed<W, E> {
     /// Close the HTML sink and finish the PDF conversion. Call this to handle
     /// any PDF conversion errors. This will wait for the PDF conversion to
@@ -363,6 +1462,20 @@ pub trait HtmlSink<W, E>: HtmlSinkBoxed<W, E> {
     where
         Self: Sized;
 
+    /// Like [`HtmlSink::complete`] but also returns problems that the
+    /// converter reported without failing, for example resources that
+    /// couldn't be loaded.
+    ///
+    /// The default implementation returns no diagnostics. Converters that can
+    /// collect them override this and implement [`HtmlSink::complete`] by
+    /// discarding them.
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), E>
+    where
+        Self: Sized,
+    {
+        self.complete().map(|writer| (writer, Vec::new()))
+    }
+
     /// Wrap this sink in a sink that maps the error that happens when the
     /// [`HtmlSink::complete`] method is called.
     fn map_completion_err<E2, F>(self, f: F) -> HtmlSinkMappedError<Self, W, E, E2, F>
@@ -377,19 +1490,163 @@ pub trait HtmlSink<W, E>: HtmlSinkBoxed<W, E> {
         }
     }
 
+    /// Wrap this sink in a sink that maps IO errors returned when writing HTML
+    /// data to it or when flushing it, for example to add context about which
+    /// stage of a layered setup failed. Complements
+    /// [`HtmlSink::map_completion_err`] which only affects
+    /// [`HtmlSink::complete`].
+    ///
+    /// Errors of kind [`io::ErrorKind::Interrupted`] are passed through
+    /// unchanged since callers are expected to retry those.
+    ///
+    /// [`io::ErrorKind::Interrupted`]: std::io::ErrorKind::Interrupted
+    fn map_err_io<F>(self, f: F) -> HtmlSinkMappedIoError<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(std::io::Error) -> std::io::Error,
+    {
+        HtmlSinkMappedIoError { inner: self, f }
+    }
+
     /// Wrap this sink in a sink that maps the [`WriteBuilder`] that is returned
     /// when the [`HtmlSink::complete`] method is called.
-    fn try_map_writer<W2, F>(self, f: F) -> HtmlSinkMappedError<Self, W, W2, E, F>
+    fn try_map_writer<W2, F>(self, f: F) -> HtmlSinkMappedWriter<Self, W, W2, E, F>
     where
         Self: Sized,
         F: FnOnce(W) -> Result<W2, E>,
     {
-        HtmlSinkMappedError {
+        HtmlSinkMappedWriter {
             inner: self,
             f,
             marker: PhantomData,
         }
     }
+
+    /// Wrap this sink in a sink that cleans up the HTML while it is written:
+    /// line endings are normalized to `\n` and control characters other than
+    /// tab and newline (for example NUL bytes) are removed. Some converters
+    /// fail or produce garbled output when given such characters.
+    ///
+    /// Use [`HtmlSink::sanitize_with`] to choose what is changed.
+    fn sanitize(self) -> HtmlSinkSanitized<Self>
+    where
+        Self: Sized,
+    {
+        self.sanitize_with(SanitizePolicy::default())
+    }
+
+    /// Like [`HtmlSink::sanitize`] but with a custom policy.
+    fn sanitize_with(self, policy: SanitizePolicy) -> HtmlSinkSanitized<Self>
+    where
+        Self: Sized,
+    {
+        HtmlSinkSanitized::new(self, policy)
+    }
+
+    /// Wrap this sink in a sink that fails writes once more than `max_bytes`
+    /// of HTML would have been written to it, so that huge inputs can be
+    /// rejected before they exhaust memory. The error wraps a
+    /// [`HtmlTooLargeError`].
+    fn limit(self, max_bytes: u64) -> HtmlSinkLimited<Self>
+    where
+        Self: Sized,
+    {
+        HtmlSinkLimited::new(self, max_bytes)
+    }
+
+    /// Wrap this sink in a sink that calls `f` with a [`ProgressEvent`] after
+    /// each write of HTML data and when the conversion completes or fails,
+    /// for example to show progress during long conversions.
+    fn with_progress<F>(self, f: F) -> HtmlSinkProgress<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(ProgressEvent),
+    {
+        HtmlSinkProgress::new(self, f)
+    }
+
+    /// Wrap this sink in a sink that fails with [`InvalidPdfOutput`] when it
+    /// is completed if the converter didn't write a complete PDF, for example
+    /// because it crashed and only wrote part of the document.
+    ///
+    /// The converter must be started with its output wrapped in
+    /// [`WriteBuilderPdfCheck`]:
+    ///
+    /// ```no_run
+    /// # use html_to_pdf::{HtmlSink, HtmlToPdfConverter, PdfScope, WriteBuilderPdfCheck, WriteBuilderSimple};
+    /// # use std::io::Write;
+    /// # fn f<C>(converter: C) -> Result<Vec<u8>, C::Error>
+    /// # where
+    /// #     C: HtmlToPdfConverter<'static, WriteBuilderPdfCheck<WriteBuilderSimple<Vec<u8>>>>,
+    /// #     C::Error: From<html_to_pdf::InvalidPdfOutput>,
+    /// # {
+    /// let output = WriteBuilderPdfCheck::new(WriteBuilderSimple(Vec::new()));
+    /// let mut html_sink = converter.start(PdfScope::owned(), output)?.validate_pdf();
+    /// html_sink.write_all(b"<p>Hello</p>").expect("failed to write HTML");
+    /// let pdf = html_sink.complete()?.0;
+    /// # Ok(pdf)
+    /// # }
+    /// ```
+    ///
+    /// Only the start of the PDF is checked unless the `eof_check` feature is
+    /// enabled, which it is by default. The check of the end remembers the last
+    /// 1024 bytes that were written, disable it if that cost matters.
+    fn validate_pdf(self) -> HtmlSinkValidatedPdf<Self>
+    where
+        Self: Sized,
+    {
+        HtmlSinkValidatedPdf::new(self)
+    }
+
+    /// Wrap this sink in a sink that finishes the gzip stream when it is
+    /// completed and returns the writer that received the compressed PDF.
+    ///
+    /// The converter must be started with its output wrapped in
+    /// [`WriteBuilderGzip`]:
+    ///
+    /// ```no_run
+    /// # use html_to_pdf::{HtmlSink, HtmlToPdfConverter, PdfScope, WriteBuilderGzip};
+    /// # use std::io::Write;
+    /// # fn f<C>(converter: C) -> Result<Vec<u8>, C::Error>
+    /// # where
+    /// #     C: HtmlToPdfConverter<'static, WriteBuilderGzip<Vec<u8>>>,
+    /// #     C::Error: From<std::io::Error>,
+    /// # {
+    /// let output = WriteBuilderGzip::new(Vec::new(), 6);
+    /// let mut html_sink = converter.start(PdfScope::owned(), output)?.gzip();
+    /// html_sink.write_all(b"<p>Hello</p>").expect("failed to write HTML");
+    /// let gzipped_pdf: Vec<u8> = html_sink.complete()?;
+    /// # Ok(gzipped_pdf)
+    /// # }
+    /// ```
+    ///
+    /// The compression level is the one that was passed to
+    /// [`WriteBuilderGzip::new`]. Completing consumes the sink so the gzip
+    /// trailer is written exactly once, after the converter has written all
+    /// of the PDF.
+    #[cfg(feature = "gzip")]
+    fn gzip(self) -> HtmlSinkGzip<Self>
+    where
+        Self: Sized,
+    {
+        HtmlSinkGzip { inner: self }
+    }
+
+    /// Wrap this sink in a sink that passes the complete PDF through `f`
+    /// before it is written to `output`, for example to add a watermark,
+    /// encrypt the document or edit its metadata. Errors from `f` fail the
+    /// conversion.
+    ///
+    /// Start the converter with `WriteBuilderSimple(Vec::new())` and pass the
+    /// real output to this method instead, see
+    /// [buffering](HtmlSinkPostProcessed#buffering).
+    fn post_process<W2, F>(self, output: W2, f: F) -> HtmlSinkPostProcessed<Self, W2, F>
+    where
+        Self: Sized,
+        F: FnOnce(Vec<u8>) -> std::io::Result<Vec<u8>>,
+    {
+        HtmlSinkPostProcessed::new(self, output, f)
+    }
 }
 impl<W, E, T> HtmlSink<W, E> for Box<T>
 where
@@ -401,6 +1658,56 @@ where
     {
         <T as HtmlSinkBoxed<W, E>>::complete_boxed(self)
     }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), E>
+    where
+        Self: Sized,
+    {
+        <T as HtmlSinkBoxed<W, E>>::complete_with_diagnostics_boxed(self)
+    }
+}
+
+/// Implement [`Write`] by forwarding every method to an inner writer, including
+/// the ones that have default implementations such as `write_vectored`.
+///
+/// Generic parameters (with bounds) are given in brackets after `impl` and the
+/// closure-like part selects the inner writer from `this` (which is `&mut
+/// self`):
+///
+/// ```
+/// struct Sink<W> {
+///     inner: W,
+/// }
+/// html_to_pdf::forward_write!(impl[W: std::io::Write] for Sink<W>, |this| &mut this.inner);
+/// ```
+#[macro_export]
+macro_rules! forward_write {
+    (impl $([$($generics:tt)*])? for $ty:ty, |$this:ident| $inner:expr) => {
+        impl$(<$($generics)*>)? ::std::io::Write for $ty {
+            fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+                let $this = self;
+                ::std::io::Write::write($inner, buf)
+            }
+            fn flush(&mut self) -> ::std::io::Result<()> {
+                let $this = self;
+                ::std::io::Write::flush($inner)
+            }
+            fn write_vectored(
+                &mut self,
+                bufs: &[::std::io::IoSlice<'_>],
+            ) -> ::std::io::Result<usize> {
+                let $this = self;
+                ::std::io::Write::write_vectored($inner, bufs)
+            }
+            fn write_all(&mut self, buf: &[u8]) -> ::std::io::Result<()> {
+                let $this = self;
+                ::std::io::Write::write_all($inner, buf)
+            }
+            fn write_fmt(&mut self, fmt: ::std::fmt::Arguments<'_>) -> ::std::io::Result<()> {
+                let $this = self;
+                ::std::io::Write::write_fmt($inner, fmt)
+            }
+        }
+    };
 }
 
 /// Used by [`HtmlSink::map_completion_err`] to map completion errors for html sinks.
@@ -428,29 +1735,88 @@ where
     {
         <S as HtmlSink<W, E1>>::complete(self.inner).map_err(self.f)
     }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), E2>
+    where
+        Self: Sized,
+    {
+        <S as HtmlSink<W, E1>>::complete_with_diagnostics(self.inner).map_err(self.f)
+    }
 }
-impl<S, W, E1, E2, F> Write for HtmlSinkMappedError<S, W, E1, E2, F>
+crate::forward_write!(
+    impl[S: Write, W, E1, E2, F] for HtmlSinkMappedError<S, W, E1, E2, F>,
+    |this| &mut this.inner
+);
+
+/// Used by [`HtmlSink::map_err_io`] to map IO errors for html sinks.
+pub struct HtmlSinkMappedIoError<S, F> {
+    inner: S,
+    f: F,
+}
+impl<S, F> HtmlSinkMappedIoError<S, F> {
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+impl<S, F> HtmlSinkMappedIoError<S, F>
+where
+    F: FnMut(std::io::Error) -> std::io::Error,
+{
+    fn map<T>(&mut self, result: std::io::Result<T>) -> std::io::Result<T> {
+        result.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::Interrupted {
+                e
+            } else {
+                (self.f)(e)
+            }
+        })
+    }
+}
+impl<S, W, E, F> HtmlSink<W, E> for HtmlSinkMappedIoError<S, F>
+where
+    S: HtmlSink<W, E>,
+    F: FnMut(std::io::Error) -> std::io::Error,
+{
+    fn complete(self) -> Result<W, E>
+    where
+        Self: Sized,
+    {
+        <S as HtmlSink<W, E>>::complete(self.inner)
+    }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), E>
+    where
+        Self: Sized,
+    {
+        <S as HtmlSink<W, E>>::complete_with_diagnostics(self.inner)
+    }
+}
+impl<S, F> Write for HtmlSinkMappedIoError<S, F>
 where
     S: Write,
+    F: FnMut(std::io::Error) -> std::io::Error,
 {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        <S as Write>::write(&mut self.inner, buf)
+        let result = <S as Write>::write(&mut self.inner, buf);
+        self.map(result)
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        <S as Write>::flush(&mut self.inner)
+        let result = <S as Write>::flush(&mut self.inner);
+        self.map(result)
     }
 
     fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
-        <S as Write>::write_vectored(&mut self.inner, bufs)
+        let result = <S as Write>::write_vectored(&mut self.inner, bufs);
+        self.map(result)
     }
 
     fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
-        <S as Write>::write_all(&mut self.inner, buf)
+        let result = <S as Write>::write_all(&mut self.inner, buf);
+        self.map(result)
     }
 
     fn write_fmt(&mut self, fmt: fmt::Arguments<'_>) -> std::io::Result<()> {
-        <S as Write>::write_fmt(&mut self.inner, fmt)
+        let result = <S as Write>::write_fmt(&mut self.inner, fmt);
+        self.map(result)
     }
 }
 
@@ -479,28 +1845,547 @@ where
     {
         <S as HtmlSink<W1, E>>::complete(self.inner).and_then(self.f)
     }
+    fn complete_with_diagnostics(self) -> Result<(W2, Vec<Diagnostic>), E>
+    where
+        Self: Sized,
+    {
+        let (writer, diagnostics) = <S as HtmlSink<W1, E>>::complete_with_diagnostics(self.inner)?;
+        Ok(((self.f)(writer)?, diagnostics))
+    }
 }
-impl<S, W1, W2, E, F> Write for HtmlSinkMappedWriter<S, W1, W2, E, F>
+crate::forward_write!(
+    impl[S: Write, W1, W2, E, F] for HtmlSinkMappedWriter<S, W1, W2, E, F>,
+    |this| &mut this.inner
+);
+
+/// Used by [`HtmlSink::gzip`] to finish the gzip stream of a
+/// [`WriteBuilderGzip`] when the sink is completed.
+#[cfg(feature = "gzip")]
+pub struct HtmlSinkGzip<S> {
+    inner: S,
+}
+#[cfg(feature = "gzip")]
+impl<S> HtmlSinkGzip<S> {
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+#[cfg(feature = "gzip")]
+impl<S, W, E> HtmlSink<W, E> for HtmlSinkGzip<S>
 where
-    S: Write,
+    S: HtmlSink<WriteBuilderGzip<W>, E>,
+    W: Write,
+    E: From<std::io::Error>,
 {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        <S as Write>::write(&mut self.inner, buf)
+    fn complete(self) -> Result<W, E>
+    where
+        Self: Sized,
+    {
+        self.complete_with_diagnostics().map(|(writer, _)| writer)
+    }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), E>
+    where
+        Self: Sized,
+    {
+        let (writer, diagnostics) =
+            <S as HtmlSink<WriteBuilderGzip<W>, E>>::complete_with_diagnostics(self.inner)?;
+        Ok((writer.finish()?, diagnostics))
     }
+}
+#[cfg(feature = "gzip")]
+crate::forward_write!(impl[S: Write] for HtmlSinkGzip<S>, |this| &mut this.inner);
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        <S as Write>::flush(&mut self.inner)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, BufRead, Read};
+
+    /// Fails all writes.
+    struct FailingHtmlSink;
+    impl Write for FailingHtmlSink {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "converter exited",
+            ))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl HtmlSink<(), io::Error> for FailingHtmlSink {
+        fn complete(self) -> io::Result<()> {
+            Ok(())
+        }
     }
 
-    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
-        <S as Write>::write_vectored(&mut self.inner, bufs)
+    #[test]
+    fn map_err_io_adds_context_to_write_errors() {
+        let mut html_sink = FailingHtmlSink.map_err_io(|e| {
+            io::Error::new(e.kind(), format!("failed to send HTML to converter: {e}"))
+        });
+
+        let error = html_sink.write_all(b"<p>").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(
+            error.to_string(),
+            "failed to send HTML to converter: converter exited"
+        );
+        HtmlSink::<(), io::Error>::complete(html_sink).unwrap();
     }
 
-    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
-        <S as Write>::write_all(&mut self.inner, buf)
+    #[test]
+    fn write_stream_detects_reader_closing_early() {
+        let mut stream = WriteStream::stream(PdfScope::owned(), |mut html: ReadStream| {
+            let mut start = [0; 3];
+            html.read_exact(&mut start).map(|()| start)
+        });
+        let error = loop {
+            if let Err(e) = stream.write_all(&[b'a'; 1024]) {
+                break e;
+            }
+        };
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        assert!(stream.reader_closed_early());
+
+        let joined = stream.join_result();
+        assert!(joined.reader_closed_early);
+        assert_eq!(joined.result.unwrap().unwrap(), *b"aaa");
     }
 
-    fn write_fmt(&mut self, fmt: fmt::Arguments<'_>) -> std::io::Result<()> {
-        <S as Write>::write_fmt(&mut self.inner, fmt)
+    #[test]
+    fn write_stream_reader_consuming_everything_is_not_early() {
+        let mut stream = WriteStream::stream(PdfScope::owned(), |mut html: ReadStream| {
+            let mut all = Vec::new();
+            html.read_to_end(&mut all).map(|_| all)
+        });
+        stream.write_all(b"<p>Hello</p>").unwrap();
+
+        let joined = stream.join_result();
+        assert!(!joined.reader_closed_early);
+        assert_eq!(joined.result.unwrap().unwrap(), b"<p>Hello</p>");
+    }
+
+    #[test]
+    fn write_stream_panic_is_returned_as_error() {
+        let stream = WriteStream::stream(PdfScope::owned(), |_html: ReadStream| -> u32 {
+            panic!("generation failed")
+        });
+        let error = stream.join_result().result.unwrap_err();
+        assert_eq!(error.message(), Some("generation failed"));
+        assert_eq!(
+            error.to_string(),
+            "a worker thread panicked: generation failed"
+        );
+
+        let stream = WriteStream::stream(PdfScope::owned(), |_html: ReadStream| 5);
+        assert_eq!(stream.join_result().result, Ok(5));
+    }
+
+    #[test]
+    fn dropped_write_stream_is_an_error_for_the_reader() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut stream = WriteStream::stream(PdfScope::owned(), move |mut html: ReadStream| {
+            let mut all = Vec::new();
+            sender.send(html.read_to_end(&mut all)).unwrap();
+        });
+        stream.write_all(b"<p>Hel").unwrap();
+        drop(stream);
+
+        let error = receiver.recv().unwrap().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn bounded_write_stream_limits_buffered_data() {
+        const CAPACITY: usize = 64 * 1024;
+        const TOTAL: usize = 50 * 1024 * 1024;
+
+        let mut stream = WriteStream::stream_bounded(
+            PdfScope::owned(),
+            CAPACITY,
+            |mut html: ReadStream| -> io::Result<_> {
+                let (mut total, mut max_buffered) = (0, 0);
+                loop {
+                    let len = html.fill_buf()?.len();
+                    if len == 0 {
+                        return Ok((total, max_buffered));
+                    }
+                    max_buffered = max_buffered.max(len);
+                    total += len;
+                    html.consume(len);
+                    if total % (1024 * 1024) < len {
+                        std::thread::sleep(std::time::Duration::from_millis(1));
+                    }
+                }
+            },
+        );
+        let chunk = vec![b'a'; 1024 * 1024];
+        for _ in 0..TOTAL / chunk.len() {
+            let written = stream.write(&chunk).unwrap();
+            assert!(written <= CAPACITY);
+            stream.write_all(&chunk[written..]).unwrap();
+        }
+
+        let (total, max_buffered) = stream.join().unwrap().unwrap();
+        assert_eq!(total, TOTAL);
+        assert!(
+            max_buffered <= CAPACITY,
+            "{max_buffered} bytes were buffered"
+        );
+    }
+
+    #[test]
+    fn bounded_write_stream_detects_reader_closing_early() {
+        let mut stream =
+            WriteStream::stream_bounded(PdfScope::owned(), 16, |mut html: ReadStream| {
+                let mut start = [0; 3];
+                html.read_exact(&mut start).map(|()| start)
+            });
+        let error = stream.write_all(&[b'a'; 1024]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+
+        let joined = stream.join_result();
+        assert!(joined.reader_closed_early);
+        assert_eq!(joined.result.unwrap().unwrap(), *b"aaa");
+    }
+
+    #[test]
+    fn file_builder_only_creates_the_file_when_used() {
+        let path = std::env::temp_dir().join(format!(
+            "html_to_pdf-lazy-file-{}.pdf",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let builder = WriteBuilderFile::create(&path);
+        drop(builder);
+        assert!(!path.exists());
+
+        let mut builder = WriteBuilderFile::create(&path);
+        builder.get_writer().unwrap().write_all(b"%PDF-").unwrap();
+        builder.get_writer().unwrap().write_all(b"1.7").unwrap();
+        assert!(builder.file().is_some());
+        assert!(builder.into_file().unwrap().is_some());
+        assert_eq!(std::fs::read(&path).unwrap(), b"%PDF-1.7");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn tee_writes_identical_bytes_to_both_writers() {
+        /// Accepts at most 3 bytes per write.
+        struct ShortWriter(Vec<u8>);
+        impl Write for ShortWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let len = buf.len().min(3);
+                self.0.extend_from_slice(&buf[..len]);
+                Ok(len)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut tee = WriteBuilderTee(
+            WriteBuilderSimple(ShortWriter(Vec::new())),
+            WriteBuilderSimple(Vec::new()),
+        );
+        let pdf = b"%PDF-1.7\n%%EOF";
+        let mut writer = tee.get_writer().unwrap();
+        let written = writer.write(pdf).unwrap();
+        assert_eq!(written, 3);
+        writer.write_all(&pdf[written..]).unwrap();
+        writer.flush().unwrap();
+
+        let WriteBuilderTee(WriteBuilderSimple(first), WriteBuilderSimple(second)) = tee;
+        assert_eq!(first.0, pdf);
+        assert_eq!(first.0, second);
+    }
+
+    #[test]
+    fn counting_builder_composes_with_tee() {
+        let mut tee = WriteBuilderTee(
+            WriteBuilderCounting::new(WriteBuilderSimple(Vec::new())),
+            WriteBuilderSimple(Vec::new()),
+        );
+        let mut writer = tee.get_writer().unwrap();
+        writer.write_all(b"%PDF-1.7\n").unwrap();
+        writer.write_all(b"%%EOF").unwrap();
+
+        let WriteBuilderTee(counting, WriteBuilderSimple(copy)) = tee;
+        assert_eq!(counting.bytes_written(), 14);
+        assert_eq!(counting.into_inner().0, copy);
+    }
+
+    #[cfg(feature = "hash")]
+    #[test]
+    fn hashing_builder_hashes_the_converted_pdf() {
+        use crate::testing::MockConverter;
+        use sha2::{Digest, Sha256};
+
+        let output = WriteBuilderHashing::<_, Sha256>::new(WriteBuilderSimple(Vec::new()));
+        let mut html_sink = MockConverter::new()
+            .start(PdfScope::owned(), output)
+            .unwrap();
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        let (WriteBuilderSimple(pdf), digest) = html_sink.complete().unwrap().finish();
+
+        assert_eq!(pdf, MockConverter::fake_pdf(12));
+        assert_eq!(digest, Sha256::digest(&pdf));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_sink_finishes_the_stream_on_complete() {
+        use crate::testing::MockConverter;
+
+        let mut html_sink = MockConverter::new()
+            .start(PdfScope::owned(), WriteBuilderGzip::new(Vec::new(), 6))
+            .unwrap()
+            .gzip();
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        let gzipped = html_sink.complete().unwrap();
+
+        let mut pdf = Vec::new();
+        flate2::read::GzDecoder::new(&gzipped[..])
+            .read_to_end(&mut pdf)
+            .unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        assert_eq!(pdf, MockConverter::fake_pdf(12));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_sink_uses_the_level_of_the_output() {
+        use crate::testing::MockConverter;
+
+        let mut html_sink = MockConverter::new()
+            .start(PdfScope::owned(), WriteBuilderGzip::new(Vec::new(), 0))
+            .unwrap()
+            .gzip();
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        let gzipped = html_sink.complete().unwrap();
+
+        // Level 0 stores the data without compressing it:
+        let expected = MockConverter::fake_pdf(12);
+        assert!(gzipped
+            .windows(expected.len())
+            .any(|window| window == expected));
+        let mut pdf = Vec::new();
+        flate2::read::GzDecoder::new(&gzipped[..])
+            .read_to_end(&mut pdf)
+            .unwrap();
+        assert_eq!(pdf, expected);
+    }
+
+    #[test]
+    fn cancelled_write_stream_fails_with_cancelled() {
+        let token = CancelToken::new();
+        let mut stream = WriteStream::stream(
+            PdfScope::owned_with_cancel(token.clone()),
+            |mut html: ReadStream| {
+                let mut all = Vec::new();
+                html.read_to_end(&mut all).map(|_| all)
+            },
+        );
+        stream.write_all(b"<p>Hel").unwrap();
+        token.cancel();
+
+        let error = stream.write_all(b"lo</p>").unwrap_err();
+        assert!(matches!(
+            HtmlToPdfError::from(error),
+            HtmlToPdfError::Cancelled
+        ));
+        let error = stream.join().unwrap().unwrap_err();
+        assert!(matches!(
+            HtmlToPdfError::from(error),
+            HtmlToPdfError::Cancelled
+        ));
+    }
+
+    #[test]
+    fn diagnostics_are_forwarded_by_wrappers() {
+        /// Reports a warning when completed.
+        struct WarningHtmlSink;
+        impl Write for WarningHtmlSink {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        impl HtmlSink<(), io::Error> for WarningHtmlSink {
+            fn complete(self) -> io::Result<()> {
+                self.complete_with_diagnostics().map(|(writer, _)| writer)
+            }
+            fn complete_with_diagnostics(self) -> io::Result<((), Vec<Diagnostic>)> {
+                Ok(((), vec![Diagnostic::warning("failed to load image")]))
+            }
+        }
+
+        let html_sink: Box<dyn HtmlSink<(), String>> = Box::new(
+            WarningHtmlSink
+                .map_completion_err(|e| e.to_string())
+                .sanitize()
+                .limit(1024),
+        );
+        let ((), diagnostics) = html_sink.complete_with_diagnostics().unwrap();
+        assert_eq!(diagnostics, [Diagnostic::warning("failed to load image")]);
+        assert_eq!(diagnostics[0].to_string(), "warning: failed to load image");
+    }
+
+    #[test]
+    fn forward_write_forwards_all_methods() {
+        /// Records which methods were called.
+        #[derive(Default)]
+        struct Recorder(Vec<&'static str>);
+        impl Write for Recorder {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.push("write");
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                self.0.push("flush");
+                Ok(())
+            }
+            fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+                self.0.push("write_vectored");
+                Ok(bufs.iter().map(|buf| buf.len()).sum())
+            }
+            fn write_all(&mut self, _buf: &[u8]) -> io::Result<()> {
+                self.0.push("write_all");
+                Ok(())
+            }
+        }
+
+        struct Sink {
+            recorder: Recorder,
+        }
+        crate::forward_write!(impl for Sink, |this| &mut this.recorder);
+
+        let mut sink = Sink {
+            recorder: Recorder::default(),
+        };
+        sink.write_all(b"a").unwrap();
+        let written = sink
+            .write_vectored(&[io::IoSlice::new(b"b"), io::IoSlice::new(b"c")])
+            .unwrap();
+        assert_eq!(written, 2);
+        sink.flush().unwrap();
+        assert_eq!(sink.recorder.0, ["write_all", "write_vectored", "flush"]);
+    }
+
+    #[test]
+    fn boxed_converters_can_be_chosen_at_runtime() {
+        use crate::testing::MockConverter;
+
+        /// Writes the HTML to the output as is.
+        struct EchoConverter;
+        impl<'scope, W> HtmlToPdfConverter<'scope, W> for EchoConverter
+        where
+            W: WriteBuilder + Send + 'scope,
+        {
+            type HtmlSink = EchoHtmlSink<W>;
+            type Error = HtmlToPdfError;
+
+            fn start(
+                self,
+                _scope: PdfScope<'scope, '_>,
+                output: W,
+            ) -> Result<Self::HtmlSink, Self::Error> {
+                Ok(EchoHtmlSink(Vec::new(), output))
+            }
+            fn output_content_type(&self) -> &'static str {
+                "text/html"
+            }
+        }
+        struct EchoHtmlSink<W>(Vec<u8>, W);
+        crate::forward_write!(impl[W] for EchoHtmlSink<W>, |this| &mut this.0);
+        impl<W: WriteBuilder> HtmlSink<W, HtmlToPdfError> for EchoHtmlSink<W> {
+            fn complete(mut self) -> Result<W, HtmlToPdfError> {
+                self.1.get_writer()?.write_all(&self.0)?;
+                Ok(self.1)
+            }
+        }
+
+        type Output = WriteBuilderSimple<Vec<u8>>;
+        let converters: Vec<Box<dyn DynHtmlToPdfConverter<'static, Output> + Send>> =
+            vec![Box::new(MockConverter::new()), Box::new(EchoConverter)];
+        let mut outputs = Vec::new();
+        for converter in converters {
+            let content_type = converter.output_content_type();
+            let mut html_sink = converter
+                .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+                .unwrap();
+            html_sink.write_all(b"<p>Hello</p>").unwrap();
+            outputs.push((content_type, html_sink.complete().unwrap().0));
+        }
+        assert_eq!(
+            outputs,
+            [
+                ("application/pdf", MockConverter::fake_pdf(12)),
+                ("text/html", b"<p>Hello</p>".to_vec()),
+            ]
+        );
+    }
+
+    fn output_type<'scope, C>(converter: &C) -> (&'static str, &'static str)
+    where
+        C: HtmlToPdfConverter<'scope, WriteBuilderSimple<Vec<u8>>>,
+    {
+        (
+            converter.output_content_type(),
+            converter.output_extension(),
+        )
+    }
+
+    #[test]
+    fn output_type_defaults_to_pdf_and_is_forwarded() {
+        use crate::testing::MockConverter;
+
+        /// Pretends to produce images.
+        struct PngConverter(MockConverter);
+        impl<'scope, W> HtmlToPdfConverter<'scope, W> for PngConverter
+        where
+            W: WriteBuilder + Send + 'scope,
+        {
+            type HtmlSink = <MockConverter as HtmlToPdfConverter<'scope, W>>::HtmlSink;
+            type Error = io::Error;
+
+            fn start(
+                self,
+                scope: PdfScope<'scope, '_>,
+                output: W,
+            ) -> Result<Self::HtmlSink, Self::Error> {
+                self.0.start(scope, output)
+            }
+            fn output_content_type(&self) -> &'static str {
+                "image/png"
+            }
+            fn output_extension(&self) -> &'static str {
+                "png"
+            }
+        }
+
+        assert_eq!(
+            output_type(&MockConverter::new()),
+            ("application/pdf", "pdf")
+        );
+        assert_eq!(
+            output_type(&TimedConverter::new(
+                PngConverter(MockConverter::new()),
+                |_| {}
+            )),
+            ("image/png", "png")
+        );
+        assert_eq!(
+            output_type(&SemaphoreConverter::new(
+                PngConverter(MockConverter::new()),
+                Semaphore::new(1)
+            )),
+            ("image/png", "png")
+        );
     }
 }
@@ -0,0 +1,118 @@
+//! Reject HTML that is larger than a max size.
+
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Write},
+};
+
+use crate::{Diagnostic, HtmlSink};
+
+/// The HTML written to a sink returned by [`HtmlSink::limit`] was larger than
+/// its max size. Writes fail with an [`io::Error`] of kind
+/// [`io::ErrorKind::OutOfMemory`] that wraps this error, use
+/// [`HtmlTooLargeError::from_io`] to detect it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HtmlTooLargeError {
+    pub max_bytes: u64,
+}
+impl HtmlTooLargeError {
+    /// Get the error that caused an IO error, if any.
+    pub fn from_io(error: &io::Error) -> Option<&Self> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+impl fmt::Display for HtmlTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the HTML is larger than the max size of {} bytes",
+            self.max_bytes
+        )
+    }
+}
+impl Error for HtmlTooLargeError {}
+
+/// Used by [`HtmlSink::limit`] to reject HTML that is too large.
+pub struct HtmlSinkLimited<S> {
+    inner: S,
+    max_bytes: u64,
+    written: u64,
+}
+impl<S> HtmlSinkLimited<S> {
+    pub(crate) fn new(inner: S, max_bytes: u64) -> Self {
+        Self {
+            inner,
+            max_bytes,
+            written: 0,
+        }
+    }
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+impl<S, W, E> HtmlSink<W, E> for HtmlSinkLimited<S>
+where
+    S: HtmlSink<W, E>,
+{
+    fn complete(self) -> Result<W, E>
+    where
+        Self: Sized,
+    {
+        <S as HtmlSink<W, E>>::complete(self.inner)
+    }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), E>
+    where
+        Self: Sized,
+    {
+        <S as HtmlSink<W, E>>::complete_with_diagnostics(self.inner)
+    }
+}
+impl<S: Write> Write for HtmlSinkLimited<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() as u64 > self.max_bytes - self.written {
+            return Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                HtmlTooLargeError {
+                    max_bytes: self.max_bytes,
+                },
+            ));
+        }
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockConverter;
+    use crate::{HtmlToPdfConverter, PdfScope, WriteBuilderSimple};
+
+    #[test]
+    fn writing_past_the_limit_fails() {
+        let converter = MockConverter::new();
+        let mut html_sink = converter
+            .clone()
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap()
+            .limit(9);
+
+        html_sink.write_all(b"<p>").unwrap();
+        let error = html_sink.write_all(b"too long").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::OutOfMemory);
+        assert_eq!(
+            HtmlTooLargeError::from_io(&error),
+            Some(&HtmlTooLargeError { max_bytes: 9 })
+        );
+
+        // Writes that fit are still accepted:
+        html_sink.write_all(b"Hi</p>").unwrap();
+        html_sink.complete().unwrap();
+        assert_eq!(converter.last_html().unwrap(), b"<p>Hi</p>");
+    }
+}
@@ -0,0 +1,262 @@
+//! The outline (bookmarks) of a converted document, for example to build a
+//! navigation pane.
+
+use std::iter::Peekable;
+
+#[cfg(feature = "lopdf")]
+use crate::{HtmlToPdfConverter, HtmlToPdfError, WriteBuilderSimple};
+
+/// An entry in a document's outline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    pub title: String,
+    /// The page that the entry links to, numbered from `1`.
+    pub page: u32,
+    /// Entries that are nested below this one, for example the `<h2>`
+    /// headings of a section that starts with an `<h1>`.
+    pub children: Vec<OutlineItem>,
+}
+
+/// A `<h1>` to `<h6>` element in some HTML, see [`html_headings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// `1` for `<h1>` up to `6` for `<h6>`.
+    pub level: u8,
+    /// The text of the heading with tags removed and whitespace collapsed.
+    pub title: String,
+}
+
+/// Find all headings in `html` in document order. Headings without any text
+/// are skipped.
+///
+/// This is a simple scan for heading tags, not a full HTML parser, so
+/// headings inside comments or scripts are found as well.
+pub fn html_headings(html: &[u8]) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut rest = html;
+    while let Some((level, after_tag)) = next_heading_start(rest) {
+        let content = &rest[after_tag..];
+        let end = find_closing_tag(content, level).unwrap_or(content.len());
+        let title = heading_text(&content[..end]);
+        if !title.is_empty() {
+            headings.push(Heading { level, title });
+        }
+        rest = &content[end..];
+    }
+    headings
+}
+
+/// Build an outline from the headings in `html`, nesting each heading below
+/// the closest preceding heading with a lower level. `page_of` is called with
+/// the title of each heading in document order and should return the page
+/// that the heading was placed on.
+pub fn outline_from_headings(
+    html: &[u8],
+    mut page_of: impl FnMut(&str) -> u32,
+) -> Vec<OutlineItem> {
+    outline_from_heading_pages(html_headings(html).into_iter().map(|heading| {
+        let page = page_of(&heading.title);
+        (heading, page)
+    }))
+}
+
+/// Build an outline from headings in document order together with the page
+/// that each of them was placed on, nesting them like
+/// [`outline_from_headings`].
+pub fn outline_from_heading_pages(
+    headings: impl IntoIterator<Item = (Heading, u32)>,
+) -> Vec<OutlineItem> {
+    let mut items = headings
+        .into_iter()
+        .map(|(heading, page)| {
+            let item = OutlineItem {
+                title: heading.title,
+                page,
+                children: Vec::new(),
+            };
+            (heading.level, item)
+        })
+        .peekable();
+    nest_items(&mut items, 0)
+}
+
+/// Take all items whose level is larger than `parent_level`, nesting later
+/// items below earlier ones with lower levels.
+fn nest_items(
+    items: &mut Peekable<impl Iterator<Item = (u8, OutlineItem)>>,
+    parent_level: u8,
+) -> Vec<OutlineItem> {
+    let mut nested = Vec::new();
+    while let Some((level, mut item)) = items.next_if(|(level, _)| *level > parent_level) {
+        item.children = nest_items(items, level);
+        nested.push(item);
+    }
+    nested
+}
+
+/// Find the next `<hN` tag and return its level and the index after the end
+/// of the tag.
+fn next_heading_start(html: &[u8]) -> Option<(u8, usize)> {
+    let mut offset = 0;
+    while let Some(start) = html[offset..].iter().position(|&b| b == b'<') {
+        let tag = &html[offset + start..];
+        offset += start + 1;
+        let [_, h, level @ b'1'..=b'6', after, ..] = *tag else {
+            continue;
+        };
+        if !h.eq_ignore_ascii_case(&b'h')
+            || !(after == b'>' || after == b'/' || after.is_ascii_whitespace())
+        {
+            continue;
+        }
+        let end = tag.iter().position(|&b| b == b'>')?;
+        return Some((level - b'0', offset - 1 + end + 1));
+    }
+    None
+}
+
+/// The index of the `</hN` tag that closes a heading with the specified level.
+fn find_closing_tag(content: &[u8], level: u8) -> Option<usize> {
+    let closing = [b'<', b'/', b'h', b'0' + level];
+    content
+        .windows(closing.len())
+        .position(|window| window.eq_ignore_ascii_case(&closing))
+}
+
+/// Remove tags, decode common character references and collapse whitespace.
+fn heading_text(content: &[u8]) -> String {
+    let mut text = Vec::with_capacity(content.len());
+    let mut in_tag = false;
+    for &byte in content {
+        match byte {
+            b'<' => in_tag = true,
+            b'>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(byte),
+            _ => {}
+        }
+    }
+    let text = decode_character_references(&String::from_utf8_lossy(&text));
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn decode_character_references(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((decode_reference(&rest[1..end])?, end)));
+        match reference {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_reference(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    })
+}
+
+/// Convert `html` and return the PDF together with its outline, see
+/// [`HtmlToPdfConverter::convert_with_outline`].
+///
+/// This picks the output type that the conversion is done with, which the
+/// trait method can't infer by itself.
+#[cfg(feature = "lopdf")]
+pub fn convert_with_outline<C>(
+    converter: C,
+    html: &[u8],
+) -> Result<(Vec<u8>, Vec<OutlineItem>), HtmlToPdfError>
+where
+    C: HtmlToPdfConverter<'static, WriteBuilderSimple<Vec<u8>>>,
+    C::Error: Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
+{
+    converter.convert_with_outline(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(title: &str, page: u32, children: Vec<OutlineItem>) -> OutlineItem {
+        OutlineItem {
+            title: title.to_owned(),
+            page,
+            children,
+        }
+    }
+
+    #[test]
+    fn headings_are_found() {
+        let html = b"<H1 class=\"title\">Fish &amp; <em>Chips</em></h1>\
+            <p>Not a heading</p><header>Nor this</header>\
+            <h2>\n  Cod\n</H2><h3></h3><h6>&#72;&#x69;</h6>";
+        assert_eq!(
+            html_headings(html),
+            [
+                Heading {
+                    level: 1,
+                    title: "Fish & Chips".to_owned()
+                },
+                Heading {
+                    level: 2,
+                    title: "Cod".to_owned()
+                },
+                Heading {
+                    level: 6,
+                    title: "Hi".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn outline_follows_heading_levels() {
+        let html = b"<h2>Preface</h2>\
+            <h1>One</h1><h2>One A</h2><h2>One B</h2>\
+            <h1>Two</h1><h2>Two A</h2>";
+        let mut page = 0;
+        let outline = outline_from_headings(html, |_| {
+            page += 1;
+            page
+        });
+        assert_eq!(
+            outline,
+            [
+                item("Preface", 1, vec![]),
+                item(
+                    "One",
+                    2,
+                    vec![item("One A", 3, vec![]), item("One B", 4, vec![])]
+                ),
+                item("Two", 5, vec![item("Two A", 6, vec![])]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Limit how many pages a generated PDF can have.
+
+use std::{borrow::Cow, error::Error, fmt};
+
+use crate::{
+    pdf, post_process::start_post_processed, ConverterCapabilities, Diagnostic, HtmlToPdfConverter,
+    HtmlToPdfError, PdfPostProcessor, PdfScope, PostProcessedHtmlSink, WriteBuilder,
+    WriteBuilderSimple,
+};
+
+/// What [`PageLimitConverter`] does with a PDF that has too many pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageLimitPolicy {
+    /// Fail the conversion with a [`TooManyPagesError`].
+    #[default]
+    Error,
+    /// Keep the first pages and remove the rest, see [`pdf::truncate_pages`].
+    /// A warning is added to the conversion's diagnostics.
+    Truncate,
+}
+
+/// A PDF had more pages than a [`PageLimitConverter`] allows. Returned as the
+/// source of an [`HtmlToPdfError::Engine`] error, use
+/// [`TooManyPagesError::from_error`] to detect it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooManyPagesError {
+    pub pages: u32,
+    pub max_pages: u32,
+}
+impl TooManyPagesError {
+    /// Get the error that caused a conversion error, if any.
+    pub fn from_error(error: &HtmlToPdfError) -> Option<&Self> {
+        error.source()?.downcast_ref()
+    }
+}
+impl fmt::Display for TooManyPagesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the PDF has {} pages which is more than the max of {} pages",
+            self.pages, self.max_pages
+        )
+    }
+}
+impl Error for TooManyPagesError {}
+
+/// Wraps a converter and checks the number of pages of the generated PDF, to
+/// protect systems that process the PDF from runaway documents. PDFs with more
+/// than `max_pages` pages are handled according to `policy`, if `max_pages`
+/// is `None` then any number of pages is allowed.
+///
+/// The pages are counted after the conversion, so the wrapped converter can be
+/// anything but the PDF is [buffered](crate::HtmlSinkPostProcessed#buffering)
+/// and the conversion isn't stopped early.
+#[derive(Debug, Clone)]
+pub struct PageLimitConverter<C> {
+    pub converter: C,
+    pub max_pages: Option<u32>,
+    pub policy: PageLimitPolicy,
+}
+impl<C> PageLimitConverter<C> {
+    pub fn new(converter: C, max_pages: u32, policy: PageLimitPolicy) -> Self {
+        Self {
+            converter,
+            max_pages: Some(max_pages),
+            policy,
+        }
+    }
+}
+impl<'scope, W, C> HtmlToPdfConverter<'scope, W> for PageLimitConverter<C>
+where
+    W: WriteBuilder + Send + 'scope,
+    C: HtmlToPdfConverter<'scope, WriteBuilderSimple<Vec<u8>>>,
+    C::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    type HtmlSink = PageLimitHtmlSink<C::HtmlSink, W, C::Error>;
+    type Error = HtmlToPdfError;
+
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        let processor = PageLimitPostProcessor {
+            max_pages: self.max_pages,
+            policy: self.policy,
+        };
+        start_post_processed(self.converter, scope, output, processor)
+    }
+
+    fn name(&self) -> Cow<'static, str> {
+        format!("page_limit({})", self.converter.name()).into()
+    }
+    fn capabilities(&self) -> ConverterCapabilities {
+        self.converter.capabilities()
+    }
+}
+
+/// The [`PdfPostProcessor`] of [`PageLimitConverter`].
+#[derive(Debug, Clone)]
+pub struct PageLimitPostProcessor {
+    max_pages: Option<u32>,
+    policy: PageLimitPolicy,
+}
+impl PdfPostProcessor for PageLimitPostProcessor {
+    type Error = HtmlToPdfError;
+
+    fn process(
+        self,
+        mut pdf: Vec<u8>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Vec<u8>, HtmlToPdfError> {
+        let Some(max_pages) = self.max_pages else {
+            return Ok(pdf);
+        };
+        let pages = pdf::page_count(&pdf)?;
+        if pages > max_pages {
+            match self.policy {
+                PageLimitPolicy::Error => {
+                    return Err(HtmlToPdfError::engine(TooManyPagesError {
+                        pages,
+                        max_pages,
+                    }))
+                }
+                PageLimitPolicy::Truncate => {
+                    pdf = pdf::truncate_pages(&pdf, max_pages)?;
+                    diagnostics.push(Diagnostic::warning(format!(
+                        "Removed {} pages since the PDF had {pages} pages but only \
+                        {max_pages} are allowed",
+                        pages - max_pages
+                    )));
+                }
+            }
+        }
+        Ok(pdf)
+    }
+}
+
+/// The [`HtmlSink`](crate::HtmlSink) for [`PageLimitConverter`].
+pub type PageLimitHtmlSink<S, W, E> = PostProcessedHtmlSink<S, W, E, PageLimitPostProcessor>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::BlankPdfConverter, HtmlSink};
+    use std::io::Write;
+
+    fn convert(
+        converter: PageLimitConverter<BlankPdfConverter>,
+    ) -> Result<(Vec<u8>, Vec<Diagnostic>), HtmlToPdfError> {
+        let mut html_sink = converter.start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))?;
+        html_sink.write_all(b"<p>Hello</p>")?;
+        let (WriteBuilderSimple(pdf), diagnostics) = html_sink.complete_with_diagnostics()?;
+        Ok((pdf, diagnostics))
+    }
+
+    #[test]
+    fn too_many_pages_is_an_error() {
+        let converter = PageLimitConverter::new(BlankPdfConverter::new(5), 3, Default::default());
+        let error = convert(converter).unwrap_err();
+        assert_eq!(
+            TooManyPagesError::from_error(&error),
+            Some(&TooManyPagesError {
+                pages: 5,
+                max_pages: 3
+            })
+        );
+
+        // At the limit:
+        let converter = PageLimitConverter::new(BlankPdfConverter::new(3), 3, Default::default());
+        let (pdf, diagnostics) = convert(converter).unwrap();
+        assert_eq!(pdf::page_count(&pdf).unwrap(), 3);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn too_many_pages_are_truncated() {
+        let converter =
+            PageLimitConverter::new(BlankPdfConverter::new(5), 3, PageLimitPolicy::Truncate);
+        let (pdf, diagnostics) = convert(converter).unwrap();
+        assert_eq!(pdf::page_count(&pdf).unwrap(), 3);
+        assert_eq!(diagnostics.len(), 1);
+    }
+}
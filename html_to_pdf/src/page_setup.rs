@@ -0,0 +1,553 @@
+//! Describes the paper that a converter should lay out the document on.
+
+use std::{error::Error, fmt, str::FromStr};
+
+/// A page setup option couldn't be parsed from a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePageSetupError(pub String);
+impl fmt::Display for ParsePageSetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+impl Error for ParsePageSetupError {}
+
+/// A distance on the paper, stored in PDF points (1/72 inch).
+///
+/// Parsed from a number followed by a unit: `mm`, `cm`, `in`, `pt` or `px`
+/// (CSS pixels, 1/96 inch), for example `12.7mm` or `0.5 in`. Only `0` can
+/// be written without a unit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct Length {
+    points: f64,
+}
+impl Length {
+    /// No distance at all.
+    pub const ZERO: Self = Self { points: 0.0 };
+
+    const UNITS: &'static [(&'static str, f64)] = &[
+        ("mm", 72.0 / 25.4),
+        ("cm", 72.0 / 2.54),
+        ("in", 72.0),
+        ("pt", 1.0),
+        ("px", 72.0 / 96.0),
+    ];
+
+    pub fn from_points(points: f64) -> Self {
+        Self { points }
+    }
+    pub fn from_mm(mm: f64) -> Self {
+        Self::from_points(mm * 72.0 / 25.4)
+    }
+    pub fn from_inches(inches: f64) -> Self {
+        Self::from_points(inches * 72.0)
+    }
+
+    pub fn points(self) -> f64 {
+        self.points
+    }
+    pub fn mm(self) -> f64 {
+        self.points * 25.4 / 72.0
+    }
+    pub fn inches(self) -> f64 {
+        self.points / 72.0
+    }
+}
+/// Formats the length in points, so that it can be parsed again.
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}pt", self.points)
+    }
+}
+impl FromStr for Length {
+    type Err = ParsePageSetupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let (number, factor) = Self::UNITS
+            .iter()
+            .find_map(|&(unit, factor)| {
+                let number = trimmed.get(..trimmed.len().checked_sub(unit.len())?)?;
+                trimmed[number.len()..]
+                    .eq_ignore_ascii_case(unit)
+                    .then_some((number, factor))
+            })
+            // A distance of zero is the same in all units:
+            .unwrap_or((trimmed, if trimmed == "0" { 1.0 } else { f64::NAN }));
+        match number.trim_end().parse::<f64>() {
+            Ok(number) if number.is_finite() && !factor.is_nan() => {
+                Ok(Self::from_points(number * factor))
+            }
+            _ => Err(ParsePageSetupError(format!(
+                "{s:?} isn't a length like \"10mm\", \"1.5cm\", \"0.5in\", \"12pt\" or \"96px\""
+            ))),
+        }
+    }
+}
+
+/// A standard paper size.
+///
+/// Only some converters accept this option, others are configured with their
+/// own page setup options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PageSize {
+    /// 297 x 420 mm.
+    A3,
+    /// 210 x 297 mm.
+    A4,
+    /// 148 x 210 mm.
+    A5,
+    /// 8.5 x 11 inches.
+    Letter,
+    /// 8.5 x 14 inches.
+    Legal,
+    /// 11 x 17 inches.
+    Tabloid,
+}
+impl PageSize {
+    /// All supported sizes.
+    pub const ALL: &'static [Self] = &[
+        Self::A3,
+        Self::A4,
+        Self::A5,
+        Self::Letter,
+        Self::Legal,
+        Self::Tabloid,
+    ];
+
+    /// The name of the size, for example `A4` or `Letter`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::A3 => "A3",
+            Self::A4 => "A4",
+            Self::A5 => "A5",
+            Self::Letter => "Letter",
+            Self::Legal => "Legal",
+            Self::Tabloid => "Tabloid",
+        }
+    }
+    /// Find a size from its [`name`](Self::name), case insensitive.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|v| v.name().eq_ignore_ascii_case(name))
+    }
+    /// The width and height of the page in portrait orientation, in PDF
+    /// points (1/72 inch).
+    pub fn size_in_points(self) -> (f64, f64) {
+        const MM: f64 = 72.0 / 25.4;
+        const INCH: f64 = 72.0;
+        match self {
+            Self::A3 => (297.0 * MM, 420.0 * MM),
+            Self::A4 => (210.0 * MM, 297.0 * MM),
+            Self::A5 => (148.0 * MM, 210.0 * MM),
+            Self::Letter => (8.5 * INCH, 11.0 * INCH),
+            Self::Legal => (8.5 * INCH, 14.0 * INCH),
+            Self::Tabloid => (11.0 * INCH, 17.0 * INCH),
+        }
+    }
+}
+impl fmt::Display for PageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The size of the paper, either a standard size or a custom width and
+/// height.
+///
+/// Parsed from a standard size's name like `a4`, or from a width and height
+/// separated by `x` like `210mm x 297mm`, see [`Length`] for the supported
+/// units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Paper {
+    Standard(PageSize),
+    /// The width and height in portrait orientation.
+    Custom {
+        width: Length,
+        height: Length,
+    },
+}
+impl Paper {
+    /// The width and height of the paper in portrait orientation.
+    pub fn size(self) -> (Length, Length) {
+        match self {
+            Self::Standard(size) => {
+                let (width, height) = size.size_in_points();
+                (Length::from_points(width), Length::from_points(height))
+            }
+            Self::Custom { width, height } => (width, height),
+        }
+    }
+}
+impl From<PageSize> for Paper {
+    fn from(size: PageSize) -> Self {
+        Self::Standard(size)
+    }
+}
+impl fmt::Display for Paper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Standard(size) => size.fmt(f),
+            Self::Custom { width, height } => write!(f, "{width} x {height}"),
+        }
+    }
+}
+impl FromStr for Paper {
+    type Err = ParsePageSetupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(size) = PageSize::from_name(s.trim()) {
+            return Ok(Self::Standard(size));
+        }
+        // The unit "px" contains an "x" so try every "x" as the separator:
+        s.match_indices(['x', 'X'])
+            .find_map(|(index, _)| {
+                Some(Self::Custom {
+                    width: s[..index].parse().ok()?,
+                    height: s[index + 1..].parse().ok()?,
+                })
+            })
+            .ok_or_else(|| {
+                let names = PageSize::ALL
+                    .iter()
+                    .map(|size| size.name())
+                    .collect::<Vec<_>>();
+                ParsePageSetupError(format!(
+                    "{s:?} isn't one of the paper sizes {} or a custom size like \"210mm x 297mm\"",
+                    names.join(", ")
+                ))
+            })
+    }
+}
+
+/// Which way the paper is turned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PageOrientation {
+    /// The page is taller than it is wide.
+    #[default]
+    Portrait,
+    /// The page is wider than it is tall.
+    Landscape,
+}
+impl PageOrientation {
+    /// Turn a width and height in portrait orientation to this orientation.
+    pub fn apply<T>(self, (width, height): (T, T)) -> (T, T) {
+        match self {
+            Self::Portrait => (width, height),
+            Self::Landscape => (height, width),
+        }
+    }
+}
+impl FromStr for PageOrientation {
+    type Err = ParsePageSetupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            s if s.eq_ignore_ascii_case("portrait") => Ok(Self::Portrait),
+            s if s.eq_ignore_ascii_case("landscape") => Ok(Self::Landscape),
+            _ => Err(ParsePageSetupError(format!(
+                "{s:?} isn't an orientation, use \"portrait\" or \"landscape\""
+            ))),
+        }
+    }
+}
+
+/// Space between the edges of the paper and the content.
+///
+/// Parsed like the CSS `margin` property from one to four lengths separated
+/// by whitespace, for example `1cm` for all sides or `10mm 20mm` for 10mm at
+/// the top and bottom and 20mm on the left and right side.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Margin {
+    pub top: Length,
+    pub right: Length,
+    pub bottom: Length,
+    pub left: Length,
+}
+impl Margin {
+    /// The same margin on all sides.
+    pub fn all(length: Length) -> Self {
+        Self {
+            top: length,
+            right: length,
+            bottom: length,
+            left: length,
+        }
+    }
+}
+/// Formats the margin with four lengths, so that it can be parsed again.
+impl fmt::Display for Margin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.top, self.right, self.bottom, self.left
+        )
+    }
+}
+impl FromStr for Margin {
+    type Err = ParsePageSetupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lengths = s
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<Length>, _>>()?;
+        let [top, right, bottom, left] = match lengths[..] {
+            [all] => [all; 4],
+            [vertical, horizontal] => [vertical, horizontal, vertical, horizontal],
+            [top, horizontal, bottom] => [top, horizontal, bottom, horizontal],
+            [top, right, bottom, left] => [top, right, bottom, left],
+            _ => {
+                return Err(ParsePageSetupError(format!(
+                    "{s:?} isn't a margin of one to four lengths like \"1cm\" or \"10mm 20mm\""
+                )))
+            }
+        };
+        Ok(Self {
+            top,
+            right,
+            bottom,
+            left,
+        })
+    }
+}
+
+/// Page geometry that can be applied to any converter with
+/// [`WithPageOptions::with_page_options`]. `None` keeps the converter's
+/// current setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PageOptions {
+    pub page_size: Option<Paper>,
+    pub orientation: Option<PageOrientation>,
+    pub margins: Option<Margin>,
+    /// The resolution that the page is laid out with, only used by
+    /// converters that render at a configurable resolution.
+    pub dpi: Option<u32>,
+}
+
+/// Implemented by converters that can lay out pages using [`PageOptions`].
+///
+/// Options that a converter can't honor are ignored rather than treated as
+/// an error, so the same options can be given to any converter. Check the
+/// converter's documentation for which options it uses.
+pub trait WithPageOptions: Sized {
+    /// Apply the options that are `Some` and leave the rest as they were.
+    fn with_page_options(self, options: PageOptions) -> Self;
+}
+
+/// A selection of pages, numbered from `1`.
+///
+/// Parsed from a comma separated list of pages and ranges of pages like
+/// Chrome's print dialog, for example `1-3,5` for the first three pages and
+/// the fifth page, or `4-` for all pages from the fourth. Pages are always
+/// kept in the order of the document, even if the ranges are listed in
+/// another order or overlap.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PageRanges {
+    /// Inclusive ranges, `None` as the end means the last page.
+    ranges: Vec<(u32, Option<u32>)>,
+}
+impl PageRanges {
+    /// `true` if the page is selected.
+    pub fn contains(&self, page: u32) -> bool {
+        self.ranges
+            .iter()
+            .any(|&(first, last)| first <= page && last.is_none_or(|last| page <= last))
+    }
+    /// Check that all selected pages exist in a document with `page_count`
+    /// pages.
+    pub fn check(&self, page_count: u32) -> Result<(), PagesOutOfRangeError> {
+        let page = self
+            .ranges
+            .iter()
+            .map(|&(first, last)| last.unwrap_or(first))
+            .max()
+            .unwrap_or(0);
+        if page > page_count {
+            return Err(PagesOutOfRangeError {
+                ranges: self.clone(),
+                page_count,
+            });
+        }
+        Ok(())
+    }
+}
+/// Formats the ranges like they are parsed, which is also the format that
+/// Chrome's `pageRanges` print option uses.
+impl fmt::Display for PageRanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, &(first, last)) in self.ranges.iter().enumerate() {
+            if index > 0 {
+                f.write_str(",")?;
+            }
+            match last {
+                Some(last) if last == first => write!(f, "{first}")?,
+                Some(last) => write!(f, "{first}-{last}")?,
+                None => write!(f, "{first}-")?,
+            }
+        }
+        Ok(())
+    }
+}
+impl FromStr for PageRanges {
+    type Err = ParsePageSetupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            ParsePageSetupError(format!(
+                "{s:?} isn't a list of pages like \"1-3,5\", pages are numbered from 1"
+            ))
+        };
+        let page = |page: &str| {
+            page.trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|&page| page > 0)
+                .ok_or_else(invalid)
+        };
+        let ranges = s
+            .split(',')
+            .map(|range| {
+                let range = match range.split_once('-') {
+                    Some((first, last)) if last.trim().is_empty() => (page(first)?, None),
+                    Some((first, last)) => (page(first)?, Some(page(last)?)),
+                    None => (page(range)?, Some(page(range)?)),
+                };
+                match range {
+                    (first, Some(last)) if last < first => Err(invalid()),
+                    range => Ok(range),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { ranges })
+    }
+}
+
+/// [`PageRanges`] selected pages that a document doesn't have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PagesOutOfRangeError {
+    pub ranges: PageRanges,
+    pub page_count: u32,
+}
+impl fmt::Display for PagesOutOfRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the pages {} can't be selected since the document only has {} pages",
+            self.ranges, self.page_count
+        )
+    }
+}
+impl Error for PagesOutOfRangeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for &size in PageSize::ALL {
+            assert_eq!(PageSize::from_name(size.name()), Some(size));
+            assert_eq!(
+                PageSize::from_name(&size.name().to_ascii_lowercase()),
+                Some(size)
+            );
+        }
+        assert_eq!(PageSize::from_name("B5"), None);
+
+        let (width, height) = PageOrientation::Landscape.apply(PageSize::A3.size_in_points());
+        assert_eq!((width.round(), height.round()), (1191.0, 842.0));
+    }
+
+    #[test]
+    fn paper_is_parsed_from_names_and_sizes() {
+        assert_eq!("a4".parse(), Ok(Paper::Standard(PageSize::A4)));
+        assert_eq!(" Letter ".parse(), Ok(Paper::Standard(PageSize::Letter)));
+
+        let paper: Paper = "210mm x 297mm".parse().unwrap();
+        let (width, height) = paper.size();
+        let (a4_width, a4_height) = Paper::Standard(PageSize::A4).size();
+        assert!((width.points() - a4_width.points()).abs() < 1e-9);
+        assert!((height.points() - a4_height.points()).abs() < 1e-9);
+
+        let paper: Paper = "816pxX1056PX".parse().unwrap();
+        assert_eq!(paper.size().0.inches(), 8.5);
+        assert_eq!(paper.size().1.inches(), 11.0);
+        assert_eq!(paper.to_string().parse(), Ok(paper));
+
+        for invalid in ["B5", "210 x 297", "210mm x", "x 297mm", "210mm by 297mm"] {
+            assert!(invalid.parse::<Paper>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn lengths_are_converted_between_units() {
+        let inch: Length = "2.54cm".parse().unwrap();
+        assert!((inch.inches() - 1.0).abs() < 1e-9);
+        assert_eq!("0.5 IN".parse::<Length>().unwrap().points(), 36.0);
+        assert_eq!("96px".parse::<Length>().unwrap().inches(), 1.0);
+        assert_eq!("12pt".parse::<Length>().unwrap().points(), 12.0);
+        assert!(("25.4mm".parse::<Length>().unwrap().mm() - 25.4).abs() < 1e-9);
+        assert_eq!("0".parse::<Length>(), Ok(Length::ZERO));
+        for invalid in ["10", "mm", "10 furlongs", "inf mm", ""] {
+            assert!(invalid.parse::<Length>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn margin_is_parsed_like_css() {
+        let mm = Length::from_mm;
+        assert_eq!("10mm".parse(), Ok(Margin::all(mm(10.0))));
+        assert_eq!(
+            "10mm 20mm".parse(),
+            Ok(Margin {
+                top: mm(10.0),
+                right: mm(20.0),
+                bottom: mm(10.0),
+                left: mm(20.0),
+            })
+        );
+        assert_eq!(
+            "10mm 20mm 30mm".parse(),
+            Ok(Margin {
+                top: mm(10.0),
+                right: mm(20.0),
+                bottom: mm(30.0),
+                left: mm(20.0),
+            })
+        );
+        let margin: Margin = "1in 0 2cm 12pt".parse().unwrap();
+        assert_eq!(margin.top.points(), 72.0);
+        assert_eq!(margin.right, Length::ZERO);
+        assert_eq!(margin.left.points(), 12.0);
+        assert_eq!(margin.to_string().parse(), Ok(margin));
+
+        for invalid in ["", "1mm 2mm 3mm 4mm 5mm", "1mm two"] {
+            assert!(invalid.parse::<Margin>().is_err(), "{invalid}");
+        }
+        assert_eq!("LANDSCAPE".parse(), Ok(PageOrientation::Landscape));
+    }
+
+    #[test]
+    fn page_ranges_are_parsed() {
+        let ranges: PageRanges = "1-3, 5,7-".parse().unwrap();
+        assert_eq!(ranges.to_string(), "1-3,5,7-");
+        assert_eq!(
+            (1..=9)
+                .filter(|&page| ranges.contains(page))
+                .collect::<Vec<_>>(),
+            [1, 2, 3, 5, 7, 8, 9]
+        );
+        assert_eq!(ranges.check(7), Ok(()));
+        assert!(ranges.check(6).is_err());
+        assert!("2".parse::<PageRanges>().unwrap().check(1).is_err());
+
+        for invalid in ["", "0", "3-1", "1,,2", "-2", "a-b", "1-2-3"] {
+            assert!(invalid.parse::<PageRanges>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,1082 @@
+//! Helpers that inspect or edit already generated PDF documents using the
+//! [`lopdf`] crate.
+//!
+//! These work with any converter since they operate on the produced PDF data,
+//! but that also means the whole PDF needs to be buffered in memory.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    num::NonZeroU32,
+};
+
+pub use lopdf;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::{outline_from_headings, OutlineItem, PageRanges, PdfAConformance};
+
+/// The number of pages in a PDF document.
+pub fn page_count(pdf: &[u8]) -> lopdf::Result<u32> {
+    Ok(Document::load_mem(pdf)?.get_pages().len() as u32)
+}
+
+/// The PDF/A conformance that a document declares in the XMP metadata of its
+/// catalog, or `None` if it doesn't declare any.
+///
+/// This doesn't validate that the document actually follows the standard.
+pub fn pdf_a_conformance(pdf: &[u8]) -> lopdf::Result<Option<PdfAConformance>> {
+    let document = Document::load_mem(pdf)?;
+    let Ok(metadata) = document.catalog()?.get(b"Metadata") else {
+        return Ok(None);
+    };
+    let (_, metadata) = document.dereference(metadata)?;
+    let metadata = metadata.as_stream()?;
+    // The metadata stream is usually uncompressed so that it can be read by
+    // tools that don't understand PDF:
+    let xmp = metadata
+        .decompressed_content()
+        .unwrap_or_else(|_| metadata.content.clone());
+    Ok(PdfAConformance::from_xmp(&String::from_utf8_lossy(&xmp)))
+}
+
+/// Declare that a PDF document follows `conformance` by storing XMP metadata
+/// with the `pdfaid` schema and an output intent with `icc_profile` in its
+/// catalog, replacing the metadata and output intents that it had.
+/// `icc_profile` must be a gray, RGB or CMYK color profile, usually sRGB, and
+/// its color space is read from the profile's header. Fails with
+/// [`lopdf::Error::Invalid`] for other profiles.
+///
+/// The document information dictionary is removed since PDF/A requires its
+/// entries to be repeated in the XMP metadata, and an `/ID` is added to the
+/// trailer if the document doesn't have one.
+///
+/// This doesn't change the content of the document, so it only conforms if
+/// the converter already followed the other rules of the standard, for
+/// example by embedding all fonts. Check important documents with a PDF/A
+/// validator such as veraPDF.
+pub fn set_pdf_a_conformance(
+    pdf: &[u8],
+    conformance: PdfAConformance,
+    icc_profile: &[u8],
+) -> lopdf::Result<Vec<u8>> {
+    let mut document = Document::load_mem(pdf)?;
+    apply_pdf_a_conformance(&mut document, conformance, icc_profile)?;
+    if document.trailer.get(b"ID").is_err() {
+        let mut hasher = DefaultHasher::new();
+        pdf.hash(&mut hasher);
+        let id = Object::String(
+            hasher.finish().to_be_bytes().to_vec(),
+            StringFormat::Hexadecimal,
+        );
+        document
+            .trailer
+            .set("ID", Object::Array(vec![id.clone(), id]));
+    }
+    // Remove the replaced metadata and the information dictionary:
+    document.prune_objects();
+
+    let mut data = Vec::new();
+    document.save_to(&mut data)?;
+    Ok(data)
+}
+
+/// The number of color components of an ICC profile, which is stored as `/N`
+/// in the stream that embeds it. The color space is at bytes `16..20` of the
+/// profile's header.
+fn icc_profile_components(icc_profile: &[u8]) -> lopdf::Result<i64> {
+    match icc_profile.get(16..20) {
+        Some(b"GRAY") => Ok(1),
+        Some(b"RGB ") => Ok(3),
+        Some(b"CMYK") => Ok(4),
+        _ => Err(lopdf::Error::Invalid(
+            "the ICC profile isn't for a gray, RGB or CMYK color space".to_owned(),
+        )),
+    }
+}
+
+fn apply_pdf_a_conformance(
+    document: &mut Document,
+    conformance: PdfAConformance,
+    icc_profile: &[u8],
+) -> lopdf::Result<()> {
+    let xmp = format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+        <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+        <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+        <rdf:Description rdf:about=\"\" xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\" \
+        pdfaid:part=\"{}\" pdfaid:conformance=\"{}\"/>\n\
+        </rdf:RDF>\n\
+        </x:xmpmeta>\n\
+        <?xpacket end=\"w\"?>",
+        conformance.part(),
+        conformance.level(),
+    );
+    // Not compressed so that tools that don't understand PDF can find it:
+    let metadata = document.add_object(Object::Stream(Stream::new(
+        Dictionary::from_iter([
+            ("Type", Object::from("Metadata")),
+            ("Subtype", Object::from("XML")),
+        ]),
+        xmp.into_bytes(),
+    )));
+
+    let mut profile = Stream::new(
+        Dictionary::from_iter([("N", Object::Integer(icc_profile_components(icc_profile)?))]),
+        icc_profile.to_vec(),
+    );
+    profile.compress()?;
+    let profile = document.add_object(Object::Stream(profile));
+    let output_intent = Dictionary::from_iter([
+        ("Type", Object::from("OutputIntent")),
+        ("S", Object::from("GTS_PDFA1")),
+        ("OutputConditionIdentifier", text_string_object("Custom")),
+        ("DestOutputProfile", Object::Reference(profile)),
+    ]);
+
+    let catalog = document.catalog_mut()?;
+    catalog.set("Metadata", Object::Reference(metadata));
+    catalog.set(
+        "OutputIntents",
+        Object::Array(vec![Object::Dictionary(output_intent)]),
+    );
+
+    document.trailer.remove(b"Info");
+    Ok(())
+}
+
+/// The outline (bookmarks) that is stored in a PDF document, or an empty list
+/// if it doesn't have one.
+///
+/// Entries whose destination can't be resolved to a page use the page of the
+/// entry before them.
+pub fn outline(pdf: &[u8]) -> lopdf::Result<Vec<OutlineItem>> {
+    read_outline(&Document::load_mem(pdf)?)
+}
+
+fn read_outline(document: &Document) -> lopdf::Result<Vec<OutlineItem>> {
+    let Ok(outlines) = document.catalog()?.get(b"Outlines") else {
+        return Ok(Vec::new());
+    };
+    let (_, outlines) = document.dereference(outlines)?;
+    let Ok(first) = outlines.as_dict()?.get(b"First") else {
+        return Ok(Vec::new());
+    };
+    let mut reader = OutlineReader {
+        page_numbers: document
+            .get_pages()
+            .into_iter()
+            .map(|(number, id)| (id, number))
+            .collect(),
+        document,
+        visited: HashSet::new(),
+        previous_page: 1,
+    };
+    reader.siblings(first)
+}
+
+struct OutlineReader<'a> {
+    document: &'a Document,
+    page_numbers: HashMap<ObjectId, u32>,
+    /// Protects against malformed documents whose entries link in a loop.
+    visited: HashSet<ObjectId>,
+    previous_page: u32,
+}
+impl<'a> OutlineReader<'a> {
+    /// Read an entry and all entries after it, following their `Next` links.
+    fn siblings(&mut self, first: &Object) -> lopdf::Result<Vec<OutlineItem>> {
+        let mut items = Vec::new();
+        let mut next = Some(first);
+        while let Some(entry) = next {
+            let (id, entry) = self.document.dereference(entry)?;
+            if id.is_some_and(|id| !self.visited.insert(id)) {
+                break;
+            }
+            let entry = entry.as_dict()?;
+            let title = entry
+                .get(b"Title")
+                .and_then(|title| self.document.dereference(title))
+                .and_then(|(_, title)| title.as_str())
+                .map(text_string)
+                .unwrap_or_default();
+            let page = self.destination_page(entry).unwrap_or(self.previous_page);
+            self.previous_page = page;
+            let children = match entry.get(b"First") {
+                Ok(first) => self.siblings(first)?,
+                Err(_) => Vec::new(),
+            };
+            items.push(OutlineItem {
+                title,
+                page,
+                children,
+            });
+            next = entry.get(b"Next").ok();
+        }
+        Ok(items)
+    }
+
+    /// The page that an outline entry's `Dest` or `GoTo` action links to.
+    fn destination_page(&self, entry: &lopdf::Dictionary) -> Option<u32> {
+        let destination = match entry.get(b"Dest") {
+            Ok(destination) => destination,
+            Err(_) => {
+                let (_, action) = self.document.dereference(entry.get(b"A").ok()?).ok()?;
+                let action = action.as_dict().ok()?;
+                if action.get(b"S").and_then(Object::as_name).ok()? != b"GoTo" {
+                    return None;
+                }
+                action.get(b"D").ok()?
+            }
+        };
+        let (_, mut destination) = self.document.dereference(destination).ok()?;
+        if let Ok(name) = destination.as_name().or_else(|_| destination.as_str()) {
+            destination = self.named_destination(name)?;
+        }
+        if let Ok(dictionary) = destination.as_dict() {
+            destination = self
+                .document
+                .dereference(dictionary.get(b"D").ok()?)
+                .ok()?
+                .1;
+        }
+        match destination.as_array().ok()?.first()? {
+            Object::Reference(page) => self.page_numbers.get(page).copied(),
+            // Destinations in other documents use page indexes:
+            Object::Integer(index) => u32::try_from(*index + 1).ok(),
+            _ => None,
+        }
+    }
+
+    /// Look up a destination by name in the catalog's `Dests` dictionary or
+    /// its `Names` tree.
+    fn named_destination(&self, name: &[u8]) -> Option<&'a Object> {
+        let catalog = self.document.catalog().ok()?;
+        if let Ok((_, dests)) = catalog
+            .get(b"Dests")
+            .and_then(|dests| self.document.dereference(dests))
+        {
+            if let Ok(destination) = dests.as_dict().and_then(|dests| dests.get(name)) {
+                return Some(self.document.dereference(destination).ok()?.1);
+            }
+        }
+        let (_, names) = self
+            .document
+            .dereference(catalog.get(b"Names").ok()?)
+            .ok()?;
+        let tree = names.as_dict().ok()?.get(b"Dests").ok()?;
+        self.name_tree_lookup(tree, name, 0)
+    }
+
+    fn name_tree_lookup(&self, node: &'a Object, name: &[u8], depth: u32) -> Option<&'a Object> {
+        // Real name trees are shallow, so this only stops malformed ones:
+        if depth > 32 {
+            return None;
+        }
+        let (_, node) = self.document.dereference(node).ok()?;
+        let node = node.as_dict().ok()?;
+        if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+            for pair in names.chunks_exact(2) {
+                let key = self.document.dereference(&pair[0]).ok()?.1;
+                if key.as_str().is_ok_and(|key| key == name) {
+                    return Some(self.document.dereference(&pair[1]).ok()?.1);
+                }
+            }
+        }
+        let kids = node.get(b"Kids").and_then(Object::as_array).ok()?;
+        kids.iter()
+            .find_map(|kid| self.name_tree_lookup(kid, name, depth + 1))
+    }
+}
+
+/// Decode a PDF text string, which is either UTF-16BE with a byte order mark
+/// or PDFDocEncoding, which is treated as Latin-1.
+fn text_string(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xFE, 0xFF]) {
+        Some(utf16) => String::from_utf16_lossy(
+            &utf16
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect::<Vec<_>>(),
+        ),
+        None => bytes.iter().map(|&b| char::from(b)).collect(),
+    }
+}
+
+/// Encode a PDF text string, using UTF-16BE unless `text` is ASCII.
+pub(crate) fn text_string_object(text: &str) -> Object {
+    if text.is_ascii() {
+        return Object::string_literal(text);
+    }
+    let mut bytes = vec![0xFE, 0xFF];
+    bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+    Object::string_literal(bytes)
+}
+
+/// Store `outline` as the outline (bookmarks) of a PDF document, replacing
+/// any outline that it already has. Entries link to the top of their page,
+/// entries for pages past the end of the document link to the last page.
+pub fn set_outline(pdf: &[u8], outline: &[OutlineItem]) -> lopdf::Result<Vec<u8>> {
+    let mut document = Document::load_mem(pdf)?;
+    apply_outline(&mut document, outline)?;
+    // Remove the entries of a replaced outline:
+    document.prune_objects();
+
+    let mut data = Vec::new();
+    document.save_to(&mut data)?;
+    Ok(data)
+}
+
+fn apply_outline(document: &mut Document, outline: &[OutlineItem]) -> lopdf::Result<()> {
+    let pages = document.get_pages();
+    let outlines = document.new_object_id();
+    let mut dictionary = Dictionary::from_iter([("Type", Object::from("Outlines"))]);
+    if let Some((first, last, count)) = add_outline_items(document, &pages, outlines, outline) {
+        dictionary.set("First", Object::Reference(first));
+        dictionary.set("Last", Object::Reference(last));
+        dictionary.set("Count", Object::Integer(count));
+    }
+    document
+        .objects
+        .insert(outlines, Object::Dictionary(dictionary));
+    document
+        .catalog_mut()?
+        .set("Outlines", Object::Reference(outlines));
+    Ok(())
+}
+
+/// Add `items` as the children of the outline entry `parent`. Returns the
+/// first and last entry and how many entries are visible below `parent`, all
+/// entries are open.
+fn add_outline_items(
+    document: &mut Document,
+    pages: &BTreeMap<u32, ObjectId>,
+    parent: ObjectId,
+    items: &[OutlineItem],
+) -> Option<(ObjectId, ObjectId, i64)> {
+    let ids = items
+        .iter()
+        .map(|_| document.new_object_id())
+        .collect::<Vec<_>>();
+    let mut count = 0;
+    for (index, item) in items.iter().enumerate() {
+        let mut entry = Dictionary::from_iter([
+            ("Title", text_string_object(&item.title)),
+            ("Parent", Object::Reference(parent)),
+        ]);
+        if let Some(&page) = pages.get(&item.page).or_else(|| pages.values().next_back()) {
+            entry.set(
+                "Dest",
+                Object::Array(vec![Object::Reference(page), Object::from("Fit")]),
+            );
+        }
+        if let Some(previous) = index.checked_sub(1) {
+            entry.set("Prev", Object::Reference(ids[previous]));
+        }
+        if let Some(&next) = ids.get(index + 1) {
+            entry.set("Next", Object::Reference(next));
+        }
+        if let Some((first, last, children)) =
+            add_outline_items(document, pages, ids[index], &item.children)
+        {
+            entry.set("First", Object::Reference(first));
+            entry.set("Last", Object::Reference(last));
+            entry.set("Count", Object::Integer(children));
+            count += children;
+        }
+        count += 1;
+        document
+            .objects
+            .insert(ids[index], Object::Dictionary(entry));
+    }
+    Some((*ids.first()?, *ids.last()?, count))
+}
+
+/// Build an outline from the headings in `html`, see
+/// [`outline_from_headings`](crate::outline_from_headings). Each heading is
+/// placed on the first page at or after the previous heading's page whose text
+/// contains the heading's title, or on the same page as the previous heading
+/// if it isn't found.
+///
+/// Finding text in a PDF isn't reliable since some engines don't store it in a
+/// form that can be extracted, so prefer [`outline`] for documents that have
+/// one.
+pub fn outline_from_html(pdf: &[u8], html: &[u8]) -> lopdf::Result<Vec<OutlineItem>> {
+    let document = Document::load_mem(pdf)?;
+    let page_count = document.get_pages().len() as u32;
+    let mut page_texts = vec![None; page_count as usize];
+    let mut current = 1;
+    Ok(outline_from_headings(html, |title| {
+        for page in current..=page_count {
+            let text = page_texts[page as usize - 1].get_or_insert_with(|| {
+                let text = document.extract_text(&[page]).unwrap_or_default();
+                text.split_whitespace().collect::<Vec<_>>().join(" ")
+            });
+            if text.contains(title) {
+                current = page;
+                break;
+            }
+        }
+        current
+    }))
+}
+
+/// How pages are arranged when a PDF is opened, stored as the catalog's
+/// `/PageLayout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageLayout {
+    /// One page at a time.
+    SinglePage,
+    /// Pages in a single scrolling column.
+    OneColumn,
+    /// Two scrolling columns with odd pages on the left.
+    TwoColumnLeft,
+    /// Two scrolling columns with odd pages on the right.
+    TwoColumnRight,
+    /// Two pages at a time with odd pages on the left.
+    TwoPageLeft,
+    /// Two pages at a time with odd pages on the right, like a book that
+    /// starts with a cover page.
+    TwoPageRight,
+}
+impl PageLayout {
+    fn name(self) -> &'static str {
+        match self {
+            PageLayout::SinglePage => "SinglePage",
+            PageLayout::OneColumn => "OneColumn",
+            PageLayout::TwoColumnLeft => "TwoColumnLeft",
+            PageLayout::TwoColumnRight => "TwoColumnRight",
+            PageLayout::TwoPageLeft => "TwoPageLeft",
+            PageLayout::TwoPageRight => "TwoPageRight",
+        }
+    }
+}
+
+/// Which panel is shown next to the pages when a PDF is opened, stored as the
+/// catalog's `/PageMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageMode {
+    /// No panel.
+    UseNone,
+    /// Show the outline (bookmarks), see [`outline`].
+    UseOutlines,
+    /// Show page thumbnails.
+    UseThumbs,
+    /// Open in full screen mode.
+    FullScreen,
+}
+impl PageMode {
+    fn name(self) -> &'static str {
+        match self {
+            PageMode::UseNone => "UseNone",
+            PageMode::UseOutlines => "UseOutlines",
+            PageMode::UseThumbs => "UseThumbs",
+            PageMode::FullScreen => "FullScreen",
+        }
+    }
+}
+
+/// The zoom that the first page is shown with when a PDF is opened, stored as
+/// a destination in the catalog's `/OpenAction`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InitialZoom {
+    /// Fit the whole page in the window.
+    FitPage,
+    /// Fit the width of the page in the window.
+    FitWidth,
+    /// A zoom factor where `1.0` is 100%.
+    Factor(f32),
+}
+
+/// Preferences for how PDF readers should display a document when it is
+/// opened, see [`set_viewer_preferences`]. Fields that are `None` or `false`
+/// leave the document's existing preferences unchanged.
+///
+/// # Reader support
+///
+/// Readers treat these as hints. Adobe Acrobat Reader honors all of them.
+/// Firefox's built-in viewer honors the page layout, page mode and initial
+/// zoom but not the window options, while Chrome's built-in viewer ignores
+/// them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ViewerPreferences {
+    pub page_layout: Option<PageLayout>,
+    pub page_mode: Option<PageMode>,
+    pub initial_zoom: Option<InitialZoom>,
+    /// Resize the reader's window to fit the first page.
+    pub fit_window: bool,
+    /// Center the reader's window on the screen.
+    pub center_window: bool,
+    /// Show the document's title instead of its file name in the window's
+    /// title bar.
+    pub display_doc_title: bool,
+}
+
+/// Store `preferences` in the catalog of a PDF document so that readers use
+/// them when the document is opened. Works with the output of any converter.
+pub fn set_viewer_preferences(
+    pdf: &[u8],
+    preferences: &ViewerPreferences,
+) -> lopdf::Result<Vec<u8>> {
+    let mut document = Document::load_mem(pdf)?;
+    apply_viewer_preferences(&mut document, preferences)?;
+
+    let mut data = Vec::new();
+    document.save_to(&mut data)?;
+    Ok(data)
+}
+
+fn apply_viewer_preferences(
+    document: &mut Document,
+    preferences: &ViewerPreferences,
+) -> lopdf::Result<()> {
+    let first_page = document.get_pages().get(&1).copied();
+    let mut viewer_preferences = match document.catalog()?.get(b"ViewerPreferences") {
+        Ok(existing) => document.dereference(existing)?.1.as_dict()?.clone(),
+        Err(_) => Dictionary::new(),
+    };
+    for (key, enabled) in [
+        ("FitWindow", preferences.fit_window),
+        ("CenterWindow", preferences.center_window),
+        ("DisplayDocTitle", preferences.display_doc_title),
+    ] {
+        if enabled {
+            viewer_preferences.set(key, true);
+        }
+    }
+
+    let catalog = document.catalog_mut()?;
+    if let Some(layout) = preferences.page_layout {
+        catalog.set("PageLayout", Object::Name(layout.name().into()));
+    }
+    if let Some(mode) = preferences.page_mode {
+        catalog.set("PageMode", Object::Name(mode.name().into()));
+    }
+    if let (Some(zoom), Some(page)) = (preferences.initial_zoom, first_page) {
+        let mut destination = vec![Object::Reference(page)];
+        match zoom {
+            InitialZoom::FitPage => destination.push(Object::Name(b"Fit".to_vec())),
+            // `null` keeps the current scroll position:
+            InitialZoom::FitWidth => {
+                destination.extend([Object::Name(b"FitH".to_vec()), Object::Null])
+            }
+            InitialZoom::Factor(factor) => destination.extend([
+                Object::Name(b"XYZ".to_vec()),
+                Object::Null,
+                Object::Null,
+                Object::Real(factor),
+            ]),
+        }
+        catalog.set("OpenAction", Object::Array(destination));
+    }
+    if !viewer_preferences.is_empty() {
+        catalog.set("ViewerPreferences", Object::Dictionary(viewer_preferences));
+    }
+    Ok(())
+}
+
+/// The natural language of a PDF document, from the `/Lang` entry of its
+/// catalog, for example `en-US`. `None` if the document doesn't declare one.
+pub fn language(pdf: &[u8]) -> lopdf::Result<Option<String>> {
+    let document = Document::load_mem(pdf)?;
+    let Ok(lang) = document.catalog()?.get(b"Lang") else {
+        return Ok(None);
+    };
+    let (_, lang) = document.dereference(lang)?;
+    Ok(Some(text_string(lang.as_str()?)))
+}
+
+/// Store `lang` as the natural language of a PDF document in the `/Lang`
+/// entry of its catalog, replacing the language that the document declared.
+/// Screen readers use it to pick a voice and pronunciation. `lang` should be
+/// a language tag like `en-US` or `fr`.
+///
+/// Works with the output of any converter, but it doesn't change the order in
+/// which the content is read, see [`LanguageConverter`](crate::LanguageConverter).
+pub fn set_language(pdf: &[u8], lang: &str) -> lopdf::Result<Vec<u8>> {
+    let mut document = Document::load_mem(pdf)?;
+    document
+        .catalog_mut()?
+        .set("Lang", text_string_object(lang));
+
+    let mut data = Vec::new();
+    document.save_to(&mut data)?;
+    Ok(data)
+}
+
+/// The URIs that the link annotations of a PDF document point to, in page
+/// order, for example `https://example.com/`. Links to other places in the
+/// document itself aren't included.
+pub fn link_uris(pdf: &[u8]) -> lopdf::Result<Vec<String>> {
+    read_link_uris(&Document::load_mem(pdf)?)
+}
+
+fn read_link_uris(document: &Document) -> lopdf::Result<Vec<String>> {
+    let mut uris = Vec::new();
+    for page in document.get_pages().into_values() {
+        let Ok(annotations) = document.get_dictionary(page)?.get(b"Annots") else {
+            continue;
+        };
+        for annotation in document.dereference(annotations)?.1.as_array()? {
+            let annotation = document.dereference(annotation)?.1.as_dict()?;
+            let Ok(action) = annotation.get(b"A") else {
+                continue;
+            };
+            if let Ok(uri) = document.dereference(action)?.1.as_dict()?.get(b"URI") {
+                let uri = document.dereference(uri)?.1.as_str()?;
+                uris.push(String::from_utf8_lossy(uri).into_owned());
+            }
+        }
+    }
+    Ok(uris)
+}
+
+/// Attach `data` as an embedded file called `name` to a PDF document, for
+/// example the HTML that the document was generated from. PDF readers list
+/// embedded files in their attachments pane. An attachment with the same name
+/// is replaced.
+///
+/// `mime_type` is stored as the file's subtype, for example `text/html`. The
+/// data is stored compressed, so the document grows by about the compressed
+/// size of `data`.
+pub fn attach_file(pdf: &[u8], name: &str, mime_type: &str, data: &[u8]) -> lopdf::Result<Vec<u8>> {
+    let mut document = Document::load_mem(pdf)?;
+    apply_attachment(&mut document, name, mime_type, data)?;
+    // Remove a replaced attachment:
+    document.prune_objects();
+
+    let mut data = Vec::new();
+    document.save_to(&mut data)?;
+    Ok(data)
+}
+
+fn apply_attachment(
+    document: &mut Document,
+    name: &str,
+    mime_type: &str,
+    data: &[u8],
+) -> lopdf::Result<()> {
+    let mut stream = Stream::new(
+        Dictionary::from_iter([
+            ("Type", Object::from("EmbeddedFile")),
+            ("Subtype", Object::Name(mime_type.as_bytes().to_vec())),
+            (
+                "Params",
+                Object::Dictionary(Dictionary::from_iter([(
+                    "Size",
+                    Object::Integer(data.len() as i64),
+                )])),
+            ),
+        ]),
+        data.to_vec(),
+    );
+    stream.compress()?;
+    let stream = document.add_object(Object::Stream(stream));
+    let file_spec = document.add_object(Dictionary::from_iter([
+        ("Type", Object::from("Filespec")),
+        ("F", text_string_object(name)),
+        ("UF", text_string_object(name)),
+        (
+            "EF",
+            Object::Dictionary(Dictionary::from_iter([
+                ("F", Object::Reference(stream)),
+                ("UF", Object::Reference(stream)),
+            ])),
+        ),
+        ("AFRelationship", Object::from("Source")),
+    ]));
+
+    let mut names = match document.catalog()?.get(b"Names") {
+        Ok(names) => document.dereference(names)?.1.as_dict()?.clone(),
+        Err(_) => Dictionary::new(),
+    };
+    // Name trees can be split into several nodes, they are merged into one:
+    let mut entries = BTreeMap::new();
+    if let Ok(tree) = names.get(b"EmbeddedFiles") {
+        collect_name_tree(document, tree, &mut entries)?;
+    }
+    entries.insert(text_string_object(name).as_str()?.to_vec(), file_spec);
+    names.set(
+        "EmbeddedFiles",
+        Object::Dictionary(Dictionary::from_iter([(
+            "Names",
+            Object::Array(
+                entries
+                    .into_iter()
+                    .flat_map(|(key, value)| {
+                        [Object::string_literal(key), Object::Reference(value)]
+                    })
+                    .collect(),
+            ),
+        )])),
+    );
+    document
+        .catalog_mut()?
+        .set("Names", Object::Dictionary(names));
+    Ok(())
+}
+
+/// Collect the entries of a name tree whose values are references, sorted by
+/// their key as the tree requires.
+fn collect_name_tree(
+    document: &Document,
+    node: &Object,
+    entries: &mut BTreeMap<Vec<u8>, ObjectId>,
+) -> lopdf::Result<()> {
+    let node = document.dereference(node)?.1.as_dict()?;
+    if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+        for pair in names.chunks_exact(2) {
+            if let (Ok(key), Ok(value)) = (pair[0].as_str(), pair[1].as_reference()) {
+                entries.insert(key.to_vec(), value);
+            }
+        }
+    }
+    if let Ok(kids) = node.get(b"Kids").and_then(Object::as_array) {
+        for kid in kids {
+            collect_name_tree(document, kid, entries)?;
+        }
+    }
+    Ok(())
+}
+
+/// The embedded files of a PDF document as pairs of file names and data, see
+/// [`attach_file`].
+pub fn attachments(pdf: &[u8]) -> lopdf::Result<Vec<(String, Vec<u8>)>> {
+    read_attachments(&Document::load_mem(pdf)?)
+}
+
+fn read_attachments(document: &Document) -> lopdf::Result<Vec<(String, Vec<u8>)>> {
+    let mut entries = BTreeMap::new();
+    if let Ok(tree) = document
+        .catalog()?
+        .get(b"Names")
+        .and_then(|names| document.dereference(names))
+        .and_then(|(_, names)| names.as_dict())
+        .and_then(|names| names.get(b"EmbeddedFiles"))
+    {
+        collect_name_tree(document, tree, &mut entries)?;
+    }
+    let mut attachments = Vec::with_capacity(entries.len());
+    for (name, file_spec) in entries {
+        let files = document.get_dictionary(file_spec)?.get(b"EF")?;
+        let files = document.dereference(files)?.1.as_dict()?;
+        let stream = files.get(b"UF").or_else(|_| files.get(b"F"))?;
+        let stream = document.dereference(stream)?.1.as_stream()?;
+        let data = stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone());
+        attachments.push((text_string(&name), data));
+    }
+    Ok(attachments)
+}
+
+/// Split a PDF document into multiple documents that have at most
+/// `pages_per_part` pages each.
+///
+/// A document with fewer pages than `pages_per_part` will result in a single
+/// document.
+pub fn split_every(pdf: &[u8], pages_per_part: NonZeroU32) -> lopdf::Result<Vec<Vec<u8>>> {
+    let pages_per_part = pages_per_part.get();
+    let document = Document::load_mem(pdf)?;
+    let page_count = document.get_pages().len() as u32;
+    if page_count <= pages_per_part {
+        return Ok(vec![pdf.to_vec()]);
+    }
+
+    let mut parts = Vec::with_capacity(page_count.div_ceil(pages_per_part) as usize);
+    let mut first = 1;
+    while first <= page_count {
+        let last = page_count.min(first + (pages_per_part - 1));
+        parts.push(keep_pages(&document, |page| {
+            (first..=last).contains(&page)
+        })?);
+        first = last + 1;
+    }
+    Ok(parts)
+}
+
+/// Remove all pages after the first `max_pages` pages of a PDF document. A
+/// document that doesn't have more pages than that is returned unchanged.
+///
+/// Outline entries and links that point to removed pages are left without a
+/// target.
+pub fn truncate_pages(pdf: &[u8], max_pages: u32) -> lopdf::Result<Vec<u8>> {
+    let document = Document::load_mem(pdf)?;
+    if document.get_pages().len() as u32 <= max_pages {
+        return Ok(pdf.to_vec());
+    }
+    keep_pages(&document, |page| page <= max_pages)
+}
+
+/// Remove all pages that aren't selected by `pages` from a PDF document.
+/// Selected pages that the document doesn't have are ignored, use
+/// [`PageRanges::check`] to treat them as an error.
+///
+/// Outline entries and links that point to removed pages are left without a
+/// target.
+pub fn select_pages(pdf: &[u8], pages: &PageRanges) -> lopdf::Result<Vec<u8>> {
+    keep_pages(&Document::load_mem(pdf)?, |page| pages.contains(page))
+}
+
+/// Create a new PDF document that only has the pages that `keep` returned
+/// `true` for. Pages are numbered from `1`.
+fn keep_pages(document: &Document, keep: impl Fn(u32) -> bool) -> lopdf::Result<Vec<u8>> {
+    let mut document = document.clone();
+    let removed = document
+        .get_pages()
+        .into_keys()
+        .filter(|&page| !keep(page))
+        .collect::<Vec<_>>();
+    document.delete_pages(&removed);
+    document.prune_objects();
+
+    let mut data = Vec::new();
+    document.save_to(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::BlankPdfConverter;
+
+    #[test]
+    fn split_every_keeps_the_remainder_in_the_last_part() {
+        let parts = split_every(
+            &BlankPdfConverter::blank_pdf(5),
+            NonZeroU32::new(2).unwrap(),
+        )
+        .unwrap();
+        let page_counts = parts
+            .iter()
+            .map(|part| page_count(part).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(page_counts, [2, 2, 1]);
+    }
+
+    #[test]
+    fn split_every_returns_short_documents_unchanged() {
+        let pdf = BlankPdfConverter::blank_pdf(3);
+        assert_eq!(
+            split_every(&pdf, NonZeroU32::new(5).unwrap()).unwrap(),
+            [pdf]
+        );
+    }
+
+    /// An ICC profile header for `color_space`, the rest of the profile isn't
+    /// read.
+    fn fake_icc_profile(color_space: &[u8; 4]) -> Vec<u8> {
+        let mut profile = vec![0; 128];
+        profile[16..20].copy_from_slice(color_space);
+        profile
+    }
+
+    #[test]
+    fn declared_pdf_a_conformance_is_read_back() {
+        let pdf = BlankPdfConverter::blank_pdf(1);
+        assert_eq!(pdf_a_conformance(&pdf).unwrap(), None);
+
+        let pdf = set_pdf_a_conformance(&pdf, PdfAConformance::PdfA2B, &fake_icc_profile(b"RGB "))
+            .unwrap();
+        assert_eq!(
+            pdf_a_conformance(&pdf).unwrap(),
+            Some(PdfAConformance::PdfA2B)
+        );
+        let document = Document::load_mem(&pdf).unwrap();
+        assert!(document.trailer.get(b"ID").is_ok());
+        let output_intents = document
+            .catalog()
+            .and_then(|catalog| catalog.get(b"OutputIntents"))
+            .and_then(Object::as_array)
+            .unwrap();
+        assert_eq!(output_intents.len(), 1);
+    }
+
+    #[test]
+    fn icc_profile_color_space_is_checked() {
+        let pdf = BlankPdfConverter::blank_pdf(1);
+        assert!(matches!(
+            set_pdf_a_conformance(&pdf, PdfAConformance::PdfA2B, &fake_icc_profile(b"Lab ")),
+            Err(lopdf::Error::Invalid(_))
+        ));
+        assert!(matches!(
+            set_pdf_a_conformance(&pdf, PdfAConformance::PdfA2B, b"too short"),
+            Err(lopdf::Error::Invalid(_))
+        ));
+
+        let pdf = set_pdf_a_conformance(&pdf, PdfAConformance::PdfA2B, &fake_icc_profile(b"CMYK"))
+            .unwrap();
+        let document = Document::load_mem(&pdf).unwrap();
+        let profile = document
+            .catalog()
+            .and_then(|catalog| catalog.get(b"OutputIntents"))
+            .and_then(Object::as_array)
+            .and_then(|intents| intents[0].as_dict())
+            .and_then(|intent| intent.get(b"DestOutputProfile"))
+            .and_then(|profile| document.dereference(profile))
+            .and_then(|(_, profile)| profile.as_stream())
+            .unwrap();
+        assert_eq!(profile.dict.get(b"N").and_then(Object::as_i64).unwrap(), 4);
+    }
+
+    #[test]
+    fn viewer_preferences_are_stored_in_catalog() {
+        let mut document = Document::with_version("1.7");
+        let pages = document.add_object(Dictionary::from_iter([
+            ("Type", Object::from("Pages")),
+            ("Kids", Object::Array(Vec::new())),
+            ("Count", Object::from(0)),
+        ]));
+        let catalog = document.add_object(Dictionary::from_iter([
+            ("Type", Object::from("Catalog")),
+            ("Pages", Object::Reference(pages)),
+        ]));
+        document.trailer.set("Root", catalog);
+
+        let preferences = ViewerPreferences {
+            page_layout: Some(PageLayout::TwoPageLeft),
+            page_mode: Some(PageMode::UseOutlines),
+            fit_window: true,
+            ..Default::default()
+        };
+        apply_viewer_preferences(&mut document, &preferences).unwrap();
+
+        let catalog = document.catalog().unwrap();
+        assert_eq!(
+            catalog
+                .get(b"PageLayout")
+                .and_then(Object::as_name)
+                .unwrap(),
+            b"TwoPageLeft"
+        );
+        assert_eq!(
+            catalog.get(b"PageMode").and_then(Object::as_name).unwrap(),
+            b"UseOutlines"
+        );
+        let viewer_preferences = catalog
+            .get(b"ViewerPreferences")
+            .and_then(Object::as_dict)
+            .unwrap();
+        assert!(viewer_preferences
+            .get(b"FitWindow")
+            .and_then(Object::as_bool)
+            .unwrap());
+        assert!(!viewer_preferences.has(b"CenterWindow"));
+    }
+
+    #[test]
+    fn link_uris_are_read_from_annotations() {
+        let mut document = Document::with_version("1.7");
+        let link = |action: Dictionary| {
+            Object::Dictionary(Dictionary::from_iter([
+                ("Type", Object::from("Annot")),
+                ("Subtype", Object::from("Link")),
+                ("A", Object::Dictionary(action)),
+            ]))
+        };
+        let external = document.add_object(link(Dictionary::from_iter([
+            ("S", Object::from("URI")),
+            ("URI", Object::string_literal("https://example.com/docs/")),
+        ])));
+        let internal = link(Dictionary::from_iter([
+            ("S", Object::from("GoTo")),
+            ("D", Object::string_literal("section")),
+        ]));
+        let pages = document.new_object_id();
+        let page = document.add_object(Dictionary::from_iter([
+            ("Type", Object::from("Page")),
+            ("Parent", Object::Reference(pages)),
+            (
+                "Annots",
+                Object::Array(vec![Object::Reference(external), internal]),
+            ),
+        ]));
+        document.objects.insert(
+            pages,
+            Object::Dictionary(Dictionary::from_iter([
+                ("Type", Object::from("Pages")),
+                ("Count", Object::from(1)),
+                ("Kids", Object::Array(vec![Object::Reference(page)])),
+            ])),
+        );
+        let catalog = document.add_object(Dictionary::from_iter([
+            ("Type", Object::from("Catalog")),
+            ("Pages", Object::Reference(pages)),
+        ]));
+        document.trailer.set("Root", catalog);
+
+        assert_eq!(
+            read_link_uris(&document).unwrap(),
+            ["https://example.com/docs/"]
+        );
+    }
+
+    #[test]
+    fn attached_files_are_read_back() {
+        let mut document = Document::with_version("1.7");
+        let catalog =
+            document.add_object(Dictionary::from_iter([("Type", Object::from("Catalog"))]));
+        document.trailer.set("Root", catalog);
+
+        apply_attachment(&mut document, "source.html", "text/html", b"<p>Old</p>").unwrap();
+        apply_attachment(&mut document, "data.json", "application/json", b"{}").unwrap();
+        apply_attachment(&mut document, "source.html", "text/html", b"<p>New</p>").unwrap();
+
+        assert_eq!(
+            read_attachments(&document).unwrap(),
+            [
+                ("data.json".to_owned(), b"{}".to_vec()),
+                ("source.html".to_owned(), b"<p>New</p>".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn written_outline_is_read_back() {
+        let mut document = Document::with_version("1.7");
+        let pages = document.new_object_id();
+        let kids = (0..3)
+            .map(|_| {
+                Object::Reference(document.add_object(Dictionary::from_iter([
+                    ("Type", Object::from("Page")),
+                    ("Parent", Object::Reference(pages)),
+                ])))
+            })
+            .collect::<Vec<_>>();
+        document.objects.insert(
+            pages,
+            Object::Dictionary(Dictionary::from_iter([
+                ("Type", Object::from("Pages")),
+                ("Count", Object::from(kids.len() as u32)),
+                ("Kids", Object::Array(kids)),
+            ])),
+        );
+        let catalog = document.add_object(Dictionary::from_iter([
+            ("Type", Object::from("Catalog")),
+            ("Pages", Object::Reference(pages)),
+        ]));
+        document.trailer.set("Root", catalog);
+
+        let item = |title: &str, page, children| OutlineItem {
+            title: title.to_owned(),
+            page,
+            children,
+        };
+        let outline = vec![
+            item(
+                "Café",
+                1,
+                vec![item("One A", 1, vec![]), item("One B", 2, vec![])],
+            ),
+            item("Two", 3, vec![item("Two A", 3, vec![])]),
+        ];
+        apply_outline(&mut document, &outline).unwrap();
+        assert_eq!(read_outline(&document).unwrap(), outline);
+
+        let outlines = document
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Outlines"))
+            .and_then(|outlines| document.dereference(outlines))
+            .and_then(|(_, outlines)| outlines.as_dict())
+            .unwrap();
+        assert_eq!(outlines.get(b"Count").and_then(Object::as_i64).unwrap(), 5);
+    }
+}
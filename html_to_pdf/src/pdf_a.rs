@@ -0,0 +1,161 @@
+//! Describes the PDF/A archival standard that a converter should follow.
+
+use std::{error::Error, fmt};
+
+use crate::HtmlToPdfError;
+
+/// A PDF/A part and conformance level, for example PDF/A-2b.
+///
+/// Only some converters can produce PDF/A documents. PDF/A requires an output
+/// intent with an embedded ICC color profile (usually sRGB) so converters that
+/// support this option also need to be given such a profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PdfAConformance {
+    /// PDF/A-1b, basic conformance based on PDF 1.4.
+    PdfA1B,
+    /// PDF/A-2b, basic conformance based on PDF 1.7.
+    PdfA2B,
+    /// PDF/A-2u, like PDF/A-2b but all text must be mapped to Unicode.
+    PdfA2U,
+    /// PDF/A-3b, like PDF/A-2b but allows embedding arbitrary files.
+    PdfA3B,
+    /// PDF/A-3u, like PDF/A-3b but all text must be mapped to Unicode.
+    PdfA3U,
+}
+impl PdfAConformance {
+    /// All supported conformance levels.
+    pub const ALL: &'static [Self] = &[
+        Self::PdfA1B,
+        Self::PdfA2B,
+        Self::PdfA2U,
+        Self::PdfA3B,
+        Self::PdfA3U,
+    ];
+
+    /// The part of the PDF/A standard, stored as `pdfaid:part` in the XMP
+    /// metadata.
+    pub fn part(self) -> u8 {
+        match self {
+            Self::PdfA1B => 1,
+            Self::PdfA2B | Self::PdfA2U => 2,
+            Self::PdfA3B | Self::PdfA3U => 3,
+        }
+    }
+    /// The conformance level, stored as `pdfaid:conformance` in the XMP
+    /// metadata.
+    pub fn level(self) -> char {
+        match self {
+            Self::PdfA1B | Self::PdfA2B | Self::PdfA3B => 'B',
+            Self::PdfA2U | Self::PdfA3U => 'U',
+        }
+    }
+    /// Find a conformance level from its part and level, the level is case
+    /// insensitive.
+    pub fn from_part_and_level(part: u8, level: char) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|v| v.part() == part && v.level().eq_ignore_ascii_case(&level))
+    }
+    /// Find the conformance that is declared by the `pdfaid` schema in XMP
+    /// metadata. Both the attribute form (`pdfaid:part="2"`) and the element
+    /// form (`<pdfaid:part>2</pdfaid:part>`) are supported.
+    pub fn from_xmp(xmp: &str) -> Option<Self> {
+        fn property<'a>(xmp: &'a str, name: &str) -> Option<&'a str> {
+            let start = xmp.find(name)? + name.len();
+            let rest = xmp[start..].trim_start();
+            let rest = if let Some(rest) = rest.strip_prefix('=') {
+                let rest = rest.trim_start();
+                let quote = rest.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+                &rest[1..][..rest[1..].find(quote)?]
+            } else {
+                let rest = rest.strip_prefix('>')?;
+                &rest[..rest.find('<')?]
+            };
+            Some(rest.trim())
+        }
+
+        let part = property(xmp, "pdfaid:part")?.parse().ok()?;
+        let mut level = property(xmp, "pdfaid:conformance")?.chars();
+        match (level.next(), level.next()) {
+            (Some(level), None) => Self::from_part_and_level(part, level),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for PdfAConformance {
+    /// Formats as for example `PDF/A-2b`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PDF/A-{}{}",
+            self.part(),
+            self.level().to_ascii_lowercase()
+        )
+    }
+}
+
+/// A converter was asked to create a PDF/A document but its engine can't
+/// produce one. Returned as the source of an [`HtmlToPdfError::Engine`]
+/// error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdfANotSupportedError {
+    /// The [name](crate::HtmlToPdfConverter::name) of the converter.
+    pub engine: &'static str,
+    pub conformance: PdfAConformance,
+}
+impl fmt::Display for PdfANotSupportedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the {} converter can't create {} documents, use a converter \
+            that supports PDF/A such as dotnet-itext",
+            self.engine, self.conformance
+        )
+    }
+}
+impl Error for PdfANotSupportedError {}
+impl From<PdfANotSupportedError> for HtmlToPdfError {
+    fn from(value: PdfANotSupportedError) -> Self {
+        Self::engine(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_pdf_a_is_an_engine_error() {
+        let error = PdfANotSupportedError {
+            engine: "pdf-min",
+            conformance: PdfAConformance::PdfA2B,
+        };
+        let converted = HtmlToPdfError::from(error.clone());
+        assert!(matches!(converted, HtmlToPdfError::Engine(_)));
+        let source = converted
+            .source()
+            .and_then(|source| source.downcast_ref::<PdfANotSupportedError>());
+        assert_eq!(source, Some(&error));
+    }
+
+    #[test]
+    fn from_xmp_reads_declared_conformance() {
+        let attributes = r#"<rdf:Description rdf:about=""
+            xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/"
+            pdfaid:part="2" pdfaid:conformance="B"/>"#;
+        assert_eq!(
+            PdfAConformance::from_xmp(attributes),
+            Some(PdfAConformance::PdfA2B)
+        );
+
+        let elements = "<pdfaid:part>3</pdfaid:part>\n<pdfaid:conformance>U</pdfaid:conformance>";
+        assert_eq!(
+            PdfAConformance::from_xmp(elements),
+            Some(PdfAConformance::PdfA3U)
+        );
+
+        assert_eq!(PdfAConformance::from_xmp("<x:xmpmeta/>"), None);
+    }
+}
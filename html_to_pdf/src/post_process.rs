@@ -0,0 +1,161 @@
+//! Edit the complete PDF before it is written to the output.
+
+use std::io::{self, Write};
+
+use crate::{
+    Diagnostic, HtmlSink, HtmlSinkMappedError, HtmlToPdfError, WriteBuilder, WriteBuilderSimple,
+};
+
+/// Edits the complete PDF for [`HtmlSinkPostProcessed`]. Implemented for
+/// closures that take and return the PDF, see [`HtmlSink::post_process`].
+pub trait PdfPostProcessor {
+    /// Returned if the PDF couldn't be edited, which fails the conversion.
+    type Error;
+
+    /// Edit `pdf`, problems that don't fail the conversion can be added to
+    /// `diagnostics`.
+    fn process(
+        self,
+        pdf: Vec<u8>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Vec<u8>, Self::Error>;
+}
+impl<F> PdfPostProcessor for F
+where
+    F: FnOnce(Vec<u8>) -> io::Result<Vec<u8>>,
+{
+    type Error = io::Error;
+
+    fn process(self, pdf: Vec<u8>, _diagnostics: &mut Vec<Diagnostic>) -> io::Result<Vec<u8>> {
+        self(pdf)
+    }
+}
+
+/// Used by [`HtmlSink::post_process`] to edit the PDF before it is written to
+/// the output.
+///
+/// # Buffering
+///
+/// This only works for sinks that write the PDF to a
+/// [`WriteBuilderSimple<Vec<u8>>`]. The whole PDF is kept in memory and
+/// nothing is written to the output until [`HtmlSink::complete`] is called, so
+/// the PDF can't be streamed while it is generated. This applies to every
+/// converter that edits the PDF of the converter it wraps.
+pub struct HtmlSinkPostProcessed<S, W, P> {
+    inner: S,
+    output: W,
+    processor: P,
+}
+impl<S, W, P> HtmlSinkPostProcessed<S, W, P> {
+    pub(crate) fn new(inner: S, output: W, processor: P) -> Self {
+        Self {
+            inner,
+            output,
+            processor,
+        }
+    }
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+    #[cfg(feature = "lopdf")]
+    pub(crate) fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+}
+impl<S, W, E, P> HtmlSink<W, E> for HtmlSinkPostProcessed<S, W, P>
+where
+    S: HtmlSink<WriteBuilderSimple<Vec<u8>>, E>,
+    W: WriteBuilder,
+    E: From<io::Error> + From<P::Error>,
+    P: PdfPostProcessor,
+{
+    fn complete(self) -> Result<W, E>
+    where
+        Self: Sized,
+    {
+        self.complete_with_diagnostics().map(|(writer, _)| writer)
+    }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), E>
+    where
+        Self: Sized,
+    {
+        let (WriteBuilderSimple(pdf), mut diagnostics) = self.inner.complete_with_diagnostics()?;
+        let pdf = self.processor.process(pdf, &mut diagnostics)?;
+        let mut output = self.output;
+        output.get_writer()?.write_all(&pdf)?;
+        Ok((output, diagnostics))
+    }
+}
+crate::forward_write!(impl[S: Write, W, P] for HtmlSinkPostProcessed<S, W, P>, |this| &mut this.inner);
+
+/// The [`HtmlSink`] of converters that wrap another converter and edit its
+/// PDF with a [`PdfPostProcessor`]. Errors from the wrapped converter become
+/// [`HtmlToPdfError::Engine`] errors.
+pub type PostProcessedHtmlSink<S, W, E, P> = HtmlSinkPostProcessed<
+    HtmlSinkMappedError<S, WriteBuilderSimple<Vec<u8>>, E, HtmlToPdfError, fn(E) -> HtmlToPdfError>,
+    W,
+    P,
+>;
+
+/// Start `converter` with an output that keeps the PDF in memory, so that
+/// `processor` can edit it before it is written to `output`.
+#[cfg(feature = "lopdf")]
+pub(crate) fn start_post_processed<'scope, C, W, P>(
+    converter: C,
+    scope: crate::PdfScope<'scope, '_>,
+    output: W,
+    processor: P,
+) -> Result<PostProcessedHtmlSink<C::HtmlSink, W, C::Error, P>, HtmlToPdfError>
+where
+    W: WriteBuilder + Send + 'scope,
+    C: crate::HtmlToPdfConverter<'scope, WriteBuilderSimple<Vec<u8>>>,
+    C::Error: Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
+{
+    let inner = converter
+        .start(scope, WriteBuilderSimple(Vec::new()))
+        .map_err(HtmlToPdfError::engine)?;
+    let map_err: fn(C::Error) -> HtmlToPdfError = HtmlToPdfError::engine;
+    Ok(HtmlSinkPostProcessed::new(
+        inner.map_completion_err(map_err),
+        output,
+        processor,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::MockConverter, HtmlToPdfConverter, PdfScope};
+
+    #[test]
+    fn post_processed_pdf_is_written_to_output() {
+        let mut html_sink = MockConverter::new()
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap()
+            .post_process(WriteBuilderSimple(Vec::new()), |mut pdf| {
+                pdf.extend_from_slice(b"%post-processed\n");
+                Ok(pdf)
+            });
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        let WriteBuilderSimple(pdf) = html_sink.complete().unwrap();
+
+        let mut expected = MockConverter::fake_pdf(12);
+        expected.extend_from_slice(b"%post-processed\n");
+        assert_eq!(pdf, expected);
+    }
+
+    #[test]
+    fn post_process_errors_fail_the_conversion() {
+        let mut html_sink = MockConverter::new()
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap()
+            .post_process(WriteBuilderSimple(Vec::new()), |_| {
+                Err(io::Error::other("can't encrypt"))
+            });
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        let Err(error) = html_sink.complete() else {
+            panic!("the conversion should fail");
+        };
+        assert_eq!(error.to_string(), "can't encrypt");
+    }
+}
@@ -0,0 +1,134 @@
+//! Report the progress of a conversion.
+
+use std::io::{self, IoSlice, Write};
+
+use crate::{Diagnostic, HtmlSink};
+
+/// Something that happened to a sink returned by [`HtmlSink::with_progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProgressEvent {
+    /// A write passed this many bytes of HTML to the converter.
+    HtmlBytesWritten(usize),
+    /// The conversion finished and the PDF has been written.
+    Completed,
+    /// The conversion failed when the sink was completed.
+    Failed,
+}
+
+/// Used by [`HtmlSink::with_progress`] to report progress.
+pub struct HtmlSinkProgress<S, F> {
+    inner: S,
+    f: F,
+}
+impl<S, F> HtmlSinkProgress<S, F> {
+    pub(crate) fn new(inner: S, f: F) -> Self {
+        Self { inner, f }
+    }
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+impl<S, F> HtmlSinkProgress<S, F>
+where
+    F: FnMut(ProgressEvent),
+{
+    fn written(&mut self, result: io::Result<usize>) -> io::Result<usize> {
+        if let Ok(written) = result {
+            (self.f)(ProgressEvent::HtmlBytesWritten(written));
+        }
+        result
+    }
+}
+impl<S, W, E, F> HtmlSink<W, E> for HtmlSinkProgress<S, F>
+where
+    S: HtmlSink<W, E>,
+    F: FnMut(ProgressEvent),
+{
+    fn complete(self) -> Result<W, E>
+    where
+        Self: Sized,
+    {
+        self.complete_with_diagnostics().map(|(writer, _)| writer)
+    }
+    fn complete_with_diagnostics(mut self) -> Result<(W, Vec<Diagnostic>), E>
+    where
+        Self: Sized,
+    {
+        let result = <S as HtmlSink<W, E>>::complete_with_diagnostics(self.inner);
+        (self.f)(match result {
+            Ok(_) => ProgressEvent::Completed,
+            Err(_) => ProgressEvent::Failed,
+        });
+        result
+    }
+}
+impl<S, F> Write for HtmlSinkProgress<S, F>
+where
+    S: Write,
+    F: FnMut(ProgressEvent),
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.inner.write(buf);
+        self.written(result)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let result = self.inner.write_vectored(bufs);
+        self.written(result)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockConverter;
+    use crate::{HtmlToPdfConverter, PdfScope, WriteBuilderSimple};
+
+    #[test]
+    fn reports_writes_and_completion() {
+        let mut events = Vec::new();
+        let mut html_sink = MockConverter::new()
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap()
+            .with_progress(|event| events.push(event));
+        html_sink.write_all(b"<p>").unwrap();
+        html_sink.write_all(b"Hello</p>").unwrap();
+        let pdf = html_sink.complete().unwrap().0;
+
+        assert_eq!(pdf, MockConverter::fake_pdf(12));
+        assert_eq!(
+            events,
+            [
+                ProgressEvent::HtmlBytesWritten(3),
+                ProgressEvent::HtmlBytesWritten(9),
+                ProgressEvent::Completed,
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_failed_conversions() {
+        let mut events = Vec::new();
+        let mut html_sink = MockConverter::new()
+            .start(
+                PdfScope::owned(),
+                crate::WriteBuilderFn::new(|| -> io::Result<Vec<u8>> {
+                    Err(io::Error::other("disk full"))
+                }),
+            )
+            .unwrap()
+            .with_progress(|event| events.push(event));
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        assert!(html_sink.complete().is_err());
+
+        assert_eq!(
+            events,
+            [ProgressEvent::HtmlBytesWritten(12), ProgressEvent::Failed]
+        );
+    }
+}
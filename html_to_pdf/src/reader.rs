@@ -0,0 +1,122 @@
+//! Read the generated PDF instead of providing a writer for it.
+
+use std::{
+    error::Error,
+    io::{self, Read, Write},
+    thread,
+};
+
+use crate::{panic_message, Cancelled, HtmlToPdfConverter, HtmlToPdfError, WriteBuilderSimple};
+
+/// Convert the HTML from `html` on a background thread and read the PDF as it
+/// is generated, see [`HtmlToPdfConverter::into_reader`].
+///
+/// This picks the output type that the conversion is done with, which the
+/// trait method can't infer by itself.
+pub fn into_reader<C, R>(converter: C, html: R) -> PdfReader
+where
+    C: HtmlToPdfConverter<'static, WriteBuilderSimple<PdfPipeWriter>> + Send + 'static,
+    C::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+    R: Read + Send + 'static,
+{
+    converter.into_reader(html)
+}
+
+/// The write end of the pipe that a [`PdfReader`] reads from.
+pub struct PdfPipeWriter(pipe::PipeWriter);
+impl Write for PdfPipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Reads PDF data that a conversion on a background thread writes, see
+/// [`HtmlToPdfConverter::into_reader`].
+///
+/// Once all data has been read the background thread is joined, so a failed
+/// conversion is reported as an error by the read that would otherwise have
+/// returned the end of the stream. Dropping the reader early makes the
+/// conversion fail the next time it writes PDF data and the background thread
+/// then exits on its own.
+pub struct PdfReader {
+    reader: pipe::PipeReader,
+    /// `None` after the conversion has been joined.
+    conversion: Option<thread::JoinHandle<Result<(), HtmlToPdfError>>>,
+}
+impl PdfReader {
+    pub(crate) fn spawn(
+        convert: impl FnOnce(WriteBuilderSimple<PdfPipeWriter>) -> Result<(), HtmlToPdfError>
+            + Send
+            + 'static,
+    ) -> Self {
+        let (reader, writer) = pipe::pipe();
+        Self {
+            reader,
+            conversion: Some(thread::spawn(move || {
+                convert(WriteBuilderSimple(PdfPipeWriter(writer)))
+            })),
+        }
+    }
+
+    /// Wait for the background thread and return its error, if any.
+    fn join(&mut self) -> io::Result<()> {
+        let Some(conversion) = self.conversion.take() else {
+            return Ok(());
+        };
+        match conversion.join() {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(HtmlToPdfError::Io(e))) => Err(e),
+            Ok(Err(HtmlToPdfError::Cancelled)) => Err(Cancelled.into()),
+            Ok(Err(e)) => Err(io::Error::other(e)),
+            Err(payload) => Err(io::Error::other(format!(
+                "the PDF conversion panicked: {}",
+                panic_message(&*payload).unwrap_or("unknown reason")
+            ))),
+        }
+    }
+}
+impl Read for PdfReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            self.join()?;
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FailingConverter, MockConverter};
+
+    #[test]
+    fn reading_yields_whole_pdf() {
+        let html = b"<p>Hello</p>".repeat(1000);
+        let converter = MockConverter::new();
+        let mut pdf = Vec::new();
+        into_reader(converter.clone(), io::Cursor::new(html.clone()))
+            .read_to_end(&mut pdf)
+            .unwrap();
+        assert_eq!(pdf, MockConverter::fake_pdf(html.len()));
+        assert_eq!(converter.captured_html(), [html]);
+    }
+
+    #[test]
+    fn failed_conversion_is_reported_by_reader() {
+        let mut pdf = Vec::new();
+        let error = into_reader(FailingConverter::new(), io::empty())
+            .read_to_end(&mut pdf)
+            .unwrap_err();
+        assert_eq!(pdf, FailingConverter::PARTIAL_PDF);
+        let engine_error = error.get_ref().and_then(Error::source);
+        assert_eq!(
+            engine_error.map(ToString::to_string).as_deref(),
+            Some(FailingConverter::ERROR_MESSAGE),
+            "{error:?}"
+        );
+    }
+}
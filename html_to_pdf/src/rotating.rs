@@ -0,0 +1,173 @@
+//! Split large outputs across several files.
+
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{WriteBuilder, WriteBuilderLifetime};
+
+/// A write builder that writes the output to numbered part files, starting a
+/// new file whenever the current one reaches `max_bytes`. The first part is
+/// written to `base_path` and later parts are written next to it with the
+/// part number inserted before the extension, so `out.pdf` is followed by
+/// `out.part2.pdf`, `out.part3.pdf` and so on.
+///
+/// The data is split at exact byte offsets, so the parts are **not** valid
+/// PDF documents on their own. This is meant for transporting or storing
+/// outputs that are larger than a file system or upload service allows; the
+/// original document is restored by concatenating the parts in order. It
+/// doesn't split the document by pages.
+///
+/// Existing files are overwritten, but parts left behind by an earlier and
+/// larger output are not removed. Use [`WriteBuilderRotating::finish`] to get
+/// the paths of the parts that belong to this output.
+#[derive(Debug)]
+pub struct WriteBuilderRotating {
+    writer: RotatingWriter,
+}
+impl WriteBuilderRotating {
+    /// Create the first part file at `base_path`.
+    ///
+    /// # Panics
+    ///
+    /// If `max_bytes` is `0`.
+    pub fn new(base_path: impl Into<PathBuf>, max_bytes: u64) -> io::Result<Self> {
+        assert!(
+            max_bytes > 0,
+            "part files must be allowed to hold some data"
+        );
+        let base_path = base_path.into();
+        let file = File::create(&base_path)?;
+        Ok(Self {
+            writer: RotatingWriter {
+                max_bytes,
+                file,
+                written: 0,
+                paths: vec![base_path],
+            },
+        })
+    }
+    /// The path of the first part.
+    pub fn base_path(&self) -> &Path {
+        &self.writer.paths[0]
+    }
+    /// The size limit of each part.
+    pub fn max_bytes(&self) -> u64 {
+        self.writer.max_bytes
+    }
+    /// The paths of the parts that have been created so far, in order.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.writer.paths
+    }
+    /// Flush the current part and return the paths of all parts, in order.
+    pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+        self.writer.file.flush()?;
+        Ok(self.writer.paths)
+    }
+}
+impl<'a> WriteBuilderLifetime<'a> for WriteBuilderRotating {
+    type Writer = &'a mut RotatingWriter;
+}
+impl WriteBuilder for WriteBuilderRotating {
+    fn get_writer(&mut self) -> io::Result<<Self as WriteBuilderLifetime<'_>>::Writer> {
+        Ok(&mut self.writer)
+    }
+}
+
+/// The writer for [`WriteBuilderRotating`].
+#[derive(Debug)]
+pub struct RotatingWriter {
+    max_bytes: u64,
+    file: File,
+    /// Bytes written to the current part.
+    written: u64,
+    paths: Vec<PathBuf>,
+}
+impl RotatingWriter {
+    /// `base.pdf` -> `base.part<number>.pdf`
+    fn part_path(base_path: &Path, number: usize) -> PathBuf {
+        let mut name = OsString::from(base_path.file_stem().unwrap_or_default());
+        name.push(format!(".part{number}"));
+        if let Some(extension) = base_path.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        base_path.with_file_name(name)
+    }
+    fn start_next_part(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let path = Self::part_path(&self.paths[0], self.paths.len() + 1);
+        self.file = File::create(&path)?;
+        self.paths.push(path);
+        self.written = 0;
+        Ok(())
+    }
+}
+impl Write for RotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // Only start a new part once there is data for it, so that a full
+        // part isn't followed by an empty one:
+        if self.written >= self.max_bytes {
+            self.start_next_part()?;
+        }
+        let remaining = self.max_bytes - self.written;
+        let len = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let written = self.file.write(&buf[..len])?;
+        self.written += written as u64;
+        Ok(written)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn output_is_split_across_parts() {
+        let dir = std::env::temp_dir().join(format!("html_to_pdf-rotating-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let data = (0..2500).map(|i: u32| (i % 251) as u8).collect::<Vec<_>>();
+
+        let mut builder = WriteBuilderRotating::new(dir.join("out.pdf"), 1000).unwrap();
+        for chunk in data.chunks(300) {
+            builder.get_writer().unwrap().write_all(chunk).unwrap();
+        }
+        let paths = builder.finish().unwrap();
+
+        assert_eq!(
+            paths,
+            [
+                dir.join("out.pdf"),
+                dir.join("out.part2.pdf"),
+                dir.join("out.part3.pdf")
+            ]
+        );
+        let parts = paths
+            .iter()
+            .map(|path| fs::read(path).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            parts.iter().map(Vec::len).collect::<Vec<_>>(),
+            [1000, 1000, 500]
+        );
+        assert!(
+            parts.concat() == data,
+            "the parts should join to the output"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+//! Clean up HTML before it reaches a converter.
+
+use std::{
+    io::{self, Write},
+    mem,
+};
+
+use crate::{Diagnostic, HtmlSink};
+
+/// What [`HtmlSink::sanitize_with`] changes in the HTML.
+///
+/// Only ASCII bytes are changed, so multi-byte UTF-8 sequences are passed
+/// through untouched even if they are split between writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SanitizePolicy {
+    /// Replace `\r\n` and lone `\r` with `\n`, just like HTML parsers do.
+    pub normalize_line_endings: bool,
+    /// Remove ASCII control characters, for example NUL bytes. Tab, `\n` and
+    /// `\r` are kept since they are whitespace.
+    pub strip_control_chars: bool,
+}
+impl Default for SanitizePolicy {
+    /// Normalize line endings and strip control characters.
+    fn default() -> Self {
+        Self {
+            normalize_line_endings: true,
+            strip_control_chars: true,
+        }
+    }
+}
+
+/// Used by [`HtmlSink::sanitize`] to clean up the HTML that is written to a
+/// sink.
+pub struct HtmlSinkSanitized<S> {
+    inner: S,
+    policy: SanitizePolicy,
+    /// The previous byte was a `\r` that was written as `\n`, so a following
+    /// `\n` should be skipped.
+    after_cr: bool,
+    /// Reused between writes.
+    buffer: Vec<u8>,
+}
+impl<S> HtmlSinkSanitized<S> {
+    pub(crate) fn new(inner: S, policy: SanitizePolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            after_cr: false,
+            buffer: Vec::new(),
+        }
+    }
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+impl<S, W, E> HtmlSink<W, E> for HtmlSinkSanitized<S>
+where
+    S: HtmlSink<W, E>,
+{
+    fn complete(self) -> Result<W, E>
+    where
+        Self: Sized,
+    {
+        <S as HtmlSink<W, E>>::complete(self.inner)
+    }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), E>
+    where
+        Self: Sized,
+    {
+        <S as HtmlSink<W, E>>::complete_with_diagnostics(self.inner)
+    }
+}
+impl<S: Write> Write for HtmlSinkSanitized<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.clear();
+        for &byte in buf {
+            let after_cr = mem::replace(&mut self.after_cr, false);
+            match byte {
+                b'\r' if self.policy.normalize_line_endings => {
+                    self.buffer.push(b'\n');
+                    self.after_cr = true;
+                }
+                b'\n' if after_cr => {}
+                b'\t' | b'\n' | b'\r' => self.buffer.push(byte),
+                0..=0x1F | 0x7F if self.policy.strip_control_chars => {}
+                _ => self.buffer.push(byte),
+            }
+        }
+        self.inner.write_all(&self.buffer)?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails like a converter that can't handle NUL bytes. Returns the HTML
+    /// that it received.
+    struct StrictHtmlSink(Vec<u8>);
+    impl Write for StrictHtmlSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl HtmlSink<Vec<u8>, String> for StrictHtmlSink {
+        fn complete(self) -> Result<Vec<u8>, String> {
+            if self.0.contains(&0) {
+                Err("unexpected NUL byte in HTML".to_owned())
+            } else {
+                Ok(self.0)
+            }
+        }
+    }
+
+    const HTML: &[&[u8]] = &[b"<p>Hello\0\r", b"\n\xC3", b"\xA9\x7F\r<br>\r", b"\n</p>"];
+
+    #[test]
+    fn nul_bytes_are_only_removed_when_sanitized() {
+        let mut html_sink = StrictHtmlSink(Vec::new());
+        for chunk in HTML {
+            html_sink.write_all(chunk).unwrap();
+        }
+        assert!(html_sink.complete().is_err());
+
+        let mut html_sink = StrictHtmlSink(Vec::new()).sanitize();
+        for chunk in HTML {
+            html_sink.write_all(chunk).unwrap();
+        }
+        let html = html_sink.complete().unwrap();
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            "<p>Hello\n\u{e9}\n<br>\n</p>"
+        );
+    }
+
+    #[test]
+    fn policy_selects_what_is_changed() {
+        let sanitize = |policy| {
+            let mut html_sink = StrictHtmlSink(Vec::new()).sanitize_with(policy);
+            for chunk in HTML {
+                html_sink.write_all(chunk).unwrap();
+            }
+            HtmlSink::<Vec<u8>, String>::complete(html_sink)
+        };
+
+        let html = sanitize(SanitizePolicy {
+            normalize_line_endings: false,
+            strip_control_chars: true,
+        });
+        assert_eq!(html.unwrap(), b"<p>Hello\r\n\xC3\xA9\r<br>\r\n</p>");
+
+        let html = sanitize(SanitizePolicy {
+            normalize_line_endings: true,
+            strip_control_chars: false,
+        });
+        assert!(html.is_err());
+    }
+}
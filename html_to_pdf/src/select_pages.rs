@@ -0,0 +1,110 @@
+//! Keep only some of the pages of a generated PDF.
+
+use std::{borrow::Cow, error::Error};
+
+use crate::{
+    pdf, post_process::start_post_processed, ConverterCapabilities, Diagnostic, HtmlToPdfConverter,
+    HtmlToPdfError, PageRanges, PdfPostProcessor, PdfScope, PostProcessedHtmlSink, WriteBuilder,
+    WriteBuilderSimple,
+};
+
+/// Wraps a converter and removes the pages of the generated PDF that aren't
+/// selected by `pages`, see [`pdf::select_pages`]. If `pages` is `None` the
+/// PDF is passed through unchanged.
+///
+/// Selecting pages that the PDF doesn't have fails the conversion with a
+/// [`PagesOutOfRangeError`](crate::PagesOutOfRangeError) as the source of an
+/// [`HtmlToPdfError::Engine`] error.
+///
+/// The pages are removed after the conversion, so the wrapped converter can be
+/// anything but the PDF is [buffered](crate::HtmlSinkPostProcessed#buffering).
+/// Prefer the converter's own option if it has one, for example Chrome only
+/// renders the selected pages.
+#[derive(Debug, Clone)]
+pub struct SelectPagesConverter<C> {
+    pub converter: C,
+    pub pages: Option<PageRanges>,
+}
+impl<C> SelectPagesConverter<C> {
+    pub fn new(converter: C, pages: PageRanges) -> Self {
+        Self {
+            converter,
+            pages: Some(pages),
+        }
+    }
+}
+impl<'scope, W, C> HtmlToPdfConverter<'scope, W> for SelectPagesConverter<C>
+where
+    W: WriteBuilder + Send + 'scope,
+    C: HtmlToPdfConverter<'scope, WriteBuilderSimple<Vec<u8>>>,
+    C::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    type HtmlSink = SelectPagesHtmlSink<C::HtmlSink, W, C::Error>;
+    type Error = HtmlToPdfError;
+
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        let processor = SelectPagesPostProcessor { pages: self.pages };
+        start_post_processed(self.converter, scope, output, processor)
+    }
+
+    fn name(&self) -> Cow<'static, str> {
+        format!("select_pages({})", self.converter.name()).into()
+    }
+    fn capabilities(&self) -> ConverterCapabilities {
+        self.converter.capabilities()
+    }
+}
+
+/// The [`PdfPostProcessor`] of [`SelectPagesConverter`].
+#[derive(Debug, Clone)]
+pub struct SelectPagesPostProcessor {
+    pages: Option<PageRanges>,
+}
+impl PdfPostProcessor for SelectPagesPostProcessor {
+    type Error = HtmlToPdfError;
+
+    fn process(
+        self,
+        pdf: Vec<u8>,
+        _diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Vec<u8>, HtmlToPdfError> {
+        let Some(pages) = &self.pages else {
+            return Ok(pdf);
+        };
+        pages
+            .check(pdf::page_count(&pdf)?)
+            .map_err(HtmlToPdfError::engine)?;
+        Ok(pdf::select_pages(&pdf, pages)?)
+    }
+}
+
+/// The [`HtmlSink`](crate::HtmlSink) for [`SelectPagesConverter`].
+pub type SelectPagesHtmlSink<S, W, E> = PostProcessedHtmlSink<S, W, E, SelectPagesPostProcessor>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::BlankPdfConverter, HtmlSink, PagesOutOfRangeError};
+    use std::io::Write;
+
+    fn convert(pages: &str) -> Result<Vec<u8>, HtmlToPdfError> {
+        let converter =
+            SelectPagesConverter::new(BlankPdfConverter::new(5), pages.parse().unwrap());
+        let mut html_sink = converter.start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))?;
+        html_sink.write_all(b"<p>Hello</p>")?;
+        Ok(html_sink.complete()?.0)
+    }
+
+    #[test]
+    fn only_selected_pages_are_kept() {
+        assert_eq!(pdf::page_count(&convert("2").unwrap()).unwrap(), 1);
+        assert_eq!(pdf::page_count(&convert("1-2,4-").unwrap()).unwrap(), 4);
+
+        let error = convert("4-6").unwrap_err();
+        let source = error.source().and_then(|e| e.downcast_ref());
+        assert!(
+            matches!(source, Some(PagesOutOfRangeError { page_count: 5, .. })),
+            "{error:?}"
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Limit how many conversions can run at the same time.
+
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt,
+    io::Write,
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ConverterCapabilities, Diagnostic, HtmlSink, HtmlToPdfConverter, PdfScope, WriteBuilder,
+};
+
+struct SemaphoreState {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+/// A counting semaphore. Clones share the same permits.
+#[derive(Clone)]
+pub struct Semaphore(Arc<SemaphoreState>);
+impl Semaphore {
+    /// Create a semaphore that allows `permits` conversions to run at the same
+    /// time.
+    pub fn new(permits: usize) -> Self {
+        Self(Arc::new(SemaphoreState {
+            available: Mutex::new(permits),
+            released: Condvar::new(),
+        }))
+    }
+    /// The number of permits that can currently be acquired without blocking.
+    pub fn available_permits(&self) -> usize {
+        *self.0.available.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    /// Block until a permit is available.
+    pub fn acquire(&self) -> SemaphorePermit {
+        let mut available = self.0.available.lock().unwrap_or_else(|e| e.into_inner());
+        while *available == 0 {
+            available = self
+                .0
+                .released
+                .wait(available)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        *available -= 1;
+        SemaphorePermit(self.clone())
+    }
+    /// Block until a permit is available or until `timeout` has passed.
+    pub fn acquire_timeout(&self, timeout: Duration) -> Option<SemaphorePermit> {
+        let deadline = Instant::now() + timeout;
+        let mut available = self.0.available.lock().unwrap_or_else(|e| e.into_inner());
+        while *available == 0 {
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            available = self
+                .0
+                .released
+                .wait_timeout(available, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        *available -= 1;
+        Some(SemaphorePermit(self.clone()))
+    }
+}
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Semaphore")
+            .field("available_permits", &self.available_permits())
+            .finish()
+    }
+}
+
+/// A permit acquired from a [`Semaphore`]. It is released when dropped.
+pub struct SemaphorePermit(Semaphore);
+impl Drop for SemaphorePermit {
+    fn drop(&mut self) {
+        let state = &(self.0).0;
+        *state.available.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        state.released.notify_one();
+    }
+}
+
+/// Error returned by [`SemaphoreConverter`].
+#[derive(Debug)]
+pub enum SemaphoreError<E> {
+    /// Timed out while waiting for other conversions to finish.
+    Timeout(Duration),
+    /// The wrapped converter failed.
+    Converter(E),
+}
+impl<E: fmt::Display> fmt::Display for SemaphoreError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemaphoreError::Timeout(timeout) => write!(
+                f,
+                "timed out after {timeout:?} while waiting for other conversions to finish"
+            ),
+            SemaphoreError::Converter(e) => e.fmt(f),
+        }
+    }
+}
+impl<E: Error + 'static> Error for SemaphoreError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SemaphoreError::Timeout(_) => None,
+            SemaphoreError::Converter(e) => Some(e),
+        }
+    }
+}
+
+/// Wraps a converter and limits how many conversions can run at the same time
+/// by acquiring a permit from a shared [`Semaphore`].
+///
+/// The permit is acquired in [`HtmlToPdfConverter::start`] (blocking the
+/// calling thread) and released when the conversion completes or the
+/// [`HtmlSink`] is dropped.
+#[derive(Debug, Clone)]
+pub struct SemaphoreConverter<C> {
+    pub converter: C,
+    pub semaphore: Semaphore,
+    /// Give up waiting for a permit after this long. If `None` then wait
+    /// forever.
+    pub timeout: Option<Duration>,
+}
+impl<C> SemaphoreConverter<C> {
+    pub fn new(converter: C, semaphore: Semaphore) -> Self {
+        Self {
+            converter,
+            semaphore,
+            timeout: None,
+        }
+    }
+    /// Give up waiting for a permit after the specified duration.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+impl<'scope, W, C> HtmlToPdfConverter<'scope, W> for SemaphoreConverter<C>
+where
+    W: WriteBuilder + Send + 'scope,
+    C: HtmlToPdfConverter<'scope, W>,
+{
+    type HtmlSink = SemaphoreHtmlSink<C::HtmlSink>;
+    type Error = SemaphoreError<C::Error>;
+
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        let permit = match self.timeout {
+            Some(timeout) => self
+                .semaphore
+                .acquire_timeout(timeout)
+                .ok_or(SemaphoreError::Timeout(timeout))?,
+            None => self.semaphore.acquire(),
+        };
+        Ok(SemaphoreHtmlSink {
+            inner: self
+                .converter
+                .start(scope, output)
+                .map_err(SemaphoreError::Converter)?,
+            permit,
+        })
+    }
+
+    fn output_content_type(&self) -> &'static str {
+        self.converter.output_content_type()
+    }
+    fn output_extension(&self) -> &'static str {
+        self.converter.output_extension()
+    }
+    fn name(&self) -> Cow<'static, str> {
+        format!("semaphore({})", self.converter.name()).into()
+    }
+    fn capabilities(&self) -> ConverterCapabilities {
+        self.converter.capabilities()
+    }
+}
+
+/// The [`HtmlSink`] for [`SemaphoreConverter`]. Holds a permit until the
+/// conversion is completed.
+pub struct SemaphoreHtmlSink<S> {
+    inner: S,
+    permit: SemaphorePermit,
+}
+impl<S, W, E> HtmlSink<W, SemaphoreError<E>> for SemaphoreHtmlSink<S>
+where
+    S: HtmlSink<W, E>,
+{
+    fn complete(self) -> Result<W, SemaphoreError<E>>
+    where
+        Self: Sized,
+    {
+        self.complete_with_diagnostics().map(|(writer, _)| writer)
+    }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), SemaphoreError<E>>
+    where
+        Self: Sized,
+    {
+        let result = self.inner.complete_with_diagnostics();
+        drop(self.permit);
+        result.map_err(SemaphoreError::Converter)
+    }
+}
+crate::forward_write!(impl[S: Write] for SemaphoreHtmlSink<S>, |this| &mut this.inner);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WriteBuilderSimple;
+    use std::io;
+    use std::thread;
+
+    /// Writes the HTML directly to the output.
+    struct EchoConverter;
+    impl<'scope, W> HtmlToPdfConverter<'scope, W> for EchoConverter
+    where
+        W: WriteBuilder + Send + 'scope,
+    {
+        type HtmlSink = EchoHtmlSink<W>;
+        type Error = io::Error;
+
+        fn start(self, _scope: PdfScope<'scope, '_>, output: W) -> io::Result<Self::HtmlSink> {
+            Ok(EchoHtmlSink(output))
+        }
+    }
+    struct EchoHtmlSink<W>(W);
+    impl<W: WriteBuilder> Write for EchoHtmlSink<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.get_writer()?.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl<W: WriteBuilder> HtmlSink<W, io::Error> for EchoHtmlSink<W> {
+        fn complete(self) -> io::Result<W> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn second_conversion_waits_for_first() {
+        let semaphore = Semaphore::new(1);
+
+        let mut first = SemaphoreConverter::new(EchoConverter, semaphore.clone())
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        first.write_all(b"first").unwrap();
+        assert_eq!(semaphore.available_permits(), 0);
+
+        let timed_out = SemaphoreConverter::new(EchoConverter, semaphore.clone())
+            .timeout(Duration::from_millis(10))
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()));
+        assert!(matches!(timed_out, Err(SemaphoreError::Timeout(_))));
+
+        let second = thread::spawn({
+            let semaphore = semaphore.clone();
+            move || {
+                let mut sink = SemaphoreConverter::new(EchoConverter, semaphore)
+                    .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+                    .unwrap();
+                sink.write_all(b"second").unwrap();
+                sink.complete().unwrap().0
+            }
+        });
+        thread::sleep(Duration::from_millis(50));
+        assert!(!second.is_finished());
+
+        assert_eq!(first.complete().unwrap().0, b"first");
+        assert_eq!(second.join().unwrap(), b"second");
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+}
@@ -0,0 +1,174 @@
+//! Convert the same HTML with several converters one after another.
+
+use std::{error::Error, fmt, io::Write};
+
+use crate::{HtmlSink, HtmlToPdfConverter, HtmlToPdfError, PdfScope, WriteBuilderSimple};
+
+/// Converts the same HTML with `first` and then with `second` and returns both
+/// PDFs together with the [`name`](HtmlToPdfConverter::name) of the converter
+/// that made them, for example to compare engines or to combine their outputs
+/// afterwards.
+///
+/// The converters always run in order on the current thread and the HTML is
+/// buffered so that both get the same input. If `continue_on_error` is
+/// `false` (the default) then `second` doesn't run if `first` fails. Use
+/// nested sequences for more than two converters.
+#[derive(Debug, Clone)]
+pub struct SequenceConverter<A, B> {
+    pub first: A,
+    pub second: B,
+    pub continue_on_error: bool,
+}
+impl<A, B> SequenceConverter<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self {
+            first,
+            second,
+            continue_on_error: false,
+        }
+    }
+}
+impl<A, B> SequenceConverter<A, B>
+where
+    A: HtmlToPdfConverter<'static, WriteBuilderSimple<Vec<u8>>>,
+    A::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+    B: HtmlToPdfConverter<'static, WriteBuilderSimple<Vec<u8>>>,
+    B::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    /// Convert `html` with both converters and return the PDFs in the order
+    /// the converters ran.
+    pub fn convert(self, html: &[u8]) -> Result<Vec<(String, Vec<u8>)>, SequenceError> {
+        let mut error = SequenceError {
+            completed: Vec::new(),
+            failures: Vec::new(),
+        };
+        error.record(
+            self.first.name().into_owned(),
+            convert_buffered(self.first, html),
+        );
+        if error.failures.is_empty() || self.continue_on_error {
+            error.record(
+                self.second.name().into_owned(),
+                convert_buffered(self.second, html),
+            );
+        }
+
+        if error.failures.is_empty() {
+            Ok(error.completed)
+        } else {
+            Err(error)
+        }
+    }
+}
+
+fn convert_buffered<C>(converter: C, html: &[u8]) -> Result<Vec<u8>, HtmlToPdfError>
+where
+    C: HtmlToPdfConverter<'static, WriteBuilderSimple<Vec<u8>>>,
+    C::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    let mut html_sink = converter
+        .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+        .map_err(HtmlToPdfError::engine)?;
+    html_sink.write_all(html)?;
+    let WriteBuilderSimple(pdf) = html_sink.complete().map_err(HtmlToPdfError::engine)?;
+    Ok(pdf)
+}
+
+/// At least one of the converters of a [`SequenceConverter`] failed.
+#[derive(Debug)]
+pub struct SequenceError {
+    /// The PDFs of the converters that succeeded, in the order they ran.
+    pub completed: Vec<(String, Vec<u8>)>,
+    /// The errors of the converters that failed, in the order they ran.
+    pub failures: Vec<(String, HtmlToPdfError)>,
+}
+impl SequenceError {
+    fn record(&mut self, name: String, result: Result<Vec<u8>, HtmlToPdfError>) {
+        match result {
+            Ok(pdf) => self.completed.push((name, pdf)),
+            Err(e) => self.failures.push((name, e)),
+        }
+    }
+}
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} converters failed",
+            self.failures.len(),
+            self.failures.len() + self.completed.len()
+        )?;
+        for (name, error) in &self.failures {
+            write!(f, "; {name}: {error}")?;
+        }
+        Ok(())
+    }
+}
+impl Error for SequenceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        let (_, error) = self.failures.first()?;
+        Some(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{FailingConverter, MockConverter},
+        TimedConverter,
+    };
+    use std::cell::RefCell;
+
+    #[test]
+    fn converters_run_in_order() {
+        let order = RefCell::new(Vec::new());
+        let converter = SequenceConverter::new(
+            TimedConverter::new(MockConverter::new(), |_| order.borrow_mut().push("first")),
+            TimedConverter::new(MockConverter::new(), |_| order.borrow_mut().push("second")),
+        );
+        let pdfs = converter.convert(b"<p>Hello</p>").unwrap();
+
+        assert_eq!(*order.borrow(), ["first", "second"]);
+        assert_eq!(
+            pdfs,
+            [
+                ("timed(mock)".to_owned(), MockConverter::fake_pdf(12)),
+                ("timed(mock)".to_owned(), MockConverter::fake_pdf(12)),
+            ]
+        );
+    }
+
+    #[test]
+    fn failure_is_reported_and_can_continue() {
+        let second = MockConverter::new();
+
+        let error = SequenceConverter::new(FailingConverter::new(), second.clone())
+            .convert(b"<p>Hello</p>")
+            .unwrap_err();
+        assert_eq!(error.failures.len(), 1);
+        assert!(error.completed.is_empty());
+        assert!(second.captured_html().is_empty(), "second shouldn't run");
+
+        let error = SequenceConverter {
+            continue_on_error: true,
+            ..SequenceConverter::new(FailingConverter::new(), second.clone())
+        }
+        .convert(b"<p>Hello</p>")
+        .unwrap_err();
+        assert_eq!(error.failures[0].0, "failing");
+        assert_eq!(
+            error.completed,
+            [("mock".to_owned(), MockConverter::fake_pdf(12))]
+        );
+        assert_eq!(second.captured_html(), [b"<p>Hello</p>".to_vec()]);
+        assert_eq!(
+            error.failures[0].1.source().unwrap().to_string(),
+            "the engine crashed"
+        );
+        assert!(
+            error.to_string().starts_with("1 of 2 converters failed"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,478 @@
+//! Digitally sign generated PDFs.
+//!
+//! The signature is created by [OpenSSL](https://docs.rs/openssl) as a
+//! detached PKCS#7 signature (the `adbe.pkcs7.detached` format that PDF
+//! readers support), so the `sign` feature requires OpenSSL to be installed
+//! when building.
+
+use std::{borrow::Cow, error::Error, fmt};
+
+use lopdf::{Dictionary, Document, Object, StringFormat};
+use openssl::{
+    pkcs12::Pkcs12,
+    pkcs7::{Pkcs7, Pkcs7Flags},
+    pkey::{PKey, Private},
+    stack::Stack,
+    x509::X509,
+};
+
+use crate::{
+    pdf, post_process::start_post_processed, ConverterCapabilities, Diagnostic, HtmlToPdfConverter,
+    HtmlToPdfError, PdfPostProcessor, PdfScope, PostProcessedHtmlSink, WriteBuilder,
+    WriteBuilderSimple,
+};
+
+/// Stands in for the numbers of the `/ByteRange` until the position of the
+/// signature is known. Wide enough for any document that fits in memory.
+const BYTE_RANGE_PLACEHOLDER: i64 = 9_999_999_999;
+
+/// A certificate and private key that PDFs are signed with, see
+/// [`PdfSigner::sign`].
+#[derive(Debug, Clone)]
+pub struct PdfSigner {
+    private_key: PKey<Private>,
+    certificate: X509,
+    /// Intermediate certificates that are included in the signature so that
+    /// PDF readers can build the chain to a trusted root.
+    chain: Vec<X509>,
+    /// The name of the signature field that is added if the PDF doesn't
+    /// already have an unsigned one. Defaults to `"Signature1"`.
+    pub field_name: String,
+    /// Why the document was signed, PDF readers show this next to the
+    /// signature.
+    pub reason: Option<String>,
+    /// How many bytes are reserved in the PDF for the signature. Defaults to
+    /// 8192 which is enough for a signature with a few certificates, signing
+    /// fails with [`SignError::SignatureTooLarge`] if it isn't.
+    pub signature_size: usize,
+}
+impl PdfSigner {
+    /// Read the private key and certificates from a PKCS#12 archive (a `.p12`
+    /// or `.pfx` file) that is encrypted with `password`.
+    pub fn from_pkcs12(der: &[u8], password: &str) -> Result<Self, SignError> {
+        let parsed = Pkcs12::from_der(der)?.parse2(password)?;
+        let (Some(private_key), Some(certificate)) = (parsed.pkey, parsed.cert) else {
+            return Err(SignError::MissingKeyOrCertificate);
+        };
+        Ok(Self {
+            private_key,
+            certificate,
+            chain: parsed.ca.into_iter().flatten().collect(),
+            field_name: "Signature1".to_owned(),
+            reason: None,
+            signature_size: 8192,
+        })
+    }
+
+    /// The certificate of the signer.
+    pub fn certificate(&self) -> &X509 {
+        &self.certificate
+    }
+
+    /// Sign a PDF document. The signature is stored in the first signature
+    /// field of the document's form that isn't signed yet. If there is no such
+    /// field then an invisible one called [`field_name`](Self::field_name) is
+    /// added to the first page.
+    pub fn sign(&self, pdf: &[u8]) -> Result<Vec<u8>, SignError> {
+        let mut pdf = self.prepare(pdf)?;
+
+        // The signature covers everything except for its own hex string:
+        let placeholder = [b"<".as_slice(), &vec![b'0'; self.signature_size * 2], b">"].concat();
+        let contents_start = find(&pdf, &placeholder).ok_or(SignError::PlaceholderNotFound)?;
+        let contents_end = contents_start + placeholder.len();
+
+        let byte_range_start = rfind(&pdf[..contents_start], b"/ByteRange")
+            .and_then(|key| Some(key + find(&pdf[key..contents_start], b"[")? + 1))
+            .ok_or(SignError::PlaceholderNotFound)?;
+        let byte_range_end = byte_range_start
+            + find(&pdf[byte_range_start..contents_start], b"]")
+                .ok_or(SignError::PlaceholderNotFound)?;
+        // Padded with spaces so that the offsets in the cross-reference table
+        // stay correct:
+        let byte_range = format!(
+            "0 {contents_start} {contents_end} {}",
+            pdf.len() - contents_end
+        );
+        let byte_range = format!(
+            "{byte_range:<width$}",
+            width = byte_range_end - byte_range_start
+        );
+        pdf[byte_range_start..byte_range_end].copy_from_slice(byte_range.as_bytes());
+
+        let signed_data = [&pdf[..contents_start], &pdf[contents_end..]].concat();
+        let mut chain = Stack::new()?;
+        for certificate in &self.chain {
+            chain.push(certificate.clone())?;
+        }
+        let signature = Pkcs7::sign(
+            &self.certificate,
+            &self.private_key,
+            &chain,
+            &signed_data,
+            Pkcs7Flags::DETACHED | Pkcs7Flags::BINARY,
+        )?
+        .to_der()?;
+        if signature.len() > self.signature_size {
+            return Err(SignError::SignatureTooLarge {
+                size: signature.len(),
+                reserved: self.signature_size,
+            });
+        }
+        let hex = signature
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<String>();
+        pdf[contents_start + 1..][..hex.len()].copy_from_slice(hex.as_bytes());
+        Ok(pdf)
+    }
+
+    /// Add a signature dictionary whose `/ByteRange` and `/Contents` are
+    /// placeholders that are filled in once the document has been saved.
+    fn prepare(&self, pdf: &[u8]) -> lopdf::Result<Vec<u8>> {
+        let mut document = Document::load_mem(pdf)?;
+
+        let mut signature = Dictionary::from_iter([
+            ("Type", Object::from("Sig")),
+            ("Filter", Object::from("Adobe.PPKLite")),
+            ("SubFilter", Object::from("adbe.pkcs7.detached")),
+        ]);
+        if let Some(reason) = &self.reason {
+            signature.set("Reason", pdf::text_string_object(reason));
+        }
+        // The `/ByteRange` must come right before the `/Contents` so that it
+        // can be found once the document is saved:
+        signature.set(
+            "ByteRange",
+            Object::Array(vec![
+                Object::Integer(0),
+                Object::Integer(BYTE_RANGE_PLACEHOLDER),
+                Object::Integer(BYTE_RANGE_PLACEHOLDER),
+                Object::Integer(BYTE_RANGE_PLACEHOLDER),
+            ]),
+        );
+        signature.set(
+            "Contents",
+            Object::String(vec![0; self.signature_size], StringFormat::Hexadecimal),
+        );
+        let signature = document.add_object(signature);
+
+        let mut acro_form = match document.catalog()?.get(b"AcroForm") {
+            Ok(acro_form) => document.dereference(acro_form)?.1.as_dict()?.clone(),
+            Err(_) => Dictionary::new(),
+        };
+        let mut fields = match acro_form.get(b"Fields") {
+            Ok(fields) => document.dereference(fields)?.1.as_array()?.clone(),
+            Err(_) => Vec::new(),
+        };
+        let unsigned_field = fields.iter().find_map(|field| {
+            let id = field.as_reference().ok()?;
+            let field = document.get_dictionary(id).ok()?;
+            let is_signature =
+                field.get(b"FT").and_then(Object::as_name).ok() == Some(b"Sig".as_slice());
+            (is_signature && !field.has(b"V")).then_some(id)
+        });
+        if let Some(field) = unsigned_field {
+            document
+                .get_dictionary_mut(field)?
+                .set("V", Object::Reference(signature));
+        } else {
+            let (_, page) = document
+                .get_pages()
+                .into_iter()
+                .next()
+                .ok_or(lopdf::Error::PageNumberNotFound(1))?;
+            // An invisible widget that is printed and locked:
+            let field = document.add_object(Dictionary::from_iter([
+                ("Type", Object::from("Annot")),
+                ("Subtype", Object::from("Widget")),
+                ("FT", Object::from("Sig")),
+                ("T", pdf::text_string_object(&self.field_name)),
+                ("V", Object::Reference(signature)),
+                ("Rect", Object::Array(vec![Object::Integer(0); 4])),
+                ("F", Object::Integer(132)),
+                ("P", Object::Reference(page)),
+            ]));
+            let mut annotations = match document.get_dictionary(page)?.get(b"Annots") {
+                Ok(annotations) => document.dereference(annotations)?.1.as_array()?.clone(),
+                Err(_) => Vec::new(),
+            };
+            annotations.push(Object::Reference(field));
+            document
+                .get_dictionary_mut(page)?
+                .set("Annots", Object::Array(annotations));
+            fields.push(Object::Reference(field));
+        }
+        acro_form.set("Fields", Object::Array(fields));
+        // The document has signatures and must only be changed by appending:
+        acro_form.set("SigFlags", Object::Integer(3));
+        document
+            .catalog_mut()?
+            .set("AcroForm", Object::Dictionary(acro_form));
+
+        let mut data = Vec::new();
+        document.save_to(&mut data)?;
+        Ok(data)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+/// Signing a PDF with [`PdfSigner`] failed.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SignError {
+    /// OpenSSL failed to read the PKCS#12 archive, for example because the
+    /// password was wrong, or failed to create the signature.
+    OpenSsl(openssl::error::ErrorStack),
+    /// The PKCS#12 archive doesn't contain both a private key and a
+    /// certificate.
+    MissingKeyOrCertificate,
+    /// The PDF couldn't be read or edited.
+    Pdf(lopdf::Error),
+    /// The space reserved for the signature couldn't be found in the saved
+    /// document.
+    PlaceholderNotFound,
+    /// The signature is larger than [`PdfSigner::signature_size`].
+    SignatureTooLarge { size: usize, reserved: usize },
+}
+impl fmt::Display for SignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignError::OpenSsl(_) => write!(f, "OpenSSL failed to sign the PDF"),
+            SignError::MissingKeyOrCertificate => write!(
+                f,
+                "the PKCS#12 archive doesn't contain a private key and a certificate"
+            ),
+            SignError::Pdf(_) => write!(f, "failed to add a signature field to the PDF"),
+            SignError::PlaceholderNotFound => {
+                write!(f, "the space reserved for the signature wasn't found")
+            }
+            SignError::SignatureTooLarge { size, reserved } => write!(
+                f,
+                "the signature is {size} bytes but only {reserved} bytes were reserved for it"
+            ),
+        }
+    }
+}
+impl Error for SignError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SignError::OpenSsl(e) => Some(e),
+            SignError::Pdf(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+impl From<openssl::error::ErrorStack> for SignError {
+    fn from(value: openssl::error::ErrorStack) -> Self {
+        Self::OpenSsl(value)
+    }
+}
+impl From<lopdf::Error> for SignError {
+    fn from(value: lopdf::Error) -> Self {
+        Self::Pdf(value)
+    }
+}
+
+/// Wraps a converter and digitally signs the generated PDF with `signer`, see
+/// [`PdfSigner::sign`]. Failing to sign fails the conversion with a
+/// [`SignError`] as the source of an [`HtmlToPdfError::Engine`] error.
+///
+/// The PDF is signed after the conversion, so the wrapped converter can be
+/// anything but the PDF is [buffered](crate::HtmlSinkPostProcessed#buffering).
+/// This should be the outermost converter since any change to the PDF after
+/// it has been signed invalidates the signature.
+#[derive(Debug, Clone)]
+pub struct SignConverter<C> {
+    pub converter: C,
+    pub signer: PdfSigner,
+}
+impl<C> SignConverter<C> {
+    pub fn new(converter: C, signer: PdfSigner) -> Self {
+        Self { converter, signer }
+    }
+}
+impl<'scope, W, C> HtmlToPdfConverter<'scope, W> for SignConverter<C>
+where
+    W: WriteBuilder + Send + 'scope,
+    C: HtmlToPdfConverter<'scope, WriteBuilderSimple<Vec<u8>>>,
+    C::Error: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    type HtmlSink = SignHtmlSink<C::HtmlSink, W, C::Error>;
+    type Error = HtmlToPdfError;
+
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        start_post_processed(self.converter, scope, output, self.signer)
+    }
+
+    fn name(&self) -> Cow<'static, str> {
+        format!("sign({})", self.converter.name()).into()
+    }
+    fn capabilities(&self) -> ConverterCapabilities {
+        self.converter.capabilities()
+    }
+}
+
+/// Signs the PDF for [`SignConverter`].
+impl PdfPostProcessor for PdfSigner {
+    type Error = HtmlToPdfError;
+
+    fn process(
+        self,
+        pdf: Vec<u8>,
+        _diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Vec<u8>, HtmlToPdfError> {
+        self.sign(&pdf).map_err(HtmlToPdfError::engine)
+    }
+}
+
+/// The [`HtmlSink`](crate::HtmlSink) for [`SignConverter`].
+pub type SignHtmlSink<S, W, E> = PostProcessedHtmlSink<S, W, E, PdfSigner>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::BlankPdfConverter, HtmlSink};
+    use openssl::{
+        asn1::Asn1Time, bn::BigNum, hash::MessageDigest, rsa::Rsa, x509::store::X509StoreBuilder,
+        x509::X509NameBuilder,
+    };
+    use std::io::Write;
+
+    /// A PKCS#12 archive with a self-signed certificate.
+    fn test_pkcs12(password: &str) -> Vec<u8> {
+        let private_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "html_to_pdf test signer")
+            .unwrap();
+        let name = name.build();
+
+        let mut certificate = X509::builder().unwrap();
+        certificate.set_version(2).unwrap();
+        let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+        certificate.set_serial_number(&serial).unwrap();
+        certificate.set_subject_name(&name).unwrap();
+        certificate.set_issuer_name(&name).unwrap();
+        certificate.set_pubkey(&private_key).unwrap();
+        certificate
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        certificate
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        certificate
+            .sign(&private_key, MessageDigest::sha256())
+            .unwrap();
+        let certificate = certificate.build();
+
+        Pkcs12::builder()
+            .name("signer")
+            .pkey(&private_key)
+            .cert(&certificate)
+            .build2(password)
+            .unwrap()
+            .to_der()
+            .unwrap()
+    }
+
+    /// The signature dictionary of the first form field.
+    fn signature_dictionary(pdf: &[u8]) -> Dictionary {
+        let document = Document::load_mem(pdf).unwrap();
+        let acro_form = document.catalog().unwrap().get(b"AcroForm").unwrap();
+        let acro_form = document
+            .dereference(acro_form)
+            .unwrap()
+            .1
+            .as_dict()
+            .unwrap();
+        let fields = acro_form.get(b"Fields").unwrap().as_array().unwrap();
+        let field = document
+            .get_dictionary(fields[0].as_reference().unwrap())
+            .unwrap();
+        let signature = field.get(b"V").unwrap();
+        let signature = document
+            .dereference(signature)
+            .unwrap()
+            .1
+            .as_dict()
+            .unwrap();
+        signature.clone()
+    }
+
+    #[test]
+    fn signed_pdf_validates_against_signer_certificate() {
+        let signer = PdfSigner::from_pkcs12(&test_pkcs12("secret"), "secret").unwrap();
+        let converter = SignConverter::new(BlankPdfConverter::new(2), signer.clone());
+        assert_eq!(
+            HtmlToPdfConverter::<WriteBuilderSimple<Vec<u8>>>::name(&converter),
+            "sign(blank)"
+        );
+        let mut html_sink = converter
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        let pdf = html_sink.complete().unwrap().0;
+        assert_eq!(pdf::page_count(&pdf).unwrap(), 2);
+
+        let signature = signature_dictionary(&pdf);
+        assert_eq!(signature.get(b"Type").unwrap().as_name().unwrap(), b"Sig");
+        let range = signature
+            .get(b"ByteRange")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_i64().unwrap() as usize)
+            .collect::<Vec<_>>();
+        assert_eq!(range[2] + range[3], pdf.len());
+        let mut signed_data = [
+            &pdf[range[0]..range[0] + range[1]],
+            &pdf[range[2]..range[2] + range[3]],
+        ]
+        .concat();
+        let contents = signature.get(b"Contents").unwrap().as_str().unwrap();
+        let pkcs7 = Pkcs7::from_der(contents).unwrap();
+
+        let mut store = X509StoreBuilder::new().unwrap();
+        store.add_cert(signer.certificate().clone()).unwrap();
+        let store = store.build();
+        let certificates = Stack::<X509>::new().unwrap();
+        pkcs7
+            .verify(
+                &certificates,
+                &store,
+                Some(&signed_data),
+                None,
+                Pkcs7Flags::BINARY,
+            )
+            .unwrap();
+
+        // Any change to the document invalidates the signature:
+        signed_data[0] ^= 1;
+        assert!(pkcs7
+            .verify(
+                &certificates,
+                &store,
+                Some(&signed_data),
+                None,
+                Pkcs7Flags::BINARY
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn wrong_password_is_an_error() {
+        let error = PdfSigner::from_pkcs12(&test_pkcs12("secret"), "wrong").unwrap_err();
+        assert!(matches!(error, SignError::OpenSsl(_)), "{error:?}");
+    }
+}
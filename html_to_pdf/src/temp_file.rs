@@ -0,0 +1,160 @@
+//! Write PDF data to a temporary file instead of keeping it in memory.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{WriteBuilder, WriteBuilderLifetime};
+
+/// Deletes the file at the path when dropped.
+#[derive(Debug)]
+struct TempPath(PathBuf);
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        // Best effort, the file might have been removed by someone else:
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[derive(Debug)]
+struct TempFile {
+    // Declared before `path` so that the file is closed before it is deleted,
+    // which is required on Windows.
+    file: File,
+    path: TempPath,
+}
+impl TempFile {
+    fn create_in(dir: &Path) -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        loop {
+            let path = dir.join(format!(
+                "html_to_pdf-output-{}-{}.pdf",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            match OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(file) => {
+                    return Ok(Self {
+                        file,
+                        path: TempPath(path),
+                    })
+                }
+                // Left behind by an earlier process with the same id:
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// A write builder that writes the PDF to a temporary file so that large
+/// outputs don't need to be held in memory. Use
+/// [`WriteBuilderTempFile::into_reader`] to read the data back once the
+/// conversion has completed, for example by using
+/// [`HtmlSink::try_map_writer`](crate::HtmlSink::try_map_writer):
+///
+/// ```no_run
+/// # use html_to_pdf::{HtmlSink, TempFileReader, WriteBuilderTempFile};
+/// # fn f<S: HtmlSink<WriteBuilderTempFile, std::io::Error>>(sink: S) -> std::io::Result<TempFileReader> {
+/// let pdf: TempFileReader = sink.try_map_writer(WriteBuilderTempFile::into_reader).complete()?;
+/// # Ok(pdf)
+/// # }
+/// ```
+///
+/// The file is deleted when the builder or the reader is dropped.
+#[derive(Debug)]
+pub struct WriteBuilderTempFile(TempFile);
+impl WriteBuilderTempFile {
+    /// Create a new file inside the system's temp folder.
+    pub fn new() -> io::Result<Self> {
+        Self::new_in(std::env::temp_dir())
+    }
+    /// Create a new file inside the specified folder.
+    pub fn new_in(dir: impl AsRef<Path>) -> io::Result<Self> {
+        TempFile::create_in(dir.as_ref()).map(Self)
+    }
+    /// The path of the temporary file.
+    pub fn path(&self) -> &Path {
+        &self.0.path.0
+    }
+    /// Read the data that has been written from the start of the file.
+    pub fn into_reader(mut self) -> io::Result<TempFileReader> {
+        self.0.file.flush()?;
+        self.0.file.seek(SeekFrom::Start(0))?;
+        Ok(TempFileReader(self.0))
+    }
+}
+impl<'a> WriteBuilderLifetime<'a> for WriteBuilderTempFile {
+    type Writer = &'a mut File;
+}
+impl WriteBuilder for WriteBuilderTempFile {
+    fn get_writer(&mut self) -> io::Result<<Self as WriteBuilderLifetime<'_>>::Writer> {
+        Ok(&mut self.0.file)
+    }
+}
+
+/// Reads the data written to a [`WriteBuilderTempFile`]. The file is deleted
+/// when this is dropped.
+#[derive(Debug)]
+pub struct TempFileReader(TempFile);
+impl TempFileReader {
+    /// The path of the temporary file.
+    pub fn path(&self) -> &Path {
+        &self.0.path.0
+    }
+    /// The size of the file in bytes.
+    pub fn len(&self) -> io::Result<u64> {
+        Ok(self.0.file.metadata()?.len())
+    }
+    /// `true` if no data was written to the file.
+    pub fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+impl Read for TempFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.file.read(buf)
+    }
+}
+impl Seek for TempFileReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.file.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_output_round_trips_through_temp_file() {
+        let data = (0..8 * 1024 * 1024)
+            .map(|i: u32| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        let mut builder = WriteBuilderTempFile::new().unwrap();
+        for chunk in data.chunks(64 * 1024) {
+            builder.get_writer().unwrap().write_all(chunk).unwrap();
+        }
+        let path = builder.path().to_owned();
+        assert!(path.exists());
+
+        let mut reader = builder.into_reader().unwrap();
+        assert_eq!(reader.len().unwrap(), data.len() as u64);
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert!(read == data, "the data should be unchanged");
+
+        drop(reader);
+        assert!(!path.exists(), "the temp file should be deleted");
+    }
+}
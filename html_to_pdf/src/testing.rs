@@ -0,0 +1,274 @@
+//! Helpers for testing code that is generic over [`HtmlToPdfConverter`]
+//! without depending on a real conversion engine.
+
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use crate::{CancelToken, HtmlSink, HtmlToPdfConverter, PdfScope, WriteBuilder};
+
+/// A converter that doesn't actually create a PDF. It remembers the HTML that
+/// it was given and writes a recognizable fake PDF to the output, see
+/// [`MockConverter::fake_pdf`].
+///
+/// Clones share the captured HTML, so keep a clone of the converter around to
+/// inspect what was converted after the original has been consumed by
+/// [`HtmlToPdfConverter::start`].
+#[derive(Debug, Clone, Default)]
+pub struct MockConverter {
+    captured: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+impl MockConverter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// The output that is written for HTML of the specified length:
+    /// `%PDF-fake\n` followed by the length and a newline.
+    pub fn fake_pdf(html_len: usize) -> Vec<u8> {
+        format!("%PDF-fake\n{html_len}\n").into_bytes()
+    }
+    /// The HTML of all completed conversions in the order they completed.
+    pub fn captured_html(&self) -> Vec<Vec<u8>> {
+        self.captured
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+    /// The HTML of the most recently completed conversion.
+    pub fn last_html(&self) -> Option<Vec<u8>> {
+        self.captured
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .last()
+            .cloned()
+    }
+}
+impl<'scope, W> HtmlToPdfConverter<'scope, W> for MockConverter
+where
+    W: WriteBuilder + Send + 'scope,
+{
+    type HtmlSink = MockHtmlSink<W>;
+    type Error = io::Error;
+
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> io::Result<Self::HtmlSink> {
+        Ok(MockHtmlSink {
+            html: Vec::new(),
+            output,
+            captured: self.captured,
+            cancel: scope.cancel_token().cloned(),
+        })
+    }
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("mock")
+    }
+}
+
+/// Buffers the HTML that is written to it, see [`MockConverter`].
+#[derive(Debug)]
+pub struct MockHtmlSink<W> {
+    html: Vec<u8>,
+    output: W,
+    captured: Arc<Mutex<Vec<Vec<u8>>>>,
+    cancel: Option<CancelToken>,
+}
+impl<W> Write for MockHtmlSink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.html.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+impl<W: WriteBuilder> HtmlSink<W, io::Error> for MockHtmlSink<W> {
+    fn complete(mut self) -> io::Result<W> {
+        if let Some(cancel) = &self.cancel {
+            cancel.check()?;
+        }
+        self.output
+            .get_writer()?
+            .write_all(&MockConverter::fake_pdf(self.html.len()))?;
+        self.captured
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(self.html);
+        Ok(self.output)
+    }
+}
+
+/// A converter that always fails. By default it writes the start of a PDF to
+/// the output and then fails when the conversion is completed, like an engine
+/// that crashes halfway through. The converter is named `failing`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FailingConverter {
+    /// Fail with [`io::ErrorKind::NotFound`] when the conversion is started
+    /// instead, like an engine that isn't installed.
+    pub fail_on_start: bool,
+}
+impl FailingConverter {
+    /// The data that is written to the output before completing fails.
+    pub const PARTIAL_PDF: &'static [u8] = b"%PDF-1.7\n";
+    /// The message of the error that completing fails with.
+    pub const ERROR_MESSAGE: &'static str = "the engine crashed";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn on_start() -> Self {
+        Self {
+            fail_on_start: true,
+        }
+    }
+}
+impl<'scope, W> HtmlToPdfConverter<'scope, W> for FailingConverter
+where
+    W: WriteBuilder + Send + 'scope,
+{
+    type HtmlSink = FailingHtmlSink<W>;
+    type Error = io::Error;
+
+    fn start(self, _scope: PdfScope<'scope, '_>, output: W) -> io::Result<Self::HtmlSink> {
+        if self.fail_on_start {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "not installed"));
+        }
+        Ok(FailingHtmlSink { output })
+    }
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("failing")
+    }
+}
+
+/// Ignores the HTML and fails when completed, see [`FailingConverter`].
+#[derive(Debug)]
+pub struct FailingHtmlSink<W> {
+    output: W,
+}
+crate::forward_write!(impl[W] for FailingHtmlSink<W>, |_this| &mut io::sink());
+impl<W: WriteBuilder> HtmlSink<W, io::Error> for FailingHtmlSink<W> {
+    fn complete(mut self) -> io::Result<W> {
+        self.output
+            .get_writer()?
+            .write_all(FailingConverter::PARTIAL_PDF)?;
+        Err(io::Error::other(FailingConverter::ERROR_MESSAGE))
+    }
+}
+
+/// A converter that ignores the HTML and writes a valid PDF with `pages` empty
+/// pages, for testing code that edits the generated PDF.
+#[cfg(feature = "lopdf")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlankPdfConverter {
+    pub pages: u32,
+}
+#[cfg(feature = "lopdf")]
+impl BlankPdfConverter {
+    pub fn new(pages: u32) -> Self {
+        Self { pages }
+    }
+    /// The PDF that is written to the output.
+    pub fn blank_pdf(pages: u32) -> Vec<u8> {
+        use lopdf::{Dictionary, Document, Object};
+
+        let mut document = Document::with_version("1.7");
+        let pages_id = document.new_object_id();
+        let kids = (0..pages)
+            .map(|_| {
+                Object::Reference(document.add_object(Dictionary::from_iter([
+                    ("Type", Object::from("Page")),
+                    ("Parent", Object::Reference(pages_id)),
+                    (
+                        "MediaBox",
+                        Object::Array(vec![0.into(), 0.into(), 595.into(), 842.into()]),
+                    ),
+                ])))
+            })
+            .collect::<Vec<_>>();
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(Dictionary::from_iter([
+                ("Type", Object::from("Pages")),
+                ("Count", Object::from(pages)),
+                ("Kids", Object::Array(kids)),
+            ])),
+        );
+        let catalog = document.add_object(Dictionary::from_iter([
+            ("Type", Object::from("Catalog")),
+            ("Pages", Object::Reference(pages_id)),
+        ]));
+        document.trailer.set("Root", catalog);
+
+        let mut pdf = Vec::new();
+        document
+            .save_to(&mut pdf)
+            .expect("writing to a Vec can't fail");
+        pdf
+    }
+}
+#[cfg(feature = "lopdf")]
+impl<'scope, W> HtmlToPdfConverter<'scope, W> for BlankPdfConverter
+where
+    W: WriteBuilder + Send + 'scope,
+{
+    type HtmlSink = BlankPdfHtmlSink<W>;
+    type Error = io::Error;
+
+    fn start(self, _scope: PdfScope<'scope, '_>, output: W) -> io::Result<Self::HtmlSink> {
+        Ok(BlankPdfHtmlSink {
+            pages: self.pages,
+            output,
+        })
+    }
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("blank")
+    }
+}
+
+/// Ignores the HTML that is written to it, see [`BlankPdfConverter`].
+#[cfg(feature = "lopdf")]
+#[derive(Debug)]
+pub struct BlankPdfHtmlSink<W> {
+    pages: u32,
+    output: W,
+}
+#[cfg(feature = "lopdf")]
+impl<W> Write for BlankPdfHtmlSink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+#[cfg(feature = "lopdf")]
+impl<W: WriteBuilder> HtmlSink<W, io::Error> for BlankPdfHtmlSink<W> {
+    fn complete(mut self) -> io::Result<W> {
+        self.output
+            .get_writer()?
+            .write_all(&BlankPdfConverter::blank_pdf(self.pages))?;
+        Ok(self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WriteBuilderSimple;
+
+    #[test]
+    fn captures_html_and_writes_marker() {
+        let converter = MockConverter::new();
+
+        let mut html_sink = converter
+            .clone()
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        assert_eq!(converter.last_html(), None);
+
+        let pdf = html_sink.complete().unwrap().0;
+        assert!(pdf.starts_with(b"%PDF-fake\n"));
+        assert_eq!(pdf, MockConverter::fake_pdf(12));
+        assert_eq!(converter.captured_html(), [b"<p>Hello</p>".to_vec()]);
+    }
+}
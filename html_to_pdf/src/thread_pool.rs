@@ -0,0 +1,181 @@
+//! Bound the number of worker threads that are used by conversions.
+
+use std::{
+    collections::VecDeque,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+use crate::{PdfScope, PdfScopedJoinHandle};
+
+type Task<'scope> = Box<dyn FnOnce() + Send + 'scope>;
+
+struct PoolState<'scope> {
+    queue: VecDeque<Task<'scope>>,
+    threads: usize,
+}
+
+struct PoolShared<'scope> {
+    state: Mutex<PoolState<'scope>>,
+    max_threads: usize,
+}
+impl<'scope> PoolShared<'scope> {
+    /// Run `task` and then any queued tasks until the queue is empty.
+    fn work(&self, mut task: Task<'scope>) {
+        loop {
+            task();
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            match state.queue.pop_front() {
+                Some(next) => task = next,
+                None => {
+                    state.threads -= 1;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Runs tasks on at most `max_threads` threads at the same time. Tasks that are
+/// submitted while all threads are busy are queued and run by the first thread
+/// that finishes its current task.
+///
+/// Threads are spawned using the [`PdfScope`] that the pool was created with,
+/// so tasks can borrow data that outlives the scope just like with
+/// [`PdfScope::spawn`]. Threads exit when there are no more queued tasks.
+pub struct PdfThreadPool<'scope, 'env> {
+    scope: PdfScope<'scope, 'env>,
+    shared: Arc<PoolShared<'scope>>,
+}
+impl<'scope, 'env> PdfThreadPool<'scope, 'env> {
+    /// Create a pool that spawns at most `max_threads` threads (at least 1)
+    /// using `scope`.
+    pub fn new(scope: PdfScope<'scope, 'env>, max_threads: usize) -> Self {
+        Self {
+            scope,
+            shared: Arc::new(PoolShared {
+                state: Mutex::new(PoolState {
+                    queue: VecDeque::new(),
+                    threads: 0,
+                }),
+                max_threads: max_threads.max(1),
+            }),
+        }
+    }
+    /// The max number of threads that the pool will use.
+    pub fn max_threads(&self) -> usize {
+        self.shared.max_threads
+    }
+    /// Run `f` on one of the pool's threads. Never blocks, if all threads are
+    /// busy then `f` is queued.
+    ///
+    /// A panic inside `f` is returned when the task is joined and doesn't stop
+    /// the thread that ran it.
+    pub fn spawn<F, T>(&self, f: F) -> PdfScopedJoinHandle<'scope, T>
+    where
+        F: FnOnce() -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let slot = Arc::new(TaskSlot {
+            result: Mutex::new(None),
+            done: Condvar::new(),
+        });
+        let task: Task<'scope> = Box::new({
+            let slot = Arc::clone(&slot);
+            move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(f));
+                *slot.result.lock().unwrap_or_else(|e| e.into_inner()) = Some(result);
+                slot.done.notify_all();
+            }
+        });
+
+        let mut state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.threads < self.shared.max_threads {
+            state.threads += 1;
+            drop(state);
+            let shared = Arc::clone(&self.shared);
+            // The pool waits for its tasks using the slots so the thread's own
+            // join handle isn't needed:
+            drop(self.scope.clone().spawn(move || shared.work(task)));
+        } else {
+            state.queue.push_back(task);
+        }
+        PdfScopedJoinHandle::pooled(slot)
+    }
+}
+
+/// Where a task run by [`PdfThreadPool`] stores its result.
+pub(crate) struct TaskSlot<T> {
+    result: Mutex<Option<thread::Result<T>>>,
+    done: Condvar,
+}
+impl<T> TaskSlot<T> {
+    /// Block until the task has finished.
+    pub(crate) fn wait(&self) -> thread::Result<T> {
+        let mut result = self.result.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(result) = result.take() {
+                return result;
+            }
+            result = self.done.wait(result).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::HashSet,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    #[test]
+    fn runs_all_tasks_without_exceeding_max_threads() {
+        let inputs = (0..10).collect::<Vec<usize>>();
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let thread_ids = Mutex::new(HashSet::new());
+
+        let (running, max_running, thread_ids) = (&running, &max_running, &thread_ids);
+
+        thread::scope(|s| {
+            let pool = PdfThreadPool::new(PdfScope::scoped(s), 3);
+            let handles = inputs
+                .iter()
+                .map(|input| {
+                    pool.spawn(move || {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_running.fetch_max(now, Ordering::SeqCst);
+                        thread_ids.lock().unwrap().insert(thread::current().id());
+                        thread::sleep(Duration::from_millis(10));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        input * 2
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let results = handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, inputs.iter().map(|v| v * 2).collect::<Vec<_>>());
+        });
+
+        assert!(max_running.load(Ordering::SeqCst) <= 3);
+        assert!(thread_ids.lock().unwrap().len() <= 3);
+    }
+
+    #[test]
+    fn panics_are_returned_when_joined() {
+        let pool = PdfThreadPool::new(PdfScope::owned(), 1);
+        let failed = pool.spawn(|| -> u32 { panic!("task failed") });
+        let succeeded = pool.spawn(|| 5);
+
+        let payload = failed.join().unwrap_err();
+        assert_eq!(crate::panic_message(&*payload), Some("task failed"));
+        assert_eq!(succeeded.join().unwrap(), 5);
+    }
+}
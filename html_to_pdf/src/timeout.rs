@@ -0,0 +1,222 @@
+//! Give up on conversions that take too long to complete.
+
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt,
+    io::Write,
+    panic,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+use crate::{
+    ConverterCapabilities, Diagnostic, HtmlSink, HtmlToPdfConverter, PdfScope, PdfScopedJoinHandle,
+    WriteBuilder,
+};
+
+/// Error returned by [`TimeoutConverter`].
+#[derive(Debug)]
+pub enum TimeoutError<E> {
+    /// The wrapped converter didn't complete within this duration.
+    Timeout(Duration),
+    /// The wrapped converter failed.
+    Converter(E),
+}
+impl<E: fmt::Display> fmt::Display for TimeoutError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeoutError::Timeout(timeout) => {
+                write!(f, "the conversion didn't complete within {timeout:?}")
+            }
+            TimeoutError::Converter(e) => e.fmt(f),
+        }
+    }
+}
+impl<E: Error + 'static> Error for TimeoutError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TimeoutError::Timeout(_) => None,
+            TimeoutError::Converter(e) => Some(e),
+        }
+    }
+}
+
+/// Wraps a converter and fails with [`TimeoutError::Timeout`] if
+/// [`HtmlSink::complete`] doesn't finish within [`timeout`](Self::timeout).
+///
+/// Writing HTML to the sink isn't limited, the deadline starts when the sink is
+/// completed. The wrapped sink is then completed on a thread spawned from the
+/// [`PdfScope`] when the conversion was started.
+///
+/// # Detached workers
+///
+/// A hung converter can't be stopped from the outside, so after a timeout the
+/// worker thread is detached and may keep running. Its result is discarded
+/// when it eventually finishes, which means that anything it writes to the
+/// output after the timeout is lost. With [`PdfScope::owned`] the thread is
+/// simply left running, but the enclosing [`std::thread::scope`] of
+/// [`PdfScope::scoped`] still waits for it before returning. Use a
+/// [`CancelToken`](crate::CancelToken) to also ask the converter to stop.
+#[derive(Debug, Clone)]
+pub struct TimeoutConverter<C> {
+    pub converter: C,
+    pub timeout: Duration,
+}
+impl<C> TimeoutConverter<C> {
+    pub fn new(converter: C, timeout: Duration) -> Self {
+        Self { converter, timeout }
+    }
+}
+impl<'scope, W, C> HtmlToPdfConverter<'scope, W> for TimeoutConverter<C>
+where
+    W: WriteBuilder + Send + 'scope,
+    C: HtmlToPdfConverter<'scope, W>,
+    C::HtmlSink: Send + 'scope,
+    C::Error: Send + 'scope,
+{
+    type HtmlSink = TimeoutHtmlSink<'scope, C::HtmlSink, W, C::Error>;
+    type Error = TimeoutError<C::Error>;
+
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        let inner = self
+            .converter
+            .start(scope.clone(), output)
+            .map_err(TimeoutError::Converter)?;
+
+        let (sink_tx, sink_rx) = mpsc::channel::<C::HtmlSink>();
+        let (result_tx, result_rx) = mpsc::sync_channel(1);
+        let worker = scope.spawn(move || {
+            // The sender is dropped without a sink if the conversion is
+            // abandoned before it is completed.
+            if let Ok(sink) = sink_rx.recv() {
+                // Fails if the conversion timed out, then the result is
+                // discarded.
+                let _ = result_tx.send(sink.complete_with_diagnostics());
+            }
+        });
+        Ok(TimeoutHtmlSink {
+            inner,
+            timeout: self.timeout,
+            sink_tx,
+            result_rx,
+            worker,
+        })
+    }
+
+    fn output_content_type(&self) -> &'static str {
+        self.converter.output_content_type()
+    }
+    fn output_extension(&self) -> &'static str {
+        self.converter.output_extension()
+    }
+    fn name(&self) -> Cow<'static, str> {
+        format!("timeout({})", self.converter.name()).into()
+    }
+    fn capabilities(&self) -> ConverterCapabilities {
+        self.converter.capabilities()
+    }
+}
+
+/// The [`HtmlSink`] for [`TimeoutConverter`]. Forwards the HTML to the
+/// wrapped sink and hands it to a worker thread when completed.
+pub struct TimeoutHtmlSink<'scope, S, W, E> {
+    inner: S,
+    timeout: Duration,
+    sink_tx: mpsc::Sender<S>,
+    result_rx: mpsc::Receiver<Result<(W, Vec<Diagnostic>), E>>,
+    worker: PdfScopedJoinHandle<'scope, ()>,
+}
+impl<'scope, S, W, E> HtmlSink<W, TimeoutError<E>> for TimeoutHtmlSink<'scope, S, W, E>
+where
+    S: HtmlSink<W, E>,
+{
+    fn complete(self) -> Result<W, TimeoutError<E>>
+    where
+        Self: Sized,
+    {
+        self.complete_with_diagnostics().map(|(writer, _)| writer)
+    }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), TimeoutError<E>>
+    where
+        Self: Sized,
+    {
+        if self.sink_tx.send(self.inner).is_err() {
+            unreachable!("the timeout worker exited before it received the sink");
+        }
+        match self.result_rx.recv_timeout(self.timeout) {
+            Ok(result) => result.map_err(TimeoutError::Converter),
+            // Dropping the join handle detaches the worker.
+            Err(RecvTimeoutError::Timeout) => Err(TimeoutError::Timeout(self.timeout)),
+            Err(RecvTimeoutError::Disconnected) => match self.worker.join() {
+                Err(payload) => panic::resume_unwind(payload),
+                Ok(()) => unreachable!("the timeout worker exited without a result"),
+            },
+        }
+    }
+}
+crate::forward_write!(
+    impl['scope, S: Write, W, E] for TimeoutHtmlSink<'scope, S, W, E>,
+    |this| &mut this.inner
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::MockConverter, CancelToken, WriteBuilderSimple};
+    use std::io;
+
+    /// Blocks in `complete` until the token is cancelled.
+    struct HangingConverter(CancelToken);
+    impl<'scope, W> HtmlToPdfConverter<'scope, W> for HangingConverter
+    where
+        W: WriteBuilder + Send + 'scope,
+    {
+        type HtmlSink = HangingHtmlSink<W>;
+        type Error = io::Error;
+
+        fn start(self, _scope: PdfScope<'scope, '_>, output: W) -> io::Result<Self::HtmlSink> {
+            Ok(HangingHtmlSink(self.0, output))
+        }
+    }
+    struct HangingHtmlSink<W>(CancelToken, W);
+    impl<W> Write for HangingHtmlSink<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl<W> HtmlSink<W, io::Error> for HangingHtmlSink<W> {
+        fn complete(self) -> io::Result<W> {
+            self.0.cancelled();
+            Ok(self.1)
+        }
+    }
+
+    #[test]
+    fn completes_within_timeout() {
+        let mut sink = TimeoutConverter::new(MockConverter::new(), Duration::from_secs(10))
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        sink.write_all(b"<p>Hello</p>").unwrap();
+        assert_eq!(sink.complete().unwrap().0, MockConverter::fake_pdf(12));
+    }
+
+    #[test]
+    fn hung_converter_times_out() {
+        let token = CancelToken::new();
+        let mut sink =
+            TimeoutConverter::new(HangingConverter(token.clone()), Duration::from_millis(20))
+                .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+                .unwrap();
+        sink.write_all(b"<p>Hello</p>").unwrap();
+        assert!(matches!(
+            sink.complete(),
+            Err(TimeoutError::Timeout(timeout)) if timeout == Duration::from_millis(20)
+        ));
+        // Let the detached worker exit.
+        token.cancel();
+    }
+}
@@ -0,0 +1,224 @@
+//! Measure how long conversions take.
+
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, IoSlice, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ConverterCapabilities, Diagnostic, HtmlSink, HtmlToPdfConverter, PdfScope, WriteBuilder,
+    WriteBuilderCounting,
+};
+
+/// Measurements from a conversion done by a [`TimedConverter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConversionTiming {
+    /// Wall time from [`HtmlToPdfConverter::start`] until
+    /// [`HtmlSink::complete`] returned.
+    pub duration: Duration,
+    /// Number of HTML bytes written to the converter.
+    pub html_bytes: u64,
+    /// Number of PDF bytes written to the output.
+    pub pdf_bytes: u64,
+    /// `false` if the conversion returned an error.
+    pub success: bool,
+}
+
+/// Wraps a converter and reports how long the conversion took by calling
+/// `on_complete` when [`HtmlSink::complete`] returns.
+///
+/// The callback isn't called if the [`HtmlSink`] is dropped without being
+/// completed. To log all conversions use something like
+/// `TimedConverter::new(converter, |timing| eprintln!("{timing:?}"))`.
+#[derive(Debug, Clone)]
+pub struct TimedConverter<C, F> {
+    pub converter: C,
+    pub on_complete: F,
+}
+impl<C, F> TimedConverter<C, F>
+where
+    F: FnOnce(ConversionTiming),
+{
+    pub fn new(converter: C, on_complete: F) -> Self {
+        Self {
+            converter,
+            on_complete,
+        }
+    }
+}
+impl<'scope, W, C, F> HtmlToPdfConverter<'scope, W> for TimedConverter<C, F>
+where
+    W: WriteBuilder + Send + 'scope,
+    C: HtmlToPdfConverter<'scope, WriteBuilderCounting<W>>,
+    F: FnOnce(ConversionTiming),
+{
+    type HtmlSink = TimedHtmlSink<C::HtmlSink, F>;
+    type Error = C::Error;
+
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        let started = Instant::now();
+        let output = WriteBuilderCounting::new(output);
+        let pdf_bytes = Arc::clone(&output.bytes);
+        Ok(TimedHtmlSink {
+            inner: self.converter.start(scope, output)?,
+            on_complete: self.on_complete,
+            started,
+            html_bytes: 0,
+            pdf_bytes,
+        })
+    }
+
+    fn output_content_type(&self) -> &'static str {
+        self.converter.output_content_type()
+    }
+    fn output_extension(&self) -> &'static str {
+        self.converter.output_extension()
+    }
+    fn name(&self) -> Cow<'static, str> {
+        format!("timed({})", self.converter.name()).into()
+    }
+    fn capabilities(&self) -> ConverterCapabilities {
+        self.converter.capabilities()
+    }
+}
+
+/// The [`HtmlSink`] for [`TimedConverter`].
+pub struct TimedHtmlSink<S, F> {
+    inner: S,
+    on_complete: F,
+    started: Instant,
+    html_bytes: u64,
+    pdf_bytes: Arc<AtomicU64>,
+}
+impl<S, W, E, F> HtmlSink<W, E> for TimedHtmlSink<S, F>
+where
+    S: HtmlSink<WriteBuilderCounting<W>, E>,
+    F: FnOnce(ConversionTiming),
+{
+    fn complete(self) -> Result<W, E>
+    where
+        Self: Sized,
+    {
+        self.complete_with_diagnostics().map(|(writer, _)| writer)
+    }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), E>
+    where
+        Self: Sized,
+    {
+        let result = self.inner.complete_with_diagnostics();
+        (self.on_complete)(ConversionTiming {
+            duration: self.started.elapsed(),
+            html_bytes: self.html_bytes,
+            pdf_bytes: self.pdf_bytes.load(Ordering::Relaxed),
+            success: result.is_ok(),
+        });
+        result.map(|(writer, diagnostics)| (writer.into_inner(), diagnostics))
+    }
+}
+impl<S, F> Write for TimedHtmlSink<S, F>
+where
+    S: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.html_bytes += written as u64;
+        Ok(written)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let written = self.inner.write_vectored(bufs)?;
+        self.html_bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+impl<S, F> fmt::Debug for TimedHtmlSink<S, F>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimedHtmlSink")
+            .field("inner", &self.inner)
+            .field("started", &self.started)
+            .field("html_bytes", &self.html_bytes)
+            .field("pdf_bytes", &self.pdf_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::MockConverter, WriteBuilderSimple};
+    use std::{sync::Mutex, thread};
+
+    #[test]
+    fn callback_receives_timing() {
+        let timing = Arc::new(Mutex::new(None));
+
+        let converter = TimedConverter::new(MockConverter::new(), {
+            let timing = Arc::clone(&timing);
+            move |value| *timing.lock().unwrap() = Some(value)
+        });
+        let mut html_sink = converter
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        thread::sleep(Duration::from_millis(1));
+        let pdf = html_sink.complete().unwrap().0;
+
+        let timing = timing.lock().unwrap().expect("callback should be called");
+        assert!(timing.duration > Duration::ZERO);
+        assert!(timing.success);
+        assert_eq!(timing.html_bytes, 12);
+        assert_eq!(timing.pdf_bytes, pdf.len() as u64);
+        assert_eq!(pdf, MockConverter::fake_pdf(12));
+    }
+
+    #[test]
+    fn vectored_writes_are_counted() {
+        let timing = Arc::new(Mutex::new(None));
+
+        let converter = TimedConverter::new(MockConverter::new(), {
+            let timing = Arc::clone(&timing);
+            move |value| *timing.lock().unwrap() = Some(value)
+        });
+        let mut html_sink = converter
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        let written = html_sink
+            .write_vectored(&[IoSlice::new(b"<p>"), IoSlice::new(b"Hello</p>")])
+            .unwrap();
+        let pdf = html_sink.complete().unwrap().0;
+
+        let timing = timing.lock().unwrap().expect("callback should be called");
+        assert_eq!(timing.html_bytes, written as u64);
+        assert_eq!(pdf, MockConverter::fake_pdf(written));
+    }
+
+    #[test]
+    fn wrapped_converter_names_compose() {
+        type Output = WriteBuilderSimple<Vec<u8>>;
+        let converter = TimedConverter::new(
+            crate::SemaphoreConverter::new(MockConverter::new(), crate::Semaphore::new(1)),
+            |_| {},
+        );
+        assert_eq!(
+            HtmlToPdfConverter::<Output>::name(&converter),
+            "timed(semaphore(mock))"
+        );
+
+        let boxed: Box<dyn crate::DynHtmlToPdfConverter<'static, Output>> =
+            Box::new(MockConverter::new());
+        assert_eq!(boxed.name(), "mock");
+    }
+}
@@ -0,0 +1,243 @@
+//! Detect converters that write something other than a complete PDF.
+
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Write},
+};
+
+use crate::{Diagnostic, HtmlSink, HtmlToPdfError, WriteBuilder, WriteBuilderLifetime};
+
+/// The start of every PDF.
+const PDF_HEADER: &[u8; 5] = b"%PDF-";
+/// The end of every PDF, followed by optional whitespace.
+const PDF_EOF: &[u8; 5] = b"%%EOF";
+/// How far from the end of the output `%%EOF` is searched for. PDF readers
+/// search the last 1024 bytes.
+const TAIL_LEN: usize = 1024;
+
+/// The output of a conversion wasn't a complete PDF, see
+/// [`HtmlSink::validate_pdf`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidPdfOutput {
+    /// The output didn't start with `%PDF-`.
+    MissingHeader,
+    /// The output didn't end with `%%EOF`, usually because the converter
+    /// crashed while it was writing the PDF.
+    MissingEof,
+}
+impl fmt::Display for InvalidPdfOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidPdfOutput::MissingHeader => {
+                write!(f, "the converter's output doesn't start like a PDF")
+            }
+            InvalidPdfOutput::MissingEof => write!(
+                f,
+                "the converter's output doesn't end like a PDF, it might have been truncated"
+            ),
+        }
+    }
+}
+impl Error for InvalidPdfOutput {}
+impl From<InvalidPdfOutput> for io::Error {
+    fn from(value: InvalidPdfOutput) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, value)
+    }
+}
+impl From<InvalidPdfOutput> for HtmlToPdfError {
+    fn from(value: InvalidPdfOutput) -> Self {
+        Self::engine(value)
+    }
+}
+
+/// What has been seen of the PDF so far.
+#[derive(Debug, Clone)]
+struct PdfCheck {
+    header: [u8; PDF_HEADER.len()],
+    header_len: usize,
+    /// The last bytes that were written, at the end of the array.
+    tail: [u8; TAIL_LEN],
+    tail_len: usize,
+}
+impl PdfCheck {
+    fn new() -> Self {
+        Self {
+            header: [0; PDF_HEADER.len()],
+            header_len: 0,
+            tail: [0; TAIL_LEN],
+            tail_len: 0,
+        }
+    }
+    fn update(&mut self, buf: &[u8]) {
+        let header_rest = &mut self.header[self.header_len..];
+        let len = header_rest.len().min(buf.len());
+        header_rest[..len].copy_from_slice(&buf[..len]);
+        self.header_len += len;
+
+        if !cfg!(feature = "eof_check") {
+            return;
+        }
+        if buf.len() >= TAIL_LEN {
+            self.tail.copy_from_slice(&buf[buf.len() - TAIL_LEN..]);
+        } else {
+            self.tail.copy_within(buf.len().., 0);
+            self.tail[TAIL_LEN - buf.len()..].copy_from_slice(buf);
+        }
+        self.tail_len = (self.tail_len + buf.len()).min(TAIL_LEN);
+    }
+    fn check(&self) -> Result<(), InvalidPdfOutput> {
+        if self.header != *PDF_HEADER {
+            return Err(InvalidPdfOutput::MissingHeader);
+        }
+        let tail = &self.tail[TAIL_LEN - self.tail_len..];
+        if cfg!(feature = "eof_check") && !tail.windows(PDF_EOF.len()).any(|w| w == PDF_EOF) {
+            return Err(InvalidPdfOutput::MissingEof);
+        }
+        Ok(())
+    }
+}
+
+/// A write builder that checks that the data written to the wrapped builder
+/// looks like a complete PDF, use it together with
+/// [`HtmlSink::validate_pdf`].
+///
+/// Nothing is buffered: the start of the data is remembered and, if the
+/// `eof_check` feature is enabled, so are the last 1024 bytes so that they
+/// can be searched for `%%EOF`.
+#[derive(Debug)]
+pub struct WriteBuilderPdfCheck<W> {
+    inner: W,
+    check: PdfCheck,
+}
+impl<W> WriteBuilderPdfCheck<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            check: PdfCheck::new(),
+        }
+    }
+    /// Return the wrapped builder if the data that was written looks like a
+    /// complete PDF.
+    pub fn finish(self) -> Result<W, InvalidPdfOutput> {
+        self.check.check()?;
+        Ok(self.inner)
+    }
+    /// Return the wrapped builder without checking the data.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+impl<'a, W> WriteBuilderLifetime<'a> for WriteBuilderPdfCheck<W>
+where
+    W: WriteBuilderLifetime<'a>,
+{
+    type Writer = PdfCheckWriter<'a, W::Writer>;
+}
+impl<W> WriteBuilder for WriteBuilderPdfCheck<W>
+where
+    W: WriteBuilder,
+{
+    fn get_writer(&mut self) -> io::Result<<Self as WriteBuilderLifetime<'_>>::Writer> {
+        Ok(PdfCheckWriter {
+            inner: self.inner.get_writer()?,
+            check: &mut self.check,
+        })
+    }
+}
+
+/// The writer returned by [`WriteBuilderPdfCheck`].
+pub struct PdfCheckWriter<'a, W> {
+    inner: W,
+    check: &'a mut PdfCheck,
+}
+impl<W: Write> Write for PdfCheckWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.check.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Used by [`HtmlSink::validate_pdf`] to check the PDF when the conversion
+/// completes.
+pub struct HtmlSinkValidatedPdf<S> {
+    inner: S,
+}
+impl<S> HtmlSinkValidatedPdf<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self { inner }
+    }
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+impl<S, W, E> HtmlSink<W, E> for HtmlSinkValidatedPdf<S>
+where
+    S: HtmlSink<WriteBuilderPdfCheck<W>, E>,
+    E: From<InvalidPdfOutput>,
+{
+    fn complete(self) -> Result<W, E>
+    where
+        Self: Sized,
+    {
+        self.complete_with_diagnostics().map(|(writer, _)| writer)
+    }
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), E>
+    where
+        Self: Sized,
+    {
+        let (writer, diagnostics) =
+            <S as HtmlSink<WriteBuilderPdfCheck<W>, E>>::complete_with_diagnostics(self.inner)?;
+        Ok((writer.finish()?, diagnostics))
+    }
+}
+crate::forward_write!(impl[S: Write] for HtmlSinkValidatedPdf<S>, |this| &mut this.inner);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WriteBuilderSimple;
+
+    #[test]
+    fn complete_pdf_is_accepted() {
+        let mut output = WriteBuilderPdfCheck::new(WriteBuilderSimple(Vec::new()));
+        for chunk in [&b"%PD"[..], b"F-1.7\n", &[b' '; 2000], b"%%EOF\n"] {
+            output.get_writer().unwrap().write_all(chunk).unwrap();
+        }
+        assert_eq!(output.finish().unwrap().0.len(), 2015);
+    }
+
+    #[test]
+    fn other_data_is_rejected() {
+        let mut output = WriteBuilderPdfCheck::new(WriteBuilderSimple(Vec::new()));
+        output.get_writer().unwrap().write_all(b"<html>").unwrap();
+        assert_eq!(output.finish().err(), Some(InvalidPdfOutput::MissingHeader));
+    }
+
+    #[cfg(feature = "eof_check")]
+    #[test]
+    fn truncated_pdf_fails_the_conversion() {
+        use crate::{testing::MockConverter, HtmlToPdfConverter, PdfScope};
+
+        let mut html_sink = MockConverter::new()
+            .start(
+                PdfScope::owned(),
+                WriteBuilderPdfCheck::new(WriteBuilderSimple(Vec::new())),
+            )
+            .unwrap()
+            .validate_pdf();
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+
+        let error = html_sink.complete().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.get_ref().unwrap().downcast_ref(),
+            Some(&InvalidPdfOutput::MissingEof)
+        );
+    }
+}
@@ -6,18 +6,61 @@
 std::compile_error!("The `html_to_pdf_adapter_chromiumoxide` crate requires either the `tokio-runtime` or `async-std-runtime` feature to be enabled.");
 
 use bytes::Bytes;
-pub use chromiumoxide::{cdp::browser_protocol::page::PrintToPdfParams, error::CdpError as Error};
-use chromiumoxide::{Browser, BrowserConfig};
-use html_to_pdf::{HtmlSink, HtmlToPdfConverter, WriteBuilder};
-use hyper::{Method, StatusCode};
+pub use chromiumoxide::{
+    cdp::browser_protocol::page::{PrintToPdfParams, PrintToPdfTransferMode},
+    error::CdpError as Error,
+};
+use chromiumoxide::{
+    cdp::{
+        browser_protocol::{
+            emulation::{
+                ClearDeviceMetricsOverrideParams, SetDeviceMetricsOverrideParams,
+                SetEmulatedMediaParams, SetScriptExecutionDisabledParams,
+            },
+            fetch::{
+                ContinueRequestParams, EnableParams as FetchEnableParams, EventRequestPaused,
+                FailRequestParams,
+            },
+            io::{CloseParams, ReadParams},
+            log::{EnableParams as LogEnableParams, EventEntryAdded, LogEntryLevel},
+            network::ErrorReason,
+            page::{CaptureScreenshotFormat, CaptureScreenshotParams, Viewport},
+        },
+        js_protocol::runtime::{ConsoleApiCalledType, EvaluateParams, EventConsoleApiCalled},
+    },
+    listeners::EventStream,
+    Browser, BrowserConfig, Page,
+};
+use html_to_pdf::{
+    mime::Mime, Assets, ConverterCapabilities, Diagnostic, HtmlSink, HtmlToPdfConverter,
+    HtmlToPdfError, HtmlTooLargeError, Margin, PageOptions, PageOrientation, PageRanges, Paper,
+    PdfAConformance, PdfPageCounter, TeeWriter, WithPageOptions, WriteBuilder, WriteBuilderSimple,
+};
+pub use hyper::header::{HeaderName, HeaderValue};
+use hyper::{
+    header::{CONTENT_SECURITY_POLICY, CONTENT_TYPE},
+    Method, StatusCode,
+};
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     convert::Infallible,
+    fmt, fs,
     future::Future,
     io::{self, Write},
     marker::PhantomData,
-    net::SocketAddr,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
+#[cfg(feature = "pdf_a")]
+use html_to_pdf::PdfANotSupportedError;
+
 #[cfg(feature = "async-std-runtime")]
 use async_std::{net::TcpListener, stream::StreamExt as _};
 #[cfg(all(feature = "tokio-runtime", not(feature = "async-std-runtime")))]
@@ -56,7 +99,130 @@ where
     }
 }
 
-async fn simple_http_server<T>(listener: TcpListener, content: Bytes) -> Result<T, Error> {
+/// Wait for a future to complete or return `None` if `duration` passes first.
+async fn timeout<F>(duration: Duration, fut: F) -> Option<F::Output>
+where
+    F: Future,
+{
+    #[cfg(feature = "async-std-runtime")]
+    {
+        async_std::future::timeout(duration, fut).await.ok()
+    }
+    #[cfg(all(feature = "tokio-runtime", not(feature = "async-std-runtime")))]
+    {
+        tokio::time::timeout(duration, fut).await.ok()
+    }
+}
+
+/// Message of the error that is returned if the browser exits before the
+/// conversion is done, see [`is_browser_terminated`].
+const BROWSER_TERMINATED: &str = "the browser terminated before the PDF was generated";
+
+/// Check if a conversion failed because the browser exited before it was done,
+/// for example because it crashed. Retrying the conversion will launch a new
+/// browser.
+pub fn is_browser_terminated(error: &Error) -> bool {
+    matches!(error, Error::ChromeMessage(message) if message == BROWSER_TERMINATED)
+}
+
+/// Run the browser's background tasks. The handler stream ends when the
+/// connection to the browser is closed, that is an error unless `done` was set
+/// first since the conversion would otherwise never finish.
+async fn run_handler<H>(mut handler: H, done: &AtomicBool) -> Result<(), Error>
+where
+    H: futures_core::Stream<Item = Result<(), Error>> + Unpin,
+{
+    loop {
+        match handler.next().await {
+            Some(Ok(())) => {}
+            Some(Err(e)) => break Err(e),
+            None if done.load(Ordering::Acquire) => break Ok(()),
+            None => break Err(Error::msg(BROWSER_TERMINATED)),
+        }
+    }
+}
+
+/// Accepts TLS connections for the local server. Without the `tls` feature
+/// this is uninhabited so the server only speaks plain HTTP.
+#[cfg(feature = "tls")]
+type TlsAcceptor = tokio_rustls::TlsAcceptor;
+#[cfg(not(feature = "tls"))]
+type TlsAcceptor = Infallible;
+
+/// Generate an ephemeral self-signed certificate for `localhost` and
+/// `127.0.0.1`.
+///
+/// Returns an acceptor for the certificate together with the base64 encoded
+/// SHA-256 hash of its public key. The hash is given to Chrome using
+/// `--ignore-certificate-errors-spki-list` so that only this certificate is
+/// trusted, certificate errors for any other https resource that the page
+/// loads are still reported.
+#[cfg(feature = "tls")]
+fn self_signed_tls_acceptor() -> Result<(TlsAcceptor, String), Error> {
+    use base64::Engine as _;
+    use sha2::{Digest as _, Sha256};
+    use tokio_rustls::rustls::{
+        pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+        ServerConfig,
+    };
+
+    let rcgen::CertifiedKey { cert, key_pair } =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_owned(), "127.0.0.1".to_owned()])
+            .map_err(|e| Error::msg(format!("failed to generate TLS certificate: {e}")))?;
+    let spki_hash =
+        base64::engine::general_purpose::STANDARD.encode(Sha256::digest(key_pair.public_key_der()));
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![cert.der().clone()],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der())),
+        )
+        .map_err(|e| Error::msg(format!("failed to configure TLS server: {e}")))?;
+
+    Ok((TlsAcceptor::from(Arc::new(config)), spki_hash))
+}
+
+/// The HTML and response headers that [`simple_http_server`] serves.
+struct ServedPage {
+    content: Bytes,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    /// Served at their path, keys don't start with `/`.
+    assets: HashMap<String, (Bytes, HeaderValue)>,
+}
+impl ServedPage {
+    fn new(
+        content: Bytes,
+        headers: Vec<(HeaderName, HeaderValue)>,
+        assets: &Assets,
+    ) -> Result<Self, Error> {
+        let assets = assets
+            .iter()
+            .map(|(key, (data, content_type))| {
+                let path = html_to_pdf::asset_path(key).map_err(|e| Error::msg(e.to_string()))?;
+                let content_type = HeaderValue::from_str(content_type.as_ref())
+                    .map_err(|e| Error::msg(e.to_string()))?;
+                Ok((path.to_owned(), (data.clone(), content_type)))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Self {
+            content,
+            headers,
+            assets,
+        })
+    }
+}
+
+/// Only allow the page to load resources from the local server, which only
+/// serves the page and its assets.
+const OFFLINE_CONTENT_SECURITY_POLICY: &str =
+    "default-src 'self' 'unsafe-inline' 'unsafe-eval' data: blob:";
+
+async fn simple_http_server<T>(
+    listener: TcpListener,
+    page: Arc<ServedPage>,
+    tls: Option<TlsAcceptor>,
+) -> Result<T, Error> {
     use http_body_util::{Either, Empty, Full};
     use hyper::service::service_fn;
     use hyper::{Request, Response};
@@ -65,114 +231,1334 @@ async fn simple_http_server<T>(listener: TcpListener, content: Bytes) -> Result<
 
     async fn handle_request(
         req: Request<impl hyper::body::Body>,
-        content: Bytes,
+        page: Arc<ServedPage>,
     ) -> Result<Response<Either<Full<Bytes>, Empty<Bytes>>>, Infallible> {
-        Ok(if Method::GET != req.method() {
+        let not_found = || {
             Response::builder()
                 .status(StatusCode::NOT_FOUND)
                 .body(Either::Right(Empty::new()))
                 .unwrap()
-        } else {
-            Response::builder()
-                .header("Content-Type", "text/html")
-                .body(Either::Left(Full::new(content.clone())))
-                .unwrap()
+        };
+        if Method::GET != req.method() {
+            return Ok(not_found());
+        }
+        Ok(match req.uri().path() {
+            "/" => {
+                let mut response = Response::builder();
+                for (name, value) in &page.headers {
+                    response = response.header(name, value);
+                }
+                response
+                    .body(Either::Left(Full::new(page.content.clone())))
+                    .unwrap()
+            }
+            path => match page.assets.get(path.strip_prefix('/').unwrap_or(path)) {
+                Some((data, content_type)) => Response::builder()
+                    .header(CONTENT_TYPE, content_type)
+                    .body(Either::Left(Full::new(data.clone())))
+                    .unwrap(),
+                None => not_found(),
+            },
         })
     }
 
+    async fn serve_connection<I>(io: I, page: Arc<ServedPage>)
+    where
+        I: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+    {
+        // Handle the connection from the client using HTTP1 and pass any
+        // HTTP requests received on that connection to the `hello` function
+        if let Err(_err) = auto::Builder::new(TokioExecutor::new())
+            // .timer(TokioTimer::new())
+            .serve_connection(
+                io,
+                service_fn({
+                    move |req| {
+                        let page = page.clone();
+                        handle_request(req, page)
+                    }
+                }),
+            )
+            .await
+        {
+            // TODO: handle error
+        }
+    }
+
     loop {
         // When an incoming TCP connection is received grab a TCP stream for
         // client<->server communication.
         let (tcp, _) = listener.accept().await?;
-        // Use an adapter to access something implementing `tokio::io` traits as if they implement
-        // `hyper::rt` IO traits.
-        let io = TokioIo::new(tcp);
 
         // Spin up a new task in Tokio so we can continue to listen for new TCP connection on the
         // current task without waiting for the processing of the HTTP1 connection we just received
         // to finish
-        let content = content.clone();
+        let page = page.clone();
+        let tls = tls.clone();
         tokio::task::spawn(async move {
-            // Handle the connection from the client using HTTP1 and pass any
-            // HTTP requests received on that connection to the `hello` function
-            if let Err(_err) = auto::Builder::new(TokioExecutor::new())
-                // .timer(TokioTimer::new())
-                .serve_connection(
-                    io,
-                    service_fn({
-                        move |req| {
-                            let content = content.clone();
-                            handle_request(req, content)
-                        }
-                    }),
-                )
-                .await
-            {
-                // TODO: handle error
+            // Use an adapter to access something implementing `tokio::io` traits as if they implement
+            // `hyper::rt` IO traits.
+            match tls {
+                #[cfg(feature = "tls")]
+                Some(tls) => {
+                    if let Ok(stream) = tls.accept(tcp).await {
+                        serve_connection(TokioIo::new(stream), page).await
+                    }
+                }
+                #[cfg(not(feature = "tls"))]
+                Some(never) => match never {},
+                None => serve_connection(TokioIo::new(tcp), page).await,
             }
         });
     }
 }
 
 pub fn html_to_pdf(html: Bytes, options: PrintToPdfParams) -> Result<Vec<u8>, Error> {
-    block_on(async {
-        // Inspired by example at:
-        // https://github.com/mattsse/chromiumoxide/blob/bd62ee35df3fad70d0b72e25faeed793bdab597c/examples/pdf.rs
-        let (mut browser, mut handler) =
-            Browser::launch(BrowserConfig::builder().build().map_err(Error::msg)?).await?;
-
-        // port 0 to bind to any available port
-        let addr: SocketAddr = ([127, 0, 0, 1], 0).into();
-        let listener = TcpListener::bind(addr).await?;
-        let port = listener.local_addr()?.port();
-
-        // Close server when chromiumoxide is done...
-        let res: Result<(Infallible, Infallible), Result<Vec<u8>, Error>> =
-            futures_util::future::try_join(
+    ChromiumoxideConverter::builder()
+        .pdf_options(options)
+        .build()
+        .convert(html)
+}
+
+/// Print an existing web page, see [`ChromiumoxideConverter::from_url`].
+pub fn url_to_pdf(url: impl Into<String>, options: PrintToPdfParams) -> Result<Vec<u8>, Error> {
+    ChromiumoxideConverter::builder()
+        .url(url)
+        .pdf_options(options)
+        .build()
+        .convert(Bytes::new())
+}
+
+/// Converts HTML to PDF by printing it with a headless Chrome browser.
+///
+/// Use [`ChromiumoxideConverter::builder`] to configure the conversion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChromiumoxideConverter {
+    pdf_options: PrintToPdfParams,
+    #[cfg(feature = "tls")]
+    https: bool,
+    wait_for_fonts: bool,
+    wait_for_fonts_timeout: Duration,
+    max_html_size: usize,
+    ensure_utf8_meta: bool,
+    base_url: Option<String>,
+    response_headers: Vec<(HeaderName, HeaderValue)>,
+    assets: Assets,
+    url: Option<String>,
+    sanitize_untrusted: bool,
+    #[cfg(feature = "bookmarks")]
+    generate_bookmarks: bool,
+    user_data_dir: Option<PathBuf>,
+    lang: Option<String>,
+    bind_address: SocketAddr,
+    #[cfg(feature = "pdf_a")]
+    pdf_a: Option<PdfAConformance>,
+    #[cfg(feature = "pdf_a")]
+    icc_profile: Option<PathBuf>,
+}
+impl ChromiumoxideConverter {
+    /// The default value for
+    /// [`ChromiumoxideConverterBuilder::max_html_size`], 512 MiB.
+    pub const DEFAULT_MAX_HTML_SIZE: usize = 512 * 1024 * 1024;
+
+    /// Configure a converter. The builder starts out with the same options as
+    /// [`ChromiumoxideConverter::default`].
+    pub fn builder() -> ChromiumoxideConverterBuilder {
+        ChromiumoxideConverterBuilder {
+            converter: Self::default(),
+        }
+    }
+    /// `false` since the PDF is only written to the output once Chrome has
+    /// printed the whole page in [`HtmlSink::complete`]. Chrome returns the
+    /// PDF all at once unless
+    /// [`transfer_mode`](ChromiumoxideConverterBuilder::transfer_mode) is set
+    /// to [`PrintToPdfTransferMode::ReturnAsStream`], so very large documents
+    /// are by default held in memory until the conversion has finished.
+    pub const STREAMS_PDF: bool = false;
+
+    /// The smallest value for [`ChromiumoxideConverterBuilder::scale`].
+    pub const MIN_SCALE: f64 = 0.1;
+    /// The largest value for [`ChromiumoxideConverterBuilder::scale`].
+    pub const MAX_SCALE: f64 = 2.0;
+
+    /// The most bytes that are requested from Chrome at a time when the PDF is
+    /// returned as a stream, 1 MiB.
+    pub const STREAM_CHUNK_SIZE: usize = 1024 * 1024;
+
+    /// The default value for [`ChromiumoxideConverterBuilder::bind_address`],
+    /// `127.0.0.1` with a port that the operating system chooses.
+    pub const DEFAULT_BIND_ADDRESS: SocketAddr =
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    /// Print an existing web page instead of HTML that is written to the
+    /// [`HtmlSink`], see [`ChromiumoxideConverterBuilder::url`].
+    pub fn from_url(url: impl Into<String>) -> Self {
+        Self::builder().url(url).build()
+    }
+
+    /// The options that are used when printing the page.
+    pub fn pdf_options(&self) -> &PrintToPdfParams {
+        &self.pdf_options
+    }
+    /// The web page that is printed, if the converter doesn't print HTML
+    /// written to the [`HtmlSink`].
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+}
+/// Note: unlike [`PrintToPdfParams::default`] this enables
+/// [`PrintToPdfParams::print_background`].
+impl Default for ChromiumoxideConverter {
+    fn default() -> Self {
+        Self {
+            pdf_options: PrintToPdfParams {
+                print_background: Some(true),
+                ..PrintToPdfParams::default()
+            },
+            #[cfg(feature = "tls")]
+            https: false,
+            wait_for_fonts: false,
+            wait_for_fonts_timeout: Duration::from_secs(10),
+            max_html_size: Self::DEFAULT_MAX_HTML_SIZE,
+            ensure_utf8_meta: false,
+            base_url: None,
+            response_headers: Vec::new(),
+            assets: Assets::new(),
+            url: None,
+            sanitize_untrusted: false,
+            #[cfg(feature = "bookmarks")]
+            generate_bookmarks: false,
+            user_data_dir: None,
+            lang: None,
+            bind_address: ChromiumoxideConverter::DEFAULT_BIND_ADDRESS,
+            #[cfg(feature = "pdf_a")]
+            pdf_a: None,
+            #[cfg(feature = "pdf_a")]
+            icc_profile: None,
+        }
+    }
+}
+
+/// Builds a [`ChromiumoxideConverter`], see
+/// [`ChromiumoxideConverter::builder`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChromiumoxideConverterBuilder {
+    converter: ChromiumoxideConverter,
+}
+impl ChromiumoxideConverterBuilder {
+    /// Replace all options that are used when printing the page.
+    ///
+    /// Note that this also replaces
+    /// [`print_background`](Self::print_background).
+    pub fn pdf_options(mut self, options: PrintToPdfParams) -> Self {
+        self.converter.pdf_options = options;
+        self
+    }
+    /// Set a single option of [`PrintToPdfParams`] by its DevTools Protocol
+    /// name, for example `scale` or `paperWidth`. The value is parsed as JSON
+    /// and used as a string if that fails, so both `true` and
+    /// `<span class="title"></span>` work as values.
+    ///
+    /// Returns an error and leaves the options unchanged if the name isn't a
+    /// known option or if the value has the wrong type.
+    pub fn set_pdf_option(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        let value = serde_json::from_str(value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.to_owned()));
+        let mut options = serde_json::to_value(&self.converter.pdf_options)?;
+        options
+            .as_object_mut()
+            .ok_or_else(|| Error::msg("PDF options weren't serialized as an object"))?
+            .insert(name.to_owned(), value);
+        let options: PrintToPdfParams = serde_json::from_value(options)?;
+        // Unknown names are silently ignored when deserializing:
+        if serde_json::to_value(&options)?.get(name).is_none() {
+            return Err(Error::msg(format!("unknown PDF option \"{name}\"")));
+        }
+        self.converter.pdf_options = options;
+        Ok(())
+    }
+    /// Print background colors and images. Chrome omits these by default when
+    /// printing, but most users of an HTML to PDF tool expect them so the
+    /// [`Default`] implementation of the converter enables it.
+    pub fn print_background(mut self, value: bool) -> Self {
+        self.converter.pdf_options.print_background = Some(value);
+        self
+    }
+    /// Wait until all fonts declared by the page have loaded before printing
+    /// it, otherwise slow loading web fonts might be replaced by fallback
+    /// glyphs. If the fonts haven't loaded within
+    /// [`wait_for_fonts_timeout`](Self::wait_for_fonts_timeout) then the page
+    /// is printed anyway and a warning [`Diagnostic`] is returned.
+    ///
+    /// Disabled by default.
+    pub fn wait_for_fonts(mut self, value: bool) -> Self {
+        self.converter.wait_for_fonts = value;
+        self
+    }
+    /// The longest time to wait for fonts to load if
+    /// [`wait_for_fonts`](Self::wait_for_fonts) is enabled. Defaults to 10
+    /// seconds.
+    pub fn wait_for_fonts_timeout(mut self, timeout: Duration) -> Self {
+        self.converter.wait_for_fonts_timeout = timeout;
+        self
+    }
+    /// The HTML is buffered in memory before it is sent to Chrome. Writing more
+    /// than this many bytes returns an error that wraps an
+    /// [`HtmlTooLargeError`] instead of growing the buffer, which protects
+    /// servers from huge inputs. Defaults to
+    /// [`ChromiumoxideConverter::DEFAULT_MAX_HTML_SIZE`].
+    pub fn max_html_size(mut self, bytes: usize) -> Self {
+        self.converter.max_html_size = bytes;
+        self
+    }
+    /// Insert `<meta charset="UTF-8">` at the start of the HTML's `<head>` if
+    /// the document doesn't declare its encoding, adding a `<head>` if it
+    /// doesn't have one. The HTML is always served with a UTF-8
+    /// `Content-Type` but the declaration makes the encoding part of the
+    /// document, so that scripts that inspect or copy it see the right
+    /// encoding as well.
+    ///
+    /// Disabled by default. Only affects HTML that is written to the
+    /// [`HtmlSink`], not pages that are loaded from a
+    /// [`url`](Self::url).
+    pub fn ensure_utf8_meta(mut self, value: bool) -> Self {
+        self.converter.ensure_utf8_meta = value;
+        self
+    }
+    /// Resolve relative URLs in the HTML against `url` instead of the local
+    /// server that serves it, for example `https://example.com/docs/`, so that
+    /// links in the PDF point to the real site. A `<base href>` tag is inserted
+    /// at the start of the HTML's `<head>`, which takes precedence over any
+    /// `<base>` tag that the document already has.
+    ///
+    /// Relative resources like images and stylesheets are then also loaded
+    /// from `url` instead of from [`assets`](Self::assets), and they are
+    /// blocked if the HTML is [sanitized](Self::sanitize_untrusted) since they
+    /// aren't on the page's origin. Only affects HTML that is written to the
+    /// [`HtmlSink`], not pages that are loaded from a [`url`](Self::url).
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.converter.base_url = Some(url.into());
+        self
+    }
+    /// How Chrome sends the PDF back to us:
+    ///
+    /// - [`PrintToPdfTransferMode::ReturnAsBase64`] is the default. The whole
+    ///   PDF is sent in a single base64 encoded message, so it is held in
+    ///   memory several times over while it is decoded.
+    /// - [`PrintToPdfTransferMode::ReturnAsStream`] reads the PDF in chunks of
+    ///   at most [`ChromiumoxideConverter::STREAM_CHUNK_SIZE`] bytes and
+    ///   writes each chunk to the output before reading the next one. Prefer
+    ///   this for large documents.
+    ///
+    /// Streaming uses the experimental `transferMode` parameter of
+    /// `Page.printToPDF` which requires Chrome 73 or newer. Older versions
+    /// ignore it and return the PDF as base64, which is then used instead.
+    pub fn transfer_mode(mut self, mode: PrintToPdfTransferMode) -> Self {
+        self.converter.pdf_options.transfer_mode = Some(mode);
+        self
+    }
+    /// Only print the selected pages, in the order of the document. Chrome
+    /// ignores selected pages past the end of the document but fails the
+    /// conversion if none of the selected pages exist.
+    pub fn page_ranges(mut self, pages: &PageRanges) -> Self {
+        self.converter.pdf_options.page_ranges = Some(pages.to_string());
+        self
+    }
+    /// Use the page size declared by CSS `@page { size: ... }` rules instead of
+    /// the paper size from the PDF options. Pages that don't declare a size
+    /// still use the configured paper size.
+    ///
+    /// Disabled by default, just like in Chrome.
+    pub fn prefer_css_page_size(mut self, value: bool) -> Self {
+        self.converter.pdf_options.prefer_css_page_size = Some(value);
+        self
+    }
+    /// Store an outline (bookmarks) in the PDF that Chrome builds from the
+    /// page's `<h1>` to `<h6>` headings, so that
+    /// [`HtmlToPdfConverter::convert_with_outline`] can read it back with the
+    /// pages that the headings were placed on.
+    ///
+    /// Disabled by default. Requires Chrome 118 or newer, older versions
+    /// ignore it.
+    pub fn document_outline(mut self, value: bool) -> Self {
+        self.converter.pdf_options.generate_document_outline = Some(value);
+        self
+    }
+    /// Add an outline (bookmarks) to the PDF for the page's `<h1>` to `<h6>`
+    /// headings, which works with all Chrome versions unlike
+    /// [`document_outline`](Self::document_outline). Replaces any outline
+    /// that Chrome stored in the PDF.
+    ///
+    /// After printing, the page is laid out like it is printed to find where
+    /// each heading is, and the PDF is edited to add the outline, so it is
+    /// held in memory even when it is
+    /// [streamed](Self::transfer_mode) from Chrome. Headings are assumed to be
+    /// on the page that their position falls on, so forced page breaks (for
+    /// example `break-before: page`) before a heading can make it link to an
+    /// earlier page than it was printed on.
+    ///
+    /// Disabled by default.
+    #[cfg(feature = "bookmarks")]
+    pub fn generate_bookmarks(mut self, value: bool) -> Self {
+        self.converter.generate_bookmarks = value;
+        self
+    }
+    /// The paper to print on. Chrome uses US Letter by default.
+    pub fn paper(mut self, paper: Paper) -> Self {
+        let (width, height) = paper_size_in_inches(paper);
+        self.converter.pdf_options.paper_width = Some(width);
+        self.converter.pdf_options.paper_height = Some(height);
+        self
+    }
+    /// Turn the paper to landscape. Portrait by default.
+    pub fn orientation(mut self, orientation: PageOrientation) -> Self {
+        self.converter.pdf_options.landscape = Some(orientation == PageOrientation::Landscape);
+        self
+    }
+    /// Space between the edges of the paper and the content. Chrome uses
+    /// 1cm (~0.4 inches) on all sides by default.
+    pub fn margin(mut self, margin: Margin) -> Self {
+        let options = &mut self.converter.pdf_options;
+        options.margin_top = Some(margin.top.inches());
+        options.margin_right = Some(margin.right.inches());
+        options.margin_bottom = Some(margin.bottom.inches());
+        options.margin_left = Some(margin.left.inches());
+        self
+    }
+    /// Scale the printed content, for example `0.5` to fit twice as much
+    /// content on each page in both directions. Unlike zooming the page this
+    /// doesn't change the viewport, so the page is laid out as if it was
+    /// wider and then shrunk.
+    ///
+    /// Chrome fails to print with values outside of
+    /// [`MIN_SCALE`](ChromiumoxideConverter::MIN_SCALE) and
+    /// [`MAX_SCALE`](ChromiumoxideConverter::MAX_SCALE), so such values are
+    /// clamped to that range and `NaN` is treated as `1.0`.
+    pub fn scale(mut self, scale: f64) -> Self {
+        let scale = if scale.is_nan() {
+            1.0
+        } else {
+            scale.clamp(
+                ChromiumoxideConverter::MIN_SCALE,
+                ChromiumoxideConverter::MAX_SCALE,
+            )
+        };
+        self.converter.pdf_options.scale = Some(scale);
+        self
+    }
+    /// Add a header to the response that serves the HTML to Chrome, for
+    /// example a `Content-Security-Policy` that the page relies on.
+    ///
+    /// The `Content-Type` defaults to `text/html; charset=utf-8` and can be
+    /// overridden with this method. If the specified content type doesn't
+    /// include a charset then `; charset=utf-8` is added since otherwise
+    /// Chrome might decode the page with the wrong encoding.
+    pub fn response_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.converter.response_headers.push((name, value));
+        self
+    }
+    /// Serve the HTML over `https://localhost` instead of plain http. This is
+    /// needed by pages that rely on secure contexts, for example secure
+    /// cookies or mixed-content rules.
+    ///
+    /// An ephemeral self-signed certificate is generated for each conversion.
+    /// Chrome is told to trust only that certificate's public key and the
+    /// server only listens on `127.0.0.1`, so this doesn't weaken certificate
+    /// validation for other sites that the page loads resources from.
+    ///
+    /// This has no effect when a [`url`](Self::url) is printed since there is
+    /// no local server then, the page is loaded with the URL's own scheme.
+    #[cfg(feature = "tls")]
+    pub fn https(mut self, value: bool) -> Self {
+        self.converter.https = value;
+        self
+    }
+    /// Replace all assets that the local server answers requests for, see
+    /// [`Assets`] for how their paths are mapped to URLs.
+    ///
+    /// If there are any assets then the page is served with a
+    /// `Content-Security-Policy` that only allows it to load resources from
+    /// the local server (and `data:` or `blob:` URLs), so nothing is fetched
+    /// over the network. Requests for paths that aren't in the map get a 404
+    /// response.
+    pub fn assets(mut self, assets: Assets) -> Self {
+        self.converter.assets = assets;
+        self
+    }
+    /// Add a single asset, see [`assets`](Self::assets).
+    pub fn asset(
+        mut self,
+        path: impl Into<String>,
+        data: impl Into<Bytes>,
+        content_type: Mime,
+    ) -> Self {
+        self.converter
+            .assets
+            .insert(path.into(), (data.into(), content_type));
+        self
+    }
+    /// Navigate directly to `url` and print that page instead of serving the
+    /// HTML from a local server. The page keeps its real origin, so relative
+    /// resources and cookies work as they would in a normal browser.
+    ///
+    /// The HTML sink is unused in this mode: anything written to it is
+    /// ignored, so it can be completed right away. The options that only
+    /// affect the local server, [`response_header`](Self::response_header),
+    /// [`assets`](Self::assets) and `https`, are ignored as well, while
+    /// options like [`wait_for_fonts`](Self::wait_for_fonts) still apply.
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.converter.url = Some(url.into());
+        self
+    }
+    /// Treat the HTML as untrusted: JavaScript is disabled and the page can't
+    /// load anything except from its own origin, which is the local server
+    /// unless a [`url`](Self::url) is printed.
+    ///
+    /// Requests are blocked by intercepting them in Chrome, and the local
+    /// server also sends the same `Content-Security-Policy` as when there are
+    /// [`assets`](Self::assets). Note that inline `<style>` and `data:` URLs
+    /// are still allowed, so untrusted HTML can still affect how the PDF looks.
+    pub fn sanitize_untrusted(mut self, value: bool) -> Self {
+        self.converter.sanitize_untrusted = value;
+        self
+    }
+    /// Keep Chrome's profile (cookies, storage and cache) in this folder so
+    /// that it is reused by later conversions, for example to cache resources
+    /// that a [`url`](Self::url) loads.
+    ///
+    /// By default every conversion launches Chrome with a new profile in a
+    /// temporary folder that is deleted afterwards, so conversions can't see
+    /// each other's cookies or cached data even if they run at the same time.
+    /// Keep that default when converting HTML from different users. Chrome
+    /// locks its profile, so conversions that share a folder can't run at the
+    /// same time.
+    pub fn user_data_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.converter.user_data_dir = Some(path.into());
+        self
+    }
+    /// Set the `lang` attribute of the page's `<html>` element before it is
+    /// printed, replacing the language that the HTML declared. Chrome stores
+    /// the page's language as the `/Lang` of tagged PDFs (see
+    /// `generate_tagged_pdf` in the [`pdf_options`](Self::pdf_options)), and
+    /// it also affects hyphenation and fonts. `lang` should be a language tag
+    /// like `en-US` or `fr`.
+    ///
+    /// Use `html_to_pdf::LanguageConverter` to set the language of untagged
+    /// PDFs.
+    pub fn lang(mut self, lang: impl Into<String>) -> Self {
+        self.converter.lang = Some(lang.into());
+        self
+    }
+    /// Declare that the PDF follows the PDF/A archival standard with the
+    /// specified conformance level. Chrome can't create PDF/A documents
+    /// itself, so after printing the PDF is edited to add the XMP metadata
+    /// and an output intent with the [`icc_profile`](Self::icc_profile),
+    /// which must be set, see `html_to_pdf::pdf::set_pdf_a_conformance`. The
+    /// PDF is then held in memory even when it is
+    /// [streamed](Self::transfer_mode) from Chrome.
+    ///
+    /// Chrome embeds the fonts that the page uses and doesn't encrypt the
+    /// PDF, as the standard requires, but the result isn't validated. Use the
+    /// dotnet-itext converter if documents must pass a PDF/A validator.
+    ///
+    /// PDF/A-1 doesn't allow transparency, which Chrome can't avoid, so
+    /// [`PdfAConformance::PdfA1B`] fails with an [`Error::Io`] that wraps a
+    /// [`PdfANotSupportedError`].
+    #[cfg(feature = "pdf_a")]
+    pub fn pdf_a(mut self, conformance: PdfAConformance) -> Self {
+        self.converter.pdf_a = Some(conformance);
+        self
+    }
+    /// Path to an ICC color profile that is embedded as the PDF's output
+    /// intent when [`pdf_a`](Self::pdf_a) is set. PDF/A requires this to make
+    /// colors device independent. Chrome prints in RGB, so this should be an
+    /// RGB profile, usually sRGB.
+    ///
+    /// On Windows a suitable profile can be found at
+    /// `C:\Windows\System32\spool\drivers\color\sRGB Color Space Profile.icm`.
+    #[cfg(feature = "pdf_a")]
+    pub fn icc_profile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.converter.icc_profile = Some(path.into());
+        self
+    }
+    /// The address that the local HTTP server which serves the HTML to Chrome
+    /// listens on. Use port `0` to let the operating system choose a free
+    /// port. Defaults to
+    /// [`ChromiumoxideConverter::DEFAULT_BIND_ADDRESS`].
+    ///
+    /// Sandboxes that don't allow listening on any port can instead print a
+    /// [`url`](Self::url), which doesn't start the server. If the server can't
+    /// be started the conversion fails with a [`ServerBindError`].
+    pub fn bind_address(mut self, address: SocketAddr) -> Self {
+        self.converter.bind_address = address;
+        self
+    }
+    pub fn build(self) -> ChromiumoxideConverter {
+        self.converter
+    }
+}
+
+/// Uses the page size, orientation and margins. The DPI is ignored since
+/// Chrome always lays out the page with 96 CSS pixels per inch, use
+/// [`scale`](Self::scale) to change the size of the content instead.
+impl WithPageOptions for ChromiumoxideConverterBuilder {
+    fn with_page_options(mut self, options: PageOptions) -> Self {
+        if let Some(paper) = options.page_size {
+            self = self.paper(paper);
+        }
+        if let Some(orientation) = options.orientation {
+            self = self.orientation(orientation);
+        }
+        if let Some(margin) = options.margins {
+            self = self.margin(margin);
+        }
+        self
+    }
+}
+/// The same as [`ChromiumoxideConverterBuilder`]'s implementation.
+impl WithPageOptions for ChromiumoxideConverter {
+    fn with_page_options(self, options: PageOptions) -> Self {
+        ChromiumoxideConverterBuilder { converter: self }
+            .with_page_options(options)
+            .build()
+    }
+}
+
+/// The width and height of `paper` in inches, which is the unit that Chrome
+/// expects in [`PrintToPdfParams`].
+fn paper_size_in_inches(paper: Paper) -> (f64, f64) {
+    let (width, height) = paper.size();
+    (width.inches(), height.inches())
+}
+
+impl ChromiumoxideConverter {
+    /// Convert the HTML into one PDF per paper size. The page is only loaded
+    /// once and then printed once for each size, which is faster than
+    /// converting it multiple times.
+    ///
+    /// Note that Chrome lays out the page again for each size, so page breaks
+    /// and anything that depends on the page width can differ between the
+    /// generated PDFs.
+    pub fn print_paper_sizes(
+        self,
+        html: impl Into<Bytes>,
+        paper_sizes: &[Paper],
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        if paper_sizes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut pdfs = vec![Vec::new(); paper_sizes.len()];
+        let print_options = paper_sizes
+            .iter()
+            .zip(&mut pdfs)
+            .map(|(&paper, pdf)| {
+                let (width, height) = paper_size_in_inches(paper);
+                let options = PrintToPdfParams {
+                    paper_width: Some(width),
+                    paper_height: Some(height),
+                    ..self.pdf_options.clone()
+                };
+                (options, pdf as &mut dyn Write)
+            })
+            .collect();
+        self.print(html.into(), print_options, &mut Vec::new(), None)?;
+        Ok(pdfs)
+    }
+
+    fn convert(self, html: Bytes) -> Result<Vec<u8>, Error> {
+        let mut pdf = Vec::new();
+        let options = self.pdf_options.clone();
+        self.print(
+            html,
+            vec![(options, &mut pdf as &mut dyn Write)],
+            &mut Vec::new(),
+            None,
+        )?;
+        Ok(pdf)
+    }
+
+    /// The headers to serve the HTML with, ensures that there is a content
+    /// type with a charset.
+    fn response_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>, Error> {
+        let mut headers = self.response_headers.clone();
+        match headers.iter_mut().find(|(name, _)| *name == CONTENT_TYPE) {
+            Some((_, content_type)) => {
+                let has_charset = content_type
+                    .as_bytes()
+                    .to_ascii_lowercase()
+                    .windows(b"charset=".len())
+                    .any(|window| window == b"charset=");
+                if !has_charset {
+                    let mut value = content_type.as_bytes().to_vec();
+                    value.extend_from_slice(b"; charset=utf-8");
+                    *content_type =
+                        HeaderValue::from_bytes(&value).map_err(|e| Error::msg(e.to_string()))?;
+                }
+            }
+            None => headers.push((
+                CONTENT_TYPE,
+                HeaderValue::from_static("text/html; charset=utf-8"),
+            )),
+        }
+        if !self.assets.is_empty() || self.sanitize_untrusted {
+            headers.push((
+                CONTENT_SECURITY_POLICY,
+                HeaderValue::from_static(OFFLINE_CONTENT_SECURITY_POLICY),
+            ));
+        }
+        Ok(headers)
+    }
+
+    /// Load the HTML in a new page and then print it once for each of the
+    /// specified options, writing each PDF to the writer next to its options.
+    ///
+    /// Warnings and errors that the page logged are added to `diagnostics`.
+    /// If `thumbnails` is specified then the pages of the first PDF are also
+    /// rendered as PNG images with that DPI, see
+    /// [`ChromiumoxideHtmlSink::complete_with_thumbnails`].
+    fn print(
+        self,
+        html: Bytes,
+        print_options: Vec<(PrintToPdfParams, &mut dyn Write)>,
+        diagnostics: &mut Vec<Diagnostic>,
+        mut thumbnails: Option<(f64, &mut Vec<Vec<u8>>)>,
+    ) -> Result<(), Error> {
+        let page = Arc::new(ServedPage::new(
+            html,
+            self.response_headers()?,
+            &self.assets,
+        )?);
+        // Assets might be loaded after the page, for example fonts that are
+        // only used when printing:
+        let keep_serving = !self.assets.is_empty();
+        #[cfg(feature = "tls")]
+        let tls = if self.https && self.url.is_none() {
+            Some(self_signed_tls_acceptor()?)
+        } else {
+            None
+        };
+        #[cfg(not(feature = "tls"))]
+        let tls: Option<(TlsAcceptor, String)> = None;
+
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        let (tls, browser_config) = match tls {
+            Some((acceptor, spki_hash)) => (
+                Some(acceptor),
+                BrowserConfig::builder()
+                    .arg(format!("--ignore-certificate-errors-spki-list={spki_hash}")),
+            ),
+            None => (None, BrowserConfig::builder()),
+        };
+        // Deleted once the browser has exited:
+        let (browser_config, _profile) = match &self.user_data_dir {
+            Some(path) => (browser_config.user_data_dir(path), None),
+            None => {
+                let profile = ProfileDir::create()?;
+                (browser_config.user_data_dir(profile.path()), Some(profile))
+            }
+        };
+        let wait_for_fonts = self.wait_for_fonts.then_some(self.wait_for_fonts_timeout);
+        let url = self.url;
+        let sanitize_untrusted = self.sanitize_untrusted;
+        let bind_address = self.bind_address;
+        let lang = self.lang;
+        #[cfg(feature = "bookmarks")]
+        let generate_bookmarks = self.generate_bookmarks;
+        #[cfg(not(feature = "bookmarks"))]
+        let generate_bookmarks = false;
+        #[cfg(feature = "pdf_a")]
+        let pdf_a = match (self.pdf_a, &self.icc_profile) {
+            // PDF/A-1 doesn't allow transparency, which Chrome uses for
+            // example for CSS opacity and shadows:
+            (Some(conformance @ PdfAConformance::PdfA1B), _) => {
+                return Err(Error::Io(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    PdfANotSupportedError {
+                        engine: "chromiumoxide",
+                        conformance,
+                    },
+                )))
+            }
+            (Some(conformance), Some(icc_profile)) => Some((conformance, fs::read(icc_profile)?)),
+            (Some(conformance), None) => {
+                return Err(Error::msg(format!(
+                    "An ICC color profile must be specified in order to create a {conformance} document"
+                )))
+            }
+            (None, _) => None,
+        };
+        #[cfg(not(feature = "pdf_a"))]
+        let pdf_a: Option<(PdfAConformance, Vec<u8>)> = None;
+
+        block_on(async {
+            // Inspired by example at:
+            // https://github.com/mattsse/chromiumoxide/blob/bd62ee35df3fad70d0b72e25faeed793bdab597c/examples/pdf.rs
+            let (mut browser, handler) =
+                Browser::launch(browser_config.build().map_err(Error::msg)?).await?;
+            let done = &AtomicBool::new(false);
+
+            // Nothing to serve if the page is loaded from its own origin:
+            let listener = match url {
+                Some(_) => None,
+                None => Some(bind_server(bind_address).await?),
+            };
+            let local_addr = listener.as_ref().map(TcpListener::local_addr).transpose()?;
+
+            // Stop the server as soon as the page has loaded so that its port
+            // is released before the PDF is printed, unless it serves assets:
+            let (server, stop_server) = futures_util::future::abortable(async move {
+                match listener {
+                    Some(listener) => simple_http_server(listener, page, tls).await,
+                    None => futures_util::future::pending().await,
+                }
+            });
+            let url = match (url, local_addr) {
+                (Some(url), _) => {
+                    stop_server.abort();
+                    url
+                }
+                (None, Some(addr)) => server_url(scheme, addr),
+                (None, None) => unreachable!("the server is started if there is no url"),
+            };
+
+            let res: Result<((), Infallible), Result<(), Error>> = futures_util::future::try_join(
                 // Serve HTML on localhost:
-                async { simple_http_server(listener, html).await.map_err(Err) },
+                async {
+                    match server.await {
+                        Ok(Ok(never)) => match never {},
+                        Ok(Err(e)) => Err(Err(e)),
+                        // Stopped after the page was loaded:
+                        Err(futures_util::future::Aborted) => Ok(()),
+                    }
+                },
                 async {
                     // Exit early if the background tasks fails:
                     let res = futures_util::future::try_join(
                         // Run background tasks:
+                        run_handler(handler, done),
+                        // Load data from local HTTP server and convert it into a PDF:
                         async move {
-                            loop {
-                                match handler.next().await {
-                                    Some(Ok(())) => {}
-                                    Some(Err(e)) => break Err(e),
-                                    None => break Ok(()),
+                            let page = async {
+                                // Listen before navigating so that no messages are missed:
+                                let page = browser.new_page("about:blank").await?;
+                                let listeners = PageDiagnostics::listen(&page).await?;
+                                let mut blocker = None;
+                                if sanitize_untrusted {
+                                    page.execute(SetScriptExecutionDisabledParams::new(true))
+                                        .await?;
+                                    blocker =
+                                        Some(ExternalRequestBlocker::enable(&page, &url).await?);
                                 }
+                                while_blocking(blocker.as_mut(), page.goto(url)).await?;
+                                Ok::<_, Error>((page, listeners, blocker))
                             }
-                        },
-                        // Load data from local HTTP server and convert it into a PDF:
-                        async move {
-                            let page = browser
-                                .new_page(format!("http://localhost:{}/", port))
-                                .await?;
+                            .await;
+                            if !keep_serving {
+                                stop_server.abort();
+                            }
+                            let (page, mut listeners, mut blocker) = page?;
+
+                            // Assets and fonts might still be requested:
+                            while_blocking(blocker.as_mut(), async {
+                                if let Some(duration) = wait_for_fonts {
+                                    let fonts_ready = EvaluateParams::builder()
+                                        .expression("document.fonts.ready.then(() => true)")
+                                        .await_promise(true)
+                                        .return_by_value(true)
+                                        .build()
+                                        .map_err(Error::msg)?;
+                                    // Print the page anyway if the fonts take too long to load:
+                                    match timeout(duration, page.evaluate_expression(fonts_ready))
+                                        .await
+                                    {
+                                        Some(result) => {
+                                            result?;
+                                        }
+                                        None => diagnostics.push(Diagnostic::warning(format!(
+                                            "fonts didn't load within {duration:?}, \
+                                            fallback fonts might have been used"
+                                        ))),
+                                    }
+                                }
+                                if let Some(lang) = &lang {
+                                    let set_lang = EvaluateParams::builder()
+                                        .expression(format!(
+                                            "document.documentElement.lang = {}",
+                                            serde_json::Value::from(lang.as_str())
+                                        ))
+                                        .build()
+                                        .map_err(Error::msg)?;
+                                    page.evaluate_expression(set_lang).await?;
+                                }
 
-                            // save the page as pdf
-                            let data = page.pdf(options).await?;
+                                // save the page as pdf
+                                for (options, output) in print_options {
+                                    // The outline and PDF/A metadata are added after printing:
+                                    let edit = generate_bookmarks || pdf_a.is_some();
+                                    let mut unedited = Vec::new();
+                                    let target: &mut dyn Write =
+                                        if edit { &mut unedited } else { &mut *output };
+                                    let Some((dpi, pngs)) = thumbnails.take() else {
+                                        print_pdf(&page, options.clone(), target).await?;
+                                        if edit {
+                                            edit_pdf(
+                                                &page,
+                                                &options,
+                                                unedited,
+                                                generate_bookmarks,
+                                                pdf_a.as_ref(),
+                                                output,
+                                            )
+                                            .await?;
+                                        }
+                                        continue;
+                                    };
+                                    let mut counter = PdfPageCounter::new();
+                                    print_pdf(
+                                        &page,
+                                        options.clone(),
+                                        &mut TeeWriter(target, &mut counter),
+                                    )
+                                    .await?;
+                                    *pngs =
+                                        capture_thumbnails(&page, &options, counter.pages(), dpi)
+                                            .await?;
+                                    if edit {
+                                        edit_pdf(
+                                            &page,
+                                            &options,
+                                            unedited,
+                                            generate_bookmarks,
+                                            pdf_a.as_ref(),
+                                            output,
+                                        )
+                                        .await?;
+                                    }
+                                }
+                                Ok::<_, Error>(())
+                            })
+                            .await?;
+                            stop_server.abort();
+                            listeners.collect(diagnostics);
 
+                            // The handler will stop when the browser is closed:
+                            done.store(true, Ordering::Release);
                             browser.close().await?;
+                            // The profile can only be deleted once Chrome has exited:
+                            browser.wait().await?;
 
-                            Ok(data)
+                            Ok(())
                         },
                     )
                     .await;
-                    Err::<Infallible, _>(res.map(|((), data)| data))
+                    Err::<Infallible, _>(res.map(|((), ())| ()))
                 },
             )
             .await;
-        match res {
-            Ok((v, _)) => match v {},
-            Err(res) => res,
+            match res {
+                Ok((_, v)) => match v {},
+                Err(res) => res,
+            }
+        })
+    }
+}
+
+/// The local HTTP server that serves the HTML to Chrome couldn't listen on its
+/// address, for example because a sandbox doesn't allow it. Returned as the
+/// source of an [`Error::Io`], use [`ServerBindError::from_error`] to detect
+/// it.
+#[derive(Debug)]
+pub struct ServerBindError {
+    pub address: SocketAddr,
+    pub source: io::Error,
+}
+impl ServerBindError {
+    /// Get the error that caused a conversion error, if any.
+    pub fn from_error(error: &Error) -> Option<&Self> {
+        match error {
+            Error::Io(error) => error.get_ref()?.downcast_ref(),
+            _ => None,
         }
+    }
+}
+impl fmt::Display for ServerBindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to start the local HTTP server that serves the HTML to Chrome \
+            at {}: {}. If the environment doesn't allow listening on that address \
+            then choose another one with `ChromiumoxideConverterBuilder::bind_address` \
+            or print a page that is hosted elsewhere with \
+            `ChromiumoxideConverterBuilder::url`",
+            self.address, self.source
+        )
+    }
+}
+impl std::error::Error for ServerBindError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Listen on `address` for the local HTTP server, see
+/// [`ChromiumoxideConverterBuilder::bind_address`].
+async fn bind_server(address: SocketAddr) -> Result<TcpListener, Error> {
+    TcpListener::bind(address).await.map_err(|source| {
+        Error::Io(io::Error::new(
+            source.kind(),
+            ServerBindError { address, source },
+        ))
     })
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct ChromiumoxideConverter {
-    pub pdf_options: PrintToPdfParams,
+/// The URL that Chrome loads from the local HTTP server.
+fn server_url(scheme: &str, addr: SocketAddr) -> String {
+    if addr.ip().is_loopback() || addr.ip().is_unspecified() {
+        format!("{scheme}://localhost:{}/", addr.port())
+    } else {
+        format!("{scheme}://{addr}/")
+    }
+}
+
+/// A temporary folder for Chrome's profile, see
+/// [`ChromiumoxideConverterBuilder::user_data_dir`]. Deleted when dropped.
+struct ProfileDir {
+    path: PathBuf,
+}
+impl ProfileDir {
+    fn create() -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let temp_dir = std::env::temp_dir();
+        loop {
+            let path = temp_dir.join(format!(
+                "html_to_pdf-chrome-profile-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(Self { path }),
+                // Left behind by an earlier process with the same id:
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+impl Drop for ProfileDir {
+    fn drop(&mut self) {
+        // Best effort, Chrome might still be running if the conversion failed:
+        fs::remove_dir_all(&self.path).ok();
+    }
+}
+
+/// Fails all requests that a page makes to other origins than the one it was
+/// loaded from, see [`ChromiumoxideConverterBuilder::sanitize_untrusted`].
+struct ExternalRequestBlocker {
+    page: Page,
+    paused_requests: EventStream<EventRequestPaused>,
+    allowed_origin: String,
+}
+impl ExternalRequestBlocker {
+    /// Start intercepting requests, they must then be handled by
+    /// [`while_blocking`] or the page will never finish loading.
+    async fn enable(page: &Page, url: &str) -> Result<Self, Error> {
+        let blocker = Self {
+            page: page.clone(),
+            paused_requests: page.event_listener::<EventRequestPaused>().await?,
+            allowed_origin: url_origin(url).to_owned(),
+        };
+        page.execute(FetchEnableParams::default()).await?;
+        Ok(blocker)
+    }
+
+    /// Continue or fail each request that is paused, until the page is closed.
+    async fn run(&mut self) -> Result<Infallible, Error> {
+        use futures_util::StreamExt;
+
+        while let Some(event) = StreamExt::next(&mut self.paused_requests).await {
+            let request_id = event.request_id.clone();
+            if url_origin(&event.request.url).eq_ignore_ascii_case(&self.allowed_origin) {
+                self.page
+                    .execute(ContinueRequestParams::new(request_id))
+                    .await?;
+            } else {
+                self.page
+                    .execute(FailRequestParams::new(
+                        request_id,
+                        ErrorReason::BlockedByClient,
+                    ))
+                    .await?;
+            }
+        }
+        // No more requests will be made:
+        std::future::pending().await
+    }
+}
+
+/// Handle requests that `blocker` intercepts while `work` is running.
+async fn while_blocking<T>(
+    blocker: Option<&mut ExternalRequestBlocker>,
+    work: impl Future<Output = Result<T, Error>>,
+) -> Result<T, Error> {
+    use futures_util::future::{select, Either};
+
+    let Some(blocker) = blocker else {
+        return work.await;
+    };
+    match select(std::pin::pin!(work), std::pin::pin!(blocker.run())).await {
+        Either::Left((result, _)) => result,
+        Either::Right((Ok(never), _)) => match never {},
+        Either::Right((Err(e), _)) => Err(e),
+    }
+}
+
+/// The `scheme://host:port` part of `url`, or all of it if it doesn't look
+/// like a URL with an authority.
+fn url_origin(url: &str) -> &str {
+    let Some(scheme_end) = url.find("://") else {
+        return url;
+    };
+    let authority_start = scheme_end + "://".len();
+    match url[authority_start..].find(['/', '?', '#']) {
+        Some(end) => &url[..authority_start + end],
+        None => url,
+    }
+}
+
+/// Listens for messages that a page logs, for example resources that failed
+/// to load or calls to `console.error`.
+struct PageDiagnostics {
+    log_entries: EventStream<EventEntryAdded>,
+    console_calls: EventStream<EventConsoleApiCalled>,
+}
+impl PageDiagnostics {
+    async fn listen(page: &Page) -> Result<Self, Error> {
+        let listeners = Self {
+            log_entries: page.event_listener::<EventEntryAdded>().await?,
+            console_calls: page.event_listener::<EventConsoleApiCalled>().await?,
+        };
+        page.execute(LogEnableParams::default()).await?;
+        Ok(listeners)
+    }
+
+    /// Add warnings and errors that have been received so far to
+    /// `diagnostics`.
+    fn collect(&mut self, diagnostics: &mut Vec<Diagnostic>) {
+        use futures_util::{FutureExt, StreamExt};
+
+        while let Some(Some(event)) = StreamExt::next(&mut self.log_entries).now_or_never() {
+            let entry = &event.entry;
+            let message = match &entry.url {
+                Some(url) => format!("{} ({url})", entry.text),
+                None => entry.text.clone(),
+            };
+            match entry.level {
+                LogEntryLevel::Warning => diagnostics.push(Diagnostic::warning(message)),
+                LogEntryLevel::Error => diagnostics.push(Diagnostic::error(message)),
+                _ => {}
+            }
+        }
+        while let Some(Some(event)) = StreamExt::next(&mut self.console_calls).now_or_never() {
+            let message = event
+                .args
+                .iter()
+                .map(|arg| match &arg.value {
+                    Some(serde_json::Value::String(text)) => text.clone(),
+                    Some(value) => value.to_string(),
+                    None => arg.description.clone().unwrap_or_default(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            match event.r#type {
+                ConsoleApiCalledType::Warning => diagnostics.push(Diagnostic::warning(message)),
+                ConsoleApiCalledType::Error | ConsoleApiCalledType::Assert => {
+                    diagnostics.push(Diagnostic::error(message))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Print the page and write the PDF to `output`.
+async fn print_pdf(
+    page: &Page,
+    options: PrintToPdfParams,
+    output: &mut dyn Write,
+) -> Result<(), Error> {
+    if options.transfer_mode != Some(PrintToPdfTransferMode::ReturnAsStream) {
+        output.write_all(&page.pdf(options).await?)?;
+        return Ok(());
+    }
+    let printed = page.execute(options).await?.result;
+    let Some(handle) = printed.stream else {
+        // Chrome is too old to support streaming:
+        output.write_all(&decode_base64(printed.data.as_ref())?)?;
+        return Ok(());
+    };
+    loop {
+        let chunk = page
+            .execute(ReadParams {
+                handle: handle.clone(),
+                offset: None,
+                size: Some(ChromiumoxideConverter::STREAM_CHUNK_SIZE as i64),
+            })
+            .await?
+            .result;
+        if chunk.base64_encoded == Some(true) {
+            output.write_all(&decode_base64(&chunk.data)?)?;
+        } else {
+            output.write_all(chunk.data.as_bytes())?;
+        }
+        if chunk.eof {
+            break;
+        }
+    }
+    page.execute(CloseParams::new(handle)).await?;
+    Ok(())
+}
+
+/// Lay out the page like it is printed with `options`, but as one long page,
+/// and return the size of each printed page's content area in CSS pixels.
+async fn emulate_print_layout(
+    page: &Page,
+    options: &PrintToPdfParams,
+) -> Result<(f64, f64), Error> {
+    // Chrome's defaults are letter paper with 1cm (~0.4 inch) margins:
+    let (mut paper_width, mut paper_height) = (
+        options.paper_width.unwrap_or(8.5),
+        options.paper_height.unwrap_or(11.0),
+    );
+    if options.landscape == Some(true) {
+        std::mem::swap(&mut paper_width, &mut paper_height);
+    }
+    let margin = |margin: Option<f64>| margin.unwrap_or(0.4);
+    let scale = options.scale.unwrap_or(1.0);
+    // Size of the content area in CSS pixels, which are 1/96 inch:
+    let width =
+        (paper_width - margin(options.margin_left) - margin(options.margin_right)) * 96.0 / scale;
+    let height =
+        (paper_height - margin(options.margin_top) - margin(options.margin_bottom)) * 96.0 / scale;
+
+    page.execute(SetEmulatedMediaParams::builder().media("print").build())
+        .await?;
+    page.execute(SetDeviceMetricsOverrideParams::new(
+        width.round() as i64,
+        height.round() as i64,
+        1.0,
+        false,
+    ))
+    .await?;
+    Ok((width, height))
+}
+
+/// Take a screenshot of each printed page's content area after the page has
+/// been printed with `options`.
+async fn capture_thumbnails(
+    page: &Page,
+    options: &PrintToPdfParams,
+    pages: u32,
+    dpi: f64,
+) -> Result<Vec<Vec<u8>>, Error> {
+    let scale = options.scale.unwrap_or(1.0);
+    let (width, height) = emulate_print_layout(page, options).await?;
+
+    let mut pngs = Vec::with_capacity(pages as usize);
+    for index in 0..pages {
+        let screenshot = CaptureScreenshotParams::builder()
+            .format(CaptureScreenshotFormat::Png)
+            .clip(Viewport {
+                x: 0.0,
+                y: f64::from(index) * height,
+                width,
+                height,
+                scale: scale * dpi / 96.0,
+            })
+            .capture_beyond_viewport(true)
+            .build();
+        let screenshot = page.execute(screenshot).await?.result;
+        pngs.push(decode_base64(screenshot.data.as_ref())?);
+    }
+    Ok(pngs)
+}
+
+/// Add the outline and the PDF/A metadata that Chrome can't create itself to
+/// `pdf` and write it to `output`.
+async fn edit_pdf(
+    page: &Page,
+    options: &PrintToPdfParams,
+    pdf: Vec<u8>,
+    generate_bookmarks: bool,
+    pdf_a: Option<&(PdfAConformance, Vec<u8>)>,
+    output: &mut dyn Write,
+) -> Result<(), Error> {
+    #[cfg(feature = "bookmarks")]
+    let pdf = if generate_bookmarks {
+        add_bookmarks(page, options, &pdf).await?
+    } else {
+        pdf
+    };
+    #[cfg(not(feature = "bookmarks"))]
+    let _ = (page, options, generate_bookmarks);
+    #[cfg(feature = "pdf_a")]
+    let pdf = match pdf_a {
+        Some((conformance, icc_profile)) => {
+            html_to_pdf::pdf::set_pdf_a_conformance(&pdf, *conformance, icc_profile)
+                .map_err(|e| Error::msg(e.to_string()))?
+        }
+        None => pdf,
+    };
+    #[cfg(not(feature = "pdf_a"))]
+    let _ = pdf_a;
+    output.write_all(&pdf)?;
+    Ok(())
+}
+
+/// Add an outline for the page's headings to `pdf`, see
+/// [`ChromiumoxideConverterBuilder::generate_bookmarks`].
+#[cfg(feature = "bookmarks")]
+async fn add_bookmarks(
+    page: &Page,
+    options: &PrintToPdfParams,
+    pdf: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let (_, page_height) = emulate_print_layout(page, options).await?;
+    let find_headings = EvaluateParams::builder()
+        .expression(
+            r#"Array.from(document.querySelectorAll("h1, h2, h3, h4, h5, h6"), (heading) => [
+                Number(heading.tagName[1]),
+                heading.textContent.replace(/\s+/g, " ").trim(),
+                heading.getBoundingClientRect().top + window.scrollY,
+            ]).filter(([, title]) => title !== "")"#,
+        )
+        .return_by_value(true)
+        .build()
+        .map_err(Error::msg)?;
+    let headings: Vec<(u8, String, f64)> = page
+        .evaluate_expression(find_headings)
+        .await?
+        .into_value()?;
+    page.execute(ClearDeviceMetricsOverrideParams::default())
+        .await?;
+
+    let outline =
+        html_to_pdf::outline_from_heading_pages(headings.into_iter().map(|(level, title, top)| {
+            let page = (top.max(0.0) / page_height) as u32 + 1;
+            (html_to_pdf::Heading { level, title }, page)
+        }));
+    html_to_pdf::pdf::set_outline(pdf, &outline).map_err(|e| Error::msg(e.to_string()))
+}
+
+fn decode_base64(data: &str) -> Result<Vec<u8>, Error> {
+    use base64::Engine as _;
+
+    base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| Error::msg(e.to_string()))
 }
 
 impl<'scope, W> HtmlToPdfConverter<'scope, W> for ChromiumoxideConverter
@@ -194,26 +1580,204 @@ where
             _scope: PhantomData,
         })
     }
+
+    /// Prints the page without backgrounds and streams the PDF from Chrome
+    /// while counting its pages, so the PDF is never held in memory.
+    fn estimate_pages(self, html: &[u8]) -> Result<u32, HtmlToPdfError>
+    where
+        Self: Sized + HtmlToPdfConverter<'static, WriteBuilderSimple<PdfPageCounter>>,
+    {
+        let html = html.strip_prefix("\u{feff}".as_bytes()).unwrap_or(html);
+        let options = PrintToPdfParams {
+            print_background: Some(false),
+            transfer_mode: Some(PrintToPdfTransferMode::ReturnAsStream),
+            ..self.pdf_options.clone()
+        };
+        let mut counter = PdfPageCounter::new();
+        self.print(
+            Bytes::copy_from_slice(html),
+            vec![(options, &mut counter as &mut dyn Write)],
+            &mut Vec::new(),
+            None,
+        )?;
+        Ok(counter.pages())
+    }
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("chromiumoxide")
+    }
+
+    /// The PDF only has an outline if
+    /// [`document_outline`](ChromiumoxideConverterBuilder::document_outline)
+    /// or `generate_bookmarks` is enabled.
+    fn capabilities(&self) -> ConverterCapabilities {
+        #[cfg(feature = "bookmarks")]
+        let generate_bookmarks = self.generate_bookmarks;
+        #[cfg(not(feature = "bookmarks"))]
+        let generate_bookmarks = false;
+        ConverterCapabilities {
+            table_of_contents: generate_bookmarks
+                || self.pdf_options.generate_document_outline == Some(true),
+            clickable_links: true,
+            streaming_input: false,
+            embedded_fonts: true,
+        }
+    }
 }
 impl<'scope, W> HtmlSink<W, Error> for ChromiumoxideHtmlSink<'scope, W>
 where
     W: WriteBuilder + Send + 'scope,
 {
-    fn complete(mut self) -> Result<W, Error> {
+    fn complete(self) -> Result<W, Error> {
+        self.complete_with_diagnostics().map(|(writer, _)| writer)
+    }
+    /// Also returns warnings and errors that the page logged, for example
+    /// resources that failed to load or calls to `console.error`.
+    fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), Error> {
+        self.finish(None)
+            .map(|(writer, diagnostics, _)| (writer, diagnostics))
+    }
+}
+impl<'scope, W> ChromiumoxideHtmlSink<'scope, W>
+where
+    W: WriteBuilder + Send + 'scope,
+{
+    /// Like [`HtmlSink::complete`] but also renders each page of the PDF as
+    /// a PNG thumbnail with the specified resolution, for example `36.0` for
+    /// half the size of the 72 DPI that PDF viewers use for 100% zoom.
+    ///
+    /// The thumbnails are screenshots of the already loaded page, taken with
+    /// the print layout, so Chrome doesn't need to be started again. They
+    /// only show each page's content area, without margins, headers and
+    /// footers, and are cut where the content would fill a page. Content
+    /// that is moved to the next page, for example by `break-before: page`,
+    /// can therefore end up in a different thumbnail than in the PDF, but
+    /// there is always one thumbnail per page.
+    ///
+    /// Other converters would need to rasterize the produced PDF instead,
+    /// which requires a PDF renderer such as PDFium or MuPDF that this crate
+    /// doesn't depend on.
+    pub fn complete_with_thumbnails(self, dpi: f64) -> Result<(W, Vec<Vec<u8>>), Error> {
+        self.finish(Some(dpi))
+            .map(|(writer, _, thumbnails)| (writer, thumbnails))
+    }
+
+    fn finish(
+        mut self,
+        thumbnail_dpi: Option<f64>,
+    ) -> Result<(W, Vec<Diagnostic>, Vec<Vec<u8>>), Error> {
         let mut writer = self.writer.get_writer()?;
         const UTF8_BOM: &[u8] = "\u{feff}".as_bytes();
         if self.buffer.starts_with(UTF8_BOM) {
             drop(self.buffer.drain(..UTF8_BOM.len()));
         }
+        // Inserted first so that the charset is declared before it:
+        if let Some(base_url) = &self.options.base_url {
+            insert_base_href(&mut self.buffer, base_url);
+        }
+        if self.options.ensure_utf8_meta {
+            ensure_utf8_meta(&mut self.buffer);
+        }
 
-        let data = html_to_pdf(self.buffer.into(), self.options.pdf_options)?;
-        writer.write_all(data.as_slice())?;
+        let options = self.options.pdf_options.clone();
+        let mut diagnostics = Vec::new();
+        let mut thumbnails = Vec::new();
+        self.options.print(
+            self.buffer.into(),
+            vec![(options, &mut writer as &mut dyn Write)],
+            &mut diagnostics,
+            thumbnail_dpi.map(|dpi| (dpi, &mut thumbnails)),
+        )?;
 
         drop(writer);
-        Ok(self.writer)
+        Ok((self.writer, diagnostics, thumbnails))
+    }
+}
+
+/// Insert `<meta charset="UTF-8">` into the `<head>` of the HTML unless the
+/// head already declares a charset, see
+/// [`ChromiumoxideConverterBuilder::ensure_utf8_meta`].
+fn ensure_utf8_meta(html: &mut Vec<u8>) {
+    const META: &str = r#"<meta charset="UTF-8">"#;
+
+    let lower = html.to_ascii_lowercase();
+    let find_tag = |name: &[u8]| find_tag(&lower, name);
+
+    // A charset declared after the head is ignored by browsers:
+    let head_end = find_tag(b"</head")
+        .or_else(|| find_tag(b"<body"))
+        .unwrap_or(lower.len());
+    let has_charset = lower[..head_end]
+        .split(|&c| c == b'<')
+        .filter(|tag| tag.starts_with(b"meta"))
+        .any(|tag| {
+            let tag = &tag[..tag.iter().position(|&c| c == b'>').unwrap_or(tag.len())];
+            tag.windows(b"charset".len()).any(|w| w == b"charset")
+        });
+    if has_charset {
+        return;
     }
+    insert_into_head(html, &lower, META);
+}
+
+/// Insert `<base href="...">` at the start of the HTML's `<head>`, see
+/// [`ChromiumoxideConverterBuilder::base_url`]. Browsers use the first `<base>`
+/// element with an `href`, so any `<base>` tag that the document already has is
+/// ignored.
+fn insert_base_href(html: &mut Vec<u8>, base_url: &str) {
+    let href = base_url
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    let lower = html.to_ascii_lowercase();
+    insert_into_head(html, &lower, &format!(r#"<base href="{href}">"#));
+}
+
+/// Insert `tag` at the start of the HTML's `<head>`, adding a `<head>` if it
+/// doesn't have one. `lower` is the HTML in ASCII lowercase.
+fn insert_into_head(html: &mut Vec<u8>, lower: &[u8], tag: &str) {
+    let (position, insert) = if let Some(head) = find_tag(lower, b"<head") {
+        (tag_end(lower, head), tag.to_owned())
+    } else {
+        let position = find_tag(lower, b"<html")
+            .or_else(|| find_tag(lower, b"<!doctype"))
+            .map_or(0, |start| tag_end(lower, start));
+        (position, format!("<head>{tag}</head>"))
+    };
+    html.splice(position..position, insert.into_bytes());
 }
 
+/// Find the start of a tag in lowercase HTML, so that `<head` doesn't match
+/// `<header`.
+fn find_tag(lower: &[u8], name: &[u8]) -> Option<usize> {
+    let find = |from: usize| {
+        lower[from..]
+            .windows(name.len())
+            .position(|window| window == name)
+            .map(|index| from + index)
+    };
+    let mut from = 0;
+    while let Some(start) = find(from) {
+        match lower.get(start + name.len()) {
+            Some(b'>' | b'/') | None => return Some(start),
+            Some(c) if c.is_ascii_whitespace() => return Some(start),
+            _ => from = start + 1,
+        }
+    }
+    None
+}
+
+/// The position after the `>` that ends the tag which starts at `start`.
+fn tag_end(lower: &[u8], start: usize) -> usize {
+    lower[start..]
+        .iter()
+        .position(|&c| c == b'>')
+        .map_or(lower.len(), |index| start + index + 1)
+}
+
+/// Buffers the HTML and only starts Chrome once the sink is completed, so
+/// dropping it without completing it writes nothing to the output.
 pub struct ChromiumoxideHtmlSink<'scope, W> {
     buffer: Vec<u8>,
     writer: W,
@@ -222,6 +1786,17 @@ pub struct ChromiumoxideHtmlSink<'scope, W> {
 }
 impl<'scope, W> Write for ChromiumoxideHtmlSink<'scope, W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() > self.options.max_html_size.saturating_sub(self.buffer.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                HtmlTooLargeError {
+                    max_bytes: self.options.max_html_size as u64,
+                },
+            ));
+        }
+        self.buffer
+            .try_reserve(buf.len())
+            .map_err(|e| io::Error::new(io::ErrorKind::OutOfMemory, e))?;
         self.buffer.extend_from_slice(buf);
         Ok(buf.len())
     }
@@ -229,3 +1804,1227 @@ impl<'scope, W> Write for ChromiumoxideHtmlSink<'scope, W> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_sets_options() {
+        let converter = ChromiumoxideConverter::builder()
+            .print_background(false)
+            .wait_for_fonts(true)
+            .wait_for_fonts_timeout(Duration::from_secs(3))
+            .user_data_dir("chrome-profile")
+            .lang("de")
+            .page_ranges(&"1-3,5".parse().unwrap())
+            .bind_address(SocketAddr::from(([0, 0, 0, 0], 8080)))
+            .base_url("https://example.com/")
+            .build();
+
+        let mut expected = ChromiumoxideConverter::default();
+        expected.pdf_options.print_background = Some(false);
+        expected.wait_for_fonts = true;
+        expected.wait_for_fonts_timeout = Duration::from_secs(3);
+        expected.user_data_dir = Some(PathBuf::from("chrome-profile"));
+        expected.lang = Some("de".to_owned());
+        expected.pdf_options.page_ranges = Some("1-3,5".to_owned());
+        expected.bind_address = SocketAddr::from(([0, 0, 0, 0], 8080));
+        expected.base_url = Some("https://example.com/".to_owned());
+
+        assert_eq!(converter, expected);
+    }
+
+    #[test]
+    fn debug_output_includes_paper_size() {
+        let converter = ChromiumoxideConverter::builder()
+            .pdf_options(PrintToPdfParams {
+                paper_width: Some(8.27),
+                paper_height: Some(11.69),
+                ..PrintToPdfParams::default()
+            })
+            .build();
+        let debug = format!("{converter:?}");
+        assert!(debug.contains("paper_width: Some(8.27)"), "{debug}");
+        assert!(debug.contains("paper_height: Some(11.69)"), "{debug}");
+    }
+
+    #[test]
+    fn cdp_error_converts_to_engine_error() {
+        let error = HtmlToPdfError::from(Error::msg("Chrome closed the connection"));
+        assert!(matches!(error, HtmlToPdfError::Engine(_)));
+        let source = std::error::Error::source(&error)
+            .and_then(|source| source.downcast_ref::<Error>())
+            .expect("the CdpError should be the source");
+        assert_eq!(source.to_string(), "Chrome closed the connection");
+    }
+
+    #[test]
+    #[cfg(feature = "pdf_a")]
+    fn pdf_a_requires_icc_profile() {
+        let error = ChromiumoxideConverter::builder()
+            .pdf_a(PdfAConformance::PdfA2B)
+            .build()
+            .convert(Bytes::from_static(b"<p>Hello</p>"))
+            .expect_err("PDF/A requires an ICC profile");
+        assert!(error.to_string().contains("ICC color profile"), "{error}");
+    }
+
+    #[test]
+    #[cfg(feature = "pdf_a")]
+    fn pdf_a_1_is_not_supported() {
+        let error = ChromiumoxideConverter::builder()
+            .pdf_a(PdfAConformance::PdfA1B)
+            .icc_profile("sRGB.icc")
+            .build()
+            .convert(Bytes::from_static(b"<p>Hello</p>"))
+            .expect_err("Chrome can't create PDF/A-1 documents");
+        let Error::Io(error) = error else {
+            panic!("expected an IO error: {error}");
+        };
+        assert_eq!(
+            error.get_ref().and_then(|e| e.downcast_ref()),
+            Some(&PdfANotSupportedError {
+                engine: "chromiumoxide",
+                conformance: PdfAConformance::PdfA1B,
+            })
+        );
+    }
+
+    #[test]
+    fn writing_past_max_html_size_fails() {
+        let mut html_sink = ChromiumoxideConverter::builder()
+            .max_html_size(8)
+            .build()
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                html_to_pdf::WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+
+        html_sink.write_all(b"<p>").unwrap();
+        let error = html_sink.write_all(b"too long").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::OutOfMemory);
+        assert_eq!(
+            HtmlTooLargeError::from_io(&error),
+            Some(&HtmlTooLargeError { max_bytes: 8 })
+        );
+    }
+
+    #[test]
+    fn dropped_sink_writes_nothing() {
+        let mut output = html_to_pdf::WriteBuilderSimple(Vec::new());
+        std::thread::scope(|s| {
+            let mut html_sink = ChromiumoxideConverter::default()
+                .start(html_to_pdf::PdfScope::scoped(s), &mut output)
+                .unwrap();
+            html_sink.write_all(b"<p>Hello</p>").unwrap();
+            drop(html_sink);
+        });
+        assert!(output.0.is_empty());
+    }
+
+    #[test]
+    fn stopped_server_releases_port() {
+        block_on(async {
+            let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+                .await
+                .unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let (server, stop_server) =
+                futures_util::future::abortable(simple_http_server::<Infallible>(
+                    listener,
+                    Arc::new(ServedPage::new(Bytes::new(), Vec::new(), &Assets::new()).unwrap()),
+                    None,
+                ));
+            stop_server.abort();
+            assert!(server.await.is_err());
+
+            TcpListener::bind(addr).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn occupied_bind_address_returns_helpful_error() {
+        block_on(async {
+            let occupied = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+                .await
+                .unwrap();
+            let address = occupied.local_addr().unwrap();
+
+            let error = bind_server(address).await.unwrap_err();
+            let bind_error = ServerBindError::from_error(&error).unwrap();
+            assert_eq!(bind_error.address, address);
+            assert_eq!(bind_error.source.kind(), io::ErrorKind::AddrInUse);
+            assert!(error.to_string().contains("bind_address"), "{error}");
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "tls")]
+    fn tls_acceptor_certificate_matches_pinned_spki_hash() {
+        use base64::Engine as _;
+        use sha2::{Digest as _, Sha256};
+        use tokio_rustls::{
+            rustls::{
+                client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+                crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+                pki_types::{CertificateDer, ServerName, UnixTime},
+                ClientConfig, DigitallySignedStruct, SignatureScheme,
+            },
+            TlsConnector,
+        };
+
+        /// The elements inside a DER encoded `SEQUENCE`.
+        fn der_children(der: &[u8]) -> Vec<&[u8]> {
+            /// Split off the first element, returns its header length, the
+            /// element and the rest of the data.
+            fn split_element(der: &[u8]) -> (usize, &[u8], &[u8]) {
+                let (header, len) = match der[1] {
+                    len @ 0..=0x7f => (2, usize::from(len)),
+                    long => {
+                        let count = usize::from(long & 0x7f);
+                        let len = der[2..2 + count]
+                            .iter()
+                            .fold(0, |len, &byte| (len << 8) | usize::from(byte));
+                        (2 + count, len)
+                    }
+                };
+                let (element, rest) = der.split_at(header + len);
+                (header, element, rest)
+            }
+            let (header, sequence, _) = split_element(der);
+            let mut rest = &sequence[header..];
+            let mut children = Vec::new();
+            while !rest.is_empty() {
+                let (_, child, tail) = split_element(rest);
+                children.push(child);
+                rest = tail;
+            }
+            children
+        }
+
+        /// Only accepts certificates whose public key has the pinned hash,
+        /// like Chrome does with `--ignore-certificate-errors-spki-list`.
+        #[derive(Debug)]
+        struct PinnedSpki(String);
+        impl PinnedSpki {
+            fn provider() -> &'static Arc<CryptoProvider> {
+                CryptoProvider::get_default().expect("the server config installs a provider")
+            }
+        }
+        impl ServerCertVerifier for PinnedSpki {
+            fn verify_server_cert(
+                &self,
+                end_entity: &CertificateDer<'_>,
+                _intermediates: &[CertificateDer<'_>],
+                _server_name: &ServerName<'_>,
+                _ocsp_response: &[u8],
+                _now: UnixTime,
+            ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+                // TBSCertificate: version, serial, signature, issuer, validity,
+                // subject, subjectPublicKeyInfo, ...
+                let spki = der_children(der_children(end_entity)[0])[6];
+                let hash = base64::engine::general_purpose::STANDARD.encode(Sha256::digest(spki));
+                if hash == self.0 {
+                    Ok(ServerCertVerified::assertion())
+                } else {
+                    Err(tokio_rustls::rustls::Error::General(format!(
+                        "public key hash {hash} isn't pinned"
+                    )))
+                }
+            }
+            fn verify_tls12_signature(
+                &self,
+                message: &[u8],
+                cert: &CertificateDer<'_>,
+                dss: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+                let algorithms = &Self::provider().signature_verification_algorithms;
+                verify_tls12_signature(message, cert, dss, algorithms)
+            }
+            fn verify_tls13_signature(
+                &self,
+                message: &[u8],
+                cert: &CertificateDer<'_>,
+                dss: &DigitallySignedStruct,
+            ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+                let algorithms = &Self::provider().signature_verification_algorithms;
+                verify_tls13_signature(message, cert, dss, algorithms)
+            }
+            fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+                Self::provider()
+                    .signature_verification_algorithms
+                    .supported_schemes()
+            }
+        }
+
+        let (acceptor, spki_hash) = self_signed_tls_acceptor().unwrap();
+        let handshake = |pinned: &str| {
+            let config = ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(PinnedSpki(pinned.to_owned())))
+                .with_no_client_auth();
+            let connector = TlsConnector::from(Arc::new(config));
+            let acceptor = acceptor.clone();
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(async move {
+                    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                    let address = listener.local_addr().unwrap();
+                    let server = tokio::spawn(async move {
+                        let (tcp, _) = listener.accept().await.unwrap();
+                        acceptor.accept(tcp).await.is_ok()
+                    });
+                    let tcp = tokio::net::TcpStream::connect(address).await.unwrap();
+                    let client = connector
+                        .connect(ServerName::try_from("localhost").unwrap(), tcp)
+                        .await;
+                    // Keep the client connection open until the server is done:
+                    let server = server.await.unwrap();
+                    (client.is_ok(), server)
+                })
+        };
+
+        assert_eq!(handshake(&spki_hash), (true, true));
+        let other_hash = base64::engine::general_purpose::STANDARD.encode([0; 32]);
+        assert_eq!(handshake(&other_hash), (false, false));
+    }
+
+    #[test]
+    fn response_headers_always_have_charset() {
+        let csp = HeaderValue::from_static("default-src 'self'");
+        let headers = ChromiumoxideConverter::builder()
+            .response_header(
+                HeaderName::from_static("content-security-policy"),
+                csp.clone(),
+            )
+            .build()
+            .response_headers()
+            .unwrap();
+        assert!(headers.contains(&(HeaderName::from_static("content-security-policy"), csp)));
+        assert!(headers.contains(&(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8")
+        )));
+
+        let headers = ChromiumoxideConverter::builder()
+            .response_header(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/xhtml+xml"),
+            )
+            .build()
+            .response_headers()
+            .unwrap();
+        assert_eq!(
+            headers,
+            [(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/xhtml+xml; charset=utf-8")
+            )]
+        );
+    }
+
+    #[test]
+    fn builder_sets_prefer_css_page_size() {
+        let converter = ChromiumoxideConverter::builder()
+            .prefer_css_page_size(true)
+            .build();
+        assert_eq!(converter.pdf_options().prefer_css_page_size, Some(true));
+    }
+
+    #[test]
+    fn page_setup_is_converted_to_inches() {
+        let converter = ChromiumoxideConverter::builder()
+            .paper("8.5in x 11in".parse().unwrap())
+            .orientation(PageOrientation::Landscape)
+            .margin("1in 0.5in".parse().unwrap())
+            .build();
+        let options = converter.pdf_options();
+        assert_eq!(
+            (options.paper_width, options.paper_height),
+            (Some(8.5), Some(11.0))
+        );
+        assert_eq!(options.landscape, Some(true));
+        assert_eq!(
+            [
+                options.margin_top,
+                options.margin_right,
+                options.margin_bottom,
+                options.margin_left
+            ],
+            [Some(1.0), Some(0.5), Some(1.0), Some(0.5)]
+        );
+    }
+
+    #[test]
+    fn page_options_are_applied_to_pdf_options() {
+        let converter = ChromiumoxideConverter::default().with_page_options(PageOptions {
+            page_size: Some(html_to_pdf::PageSize::Letter.into()),
+            margins: Some(Margin::all(html_to_pdf::Length::from_inches(0.25))),
+            dpi: Some(300),
+            ..Default::default()
+        });
+        let options = converter.pdf_options();
+        assert_eq!(
+            (options.paper_width, options.paper_height),
+            (Some(8.5), Some(11.0))
+        );
+        assert_eq!(options.landscape, None);
+        assert_eq!(options.margin_left, Some(0.25));
+    }
+
+    #[test]
+    fn scale_is_clamped_to_valid_range() {
+        for (scale, expected) in [(0.5, 0.5), (0.01, 0.1), (5.0, 2.0), (f64::NAN, 1.0)] {
+            let converter = ChromiumoxideConverter::builder().scale(scale).build();
+            assert_eq!(converter.pdf_options().scale, Some(expected), "{scale}");
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn half_scale_fits_more_content_on_each_page() {
+        let html = Bytes::from("<p>Lorem ipsum dolor sit amet.</p>".repeat(500));
+        let pages = |scale: f64| {
+            let pdf = ChromiumoxideConverter::builder()
+                .scale(scale)
+                .build()
+                .convert(html.clone())
+                .unwrap();
+            let mut counter = PdfPageCounter::new();
+            counter.write_all(&pdf).unwrap();
+            counter.pages()
+        };
+
+        let normal = pages(1.0);
+        let halved = pages(0.5);
+        // Content that is half as tall only needs about half as many pages:
+        assert!(normal >= 4, "{normal}");
+        assert!(
+            halved <= normal / 2 + 1,
+            "{normal} pages vs {halved} halved"
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn headings_become_document_outline() {
+        let converter = ChromiumoxideConverter::builder()
+            .document_outline(true)
+            .build();
+        let html = b"<h1>One</h1><h2>One A</h2><h2>One B</h2>\
+            <h1 style=\"break-before: page\">Two</h1><h2>Two A</h2>";
+        let (pdf, outline) = html_to_pdf::convert_with_outline(converter, html).unwrap();
+        assert!(pdf.starts_with(b"%PDF-"));
+
+        let item = |title: &str, page: u32, children| html_to_pdf::OutlineItem {
+            title: title.to_owned(),
+            page,
+            children,
+        };
+        assert_eq!(
+            outline,
+            [
+                item(
+                    "One",
+                    1,
+                    vec![item("One A", 1, vec![]), item("One B", 1, vec![])]
+                ),
+                item("Two", 2, vec![item("Two A", 2, vec![])]),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "pdf_a")]
+    #[ignore = "requires Chrome to be installed"]
+    fn pdf_a_conformance_is_declared_in_xmp_metadata() {
+        let icc_profile = std::env::temp_dir().join(format!(
+            "html-to-pdf-chromiumoxide-icc-{}.icm",
+            std::process::id()
+        ));
+        // Only the color space in the profile's header is read, the rest is
+        // embedded as is:
+        let mut header = vec![0; 128];
+        header[16..20].copy_from_slice(b"RGB ");
+        fs::write(&icc_profile, header).unwrap();
+        let pdf = ChromiumoxideConverter::builder()
+            .pdf_a(PdfAConformance::PdfA2B)
+            .icc_profile(&icc_profile)
+            .build()
+            .convert(Bytes::from_static(b"<p>Hello</p>"));
+        fs::remove_file(&icc_profile).unwrap();
+
+        assert_eq!(
+            html_to_pdf::pdf::pdf_a_conformance(&pdf.unwrap()).unwrap(),
+            Some(PdfAConformance::PdfA2B)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "bookmarks")]
+    #[ignore = "requires Chrome to be installed"]
+    fn nested_headings_become_bookmarks() {
+        let html = b"<h1>One</h1><h2>One A</h2><div style=\"height: 3000px\"></div>\
+            <h2>One <em>B</em></h2><h1>Two</h1><h3>Two A</h3><p>Text</p>";
+        let pdf = ChromiumoxideConverter::builder()
+            .generate_bookmarks(true)
+            .build()
+            .convert(Bytes::from_static(html))
+            .unwrap();
+
+        let item = |title: &str, page: u32, children| html_to_pdf::OutlineItem {
+            title: title.to_owned(),
+            page,
+            children,
+        };
+        // The tall block pushes the rest of the headings to a later page:
+        let later_page = html_to_pdf::pdf::page_count(&pdf).unwrap();
+        assert!(later_page > 1);
+        assert_eq!(
+            html_to_pdf::pdf::outline(&pdf).unwrap(),
+            [
+                item(
+                    "One",
+                    1,
+                    vec![item("One A", 1, vec![]), item("One B", later_page, vec![])]
+                ),
+                item("Two", later_page, vec![item("Two A", later_page, vec![])]),
+            ]
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn css_page_size_is_used_when_preferred() {
+        let pdf = ChromiumoxideConverter::builder()
+            .prefer_css_page_size(true)
+            .build()
+            .convert(Bytes::from_static(
+                b"<style>@page { size: A5 }</style><p>Hello</p>",
+            ))
+            .unwrap();
+
+        // A5 is 148 x 210 mm, in PDF points that is about 419.5 x 595.3:
+        let pdf = String::from_utf8_lossy(&pdf);
+        let media_box = pdf
+            .split("/MediaBox")
+            .nth(1)
+            .and_then(|rest| rest.trim_start().strip_prefix('['))
+            .and_then(|rest| rest.split(']').next())
+            .expect("PDF should have a media box");
+        let size = media_box
+            .split_whitespace()
+            .map(|v| v.parse::<f64>().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(size.len(), 4);
+        assert!((size[2] - size[0] - 419.5).abs() < 1.0, "{media_box}");
+        assert!((size[3] - size[1] - 595.3).abs() < 1.0, "{media_box}");
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn each_paper_size_sets_the_media_box() {
+        use html_to_pdf::{pdf::lopdf::Document, PageSize};
+
+        let sizes = [PageSize::A4, PageSize::Letter, PageSize::Legal].map(Paper::from);
+        let pdfs = ChromiumoxideConverter::default()
+            .print_paper_sizes(Bytes::from_static(b"<p>Hello</p>"), &sizes)
+            .unwrap();
+        assert_eq!(pdfs.len(), sizes.len());
+
+        for (pdf, paper) in pdfs.iter().zip(sizes) {
+            let (paper_width, paper_height) = paper_size_in_inches(paper);
+            let document = Document::load_mem(pdf).unwrap();
+            for page in document.get_pages().into_values() {
+                let media_box = document
+                    .get_dictionary(page)
+                    .and_then(|page| page.get(b"MediaBox"))
+                    .and_then(|media_box| media_box.as_array())
+                    .unwrap()
+                    .iter()
+                    .map(|v| f64::from(v.as_float().unwrap()))
+                    .collect::<Vec<_>>();
+                assert_eq!(media_box.len(), 4);
+                // PDF points are 1/72 inch:
+                let width = (media_box[2] - media_box[0]) / 72.0;
+                let height = (media_box[3] - media_box[1]) / 72.0;
+                assert!((width - paper_width).abs() < 0.02, "{media_box:?} {paper}");
+                assert!(
+                    (height - paper_height).abs() < 0.02,
+                    "{media_box:?} {paper}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn background_colors_are_printed_when_enabled() {
+        use html_to_pdf::pdf::lopdf::{content::Content, Document};
+
+        let has_red_fill = |print_background: bool| {
+            let pdf = ChromiumoxideConverter::builder()
+                .print_background(print_background)
+                .build()
+                .convert(Bytes::from_static(
+                    b"<body style=\"background: #ff0000\"><p>Hello</p></body>",
+                ))
+                .unwrap();
+            let document = Document::load_mem(&pdf).unwrap();
+            let page = *document.get_pages().get(&1).unwrap();
+            let content = Content::decode(&document.get_page_content(page).unwrap()).unwrap();
+            content.operations.iter().any(|op| {
+                let color = op
+                    .operands
+                    .iter()
+                    .map(|v| v.as_float().unwrap_or(-1.0))
+                    .collect::<Vec<_>>();
+                op.operator == "rg" && color == [1.0, 0.0, 0.0]
+            })
+        };
+
+        assert!(has_red_fill(true));
+        assert!(!has_red_fill(false));
+    }
+
+    #[test]
+    fn builder_sets_transfer_mode() {
+        assert_eq!(
+            ChromiumoxideConverter::default()
+                .pdf_options()
+                .transfer_mode,
+            None
+        );
+        let converter = ChromiumoxideConverter::builder()
+            .transfer_mode(PrintToPdfTransferMode::ReturnAsStream)
+            .build();
+        assert_eq!(
+            converter.pdf_options().transfer_mode,
+            Some(PrintToPdfTransferMode::ReturnAsStream)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn transfer_modes_produce_the_same_pdf() {
+        // Large enough that streaming needs several chunks:
+        let html = format!(
+            "<img src=\"data:image/png;base64,{}\">{}",
+            "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGAhKmMIQAAAABJRU5ErkJggg==",
+            "<p>Lorem ipsum dolor sit amet.</p>".repeat(50_000)
+        );
+        let converter = ChromiumoxideConverter::default();
+        let mut pdfs = [Vec::new(), Vec::new()];
+        let [base64, stream] = &mut pdfs;
+        let print_options = [
+            PrintToPdfTransferMode::ReturnAsBase64,
+            PrintToPdfTransferMode::ReturnAsStream,
+        ]
+        .into_iter()
+        .zip([base64 as &mut dyn Write, stream])
+        .map(|(mode, pdf)| {
+            let options = PrintToPdfParams {
+                transfer_mode: Some(mode),
+                ..converter.pdf_options.clone()
+            };
+            (options, pdf)
+        })
+        .collect();
+        converter
+            .print(Bytes::from(html), print_options, &mut Vec::new(), None)
+            .unwrap();
+
+        let [base64, stream] = pdfs.map(|pdf| {
+            assert!(pdf.starts_with(b"%PDF-"));
+            assert!(pdf.trim_ascii_end().ends_with(b"%%EOF"));
+            without_dates(pdf)
+        });
+        assert!(stream.len() > ChromiumoxideConverter::STREAM_CHUNK_SIZE);
+        assert!(base64 == stream, "the PDFs should be identical");
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn estimated_pages_are_close_to_actual_pages() {
+        let html = "<p>Lorem ipsum dolor sit amet.</p>".repeat(500);
+        let estimate =
+            html_to_pdf::estimate_pages(ChromiumoxideConverter::default(), html.as_bytes())
+                .unwrap();
+
+        let pdf = ChromiumoxideConverter::default()
+            .convert(Bytes::from(html))
+            .unwrap();
+        let mut counter = PdfPageCounter::new();
+        counter.write_all(&pdf).unwrap();
+        let actual = counter.pages();
+
+        assert!(actual > 1, "{actual}");
+        assert!(estimate.abs_diff(actual) <= 1, "{estimate} vs {actual}");
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn page_errors_are_returned_as_diagnostics() {
+        let mut html_sink = ChromiumoxideConverter::default()
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+        html_sink
+            .write_all(b"<script>console.error('broken')</script><p>Hello</p>")
+            .unwrap();
+        let (WriteBuilderSimple(pdf), diagnostics) = html_sink.complete_with_diagnostics().unwrap();
+
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(
+            diagnostics.contains(&Diagnostic::error("broken")),
+            "{diagnostics:?}"
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn slow_fonts_are_reported_after_the_timeout() {
+        // Accepts connections but never answers, so the font never loads:
+        let font_server = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = font_server.local_addr().unwrap().port();
+        // The font is loaded after the load event so that navigation doesn't
+        // wait for it:
+        let html = format!(
+            "<style>@font-face {{ font-family: Slow; src: url(http://127.0.0.1:{port}/slow.woff2) }}</style>\
+            <script>addEventListener('load', () => document.fonts.load('16px Slow'))</script>\
+            <p>Hello</p>"
+        );
+
+        let start = std::time::Instant::now();
+        let mut html_sink = ChromiumoxideConverter::builder()
+            .wait_for_fonts(true)
+            .wait_for_fonts_timeout(Duration::from_secs(1))
+            .build()
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+        html_sink.write_all(html.as_bytes()).unwrap();
+        let (WriteBuilderSimple(pdf), diagnostics) = html_sink.complete_with_diagnostics().unwrap();
+
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert!(
+            diagnostics.iter().any(|diagnostic| {
+                diagnostic.level == html_to_pdf::DiagnosticLevel::Warning
+                    && diagnostic.message.starts_with("fonts didn't load")
+            }),
+            "{diagnostics:?}"
+        );
+        drop(font_server);
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn lang_is_stored_in_tagged_pdf() {
+        let converter = ChromiumoxideConverter::builder()
+            .pdf_options(PrintToPdfParams {
+                generate_tagged_pdf: Some(true),
+                ..PrintToPdfParams::default()
+            })
+            .lang("fr-CA")
+            .build();
+        let pdf = converter
+            .convert(Bytes::from("<html lang=en><p>Bonjour</p></html>"))
+            .unwrap();
+        assert_eq!(
+            html_to_pdf::pdf::language(&pdf).unwrap().as_deref(),
+            Some("fr-CA")
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn concurrent_conversions_do_not_share_cookies() {
+        // Cookies ignore the port, so both pages would see each other's
+        // cookies if they shared a profile:
+        let convert = |tenant: &'static str| {
+            std::thread::spawn(move || {
+                let mut html_sink = ChromiumoxideConverter::default()
+                    .start(
+                        html_to_pdf::PdfScope::owned(),
+                        WriteBuilderSimple(Vec::new()),
+                    )
+                    .unwrap();
+                write!(
+                    html_sink,
+                    "<script>\
+                    document.cookie = '{tenant}=1; max-age=3600';\
+                    const start = Date.now();\
+                    while (Date.now() - start < 1000) {{}}\
+                    console.error(document.cookie);\
+                    </script><p>Hello</p>"
+                )
+                .unwrap();
+                html_sink.complete_with_diagnostics().unwrap().1
+            })
+        };
+        let a = convert("tenant_a");
+        let b = convert("tenant_b");
+
+        for (tenant, diagnostics) in [("tenant_a", a), ("tenant_b", b)] {
+            let diagnostics = diagnostics.join().unwrap();
+            assert!(
+                diagnostics.contains(&Diagnostic::error(format!("{tenant}=1"))),
+                "{diagnostics:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn every_page_gets_a_thumbnail() {
+        let mut html_sink = ChromiumoxideConverter::default()
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+        html_sink
+            .write_all(b"<p>First</p><p style=\"break-before: page\">Second</p>")
+            .unwrap();
+        let (WriteBuilderSimple(pdf), thumbnails) =
+            html_sink.complete_with_thumbnails(36.0).unwrap();
+
+        let mut counter = PdfPageCounter::new();
+        counter.write_all(&pdf).unwrap();
+        assert_eq!(counter.pages(), 2);
+        assert_eq!(thumbnails.len(), 2);
+        for png in &thumbnails {
+            assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        }
+    }
+
+    #[test]
+    fn from_url_sets_url() {
+        assert_eq!(ChromiumoxideConverter::default().url(), None);
+        let converter = ChromiumoxideConverter::from_url("https://example.com/");
+        assert_eq!(converter.url(), Some("https://example.com/"));
+        assert_eq!(
+            converter.pdf_options(),
+            ChromiumoxideConverter::default().pdf_options()
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn url_is_printed_instead_of_html() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        // Serve a page with a relative resource so that its origin matters:
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (requested_tx, requested) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&mut stream);
+                if reader.read_line(&mut request_line).is_err() {
+                    continue;
+                }
+                // Skip the headers:
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                    line.clear();
+                }
+                let _ = requested_tx.send(request_line.clone());
+                let (content_type, body) = if request_line.starts_with("GET / ") {
+                    (
+                        "text/html",
+                        "<link rel=stylesheet href=style.css><p>Remote page</p>",
+                    )
+                } else if request_line.starts_with("GET /style.css ") {
+                    ("text/css", "p { color: red }")
+                } else {
+                    ("text/plain", "")
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\n\
+                    Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+            }
+        });
+
+        let mut html_sink = ChromiumoxideConverter::from_url(format!("http://127.0.0.1:{port}/"))
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+        // Ignored since the URL is printed:
+        html_sink.write_all(b"<p>Local HTML</p>").unwrap();
+        let (WriteBuilderSimple(pdf), diagnostics) = html_sink.complete_with_diagnostics().unwrap();
+
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let requested = requested.try_iter().collect::<Vec<_>>();
+        for path in ["/", "/style.css"] {
+            assert!(
+                requested
+                    .iter()
+                    .any(|line| line.starts_with(&format!("GET {path} "))),
+                "{requested:?}"
+            );
+        }
+    }
+
+    /// Blank out timestamps like `(D:20240101120000+00'00')` since the two
+    /// PDFs aren't printed at exactly the same time.
+    fn without_dates(mut pdf: Vec<u8>) -> Vec<u8> {
+        let mut i = 0;
+        while let Some(start) = pdf[i..].windows(3).position(|w| w == b"(D:") {
+            i += start + 3;
+            while i < pdf.len() && pdf[i] != b')' {
+                pdf[i] = b'0';
+                i += 1;
+            }
+        }
+        pdf
+    }
+
+    #[test]
+    fn set_pdf_option_rejects_unknown_names() {
+        let mut builder = ChromiumoxideConverter::builder();
+        builder.set_pdf_option("scale", "0.5").unwrap();
+        builder.set_pdf_option("landscape", "true").unwrap();
+        builder.set_pdf_option("notAnOption", "1").unwrap_err();
+        builder.set_pdf_option("scale", "not a number").unwrap_err();
+
+        let options = builder.build().pdf_options().clone();
+        assert_eq!(options.scale, Some(0.5));
+        assert_eq!(options.landscape, Some(true));
+    }
+
+    #[test]
+    fn handler_ending_early_is_an_error() {
+        let done = AtomicBool::new(false);
+        let handler = futures_util::stream::iter([Ok(()), Ok(())]);
+        let error = block_on(run_handler(handler, &done)).unwrap_err();
+        assert!(is_browser_terminated(&error), "{error}");
+
+        done.store(true, Ordering::Release);
+        let handler = futures_util::stream::iter([Ok(())]);
+        block_on(run_handler(handler, &done)).unwrap();
+
+        let handler = futures_util::stream::iter([Err(Error::msg("connection lost"))]);
+        let error = block_on(run_handler(handler, &done)).unwrap_err();
+        assert!(!is_browser_terminated(&error), "{error}");
+    }
+
+    /// Send a GET request for `path` and return the whole response.
+    fn http_get(addr: SocketAddr, path: &str) -> String {
+        use std::io::Read;
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn assets_are_served_by_path() {
+        let assets = Assets::from([(
+            "/images/logo.png".to_owned(),
+            (
+                Bytes::from_static(b"png data"),
+                html_to_pdf::mime::IMAGE_PNG,
+            ),
+        )]);
+        let page = ServedPage::new(Bytes::from_static(b"<p>Hello</p>"), Vec::new(), &assets);
+        let page = Arc::new(page.unwrap());
+
+        block_on(async {
+            let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+                .await
+                .unwrap();
+            let addr = listener.local_addr().unwrap();
+            let server = tokio::task::spawn(simple_http_server::<Infallible>(listener, page, None));
+
+            let responses = tokio::task::spawn_blocking(move || {
+                ["/", "/images/logo.png", "/missing.png"].map(|path| http_get(addr, path))
+            })
+            .await
+            .unwrap();
+            server.abort();
+
+            let [html, logo, missing] = responses;
+            assert!(html.ends_with("<p>Hello</p>"), "{html}");
+            assert!(logo.starts_with("HTTP/1.1 200"), "{logo}");
+            assert!(logo.contains("content-type: image/png"), "{logo}");
+            assert!(logo.ends_with("png data"), "{logo}");
+            assert!(missing.starts_with("HTTP/1.1 404"), "{missing}");
+        });
+
+        let invalid = Assets::from([(
+            "../logo.png".to_owned(),
+            (Bytes::new(), html_to_pdf::mime::IMAGE_PNG),
+        )]);
+        assert!(ServedPage::new(Bytes::new(), Vec::new(), &invalid).is_err());
+    }
+
+    #[test]
+    fn assets_block_external_resources() {
+        let headers = ChromiumoxideConverter::builder()
+            .build()
+            .response_headers()
+            .unwrap();
+        assert!(!headers
+            .iter()
+            .any(|(name, _)| *name == CONTENT_SECURITY_POLICY));
+
+        let headers = ChromiumoxideConverter::builder()
+            .asset("style.css", "p {}", html_to_pdf::mime::TEXT_CSS)
+            .build()
+            .response_headers()
+            .unwrap();
+        assert!(headers.contains(&(
+            CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static(OFFLINE_CONTENT_SECURITY_POLICY)
+        )));
+    }
+
+    #[test]
+    fn sanitized_pages_only_load_from_their_origin() {
+        let headers = ChromiumoxideConverter::builder()
+            .sanitize_untrusted(true)
+            .build()
+            .response_headers()
+            .unwrap();
+        assert!(headers.contains(&(
+            CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static(OFFLINE_CONTENT_SECURITY_POLICY)
+        )));
+
+        assert_eq!(
+            url_origin("http://localhost:8080/"),
+            "http://localhost:8080"
+        );
+        assert_eq!(url_origin("https://example.com?q=1"), "https://example.com");
+        assert_eq!(url_origin("http://evil"), "http://evil");
+        assert_ne!(
+            url_origin("http://localhost:8080.evil/"),
+            url_origin("http://localhost:8080/")
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn sanitized_page_makes_no_external_requests() {
+        // Stands in for http://evil/, nothing should connect to it:
+        let evil = std::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        evil.set_nonblocking(true).unwrap();
+        let evil_port = evil.local_addr().unwrap().port();
+
+        let html = format!(
+            r#"<p>Untrusted</p>
+            <img src="http://127.0.0.1:{evil_port}/image.png">
+            <script>fetch("http://127.0.0.1:{evil_port}/script")</script>"#
+        );
+        let pdf = ChromiumoxideConverter::builder()
+            .sanitize_untrusted(true)
+            .build()
+            .convert(Bytes::from(html))
+            .unwrap();
+
+        assert!(pdf.starts_with(b"%PDF"));
+        assert_eq!(
+            evil.accept().unwrap_err().kind(),
+            io::ErrorKind::WouldBlock,
+            "the page shouldn't make external requests"
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn image_is_loaded_from_assets_without_network_access() {
+        /// A 1x1 red PNG image.
+        const PIXEL_PNG: &[u8] = &[
+            0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00,
+            0x00, 0x90, 0x77, 0x53, 0xDE, 0x00, 0x00, 0x00, 0x0C, 0x49, 0x44, 0x41, 0x54, 0x08,
+            0xD7, 0x63, 0xF8, 0xCF, 0xC0, 0x00, 0x00, 0x03, 0x01, 0x01, 0x00, 0x18, 0xDD, 0x8D,
+            0xB0, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82,
+        ];
+
+        // Stands in for an external server, nothing should connect to it:
+        let external = std::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        external.set_nonblocking(true).unwrap();
+        let external_port = external.local_addr().unwrap().port();
+
+        let html = format!(
+            r#"<img src="images/pixel.png" width="100" height="100">
+            <img src="http://127.0.0.1:{external_port}/external.png">"#
+        );
+        let pdf = ChromiumoxideConverter::builder()
+            .asset(
+                "images/pixel.png",
+                Bytes::from_static(PIXEL_PNG),
+                html_to_pdf::mime::IMAGE_PNG,
+            )
+            .build()
+            .convert(Bytes::from(html))
+            .unwrap();
+
+        assert!(
+            String::from_utf8_lossy(&pdf).contains("/Image"),
+            "the image from the assets should be in the PDF"
+        );
+        assert_eq!(
+            external.accept().unwrap_err().kind(),
+            io::ErrorKind::WouldBlock,
+            "the page shouldn't load external resources"
+        );
+    }
+
+    #[test]
+    fn utf8_meta_is_inserted_when_missing() {
+        let meta = r#"<meta charset="UTF-8">"#;
+        for (html, expected) in [
+            (
+                "<html><head><title>Café</title></head></html>".to_owned(),
+                format!("<html><head>{meta}<title>Café</title></head></html>"),
+            ),
+            (
+                "<!DOCTYPE html><HEAD lang=fr></HEAD><header>".to_owned(),
+                format!("<!DOCTYPE html><HEAD lang=fr>{meta}</HEAD><header>"),
+            ),
+            (
+                "<!doctype html>\n<html lang=fr><body><p>Café</p></body>".to_owned(),
+                format!("<!doctype html>\n<html lang=fr><head>{meta}</head><body><p>Café</p></body>"),
+            ),
+            (
+                "<header>Café</header>".to_owned(),
+                format!("<head>{meta}</head><header>Café</header>"),
+            ),
+            // Already declared:
+            (
+                "<head><meta charset=utf-8></head>".to_owned(),
+                "<head><meta charset=utf-8></head>".to_owned(),
+            ),
+            (
+                r#"<head><META http-equiv="Content-Type" content="text/html; charset=utf-8"></head>"#
+                    .to_owned(),
+                r#"<head><META http-equiv="Content-Type" content="text/html; charset=utf-8"></head>"#
+                    .to_owned(),
+            ),
+            // Declared too late to be used:
+            (
+                "<head></head><body><meta charset=utf-8>".to_owned(),
+                format!("<head>{meta}</head><body><meta charset=utf-8>"),
+            ),
+        ] {
+            let mut html = html.into_bytes();
+            ensure_utf8_meta(&mut html);
+            assert_eq!(String::from_utf8(html).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn base_href_is_inserted_first_in_head() {
+        for (html, expected) in [
+            (
+                "<html><head><title>Docs</title></head></html>",
+                r#"<html><head><base href="https://example.com/?a=1&amp;b=&quot;"><title>Docs</title></head></html>"#,
+            ),
+            (
+                r#"<head><base href="/old/"></head><a href="page.html">"#,
+                r#"<head><base href="https://example.com/?a=1&amp;b=&quot;"><base href="/old/"></head><a href="page.html">"#,
+            ),
+            (
+                "<p>Docs</p>",
+                r#"<head><base href="https://example.com/?a=1&amp;b=&quot;"></head><p>Docs</p>"#,
+            ),
+        ] {
+            let mut html = html.as_bytes().to_vec();
+            insert_base_href(&mut html, r#"https://example.com/?a=1&b=""#);
+            assert_eq!(String::from_utf8(html).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn relative_links_resolve_against_base_url() {
+        let mut html_sink = ChromiumoxideConverter::builder()
+            .base_url("https://example.com/docs/")
+            .build()
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+        html_sink
+            .write_all(br#"<head><base href="/ignored/"></head><a href="guide.html">Guide</a>"#)
+            .unwrap();
+        let WriteBuilderSimple(pdf) = html_sink.complete().unwrap();
+
+        assert_eq!(
+            html_to_pdf::pdf::link_uris(&pdf).unwrap(),
+            ["https://example.com/docs/guide.html"]
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Chrome to be installed"]
+    fn inserted_utf8_meta_is_used_by_the_page() {
+        let mut html_sink = ChromiumoxideConverter::builder()
+            .ensure_utf8_meta(true)
+            .build()
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+        html_sink
+            .write_all(
+                "<title>Café</title><script>console.error(\
+                document.querySelector('meta[charset]').getAttribute('charset'), \
+                document.characterSet, document.title)</script><p>Crème brûlée</p>"
+                    .as_bytes(),
+            )
+            .unwrap();
+        let (WriteBuilderSimple(pdf), diagnostics) = html_sink.complete_with_diagnostics().unwrap();
+
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(
+            diagnostics.contains(&Diagnostic::error("UTF-8 UTF-8 Café")),
+            "{diagnostics:?}"
+        );
+    }
+
+    #[test]
+    fn builder_starts_from_default() {
+        assert_eq!(
+            ChromiumoxideConverter::builder().build(),
+            ChromiumoxideConverter::default()
+        );
+    }
+
+    #[test]
+    fn reports_name() {
+        let converter = ChromiumoxideConverter::default();
+        assert_eq!(
+            HtmlToPdfConverter::<WriteBuilderSimple<Vec<u8>>>::name(&converter),
+            "chromiumoxide"
+        );
+    }
+}
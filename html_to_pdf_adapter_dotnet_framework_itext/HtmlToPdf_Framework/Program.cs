@@ -5,6 +5,7 @@ using System.Text;
 using System.Threading.Tasks;
 
 using System.IO;
+using System.Globalization;
 using iTextSharp.text;
 using System.Xml.Linq;
 
@@ -25,10 +26,102 @@ namespace HtmlToPdf_Framework
                 Console.WriteLine($"{PDFWriteMode.HTMLParse_XMLWorkerAdvanced} (alias {(int)PDFWriteMode.HTMLParse_XMLWorkerAdvanced}): uses iTextSharp.tool.xml.XMLWorkerHelper with empty CSS");
                 Console.WriteLine("");
                 Console.WriteLine("Second argument is an optional separator for the input text that will be used to split it into multiple pages");
+                Console.WriteLine("");
+                Console.WriteLine("Options that can be placed anywhere:");
+                Console.WriteLine("--page-size <SIZE>: one of A3, A4 (default), A5, Letter, Legal or Tabloid, or a custom size in points like 595x842");
+                Console.WriteLine("--landscape: turn the pages to landscape");
+                Console.WriteLine("--margin <MARGIN>: the top, right, bottom and left margin in points, for example \"36 36 36 36\" (default)");
+                Console.WriteLine("--font <FILE>: a font file that the HTML can use, can be specified multiple times");
                 return;
             }
             try
             {
+                // Named options can be anywhere, the remaining arguments are positional:
+                Rectangle pageSize = PageSize.A4;
+                bool landscape = false;
+                float[] margins = null;
+                var fonts = new List<string>();
+                var positional = new List<string>();
+                for (int i = 0; i < args.Length; i++)
+                {
+                    if (args[i] == "--page-size" && i + 1 < args.Length)
+                    {
+                        i++;
+                        switch (args[i].ToUpperInvariant())
+                        {
+                            case "A3": pageSize = PageSize.A3; break;
+                            case "A4": pageSize = PageSize.A4; break;
+                            case "A5": pageSize = PageSize.A5; break;
+                            case "LETTER": pageSize = PageSize.LETTER; break;
+                            case "LEGAL": pageSize = PageSize.LEGAL; break;
+                            case "TABLOID": pageSize = PageSize.TABLOID; break;
+                            default:
+                                var size = args[i].Split('x', 'X');
+                                if (size.Length == 2 &&
+                                    float.TryParse(size[0], NumberStyles.Float, CultureInfo.InvariantCulture, out float width) &&
+                                    float.TryParse(size[1], NumberStyles.Float, CultureInfo.InvariantCulture, out float height))
+                                {
+                                    pageSize = new Rectangle(width, height);
+                                    break;
+                                }
+                                Console.Error.WriteLine($"--page-size is invalid, found \"{args[i]}\" but expected A3, A4, A5, Letter, Legal, Tabloid or WIDTHxHEIGHT");
+                                Environment.Exit(3);
+                                break;
+                        }
+                    }
+                    else if (args[i] == "--margin" && i + 1 < args.Length)
+                    {
+                        i++;
+                        var values = args[i].Split(new[] { ' ' }, StringSplitOptions.RemoveEmptyEntries);
+                        margins = new float[values.Length];
+                        for (int j = 0; j < values.Length; j++)
+                        {
+                            if (!float.TryParse(values[j], NumberStyles.Float, CultureInfo.InvariantCulture, out margins[j]))
+                            {
+                                margins = null;
+                                break;
+                            }
+                        }
+                        if (margins == null || margins.Length != 4)
+                        {
+                            Console.Error.WriteLine($"--margin is invalid, found \"{args[i]}\" but expected four numbers like \"36 36 36 36\"");
+                            Environment.Exit(3);
+                        }
+                    }
+                    else if (args[i] == "--landscape")
+                    {
+                        landscape = true;
+                    }
+                    else if (args[i] == "--font" && i + 1 < args.Length)
+                    {
+                        fonts.Add(args[++i]);
+                    }
+                    else
+                    {
+                        positional.Add(args[i]);
+                    }
+                }
+                if (landscape)
+                {
+                    // Swap the sides instead of using Rotate() so that the MediaBox is landscape
+                    // rather than a portrait page with a /Rotate entry:
+                    pageSize = new Rectangle(pageSize.Height, pageSize.Width);
+                }
+                args = positional.ToArray();
+
+                iTextSharp.tool.xml.XMLWorkerFontProvider fontProvider = null;
+                if (fonts.Count > 0)
+                {
+                    fontProvider = new iTextSharp.tool.xml.XMLWorkerFontProvider(iTextSharp.tool.xml.XMLWorkerFontProvider.DONTLOOKFORFONTS);
+                    foreach (var font in fonts)
+                    {
+                        // Used by the XML worker:
+                        fontProvider.Register(font);
+                        // Used by the obsolete HTML parser:
+                        FontFactory.Register(font);
+                    }
+                }
+
                 var mode = PDFWriteMode.Default;
                 if (args.Length > 0)
                 {
@@ -78,7 +171,7 @@ namespace HtmlToPdf_Framework
                 }
                 using (var stdout = Console.OpenStandardOutput())
                 {
-                    getPDFData(inData, stdout, mode);
+                    getPDFData(inData, stdout, mode, pageSize, fontProvider, margins);
                     // Can use temporary buffer instead of stdout as output stream to ignore System.NotSupportedException: Stream does not support writing.
                     // This likely because we are closing the stream early or something (memory stream probably ignores close and so works anyway).
                     // When this happens we only get part of the HTML convert to a PDF, so its likely that the output is closed early when there are errors.
@@ -167,7 +260,7 @@ namespace HtmlToPdf_Framework
             return bytes;
         }
 
-        private static void getPDFData(string[] HTMLTexts, Stream output, PDFWriteMode HTMLParseMethod = PDFWriteMode.Default)
+        private static void getPDFData(string[] HTMLTexts, Stream output, PDFWriteMode HTMLParseMethod = PDFWriteMode.Default, Rectangle pageSize = null, iTextSharp.tool.xml.XMLWorkerFontProvider fontProvider = null, float[] margins = null)
         {
             if (HTMLParseMethod != PDFWriteMode.HTMLParse_ObsoleteHTMLParser &&
                 HTMLParseMethod != PDFWriteMode.HTMLParse_XMLWorkerAdvanced &&
@@ -196,8 +289,13 @@ namespace HtmlToPdf_Framework
             try
             {
                 //Create an iTextSharp Document which is an abstraction of a PDF but **NOT** a PDF
-                using (var doc = new Document())
+                using (var doc = new Document(pageSize ?? PageSize.A4))
                 {
+                    if (margins != null)
+                    {
+                        // Specified as top, right, bottom, left like in CSS:
+                        doc.SetMargins(margins[3], margins[1], margins[0], margins[2]);
+                    }
                     //Create a writer that's bound to our PDF abstraction and our stream
                     using (var writer = iTextSharp.text.pdf.PdfWriter.GetInstance(doc, output))
                     {
@@ -268,12 +366,23 @@ namespace HtmlToPdf_Framework
                                     * ************************************************/
 
 
-                                //XMLWorker also reads from a TextReader and not directly from a string
-                                using (var srHtml = new StringReader(HTMLText))
+                                if (fontProvider == null)
                                 {
+                                    //XMLWorker also reads from a TextReader and not directly from a string
+                                    using (var srHtml = new StringReader(HTMLText))
+                                    {
 
-                                    //Parse the HTML
-                                    iTextSharp.tool.xml.XMLWorkerHelper.GetInstance().ParseXHtml(writer, doc, srHtml);
+                                        //Parse the HTML
+                                        iTextSharp.tool.xml.XMLWorkerHelper.GetInstance().ParseXHtml(writer, doc, srHtml);
+                                    }
+                                }
+                                else
+                                {
+                                    // Only the overload that takes streams accepts a font provider, without a CSS stream the default CSS is used:
+                                    using (var msHtml = new MemoryStream(System.Text.Encoding.UTF8.GetBytes(HTMLText)))
+                                    {
+                                        iTextSharp.tool.xml.XMLWorkerHelper.GetInstance().ParseXHtml(writer, doc, msHtml, null, System.Text.Encoding.UTF8, fontProvider);
+                                    }
                                 }
                             }
                             else if (HTMLParseMethod == PDFWriteMode.HTMLParse_XMLWorkerAdvanced)
@@ -303,7 +412,14 @@ namespace HtmlToPdf_Framework
                                     using (var msHtml = new MemoryStream(System.Text.Encoding.UTF8.GetBytes(HTMLText)))
                                     {
                                         //Parse the HTML
-                                        iTextSharp.tool.xml.XMLWorkerHelper.GetInstance().ParseXHtml(writer, doc, msHtml, msCss);
+                                        if (fontProvider == null)
+                                        {
+                                            iTextSharp.tool.xml.XMLWorkerHelper.GetInstance().ParseXHtml(writer, doc, msHtml, msCss);
+                                        }
+                                        else
+                                        {
+                                            iTextSharp.tool.xml.XMLWorkerHelper.GetInstance().ParseXHtml(writer, doc, msHtml, msCss, System.Text.Encoding.UTF8, fontProvider);
+                                        }
                                     }
                                 }
                             }
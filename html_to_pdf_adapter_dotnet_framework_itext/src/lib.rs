@@ -1,12 +1,21 @@
 use std::{
+    borrow::Cow,
+    error::Error,
     ffi::OsString,
-    io::{self, BufReader, BufWriter, Write},
+    fmt,
+    io::{self, BufReader, BufWriter},
     path::PathBuf,
-    process::{Child, ChildStdin, Command, Stdio},
+    process::{ChildStdin, Command, Stdio},
+    str::FromStr,
+    time::Duration,
 };
 
-use eyre::{bail, Context, ContextCompat, Result};
-use html_to_pdf::{HtmlSink, HtmlToPdfConverter, PdfScope, PdfScopedJoinHandle, WriteBuilder};
+use eyre::{Context, ContextCompat, Result};
+use html_to_pdf::{
+    copy_with_flush_interval, forward_stderr, hide_console_window, ChildGuard,
+    ConverterCapabilities, HtmlSink, HtmlToPdfConverter, Margin, PageOptions, PageOrientation,
+    Paper, PdfScope, PdfScopedJoinHandle, ProcessError, WithPageOptions, WriteBuilder,
+};
 
 #[cfg(feature = "include_exe")]
 static EMBEDDED_CONVERTER: include_dir::Dir =
@@ -23,6 +32,36 @@ pub enum DotNetFrameworkPdfConverterMode {
     XMLWorkerAdvanced,
 }
 impl DotNetFrameworkPdfConverterMode {
+    /// All modes, for example to let users pick one.
+    pub fn all() -> &'static [Self] {
+        &[
+            DotNetFrameworkPdfConverterMode::Default,
+            DotNetFrameworkPdfConverterMode::ObsoleteHTMLParser,
+            DotNetFrameworkPdfConverterMode::XMLWorkerSimple,
+            DotNetFrameworkPdfConverterMode::XMLWorkerAdvanced,
+        ]
+    }
+    /// A short description of the trade-offs of this mode.
+    pub fn description(&self) -> &'static str {
+        match self {
+            DotNetFrameworkPdfConverterMode::Default => {
+                "Let the converter program choose a mode, might change with newer versions."
+            }
+            DotNetFrameworkPdfConverterMode::ObsoleteHTMLParser => {
+                "Older legacy implementation. Links are not colored blue but can \
+                still be clicked, no PDF Table of Contents and no page breaks from \
+                the HTML (a custom page break string can be used instead)."
+            }
+            DotNetFrameworkPdfConverterMode::XMLWorkerSimple => {
+                "XML implementation in its simpler mode. Supports PDF Table of \
+                Contents but is more than twice as slow when <a> tags are inside a <div>."
+            }
+            DotNetFrameworkPdfConverterMode::XMLWorkerAdvanced => {
+                "XML implementation in advanced mode. No PDF Table of Contents and \
+                more than twice as slow when <a> tags are inside a <div>."
+            }
+        }
+    }
     pub fn as_arg(self) -> &'static str {
         match self {
             DotNetFrameworkPdfConverterMode::Default => "Default",
@@ -31,10 +70,65 @@ impl DotNetFrameworkPdfConverterMode {
             DotNetFrameworkPdfConverterMode::XMLWorkerAdvanced => "HTMLParse_XMLWorkerAdvanced",
         }
     }
+    /// The name of the enum variant, for example `"XMLWorkerSimple"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            DotNetFrameworkPdfConverterMode::Default => "Default",
+            DotNetFrameworkPdfConverterMode::ObsoleteHTMLParser => "ObsoleteHTMLParser",
+            DotNetFrameworkPdfConverterMode::XMLWorkerSimple => "XMLWorkerSimple",
+            DotNetFrameworkPdfConverterMode::XMLWorkerAdvanced => "XMLWorkerAdvanced",
+        }
+    }
 }
+/// Formats the mode with its [`name`](DotNetFrameworkPdfConverterMode::name).
+impl fmt::Display for DotNetFrameworkPdfConverterMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+/// Parses either the [`name`](DotNetFrameworkPdfConverterMode::name) or the
+/// [`as_arg`](DotNetFrameworkPdfConverterMode::as_arg) value of a mode,
+/// ignoring ASCII case.
+impl FromStr for DotNetFrameworkPdfConverterMode {
+    type Err = ParseModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Self::all()
+            .iter()
+            .copied()
+            .find(|mode| {
+                mode.name().eq_ignore_ascii_case(s) || mode.as_arg().eq_ignore_ascii_case(s)
+            })
+            .ok_or_else(|| {
+                let names = Self::all()
+                    .iter()
+                    .map(|mode| mode.name())
+                    .collect::<Vec<_>>();
+                ParseModeError(format!("{s:?} isn't one of the modes {}", names.join(", ")))
+            })
+    }
+}
+impl TryFrom<&str> for DotNetFrameworkPdfConverterMode {
+    type Error = ParseModeError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// A string that isn't a [`DotNetFrameworkPdfConverterMode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseModeError(pub String);
+impl fmt::Display for ParseModeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+impl Error for ParseModeError {}
 
 /// Use a small C# program to generate a PDF.
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DotNetFrameworkPdfConverter {
     /// The program supports different modes since the C# library it uses
     /// has different ways to handle the conversion.
@@ -45,9 +139,77 @@ pub struct DotNetFrameworkPdfConverter {
     /// Extract executable that was embedded into the program at compile time to
     /// this location, and then run them.
     pub extract_included_exe_at: Option<PathBuf>,
+    /// The paper size of the pages. Uses iText's default (A4) if `None`.
+    pub paper: Option<Paper>,
+    /// Turn the pages to landscape, also applies to iText's default size.
+    pub orientation: PageOrientation,
+    /// Space between the edges of the pages and the content. Uses iText's
+    /// default of 36 points (half an inch) on all sides if `None`.
+    pub margin: Option<Margin>,
+    /// Font files (for example `.ttf` or `.otf`) that the HTML can select by
+    /// their family name in CSS, for example to render CJK characters that
+    /// iText's default fonts don't have.
+    ///
+    /// The fonts are embedded in the generated PDF, which is a form of
+    /// redistribution. Make sure that the font's license allows embedding,
+    /// many commercial fonts restrict it.
+    pub fonts: Vec<PathBuf>,
+    /// Flush the output at most this often while PDF data arrives from the
+    /// "HtmlToPdf_Framework" program, so that clients can start reading the
+    /// PDF before a buffered output fills up. `None` (the default) never
+    /// flushes until the conversion is done, which gives the best throughput
+    /// since each flush can mean a small write, see
+    /// [`copy_with_flush_interval`](html_to_pdf::copy_with_flush_interval).
+    pub flush_interval: Option<Duration>,
+    /// Start the "HtmlToPdf_Framework" program without a console window on
+    /// Windows, which otherwise pops up in GUI programs. Disable it to see
+    /// the program's console while debugging. Ignored on other platforms.
+    ///
+    /// Defaults to `true` if the `windows-gui` feature is enabled.
+    pub hide_window: bool,
+}
+// Only derivable if the `windows-gui` feature is disabled:
+#[allow(clippy::derivable_impls)]
+impl Default for DotNetFrameworkPdfConverter {
+    fn default() -> Self {
+        Self {
+            mode: DotNetFrameworkPdfConverterMode::default(),
+            custom_page_break: None,
+            extract_included_exe_at: None,
+            paper: None,
+            orientation: PageOrientation::default(),
+            margin: None,
+            fonts: Vec::new(),
+            flush_interval: None,
+            hide_window: cfg!(feature = "windows-gui"),
+        }
+    }
+}
+impl DotNetFrameworkPdfConverter {
+    /// `true` since the PDF is written to the output while the
+    /// "HtmlToPdf_Framework" program is producing it, so
+    /// [`HtmlSink::complete`] doesn't need to copy the whole document at the
+    /// end.
+    pub const STREAMS_PDF: bool = true;
 }
 pub const RECOMMENDED_PAGE_BREAK: &str = "_____CUSTOM_PAGE_BREAK_____";
 
+/// Uses the page size, orientation and margins. The DPI is ignored.
+impl WithPageOptions for DotNetFrameworkPdfConverter {
+    fn with_page_options(mut self, options: PageOptions) -> Self {
+        if let Some(paper) = options.page_size {
+            self.paper = Some(paper);
+        }
+        if let Some(orientation) = options.orientation {
+            self.orientation = orientation;
+        }
+        if let Some(margin) = options.margins {
+            self.margin = Some(margin);
+        }
+        self
+    }
+}
+
 impl<'scope, W> HtmlToPdfConverter<'scope, W> for DotNetFrameworkPdfConverter
 where
     W: WriteBuilder + Send + 'scope,
@@ -84,16 +246,29 @@ where
 
         let DotNetFrameworkPdfConverter { mode, .. } = self;
         let mut process = Command::new(&program_path);
-        #[cfg(all(windows, feature = "windows-gui"))]
-        {
-            use std::os::windows::process::CommandExt;
-
-            // Hide console window:
-            // https://stackoverflow.com/questions/6371149/what-is-the-difference-between-detach-process-and-create-no-window-process-creat
-            // https://learn.microsoft.com/sv-se/windows/win32/procthread/process-creation-flags?redirectedfrom=MSDN
-            // Need "CREATE_NO_WINDOW" if the created process will spawn its own sub-processes,
-            // otherwise DETACHED_PROCESS is enough to prevent a console from being opened.
-            process.creation_flags(/*CREATE_NO_WINDOW*/ 0x08000000);
+        hide_console_window(&mut process, self.hide_window);
+        if let Some(paper) = self.paper {
+            process.arg("--page-size").arg(match paper {
+                Paper::Standard(size) => size.name().to_owned(),
+                Paper::Custom { width, height } => {
+                    format!("{}x{}", width.points(), height.points())
+                }
+            });
+        }
+        if self.orientation == PageOrientation::Landscape {
+            process.arg("--landscape");
+        }
+        if let Some(margin) = self.margin {
+            process.arg("--margin").arg(format!(
+                "{} {} {} {}",
+                margin.top.points(),
+                margin.right.points(),
+                margin.bottom.points(),
+                margin.left.points()
+            ));
+        }
+        for font in &self.fonts {
+            process.arg("--font").arg(font);
         }
         process.arg(mode.as_arg());
 
@@ -109,6 +284,7 @@ where
         let mut process = process
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             .spawn()
             .with_context(|| {
                 format!(
@@ -126,24 +302,46 @@ where
             .stdin
             .take()
             .context(r#"Couldn't open stdin for "HtmlToPdf_Framework.exe" conversion program."#)?;
+        let stderr = process
+            .stderr
+            .take()
+            .context(r#"Couldn't open stderr for "HtmlToPdf_Framework.exe" conversion program."#)?;
 
+        let flush_interval = self.flush_interval;
         let reader_thread =
-            scope.spawn(move || -> Result<_> {
+            scope.spawn_named("dotnet-itext-framework reader", move || -> Result<_> {
                 let mut pdf_reader = BufReader::new(pdf_reader);
                 // Read piped "ToPdf" stdout and redirect it to our output writer:
 
-                io::copy(&mut pdf_reader, &mut output.get_writer()?).context(
+                copy_with_flush_interval(&mut pdf_reader, &mut output.get_writer()?, flush_interval).context(
                 r#"Failed to read pdf data from "HtmlToPdf_Framework" program's stdout and write it to output."#
             )?;
                 Ok(output)
             });
 
         Ok(DotNetFrameworkHtmlSink(DotNetFrameworkHtmlSinkInner {
-            process,
+            process: process.into(),
             reader_thread,
+            stderr_thread: std::thread::spawn(move || forward_stderr(stderr, io::stderr())),
             writer: BufWriter::new(pdf_writer),
         }))
     }
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("dotnet-itext-framework")
+    }
+
+    /// Only [`XMLWorkerSimple`](DotNetFrameworkPdfConverterMode::XMLWorkerSimple)
+    /// creates an outline. iText uses the standard PDF fonts, which aren't
+    /// embedded, unless [`fonts`](Self::fonts) are given.
+    fn capabilities(&self) -> ConverterCapabilities {
+        ConverterCapabilities {
+            table_of_contents: self.mode == DotNetFrameworkPdfConverterMode::XMLWorkerSimple,
+            clickable_links: true,
+            streaming_input: true,
+            embedded_fonts: !self.fonts.is_empty(),
+        }
+    }
 }
 impl<'scope, W> HtmlSink<W, eyre::Error> for DotNetFrameworkHtmlSink<'scope, W>
 where
@@ -154,6 +352,7 @@ where
             mut process,
             writer,
             reader_thread,
+            stderr_thread,
         }) = self;
 
         // The HtmlToPdf_Framework conversion program's stdin pipe was owned by
@@ -165,49 +364,117 @@ where
             r#"Failed to wait for the "HtmlToPdf_Framework" conversion program to exit."#,
         )?;
 
-        if let Some(error_code) = exit_status.code() {
-            if error_code != 0 {
-                bail!(
-                    r#"The "HtmlToPdf_Framework" conversion program exited with an error (code: {})."#,
-                    error_code
-                );
-            }
-        } else {
-            bail!(
-                r#"The "HtmlToPdf_Framework" conversion program exited with an error (no exit code)."#
-            );
-        };
+        // Finishes when the program's stderr is closed:
+        let stderr = stderr_thread.join().unwrap_or_default();
+        if !exit_status.success() {
+            return Err(ProcessError::new("HtmlToPdf_Framework", exit_status)
+                .with_stderr(stderr)
+                .into());
+        }
         // The worker thread should finish now that stdout for "HtmlToPdf_Framework" has been closed.
-        reader_thread.join().unwrap()
+        reader_thread
+            .join_result()
+            .context(r#"The thread reading PDF data from the "HtmlToPdf_Framework" conversion program failed"#)?
     }
 }
 
 struct DotNetFrameworkHtmlSinkInner<'scope, W> {
-    process: Child,
+    /// Dropped first so that a sink that is dropped without being completed
+    /// kills the program before its stdin is closed, otherwise the program
+    /// would convert the partially written HTML.
+    process: ChildGuard,
     writer: BufWriter<ChildStdin>,
     reader_thread: PdfScopedJoinHandle<'scope, Result<W>>,
+    /// Returns the end of the program's stderr.
+    stderr_thread: std::thread::JoinHandle<String>,
 }
 pub struct DotNetFrameworkHtmlSink<'scope, W>(DotNetFrameworkHtmlSinkInner<'scope, W>);
-impl<W> DotNetFrameworkHtmlSink<'_, W> {
-    #[inline]
-    fn writer(&mut self) -> &mut BufWriter<ChildStdin> {
-        &mut self.0.writer
-    }
-}
-impl<W> Write for DotNetFrameworkHtmlSink<'_, W> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.writer().write(buf)
-    }
-    fn flush(&mut self) -> io::Result<()> {
-        self.writer().flush()
+html_to_pdf::forward_write!(
+    impl['scope, W] for DotNetFrameworkHtmlSink<'scope, W>,
+    |this| &mut this.0.writer
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use html_to_pdf::PageSize;
+
+    #[test]
+    fn all_modes_have_descriptions() {
+        // Update `all` if this match stops compiling:
+        for mode in DotNetFrameworkPdfConverterMode::all() {
+            match mode {
+                DotNetFrameworkPdfConverterMode::Default
+                | DotNetFrameworkPdfConverterMode::ObsoleteHTMLParser
+                | DotNetFrameworkPdfConverterMode::XMLWorkerSimple
+                | DotNetFrameworkPdfConverterMode::XMLWorkerAdvanced => {}
+            }
+            assert!(!mode.description().is_empty());
+        }
+        assert_eq!(DotNetFrameworkPdfConverterMode::all().len(), 4);
     }
-    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
-        self.writer().write_vectored(bufs)
+
+    #[test]
+    fn modes_are_parsed_from_names_and_args() {
+        for &mode in DotNetFrameworkPdfConverterMode::all() {
+            assert_eq!(mode.to_string().parse(), Ok(mode));
+            assert_eq!(
+                DotNetFrameworkPdfConverterMode::try_from(mode.as_arg()),
+                Ok(mode)
+            );
+        }
+        assert_eq!(
+            "xmlworkersimple".parse(),
+            Ok(DotNetFrameworkPdfConverterMode::XMLWorkerSimple)
+        );
+
+        let error = "Fast"
+            .parse::<DotNetFrameworkPdfConverterMode>()
+            .unwrap_err();
+        assert!(error.to_string().contains("XMLWorkerAdvanced"), "{error}");
     }
-    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-        self.writer().write_all(buf)
+
+    #[test]
+    #[ignore = "requires the \"HtmlToPdf_Framework\" program"]
+    fn a3_landscape_changes_media_box() {
+        let converter = DotNetFrameworkPdfConverter {
+            paper: Some(PageSize::A3.into()),
+            orientation: PageOrientation::Landscape,
+            ..Default::default()
+        };
+        let mut html_sink = converter
+            .start(
+                PdfScope::owned(),
+                html_to_pdf::WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+        io::Write::write_all(&mut html_sink, b"<p>Hello</p>").unwrap();
+        let pdf = html_sink.complete().unwrap().0;
+
+        let pdf = String::from_utf8_lossy(&pdf);
+        let media_box = pdf
+            .split("/MediaBox")
+            .nth(1)
+            .and_then(|rest| rest.trim_start().strip_prefix('['))
+            .and_then(|rest| rest.split(']').next())
+            .expect("PDF should have a media box");
+        let size = media_box
+            .split_whitespace()
+            .map(|v| v.parse::<f64>().unwrap())
+            .collect::<Vec<_>>();
+        let (width, height) = PageOrientation::Landscape.apply(PageSize::A3.size_in_points());
+        assert_eq!(size.len(), 4);
+        assert!((size[2] - size[0] - width).abs() < 1.0, "{media_box}");
+        assert!((size[3] - size[1] - height).abs() < 1.0, "{media_box}");
     }
-    fn write_fmt(&mut self, fmt: std::fmt::Arguments<'_>) -> io::Result<()> {
-        self.writer().write_fmt(fmt)
+
+    #[test]
+    fn reports_name() {
+        type Output = html_to_pdf::WriteBuilderSimple<Vec<u8>>;
+        let converter = DotNetFrameworkPdfConverter::default();
+        assert_eq!(
+            HtmlToPdfConverter::<Output>::name(&converter),
+            "dotnet-itext-framework"
+        );
     }
 }
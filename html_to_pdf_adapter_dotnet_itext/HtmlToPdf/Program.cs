@@ -1,10 +1,138 @@
 ﻿// See https://aka.ms/new-console-template for more information
 
+using iText.Html2pdf;
+using iText.Html2pdf.Resolver.Font;
+using iText.Kernel.Geom;
+using iText.Kernel.Pdf;
+using iText.Pdfa;
+using iText.StyledXmlParser.Resolver.Resource;
+using System.Globalization;
+
 try
 {
+    // Optional PDF/A output, for example: --pdf-a 2B --icc-profile sRGB.icm
+    string? pdfA = null;
+    string? iccProfile = null;
+    // Optional folder with the resources that the HTML references: --assets DIR
+    string? assets = null;
+    // Optional page setup for pages without a CSS @page size: --page-size A3 --landscape
+    // A custom size is specified as WIDTHxHEIGHT in points, for example: --page-size 595x842
+    PageSize pageSize = PageSize.DEFAULT;
+    bool landscape = false;
+    // Optional font files that the HTML can use, can be specified multiple times: --font FILE
+    List<string> fonts = new List<string>();
+    // Convert many documents instead of one, see Serve below: --server
+    bool server = false;
+    for (int i = 0; i < args.Length; i++)
+    {
+        switch (args[i])
+        {
+            case "--pdf-a" when i + 1 < args.Length:
+                pdfA = args[++i];
+                break;
+            case "--icc-profile" when i + 1 < args.Length:
+                iccProfile = args[++i];
+                break;
+            case "--assets" when i + 1 < args.Length:
+                assets = args[++i];
+                break;
+            case "--page-size" when i + 1 < args.Length:
+                pageSize = args[++i].ToUpperInvariant() switch
+                {
+                    "A3" => PageSize.A3,
+                    "A4" => PageSize.A4,
+                    "A5" => PageSize.A5,
+                    "LETTER" => PageSize.LETTER,
+                    "LEGAL" => PageSize.LEGAL,
+                    "TABLOID" => PageSize.TABLOID,
+                    string custom when custom.Split('X') is [string width, string height] => new PageSize(
+                        float.Parse(width, CultureInfo.InvariantCulture),
+                        float.Parse(height, CultureInfo.InvariantCulture)
+                    ),
+                    _ => throw new ArgumentException($"Unknown page size: {args[i]}"),
+                };
+                break;
+            case "--landscape":
+                landscape = true;
+                break;
+            case "--font" when i + 1 < args.Length:
+                fonts.Add(args[++i]);
+                break;
+            case "--server":
+                server = true;
+                break;
+            default:
+                throw new ArgumentException($"Unknown argument: {args[i]}");
+        }
+    }
+
+    ConverterProperties properties = new ConverterProperties();
+    if (assets is not null)
+    {
+        // Resolve relative URLs against the assets folder and never fetch anything over the network:
+        properties
+            .SetBaseUri(assets)
+            .SetResourceRetriever(new LocalFileResourceRetriever());
+    }
+    if (fonts.Count > 0 || pdfA is not null)
+    {
+        // PDF/A requires all fonts to be embedded so don't use the standard PDF fonts:
+        DefaultFontProvider fontProvider = new DefaultFontProvider(pdfA is null, true, false);
+        foreach (string font in fonts)
+        {
+            if (!fontProvider.AddFont(font))
+            {
+                throw new ArgumentException($"Failed to load font: {font}");
+            }
+        }
+        properties.SetFontProvider(fontProvider);
+    }
+    if (landscape)
+    {
+        pageSize = pageSize.Rotate();
+    }
+    PdfAConformanceLevel? conformance = null;
+    if (pdfA is not null)
+    {
+        if (iccProfile is null)
+        {
+            throw new ArgumentException("--pdf-a requires an ICC color profile specified with --icc-profile");
+        }
+        conformance = PdfAConformanceLevel.GetConformanceLevel(pdfA.Substring(0, 1), pdfA.Substring(1))
+            ?? throw new ArgumentException($"Unknown PDF/A conformance level: {pdfA}");
+    }
+
+    void Convert(Stream html, Stream pdf)
+    {
+        if (conformance is null)
+        {
+            PdfDocument document = new PdfDocument(new PdfWriter(pdf));
+            document.SetDefaultPageSize(pageSize);
+            HtmlConverter.ConvertToPdf(html, document, properties);
+        }
+        else
+        {
+            using Stream icc = File.OpenRead(iccProfile!);
+            PdfADocument document = new PdfADocument(
+                new PdfWriter(pdf),
+                conformance,
+                new PdfOutputIntent("Custom", "", "http://www.color.org", "sRGB IEC61966-2.1", icc)
+            );
+            document.SetDefaultPageSize(pageSize);
+            HtmlConverter.ConvertToPdf(html, document, properties);
+        }
+    }
+
     using Stream stdin = Console.OpenStandardInput();
     using Stream stdout = Console.OpenStandardOutput();
-    iText.Html2pdf.HtmlConverter.ConvertToPdf(stdin, stdout);
+    if (server)
+    {
+        Serve(stdin, stdout, Convert);
+    }
+    else
+    {
+        Convert(stdin, stdout);
+    }
 }
 catch (Exception ex)
 {
@@ -12,3 +140,64 @@ catch (Exception ex)
     Environment.Exit(1);
 }
 Environment.Exit(0);
+
+/// <summary>
+/// Convert one document per request until stdin is closed. A request is the length of the HTML as a
+/// little endian 32-bit integer followed by the HTML. The response is a status byte (0 if the conversion
+/// succeeded, 1 if it failed) followed by the length of the data as a little endian 32-bit integer and
+/// then the PDF or a UTF-8 error message.
+/// </summary>
+static void Serve(Stream input, Stream output, Action<Stream, Stream> convert)
+{
+    BinaryReader reader = new BinaryReader(input);
+    BinaryWriter writer = new BinaryWriter(output);
+    while (true)
+    {
+        byte[] header = reader.ReadBytes(4);
+        if (header.Length == 0)
+        {
+            // No more requests:
+            return;
+        }
+        if (header.Length < 4)
+        {
+            throw new EndOfStreamException("stdin was closed in the middle of a request");
+        }
+        int length = System.Buffers.Binary.BinaryPrimitives.ReadInt32LittleEndian(header);
+        byte[] html = reader.ReadBytes(length);
+        if (html.Length < length)
+        {
+            throw new EndOfStreamException("stdin was closed in the middle of a request");
+        }
+
+        byte status;
+        byte[] response;
+        try
+        {
+            // The PDF writer closes the stream, but ToArray still works after that:
+            MemoryStream pdf = new MemoryStream();
+            convert(new MemoryStream(html), pdf);
+            status = 0;
+            response = pdf.ToArray();
+        }
+        catch (Exception ex)
+        {
+            status = 1;
+            response = System.Text.Encoding.UTF8.GetBytes(ex.ToString());
+        }
+        // BinaryWriter always uses little endian:
+        writer.Write(status);
+        writer.Write(response.Length);
+        writer.Write(response);
+        writer.Flush();
+    }
+}
+
+/// <summary>Only loads resources from local files.</summary>
+class LocalFileResourceRetriever : DefaultResourceRetriever
+{
+    public override Stream? GetInputStreamByUrl(Uri url)
+    {
+        return url.IsFile ? base.GetInputStreamByUrl(url) : null;
+    }
+}
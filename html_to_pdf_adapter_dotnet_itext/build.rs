@@ -29,6 +29,26 @@ fn main() {
             build_status.success(),
             "Build of C# HtmlToPdf should succeed."
         );
+        let exe_path = PathBuf::from(&out_dir).join(format!(
+            "HtmlToPdf_Publish/HtmlToPdf{}",
+            if std::env::var_os("CARGO_CFG_WINDOWS").is_some() {
+                ".exe"
+            } else {
+                ""
+            }
+        ));
+
+        // Store the checksum so that the extracted exe can be verified at runtime:
+        {
+            use sha2::{Digest, Sha256};
+
+            let exe = fs::read(&exe_path).expect("Failed to read published HtmlToPdf program");
+            fs::write(
+                PathBuf::from(&out_dir).join("HtmlToPdf.sha256"),
+                Sha256::digest(exe),
+            )
+            .unwrap();
+        }
 
         // Generate compressed include macro with path to ".dll" file since the macro can't specify path's relative to env!("OUT_DIR"):
         fs::write(
@@ -40,11 +60,7 @@ fn embedded_converter() -> &'static [u8] {{
     &*EMBEDDED_CONVERTER_DATA
 }}
 "#####,
-            PathBuf::from(&out_dir)
-                .join(format!(
-                    "HtmlToPdf_Publish/HtmlToPdf{}",
-                    if std::env::var_os("CARGO_CFG_WINDOWS").is_some() { ".exe" } else { "" }
-                ))
+            exe_path
                 .to_str()
                 .expect("the OUT_DIR should be valid UTF-8")
         ),
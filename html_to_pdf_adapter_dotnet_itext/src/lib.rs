@@ -1,12 +1,19 @@
 use std::{
+    borrow::Cow,
     ffi::OsString,
-    io::{self, BufReader, BufWriter, Write},
+    io::{self, BufReader, BufWriter, Read, Write},
     path::PathBuf,
-    process::{Child, ChildStdin, Command, Stdio},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::Mutex,
+    time::Duration,
 };
 
-use eyre::{bail, Context, ContextCompat, Result};
-use html_to_pdf::{HtmlSink, HtmlToPdfConverter, PdfScope, PdfScopedJoinHandle, WriteBuilder};
+use eyre::{bail, eyre, Context, ContextCompat, Result};
+use html_to_pdf::{
+    copy_with_flush_interval, forward_stderr, hide_console_window, AssetDir, Assets, ChildGuard,
+    ConverterCapabilities, HtmlSink, HtmlToPdfConverter, PageOptions, PageOrientation, Paper,
+    PdfAConformance, PdfScope, PdfScopedJoinHandle, ProcessError, WithPageOptions, WriteBuilder,
+};
 
 #[cfg(all(feature = "include_exe", feature = "compression"))]
 include!(concat!(env!("OUT_DIR"), "/compressed.rs"));
@@ -22,12 +29,125 @@ fn embedded_converter() -> &'static [u8] {
     EMBEDDED_CONVERTER
 }
 
+/// SHA-256 digest of [`embedded_converter`], computed by the build script.
+#[cfg(feature = "include_exe")]
+static EMBEDDED_CONVERTER_SHA256: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/HtmlToPdf.sha256"));
+
+/// Write `data` to `path` unless the file there already has the expected
+/// SHA-256 digest, so that a partially written or modified file is replaced
+/// instead of executed.
+#[cfg(feature = "include_exe")]
+fn extract_verified(path: &std::path::Path, data: &[u8], expected_sha256: &[u8]) -> Result<()> {
+    fn sha256_of_file(path: &std::path::Path) -> io::Result<Vec<u8>> {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+        Ok(hasher.finalize().to_vec())
+    }
+
+    if sha256_of_file(path).is_ok_and(|digest| digest == expected_sha256) {
+        return Ok(());
+    }
+    std::fs::write(path, data)
+        .with_context(|| format!("Failed to write file at: {}", path.display()))?;
+
+    let digest = sha256_of_file(path)
+        .with_context(|| format!("Failed to read file at: {}", path.display()))?;
+    if digest != expected_sha256 {
+        bail!(
+            "The SHA-256 checksum of the extracted file at {} didn't match the embedded data",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
 /// Use a small C# program to generate a PDF.
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DotNetPdfConverter {
     /// Extract executable that was embedded into the program at compile time to
     /// this location, and then run them.
     pub extract_included_exe_at: Option<PathBuf>,
+    /// Write a PDF/A document with the specified conformance level. Requires
+    /// [`icc_profile`](Self::icc_profile) to be set.
+    pub pdf_a: Option<PdfAConformance>,
+    /// Path to an ICC color profile (usually sRGB) that is embedded as the
+    /// document's output intent. PDF/A requires this to make colors device
+    /// independent.
+    ///
+    /// On Windows a suitable profile can be found at
+    /// `C:\Windows\System32\spool\drivers\color\sRGB Color Space Profile.icm`.
+    pub icc_profile: Option<PathBuf>,
+    /// Resources that the HTML references, see [`Assets`] for how their paths
+    /// are mapped. They are written to a temporary folder that relative URLs
+    /// are resolved against.
+    ///
+    /// If there are any assets then the "HtmlToPdf" program only loads local
+    /// files, so nothing is fetched over the network.
+    pub assets: Assets,
+    /// The paper size to use for pages that don't declare their own size with
+    /// a CSS `@page` rule. Uses iText's default (A4) if `None`.
+    ///
+    /// There is no margin option since iText takes margins from CSS `@page`
+    /// rules, which default to 36 points (half an inch) on all sides.
+    pub paper: Option<Paper>,
+    /// Turn the pages to landscape, also applies to iText's default size.
+    pub orientation: PageOrientation,
+    /// Font files (for example `.ttf` or `.otf`) that the HTML can use, for
+    /// example to render CJK characters that iText's default fonts don't
+    /// have. They are selected by their family name in CSS and also used as
+    /// fallbacks for characters that the selected font doesn't have.
+    ///
+    /// The fonts are embedded (as subsets) in the generated PDF, which is a
+    /// form of redistribution. Make sure that the font's license allows
+    /// embedding, many commercial fonts restrict it.
+    pub fonts: Vec<PathBuf>,
+    /// Flush the output at most this often while PDF data arrives from the
+    /// "HtmlToPdf" program, so that clients can start reading the PDF
+    /// before a buffered output fills up. `None` (the default) never flushes
+    /// until the conversion is done, which gives the best throughput since
+    /// each flush can mean a small write, see
+    /// [`copy_with_flush_interval`](html_to_pdf::copy_with_flush_interval).
+    pub flush_interval: Option<Duration>,
+    /// Start the "HtmlToPdf" program without a console window on Windows,
+    /// which otherwise pops up in GUI programs. Disable it to see the
+    /// program's console while debugging. Ignored on other platforms.
+    ///
+    /// Defaults to `true` if the `windows-gui` feature is enabled.
+    pub hide_window: bool,
+}
+// Only derivable if the `windows-gui` feature is disabled:
+#[allow(clippy::derivable_impls)]
+impl Default for DotNetPdfConverter {
+    fn default() -> Self {
+        Self {
+            extract_included_exe_at: None,
+            pdf_a: None,
+            icc_profile: None,
+            assets: Assets::default(),
+            paper: None,
+            orientation: PageOrientation::default(),
+            fonts: Vec::new(),
+            flush_interval: None,
+            hide_window: cfg!(feature = "windows-gui"),
+        }
+    }
+}
+
+/// Uses the page size and orientation. Margins are ignored since iText takes
+/// them from CSS `@page` rules, and so is the DPI.
+impl WithPageOptions for DotNetPdfConverter {
+    fn with_page_options(mut self, options: PageOptions) -> Self {
+        if let Some(paper) = options.page_size {
+            self.paper = Some(paper);
+        }
+        if let Some(orientation) = options.orientation {
+            self.orientation = orientation;
+        }
+        self
+    }
 }
 
 impl<'scope, W> HtmlToPdfConverter<'scope, W> for DotNetPdfConverter
@@ -37,11 +157,75 @@ where
     type HtmlSink = DotNetHtmlSink<'scope, W>;
     type Error = eyre::Error;
 
-    fn start(
-        self,
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        let (mut process, assets) = self.command()?;
+
+        let process = process
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(r#"Failed to start "HtmlToPdf" in order to convert HTML to PDF."#)?;
+
+        DotNetHtmlSink::from_process(scope, process, output, self.flush_interval, assets)
+    }
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("dotnet-itext")
+    }
+
+    fn capabilities(&self) -> ConverterCapabilities {
+        ConverterCapabilities {
+            table_of_contents: false,
+            clickable_links: true,
+            streaming_input: true,
+            embedded_fonts: true,
+        }
+    }
+}
+impl<'scope, W> DotNetHtmlSink<'scope, W>
+where
+    W: WriteBuilder + Send + 'scope,
+{
+    fn from_process(
         scope: PdfScope<'scope, '_>,
-        mut output: W,
-    ) -> Result<Self::HtmlSink, Self::Error> {
+        mut process: Child,
+        output: W,
+        flush_interval: Option<Duration>,
+        assets: Option<AssetDir>,
+    ) -> Result<Self> {
+        let pdf_reader = process
+            .stdout
+            .take()
+            .context(r#"Couldn't open stdout for "HtmlToPdf" conversion program."#)?;
+        let pdf_writer = process
+            .stdin
+            .take()
+            .context(r#"Couldn't open stdin for "HtmlToPdf" conversion program."#)?;
+        let stderr = process
+            .stderr
+            .take()
+            .context(r#"Couldn't open stderr for "HtmlToPdf" conversion program."#)?;
+
+        Ok(DotNetHtmlSink(DotNetHtmlSinkInner {
+            process: process.into(),
+            stderr_thread: std::thread::spawn(move || forward_stderr(stderr, io::stderr())),
+            reader_thread: forward_pdf(scope, pdf_reader, output, flush_interval),
+            writer: BufWriter::new(pdf_writer),
+            _assets: assets,
+        }))
+    }
+}
+impl DotNetPdfConverter {
+    /// `true` since the PDF is written to the output while the "HtmlToPdf"
+    /// program is producing it, so [`HtmlSink::complete`] doesn't need to copy
+    /// the whole document at the end.
+    pub const STREAMS_PDF: bool = true;
+
+    /// The command that runs the "HtmlToPdf" program with these options. Also
+    /// returns the folder with the assets, which must be kept until the
+    /// program has exited.
+    fn command(&self) -> Result<(Command, Option<AssetDir>)> {
         #[allow(unused_mut)]
         let mut program_path = OsString::from("HtmlToPdf");
         #[cfg(feature = "include_exe")]
@@ -49,19 +233,21 @@ where
             if !path.exists() {
                 std::fs::create_dir_all(path)
                     .with_context(|| format!("Failed to create folder at: {}", path.display()))?;
-                std::fs::write(
-                    path.join(if cfg!(windows) {
-                        "HtmlToPdf.exe"
-                    } else {
-                        "HtmlToPdf"
-                    }),
-                    embedded_converter(),
-                )
-                .context(
-                    "Failed to extract HtmlToPdf.exe that was \
-                    embedded into the program at compile time",
-                )?;
             }
+            // Checked every time since the file might have been modified:
+            extract_verified(
+                &path.join(if cfg!(windows) {
+                    "HtmlToPdf.exe"
+                } else {
+                    "HtmlToPdf"
+                }),
+                embedded_converter(),
+                EMBEDDED_CONVERTER_SHA256,
+            )
+            .context(
+                "Failed to extract HtmlToPdf.exe that was \
+                embedded into the program at compile time",
+            )?;
             program_path = path.join("HtmlToPdf").into();
         }
         #[cfg(not(feature = "include_exe"))]
@@ -73,50 +259,67 @@ where
         }
 
         let mut process = Command::new(program_path);
-        #[cfg(all(windows, feature = "windows-gui"))]
-        {
-            use std::os::windows::process::CommandExt;
-
-            // Hide console window:
-            // https://stackoverflow.com/questions/6371149/what-is-the-difference-between-detach-process-and-create-no-window-process-creat
-            // https://learn.microsoft.com/sv-se/windows/win32/procthread/process-creation-flags?redirectedfrom=MSDN
-            // Need "CREATE_NO_WINDOW" if the created process will spawn its own sub-processes,
-            // otherwise DETACHED_PROCESS is enough to prevent a console from being opened.
-            process.creation_flags(/*CREATE_NO_WINDOW*/ 0x08000000);
+        if let Some(pdf_a) = self.pdf_a {
+            let icc_profile = self.icc_profile.as_deref().with_context(|| {
+                format!(
+                    "An ICC color profile must be specified in order to create a {pdf_a} document"
+                )
+            })?;
+            process
+                .arg("--pdf-a")
+                .arg(format!("{}{}", pdf_a.part(), pdf_a.level()))
+                .arg("--icc-profile")
+                .arg(icc_profile);
         }
+        if let Some(paper) = self.paper {
+            process.arg("--page-size").arg(match paper {
+                Paper::Standard(size) => size.name().to_owned(),
+                Paper::Custom { width, height } => {
+                    format!("{}x{}", width.points(), height.points())
+                }
+            });
+        }
+        if self.orientation == PageOrientation::Landscape {
+            process.arg("--landscape");
+        }
+        for font in &self.fonts {
+            process.arg("--font").arg(font);
+        }
+        let assets = if self.assets.is_empty() {
+            None
+        } else {
+            let assets = AssetDir::create(&self.assets)
+                .context("Failed to write the assets to a temporary folder")?;
+            process.arg("--assets").arg(assets.path());
+            Some(assets)
+        };
+        hide_console_window(&mut process, self.hide_window);
+        Ok((process, assets))
+    }
+}
 
-        let mut process = process
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-            .context(r#"Failed to start "HtmlToPdf" in order to convert HTML to PDF."#)?;
-
-        let pdf_reader = process
-            .stdout
-            .take()
-            .context(r#"Couldn't open stdout for "HtmlToPdf" conversion program."#)?;
-        let pdf_writer = process
-            .stdin
-            .take()
-            .context(r#"Couldn't open stdin for "HtmlToPdf" conversion program."#)?;
-
-        let reader_thread =
-            scope.spawn(move || -> Result<_> {
-                let mut pdf_reader = BufReader::new(pdf_reader);
-                // Read piped "ToPdf" stdout and redirect it to our output writer:
+/// Read the PDF from the "HtmlToPdf" program's stdout on a separate thread and
+/// write each chunk to the output as soon as it arrives.
+fn forward_pdf<'scope, R, W>(
+    scope: PdfScope<'scope, '_>,
+    pdf_reader: R,
+    mut output: W,
+    flush_interval: Option<Duration>,
+) -> PdfScopedJoinHandle<'scope, Result<W>>
+where
+    R: Read + Send + 'scope,
+    W: WriteBuilder + Send + 'scope,
+{
+    scope.spawn_named("dotnet-itext reader", move || -> Result<_> {
+        let mut pdf_reader = BufReader::new(pdf_reader);
+        // Read piped "ToPdf" stdout and redirect it to our output writer:
 
-                io::copy(&mut pdf_reader, &mut output.get_writer()?).context(
-                r#"Failed to read pdf data from "HtmlToPdf" program's stdout and write it to output."#
+        copy_with_flush_interval(&mut pdf_reader, &mut output.get_writer()?, flush_interval)
+            .context(
+                r#"Failed to read pdf data from "HtmlToPdf" program's stdout and write it to output."#,
             )?;
-                Ok(output)
-            });
-
-        Ok(DotNetHtmlSink(DotNetHtmlSinkInner {
-            process,
-            reader_thread,
-            writer: BufWriter::new(pdf_writer),
-        }))
-    }
+        Ok(output)
+    })
 }
 impl<'scope, W> HtmlSink<W, eyre::Error> for DotNetHtmlSink<'scope, W>
 where
@@ -127,6 +330,8 @@ where
             mut process,
             writer,
             reader_thread,
+            stderr_thread,
+            _assets,
         }) = self;
 
         // The HtmlToPdf conversion program's stdin pipe was owned by
@@ -138,47 +343,515 @@ where
             .wait()
             .context(r#"Failed to wait for the "HtmlToPdf" conversion program to exit."#)?;
 
-        if let Some(error_code) = exit_status.code() {
-            if error_code != 0 {
-                bail!(
-                    r#"The "HtmlToPdf" conversion program exited with an error (code: {})."#,
-                    error_code
-                );
-            }
-        } else {
-            bail!(r#"The "HtmlToPdf" conversion program exited with an error (no exit code)."#);
-        };
+        // Finishes when the program's stderr is closed:
+        let stderr = stderr_thread.join().unwrap_or_default();
+        if !exit_status.success() {
+            return Err(ProcessError::new("HtmlToPdf", exit_status)
+                .with_stderr(stderr)
+                .into());
+        }
         // The worker thread should finish now that stdout for "HtmlToPdf" has been closed.
-        reader_thread.join().unwrap()
+        reader_thread.join_result().context(
+            r#"The thread reading PDF data from the "HtmlToPdf" conversion program failed"#,
+        )?
     }
 }
 
 struct DotNetHtmlSinkInner<'scope, W> {
-    process: Child,
+    /// Dropped first so that a sink that is dropped without being completed
+    /// kills the program before its stdin is closed, otherwise the program
+    /// would convert the partially written HTML.
+    process: ChildGuard,
     writer: BufWriter<ChildStdin>,
     reader_thread: PdfScopedJoinHandle<'scope, Result<W>>,
+    /// Returns the end of the program's stderr.
+    stderr_thread: std::thread::JoinHandle<String>,
+    /// Deleted once the conversion is done.
+    _assets: Option<AssetDir>,
 }
 pub struct DotNetHtmlSink<'scope, W>(DotNetHtmlSinkInner<'scope, W>);
-impl<'scope, W> DotNetHtmlSink<'scope, W> {
-    #[inline]
-    fn writer(&mut self) -> &mut BufWriter<ChildStdin> {
-        &mut self.0.writer
+html_to_pdf::forward_write!(
+    impl['scope, W] for DotNetHtmlSink<'scope, W>,
+    |this| &mut this.0.writer
+);
+
+/// Keeps one "HtmlToPdf" process running and converts documents with it one
+/// at a time, which avoids starting the .NET runtime for every conversion.
+///
+/// Convert with [`DotNetPdfServer::convert`] or use `&DotNetPdfServer` as a
+/// [`HtmlToPdfConverter`]. Conversions from multiple threads wait for each
+/// other. Unlike [`DotNetPdfConverter`] the PDF isn't streamed, since the
+/// program sends each document's length before its data.
+///
+/// The program is started with `--server` and then handles requests on its
+/// stdin until that is closed. A request is the length of the HTML as a little
+/// endian `u32` followed by the HTML. The response is a status byte (`0` on
+/// success, `1` on failure), the length of the data as a little endian `u32`
+/// and then the PDF or a UTF-8 error message.
+pub struct DotNetPdfServer {
+    server: Mutex<ServerProcess>,
+    process_id: u32,
+}
+struct ServerProcess {
+    process: Child,
+    /// `None` once the server is shutting down.
+    requests: Option<BufWriter<ChildStdin>>,
+    responses: BufReader<ChildStdout>,
+    /// Deleted once the server has exited.
+    _assets: Option<AssetDir>,
+}
+impl DotNetPdfServer {
+    /// The program can't read more HTML than this per request.
+    pub const MAX_HTML_SIZE: usize = i32::MAX as usize;
+
+    /// Start the "HtmlToPdf" program. The options of `converter` are used for
+    /// all conversions.
+    pub fn spawn(converter: &DotNetPdfConverter) -> Result<Self> {
+        let (mut process, assets) = converter.command()?;
+        let mut process = process
+            .arg("--server")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context(r#"Failed to start "HtmlToPdf" in server mode."#)?;
+
+        let responses = process
+            .stdout
+            .take()
+            .context(r#"Couldn't open stdout for "HtmlToPdf" conversion program."#)?;
+        let requests = process
+            .stdin
+            .take()
+            .context(r#"Couldn't open stdin for "HtmlToPdf" conversion program."#)?;
+        Ok(Self {
+            process_id: process.id(),
+            server: Mutex::new(ServerProcess {
+                process,
+                requests: Some(BufWriter::new(requests)),
+                responses: BufReader::new(responses),
+                _assets: assets,
+            }),
+        })
+    }
+
+    /// The OS identifier of the "HtmlToPdf" process.
+    pub fn process_id(&self) -> u32 {
+        self.process_id
+    }
+
+    /// Convert `html` and write the PDF to `output`.
+    pub fn convert(&self, html: &[u8], output: &mut dyn Write) -> Result<()> {
+        if html.len() > Self::MAX_HTML_SIZE {
+            bail!(
+                "The HTML is larger than the max size of {} bytes",
+                Self::MAX_HTML_SIZE
+            );
+        }
+        let mut server = self.server.lock().map_err(|_| {
+            eyre!(r#"A previous conversion panicked while using the "HtmlToPdf" server"#)
+        })?;
+        let ServerProcess {
+            process,
+            requests,
+            responses,
+            ..
+        } = &mut *server;
+        let requests = requests
+            .as_mut()
+            .context(r#"The "HtmlToPdf" server is shutting down"#)?;
+
+        let exchange = (|| -> io::Result<(u8, u32)> {
+            requests.write_all(&(html.len() as u32).to_le_bytes())?;
+            requests.write_all(html)?;
+            requests.flush()?;
+
+            let mut header = [0; 5];
+            responses.read_exact(&mut header)?;
+            let [status, length @ ..] = header;
+            Ok((status, u32::from_le_bytes(length)))
+        })();
+        let (status, length) = match exchange {
+            Ok(response) => response,
+            Err(e) => {
+                let exited = match process.try_wait() {
+                    Ok(Some(status)) => format!(" and exited with {status}"),
+                    _ => String::new(),
+                };
+                return Err(e).with_context(|| {
+                    format!(r#"The "HtmlToPdf" server stopped responding{exited}"#)
+                });
+            }
+        };
+
+        let mut data = responses.by_ref().take(length.into());
+        if status != 0 {
+            let mut message = Vec::new();
+            data.read_to_end(&mut message)
+                .context(r#"Failed to read error message from the "HtmlToPdf" server"#)?;
+            bail!(
+                r#"The "HtmlToPdf" server failed to convert the HTML: {}"#,
+                String::from_utf8_lossy(&message)
+            );
+        }
+        let copied = io::copy(&mut data, output);
+        // Skip the rest of the PDF so that the next response can be read:
+        io::copy(&mut data, &mut io::sink())
+            .context(r#"Failed to read pdf data from the "HtmlToPdf" server"#)?;
+        copied.context(r#"Failed to write pdf data from the "HtmlToPdf" server to output."#)?;
+        Ok(())
+    }
+}
+/// Closes the program's stdin so that it exits and then waits for it.
+impl Drop for DotNetPdfServer {
+    fn drop(&mut self) {
+        let server = match self.server.get_mut() {
+            Ok(server) => server,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        drop(server.requests.take());
+        let _ = server.process.wait();
     }
 }
-impl<'scope, W> Write for DotNetHtmlSink<'scope, W> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.writer().write(buf)
+
+impl<'a, 'scope, W> HtmlToPdfConverter<'scope, W> for &'a DotNetPdfServer
+where
+    W: WriteBuilder + Send + 'scope,
+{
+    type HtmlSink = DotNetServerHtmlSink<'a, W>;
+    type Error = eyre::Error;
+
+    fn start(self, _scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        Ok(DotNetServerHtmlSink {
+            server: self,
+            html: Vec::new(),
+            output,
+        })
     }
-    fn flush(&mut self) -> io::Result<()> {
-        self.writer().flush()
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("dotnet-itext-server")
     }
-    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
-        self.writer().write_vectored(bufs)
+
+    /// Like [`DotNetPdfConverter`] except that the HTML is buffered until the
+    /// conversion is completed.
+    fn capabilities(&self) -> ConverterCapabilities {
+        ConverterCapabilities {
+            table_of_contents: false,
+            clickable_links: true,
+            streaming_input: false,
+            embedded_fonts: true,
+        }
     }
-    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-        self.writer().write_all(buf)
+}
+
+/// Buffers the HTML until the conversion is completed, see
+/// [`DotNetPdfServer`].
+pub struct DotNetServerHtmlSink<'a, W> {
+    server: &'a DotNetPdfServer,
+    html: Vec<u8>,
+    output: W,
+}
+html_to_pdf::forward_write!(
+    impl['a, W] for DotNetServerHtmlSink<'a, W>,
+    |this| &mut this.html
+);
+impl<W> HtmlSink<W, eyre::Error> for DotNetServerHtmlSink<'_, W>
+where
+    W: WriteBuilder,
+{
+    fn complete(mut self) -> eyre::Result<W> {
+        let mut writer = self.output.get_writer()?;
+        self.server.convert(&self.html, &mut writer)?;
+        drop(writer);
+        Ok(self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use html_to_pdf::{HtmlToPdfError, PageSize, WriteBuilderSimple};
+    use std::{
+        sync::mpsc::{self, Receiver, Sender},
+        thread,
+        time::Duration,
+    };
+
+    /// Returns one chunk and then blocks until told to finish, like the stdout
+    /// of a program that is still writing the PDF.
+    struct SlowReader {
+        chunk: Option<&'static [u8]>,
+        finish: Receiver<()>,
+    }
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if let Some(chunk) = self.chunk.take() {
+                buf[..chunk.len()].copy_from_slice(chunk);
+                return Ok(chunk.len());
+            }
+            self.finish.recv().ok();
+            Ok(0)
+        }
     }
-    fn write_fmt(&mut self, fmt: std::fmt::Arguments<'_>) -> io::Result<()> {
-        self.writer().write_fmt(fmt)
+
+    /// Reports every write so that the test can see when data arrives.
+    struct SniffingWriter(Sender<Vec<u8>>);
+    impl io::Write for SniffingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.send(buf.to_vec()).ok();
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn eyre_error_converts_to_engine_error() {
+        let report = eyre::Report::new(io::Error::new(
+            io::ErrorKind::NotFound,
+            "dotnet executable not found",
+        ));
+        let error = HtmlToPdfError::from(report);
+        assert!(matches!(error, HtmlToPdfError::Engine(_)));
+        let source = std::error::Error::source(&error)
+            .and_then(|source| source.downcast_ref::<io::Error>())
+            .expect("the io::Error should be the source");
+        assert_eq!(source.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn pdf_is_forwarded_before_completion() {
+        let (finish_tx, finish_rx) = mpsc::channel();
+        let (written_tx, written_rx) = mpsc::channel();
+        let reader = SlowReader {
+            chunk: Some(b"%PDF-1.7"),
+            finish: finish_rx,
+        };
+
+        thread::scope(|s| {
+            let reader_thread = forward_pdf(
+                PdfScope::scoped(s),
+                reader,
+                WriteBuilderSimple(SniffingWriter(written_tx)),
+                None,
+            );
+
+            // The reader hasn't reached the end yet but the output should
+            // already have received the first chunk:
+            let written = written_rx
+                .recv_timeout(Duration::from_secs(5))
+                .expect("output should receive data while the PDF is being produced");
+            assert_eq!(written, b"%PDF-1.7");
+
+            finish_tx.send(()).unwrap();
+            reader_thread.join_result().unwrap().unwrap();
+        });
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn dropped_sink_stops_program() {
+        let is_running = |id: u32| {
+            Command::new("kill")
+                .args(["-0", &id.to_string()])
+                .stderr(Stdio::null())
+                .status()
+                .unwrap()
+                .success()
+        };
+        // "cat" echoes its stdin, so it would write the HTML to the output if
+        // it saw the end of its input before being stopped:
+        let process = Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let id = process.id();
+        let mut output = WriteBuilderSimple(Vec::new());
+
+        thread::scope(|s| {
+            let mut html_sink =
+                DotNetHtmlSink::from_process(PdfScope::scoped(s), process, &mut output, None, None)
+                    .unwrap();
+            html_sink.write_all(b"<p>Hello</p>").unwrap();
+            drop(html_sink);
+            assert!(!is_running(id));
+        });
+        assert!(output.0.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "include_exe")]
+    fn corrupted_extracted_file_is_replaced() {
+        use sha2::{Digest, Sha256};
+
+        let data = b"HtmlToPdf program";
+        let sha256 = Sha256::digest(data);
+        let path = std::env::temp_dir().join(format!("HtmlToPdf-checksum-{}", std::process::id()));
+
+        extract_verified(&path, data, &sha256).unwrap();
+        std::fs::write(&path, b"HtmlToPdf prog").unwrap();
+        extract_verified(&path, data, &sha256).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+
+        // Data that doesn't match the checksum is an error:
+        std::fs::remove_file(&path).unwrap();
+        let error = extract_verified(&path, b"other data", &sha256).unwrap_err();
+        assert!(error.to_string().contains("SHA-256"), "{error}");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[ignore = "requires the \"HtmlToPdf\" program"]
+    fn server_reuses_one_process() {
+        let server = DotNetPdfServer::spawn(&DotNetPdfConverter::default()).unwrap();
+        let process_id = server.process_id();
+
+        for text in ["First", "Second"] {
+            let mut html_sink = (&server)
+                .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+                .unwrap();
+            write!(html_sink, "<p>{text}</p>").unwrap();
+            let pdf = html_sink.complete().unwrap().0;
+            assert!(pdf.starts_with(b"%PDF-"));
+            assert!(pdf.trim_ascii_end().ends_with(b"%%EOF"));
+        }
+        assert_eq!(server.process_id(), process_id);
+
+        let mut process = server.server.lock().unwrap();
+        assert!(process.process.try_wait().unwrap().is_none());
+    }
+
+    #[test]
+    #[ignore = "requires the \"HtmlToPdf\" program"]
+    fn a3_landscape_changes_media_box() {
+        let converter = DotNetPdfConverter {
+            paper: Some(PageSize::A3.into()),
+            orientation: PageOrientation::Landscape,
+            ..Default::default()
+        };
+        let mut html_sink = converter
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        io::Write::write_all(&mut html_sink, b"<p>Hello</p>").unwrap();
+        let pdf = html_sink.complete().unwrap().0;
+
+        let pdf = String::from_utf8_lossy(&pdf);
+        let media_box = pdf
+            .split("/MediaBox")
+            .nth(1)
+            .and_then(|rest| rest.trim_start().strip_prefix('['))
+            .and_then(|rest| rest.split(']').next())
+            .expect("PDF should have a media box");
+        let size = media_box
+            .split_whitespace()
+            .map(|v| v.parse::<f64>().unwrap())
+            .collect::<Vec<_>>();
+        let (width, height) = PageOrientation::Landscape.apply(PageSize::A3.size_in_points());
+        assert_eq!(size.len(), 4);
+        assert!((size[2] - size[0] - width).abs() < 1.0, "{media_box}");
+        assert!((size[3] - size[1] - height).abs() < 1.0, "{media_box}");
+    }
+
+    #[test]
+    #[ignore = "requires the \"HtmlToPdf\" program and an ICC profile"]
+    fn pdf_a_conformance_is_declared_in_xmp_metadata() {
+        // Defaults to the sRGB profile that Windows includes:
+        let icc_profile = std::env::var_os("HTML_TO_PDF_ICC_PROFILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                r"C:\Windows\System32\spool\drivers\color\sRGB Color Space Profile.icm".into()
+            });
+        let converter = DotNetPdfConverter {
+            pdf_a: Some(PdfAConformance::PdfA2B),
+            icc_profile: Some(icc_profile),
+            ..Default::default()
+        };
+        let mut html_sink = converter
+            .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+            .unwrap();
+        io::Write::write_all(&mut html_sink, b"<p>Hello</p>").unwrap();
+        let pdf = html_sink.complete().unwrap().0;
+
+        assert_eq!(
+            html_to_pdf::pdf::pdf_a_conformance(&pdf).unwrap(),
+            Some(PdfAConformance::PdfA2B)
+        );
+    }
+
+    /// The names of all fonts in the PDF without any subset prefix like
+    /// `ABCDEF+`.
+    fn base_fonts(pdf: &[u8]) -> std::collections::BTreeSet<String> {
+        String::from_utf8_lossy(pdf)
+            .split("/BaseFont")
+            .skip(1)
+            .filter_map(|rest| {
+                let name = rest.trim_start().strip_prefix('/')?;
+                let name = name
+                    .split(|c: char| c.is_whitespace() || "/<>[]()".contains(c))
+                    .next()?;
+                Some(
+                    name.split_once('+')
+                        .map_or(name, |(_, name)| name)
+                        .to_owned(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    #[ignore = "requires the \"HtmlToPdf\" program and a CJK font at $HTML_TO_PDF_CJK_FONT"]
+    fn custom_font_is_embedded() {
+        let font = std::env::var_os("HTML_TO_PDF_CJK_FONT")
+            .expect("HTML_TO_PDF_CJK_FONT should be the path to a font with Japanese glyphs");
+        let convert = |fonts: Vec<PathBuf>| {
+            let converter = DotNetPdfConverter {
+                fonts,
+                ..Default::default()
+            };
+            let mut html_sink = converter
+                .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))
+                .unwrap();
+            io::Write::write_all(
+                &mut html_sink,
+                "<meta charset=\"UTF-8\"><p>こんにちは世界</p>".as_bytes(),
+            )
+            .unwrap();
+            html_sink.complete().unwrap().0
+        };
+
+        let default_fonts = base_fonts(&convert(Vec::new()));
+        let pdf = convert(vec![PathBuf::from(font)]);
+        let added_fonts = base_fonts(&pdf)
+            .difference(&default_fonts)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(!added_fonts.is_empty(), "{default_fonts:?}");
+        // The font should be embedded, not just referenced:
+        assert!(
+            pdf.windows(b"/FontFile".len()).any(|w| w == b"/FontFile"),
+            "{added_fonts:?}"
+        );
+    }
+
+    #[test]
+    fn reports_name() {
+        type Output = WriteBuilderSimple<Vec<u8>>;
+        let converter = DotNetPdfConverter::default();
+        assert_eq!(
+            HtmlToPdfConverter::<Output>::name(&converter),
+            "dotnet-itext"
+        );
+    }
+
+    #[test]
+    fn hide_window_defaults_to_feature() {
+        assert_eq!(
+            DotNetPdfConverter::default().hide_window,
+            cfg!(feature = "windows-gui")
+        );
     }
 }
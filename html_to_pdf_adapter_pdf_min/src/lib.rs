@@ -2,43 +2,123 @@
 //!
 //! [`pdf-min`]: https://crates.io/crates/pdf-min
 
-use html_to_pdf::{HtmlSink, HtmlToPdfConverter, WriteBuilder};
-use std::{
-    io::{self, Error, Write},
-    marker::PhantomData,
+use html_to_pdf::{
+    CancelToken, ConverterCapabilities, HtmlSink, HtmlToPdfConverter, HtmlToPdfError, Length,
+    Margin, PageOptions, PageOrientation, Paper, PdfAConformance, PdfANotSupportedError,
+    WithPageOptions, WriteBuilder,
 };
+use std::{borrow::Cow, io::Write, marker::PhantomData};
 
-#[derive(Debug, Clone, Default)]
-pub struct PdfMinConverter;
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PdfMinConverter {
+    /// The paper size of the pages. Uses `pdf-min`'s default if `None`.
+    pub paper: Option<Paper>,
+    /// Turn the pages to landscape, also applies to `pdf-min`'s default size.
+    pub orientation: PageOrientation,
+    /// Space between the edges of the pages and the content. `pdf-min` only
+    /// has a left and a bottom margin so the top and right margins are
+    /// ignored. Uses `pdf-min`'s defaults if `None`.
+    pub margin: Option<Margin>,
+    /// `pdf-min` can't create PDF/A documents, so starting a conversion fails
+    /// with an [`HtmlToPdfError::Engine`] error whose source is a
+    /// [`PdfANotSupportedError`] if this is set.
+    pub pdf_a: Option<PdfAConformance>,
+}
+impl PdfMinConverter {
+    /// `false` since `pdf-min` builds the whole PDF in memory, it is then
+    /// written to the output in [`HtmlSink::complete`].
+    pub const STREAMS_PDF: bool = false;
+
+    /// Lay out the pages on `writer` with this converter's page setup.
+    fn apply_page_setup(&self, writer: &mut ::pdf_min::Writer) {
+        let points = |length: Length| length.points().round() as _;
+        let size = match self.paper {
+            Some(paper) => {
+                let (width, height) = paper.size();
+                (points(width), points(height))
+            }
+            None => (writer.page_width, writer.page_height),
+        };
+        (writer.page_width, writer.page_height) = self.orientation.apply(size);
+        if let Some(margin) = self.margin {
+            writer.margin_left = points(margin.left);
+            writer.margin_bottom = points(margin.bottom);
+        }
+    }
+}
+/// Uses the page size, orientation and the left and bottom margins. The DPI
+/// is ignored.
+impl WithPageOptions for PdfMinConverter {
+    fn with_page_options(mut self, options: PageOptions) -> Self {
+        if let Some(paper) = options.page_size {
+            self.paper = Some(paper);
+        }
+        if let Some(orientation) = options.orientation {
+            self.orientation = orientation;
+        }
+        if let Some(margin) = options.margins {
+            self.margin = Some(margin);
+        }
+        self
+    }
+}
 
 impl<'scope, W> HtmlToPdfConverter<'scope, W> for PdfMinConverter
 where
     W: WriteBuilder + Send + 'scope,
 {
     type HtmlSink = PdfMinHtmlSink<'scope, W>;
-    type Error = Error;
+    type Error = HtmlToPdfError;
 
     fn start(
         self,
-        _scope: html_to_pdf::PdfScope<'scope, '_>,
+        scope: html_to_pdf::PdfScope<'scope, '_>,
         output: W,
     ) -> Result<Self::HtmlSink, Self::Error> {
+        if let Some(conformance) = self.pdf_a {
+            return Err(PdfANotSupportedError {
+                engine: "pdf-min",
+                conformance,
+            }
+            .into());
+        }
         Ok(PdfMinHtmlSink {
+            converter: self,
             buffer: Vec::new(),
             writer: output,
+            cancel: scope.cancel_token().cloned(),
             _scope: PhantomData,
         })
     }
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("pdf-min")
+    }
+
+    /// `pdf-min` only lays out text using the standard PDF fonts, which
+    /// aren't embedded, and ignores links and headings.
+    fn capabilities(&self) -> ConverterCapabilities {
+        ConverterCapabilities {
+            table_of_contents: false,
+            clickable_links: false,
+            streaming_input: false,
+            embedded_fonts: false,
+        }
+    }
 }
-impl<'scope, W> HtmlSink<W, Error> for PdfMinHtmlSink<'scope, W>
+impl<'scope, W> HtmlSink<W, HtmlToPdfError> for PdfMinHtmlSink<'scope, W>
 where
     W: WriteBuilder + Send + 'scope,
 {
-    fn complete(mut self) -> Result<W, Error> {
+    fn complete(mut self) -> Result<W, HtmlToPdfError> {
+        if let Some(cancel) = &self.cancel {
+            cancel.check()?;
+        }
         let mut writer = self.writer.get_writer()?;
         let mut w = ::pdf_min::Writer::default();
         w.b.nocomp = true;
         w.line_pad = 8; // Other Writer default values could be adjusted here.
+        self.converter.apply_page_setup(&mut w);
 
         const UTF8_BOM: &[u8] = "\u{feff}".as_bytes();
         let text = if self.buffer.starts_with(UTF8_BOM) {
@@ -48,6 +128,9 @@ where
         };
         ::pdf_min::html(&mut w, text);
         w.finish();
+        if let Some(cancel) = &self.cancel {
+            cancel.check()?;
+        }
 
         writer.write_all(&w.b.b)?;
         drop(writer);
@@ -55,17 +138,191 @@ where
     }
 }
 
+/// Buffers the HTML until [`HtmlSink::complete`], so dropping it without
+/// completing it writes nothing to the output.
 pub struct PdfMinHtmlSink<'scope, W> {
+    converter: PdfMinConverter,
     buffer: Vec<u8>,
     writer: W,
+    cancel: Option<CancelToken>,
     _scope: PhantomData<&'scope ()>,
 }
-impl<'scope, W> Write for PdfMinHtmlSink<'scope, W> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.buffer.extend_from_slice(buf);
-        Ok(buf.len())
+html_to_pdf::forward_write!(
+    impl['scope, W] for PdfMinHtmlSink<'scope, W>,
+    |this| &mut this.buffer
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use html_to_pdf::WriteBuilderSimple;
+    use std::io::{self, Read};
+
+    #[test]
+    fn convert_all_converts_each_input() {
+        let inputs = ["<p>first</p>".as_bytes(), "<h1>second</h1>".as_bytes()]
+            .map(|html| (html, WriteBuilderSimple(Vec::new())));
+
+        let results = html_to_pdf::convert_all(PdfMinConverter::default, inputs);
+
+        assert_eq!(results.len(), 2);
+        for result in results {
+            assert!(result.unwrap().0.starts_with(b"%PDF"));
+        }
+    }
+
+    #[test]
+    fn into_reader_yields_complete_pdf() {
+        let mut pdf = Vec::new();
+        html_to_pdf::into_reader(
+            PdfMinConverter::default(),
+            &b"<h1>Title</h1><p>Text</p>"[..],
+        )
+        .read_to_end(&mut pdf)
+        .unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        assert!(pdf.trim_ascii_end().ends_with(b"%%EOF"));
     }
-    fn flush(&mut self) -> io::Result<()> {
-        Ok(())
+
+    #[test]
+    fn html_sink_buffers_all_writes() {
+        let mut html_sink = PdfMinConverter::default()
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+        html_sink.write_all(b"<p>first").unwrap();
+        let written = html_sink
+            .write_vectored(&[io::IoSlice::new(b" second"), io::IoSlice::new(b"</p>")])
+            .unwrap();
+        assert_eq!(written, 11);
+        assert_eq!(html_sink.buffer, b"<p>first second</p>");
+    }
+
+    #[test]
+    fn pdf_a_is_rejected() {
+        let converter = PdfMinConverter {
+            pdf_a: Some(PdfAConformance::PdfA2B),
+            ..PdfMinConverter::default()
+        };
+        let error = converter
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                WriteBuilderSimple(Vec::new()),
+            )
+            .err()
+            .expect("pdf-min can't create PDF/A documents");
+        assert!(matches!(error, HtmlToPdfError::Engine(_)), "{error:?}");
+        assert_eq!(
+            std::error::Error::source(&error).and_then(|source| source.downcast_ref()),
+            Some(&PdfANotSupportedError {
+                engine: "pdf-min",
+                conformance: PdfAConformance::PdfA2B,
+            })
+        );
+    }
+
+    #[test]
+    fn dropped_sink_writes_nothing() {
+        let mut output = WriteBuilderSimple(Vec::new());
+        std::thread::scope(|s| {
+            let mut html_sink = PdfMinConverter::default()
+                .start(html_to_pdf::PdfScope::scoped(s), &mut output)
+                .unwrap();
+            html_sink.write_all(b"<p>Hello</p>").unwrap();
+            drop(html_sink);
+        });
+        assert!(output.0.is_empty());
+    }
+
+    #[test]
+    fn counting_builder_counts_pdf_bytes() {
+        let output = html_to_pdf::WriteBuilderCounting::new(WriteBuilderSimple(Vec::new()));
+        let mut html_sink = PdfMinConverter::default()
+            .start(html_to_pdf::PdfScope::owned(), output)
+            .unwrap();
+        html_sink.write_all(b"<h1>Title</h1><p>Text</p>").unwrap();
+        let output = html_sink.complete().unwrap();
+
+        let written = output.bytes_written();
+        let pdf = output.into_inner().0;
+        assert!(pdf.starts_with(b"%PDF"));
+        assert_eq!(written, pdf.len() as u64);
+    }
+
+    #[test]
+    fn page_options_change_media_box() {
+        let converter = PdfMinConverter::default().with_page_options(PageOptions {
+            page_size: Some(html_to_pdf::PageSize::A4.into()),
+            orientation: Some(PageOrientation::Landscape),
+            dpi: Some(300),
+            ..Default::default()
+        });
+        let mut html_sink = converter
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        let pdf = html_sink.complete().unwrap().0;
+
+        let pdf = String::from_utf8_lossy(&pdf);
+        let media_box = pdf
+            .split("/MediaBox")
+            .nth(1)
+            .and_then(|rest| rest.trim_start().strip_prefix('['))
+            .and_then(|rest| rest.split(']').next())
+            .expect("PDF should have a media box");
+        let size = media_box
+            .split_whitespace()
+            .map(|v| v.parse::<f64>().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(size, [0.0, 0.0, 842.0, 595.0], "{media_box}");
+    }
+
+    #[test]
+    fn reports_pdf_output_type() {
+        type Output = WriteBuilderSimple<Vec<u8>>;
+        let converter = PdfMinConverter::default();
+        assert_eq!(
+            HtmlToPdfConverter::<Output>::output_content_type(&converter),
+            "application/pdf"
+        );
+        assert_eq!(
+            HtmlToPdfConverter::<Output>::output_extension(&converter),
+            "pdf"
+        );
+    }
+
+    #[test]
+    fn reports_name() {
+        type Output = WriteBuilderSimple<Vec<u8>>;
+        assert_eq!(
+            HtmlToPdfConverter::<Output>::name(&PdfMinConverter::default()),
+            "pdf-min"
+        );
+        let wrapped = html_to_pdf::SemaphoreConverter::new(
+            PdfMinConverter::default(),
+            html_to_pdf::Semaphore::new(1),
+        );
+        assert_eq!(
+            HtmlToPdfConverter::<Output>::name(&wrapped),
+            "semaphore(pdf-min)"
+        );
+    }
+
+    #[test]
+    fn reports_no_capabilities() {
+        type Output = WriteBuilderSimple<Vec<u8>>;
+        let wrapped = html_to_pdf::SemaphoreConverter::new(
+            PdfMinConverter::default(),
+            html_to_pdf::Semaphore::new(1),
+        );
+        assert_eq!(
+            HtmlToPdfConverter::<Output>::capabilities(&wrapped),
+            ConverterCapabilities::default()
+        );
     }
 }
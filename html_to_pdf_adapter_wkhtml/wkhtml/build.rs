@@ -100,11 +100,12 @@ fn main() {
     // Activate re-run detection:
     "build.rs".detect_change();
 
-    // Currently can only interact with wkhtml on windows, program will gracefully return an error at runtime on other platforms.
+    // Currently can only interact with wkhtml on Windows and Linux, program will gracefully return an error at runtime on other platforms.
     let is_windows = env::var_os("CARGO_CFG_WINDOWS").is_some();
-    if !is_windows {
+    let is_linux = env::var("CARGO_CFG_TARGET_OS").map_or(false, |os| os == "linux");
+    if !is_windows && !is_linux {
         println!(
-            "cargo:warning=Currently only windows libraries are linked correctly to wkHtmlToPdf."
+            "cargo:warning=Currently only Windows and Linux libraries are linked correctly to wkHtmlToPdf."
         );
         return;
     }
@@ -178,4 +179,16 @@ fn main() {
             .open(PathBuf::from(&out_dir).join("wkhtml_runner.exe"))
             .unwrap();
     }
+
+    // Store the runner's checksum so that extracted copies can be verified at runtime:
+    {
+        use sha2::{Digest, Sha256};
+
+        let runner = fs::read(PathBuf::from(&out_dir).join("wkhtml_runner.exe")).unwrap();
+        fs::write(
+            PathBuf::from(&out_dir).join("wkhtml_runner.exe.sha256"),
+            Sha256::digest(runner),
+        )
+        .unwrap();
+    }
 }
@@ -1,16 +1,22 @@
 #![warn(clippy::all)]
 
 use eyre::{bail, ContextCompat, WrapErr};
-use html_to_pdf::WriteBuilder;
+use html_to_pdf::{
+    AssetDir, Assets, Diagnostic, Margin, PageOrientation, Paper, PdfAConformance,
+    PdfANotSupportedError, WriteBuilder,
+};
 use std::{
+    borrow::Cow,
     error::Error as StdError,
     fmt,
     io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
 macro_rules! is_supported {
     ($( $token:tt )*) => {
-        #[cfg(windows)]
+        #[cfg(any(windows, target_os = "linux"))]
         $( $token )*
     };
 }
@@ -66,6 +72,205 @@ impl fmt::Display for NotSupportedError {
 }
 impl StdError for NotSupportedError {}
 
+/// Returned by [`WkHtmlPdfConverter::set`] for settings that wkhtmltopdf
+/// doesn't support or that are controlled by this crate.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UnknownSettingError(pub String);
+impl fmt::Display for UnknownSettingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, r#""{}" is not a known wkhtmltopdf setting."#, self.0)
+    }
+}
+impl StdError for UnknownSettingError {}
+
+/// Names of wkhtmltopdf's global settings, see:
+/// <https://wkhtmltopdf.org/libwkhtmltox/pagesettings.html#pageGlobal>
+///
+/// `out` is left out since the PDF is always written to the output writer.
+pub const GLOBAL_SETTINGS: &[&str] = &[
+    "size.paperSize",
+    "size.width",
+    "size.height",
+    "orientation",
+    "colorMode",
+    "resolution",
+    "dpi",
+    "pageOffset",
+    "copies",
+    "collate",
+    "outline",
+    "outlineDepth",
+    "dumpOutline",
+    "documentTitle",
+    "useCompression",
+    "margin.top",
+    "margin.bottom",
+    "margin.left",
+    "margin.right",
+    "imageDPI",
+    "imageQuality",
+    "load.cookieJar",
+];
+/// Names of wkhtmltopdf's object (page) settings, see:
+/// <https://wkhtmltopdf.org/libwkhtmltox/pagesettings.html#pageObject>
+///
+/// `page` is left out since the HTML is always read from the input.
+pub const OBJECT_SETTINGS: &[&str] = &[
+    "toc.useDottedLines",
+    "toc.captionText",
+    "toc.forwardLinks",
+    "toc.backLinks",
+    "toc.indentation",
+    "toc.fontScale",
+    "header.fontSize",
+    "header.fontName",
+    "header.left",
+    "header.center",
+    "header.right",
+    "header.line",
+    "header.spacing",
+    "header.htmlUrl",
+    "footer.fontSize",
+    "footer.fontName",
+    "footer.left",
+    "footer.center",
+    "footer.right",
+    "footer.line",
+    "footer.spacing",
+    "footer.htmlUrl",
+    "useExternalLinks",
+    "useLocalLinks",
+    "replacements",
+    "produceForms",
+    "load.username",
+    "load.password",
+    "load.jsdelay",
+    "load.zoomFactor",
+    "load.customHeaders",
+    "load.repeatCustomHeaders",
+    "load.cookies",
+    "load.post",
+    "load.blockLocalFileAccess",
+    "load.stopSlowScript",
+    "load.debugJavascript",
+    "load.loadErrorHandling",
+    "load.proxy",
+    "load.runScript",
+    "web.background",
+    "web.loadImages",
+    "web.enableJavascript",
+    "web.enableIntelligentShrinking",
+    "web.minimumFontSize",
+    "web.printMediaType",
+    "web.defaultEncoding",
+    "web.userStyleSheet",
+    "web.enablePlugins",
+    "includeInOutline",
+    "pagesCount",
+    "tocXsl",
+];
+
+/// Extra settings that are given to wkhtmltopdf. Only names from
+/// [`GLOBAL_SETTINGS`] and [`OBJECT_SETTINGS`] can be stored here.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+struct Settings {
+    global: Vec<(&'static str, String)>,
+    object: Vec<(&'static str, String)>,
+}
+
+impl Settings {
+    /// Only allow wkhtmltopdf to load local files, so that assets are read
+    /// from their folder and nothing is fetched over the network.
+    fn only_load_local_files(&mut self) {
+        // Nothing should listen on the discard port so all requests that
+        // would go over the network fail:
+        for (name, value) in [
+            ("load.proxy", "http://127.0.0.1:9"),
+            ("load.blockLocalFileAccess", "false"),
+        ] {
+            self.object.retain(|(existing, _)| *existing != name);
+            self.object.push((name, value.to_owned()));
+        }
+    }
+}
+
+/// Find where a `<base>` tag can be inserted into the start of an HTML
+/// document. That is after a leading doctype since anything before it would
+/// switch the page to quirks mode.
+///
+/// Returns `None` if more of the document is needed to decide, `eof` should be
+/// `true` if `head` is the whole document.
+fn base_href_position(head: &[u8], eof: bool) -> Option<usize> {
+    const UTF8_BOM: &[u8] = "\u{feff}".as_bytes();
+    const DOCTYPE: &[u8] = b"<!doctype";
+
+    if !eof && head.len() < UTF8_BOM.len() && UTF8_BOM.starts_with(head) {
+        return None;
+    }
+    let mut start = if head.starts_with(UTF8_BOM) {
+        UTF8_BOM.len()
+    } else {
+        0
+    };
+    start += head[start..]
+        .iter()
+        .take_while(|b| b.is_ascii_whitespace())
+        .count();
+    let rest = &head[start..];
+
+    if rest.len() < DOCTYPE.len() {
+        if !eof && DOCTYPE[..rest.len()].eq_ignore_ascii_case(rest) {
+            return None;
+        }
+        return Some(start);
+    }
+    if !rest[..DOCTYPE.len()].eq_ignore_ascii_case(DOCTYPE) {
+        return Some(start);
+    }
+    match rest.iter().position(|&b| b == b'>') {
+        Some(end) => Some(start + end + 1),
+        None if eof => Some(head.len()),
+        None => None,
+    }
+}
+
+/// A `<base>` tag pointing at `base_url`, with the characters that would end
+/// the attribute or tag escaped.
+fn base_href_tag(base_url: &str) -> String {
+    let href = base_url
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!(r#"<base href="{href}">"#)
+}
+
+/// Insert a `<base>` tag pointing at `base_url` into the HTML that is read
+/// from `html`, see [`base_href_position`]. Only reads as much as needed to
+/// find the position.
+#[allow(dead_code)] // Only used when wkhtml is supported on this platform.
+fn with_base_href<R: Read>(
+    mut html: R,
+    base_url: Option<&str>,
+) -> io::Result<io::Chain<io::Cursor<Vec<u8>>, R>> {
+    let mut head = Vec::new();
+    if let Some(base_url) = base_url {
+        let position = loop {
+            let mut chunk = [0; 256];
+            let read = match html.read(&mut chunk) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                read => read?,
+            };
+            head.extend_from_slice(&chunk[..read]);
+            if let Some(position) = base_href_position(&head, read == 0) {
+                break position;
+            }
+        };
+        head.splice(position..position, base_href_tag(base_url).into_bytes());
+    }
+    Ok(io::Cursor::new(head).chain(html))
+}
+
 /// `true` if we should prefer providing a buffer (via `convert_html_str_to_pdf`)
 /// over a reader (via `convert_html_to_pdf`).
 pub const PREFER_BUFFER_OVER_READER: bool = {
@@ -83,24 +288,254 @@ pub const PREFER_BUFFER_OVER_READER: bool = {
 };
 
 /// Convert HTML to PDF. Takes a reader and a writer. If you already have a string then use the [`convert_html_str_to_pdf`] function instead.
-pub fn convert_html_to_pdf<R, W>(mut html_reader: R, mut writer: W) -> eyre::Result<()>
+pub fn convert_html_to_pdf<R, W>(html_reader: R, writer: W) -> eyre::Result<()>
+where
+    R: Read,
+    W: WriteBuilder + Send,
+{
+    convert_reader_with_settings(
+        html_reader,
+        &Settings::default(),
+        &Default::default(),
+        None,
+        cfg!(feature = "windows-gui"),
+        writer,
+    )?;
+    Ok(())
+}
+
+/// Extract the runner program and the wkhtml library once and reuse them for
+/// later conversions.
+#[allow(dead_code)] // Only used when running wkhtml in a separate program.
+mod extract {
+    use super::*;
+    use std::{
+        fs,
+        sync::{Mutex, Once, PoisonError},
+    };
+
+    /// Where to extract files, see the fields of [`WkHtmlPdfConverter`] with
+    /// the same names.
+    #[derive(Debug, Default, Clone, PartialEq, Eq)]
+    pub(super) struct TempDirOptions {
+        /// See [`WkHtmlPdfConverter::temp_dir`].
+        pub parent: Option<PathBuf>,
+        /// See [`WkHtmlPdfConverter::temp_dir_prefix`].
+        pub prefix: Option<String>,
+        /// See [`WkHtmlPdfConverter::temp_dir_name`].
+        pub name: Option<String>,
+        /// See [`WkHtmlPdfConverter::keep_temp_dir`].
+        pub keep: bool,
+    }
+
+    /// A folder for extracted files.
+    pub(super) struct ExtractDir {
+        path: PathBuf,
+        /// Deletes the folder when dropped, `None` if the folder should be
+        /// kept.
+        temp: Option<tempfile::TempDir>,
+    }
+    impl ExtractDir {
+        pub(super) fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    struct ExtractedFiles {
+        options: TempDirOptions,
+        dir: ExtractDir,
+        /// Names of the files whose checksums have been verified by this
+        /// process, they aren't read again by later conversions.
+        verified: Vec<String>,
+    }
+
+    /// Folders with extracted files, at most one per set of options. The lock
+    /// is held while extracting so that concurrent conversions don't write the
+    /// same files.
+    static EXTRACTED: Mutex<Vec<ExtractedFiles>> = Mutex::new(Vec::new());
+
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> std::os::raw::c_int;
+    }
+
+    /// Destructors aren't run for statics so delete the extracted files when
+    /// the process exits instead.
+    extern "C" fn remove_extracted_files() {
+        if let Ok(mut extracted) = EXTRACTED.try_lock() {
+            extracted.clear();
+        }
+    }
+
+    /// Create a folder for the runner program and the wkhtml library inside
+    /// the options' parent folder, or inside the system's temp folder if it is
+    /// `None`.
+    pub(super) fn create_temp_dir(options: &TempDirOptions) -> io::Result<ExtractDir> {
+        let parent = options.parent.clone().unwrap_or_else(std::env::temp_dir);
+        fs::create_dir_all(&parent)?;
+        if let Some(name) = &options.name {
+            let path = parent.join(name);
+            fs::create_dir_all(&path)?;
+            return Ok(ExtractDir { path, temp: None });
+        }
+
+        let prefix = match &options.prefix {
+            Some(prefix) => prefix.clone(),
+            None => format!("wkhtml-{}", WK_HTML_LIBRARY_VERSION),
+        };
+        let dir = tempfile::Builder::new()
+            .prefix(&prefix)
+            .tempdir_in(&parent)?;
+        Ok(if options.keep {
+            ExtractDir {
+                path: dir.into_path(),
+                temp: None,
+            }
+        } else {
+            ExtractDir {
+                path: dir.path().to_owned(),
+                temp: Some(dir),
+            }
+        })
+    }
+
+    /// A file that was embedded into the program at compile time.
+    pub(super) struct EmbeddedFile<'a> {
+        pub name: &'a str,
+        pub data: &'a [u8],
+        /// SHA-256 digest of `data`, computed by a build script.
+        pub sha256: &'a [u8],
+    }
+
+    /// Get a folder that contains `files`. The folder is only created the
+    /// first time this is called with the same options and is deleted when
+    /// the process exits, unless the options say to keep it.
+    ///
+    /// The checksum of each file is verified the first time the folder is
+    /// used by this process and the file is only written if the checksum
+    /// doesn't match, for example if a kept folder was modified.
+    pub(super) fn extracted_dir(
+        options: &TempDirOptions,
+        files: &[EmbeddedFile<'_>],
+    ) -> eyre::Result<PathBuf> {
+        let mut extracted = EXTRACTED.lock().unwrap_or_else(PoisonError::into_inner);
+        let index = match extracted
+            .iter()
+            .position(|existing| existing.options == *options)
+        {
+            Some(index) => index,
+            None => {
+                static REGISTER_CLEANUP: Once = Once::new();
+                REGISTER_CLEANUP.call_once(|| {
+                    // Safety: the callback doesn't unwind and only touches a static.
+                    unsafe {
+                        atexit(remove_extracted_files);
+                    }
+                });
+
+                let dir = create_temp_dir(options)
+                    .context("Failed to create temporary folder for wkhtml files.")?;
+                extracted.push(ExtractedFiles {
+                    options: options.clone(),
+                    dir,
+                    verified: Vec::new(),
+                });
+                extracted.len() - 1
+            }
+        };
+        let existing = &mut extracted[index];
+        let dir = existing.dir.path().to_owned();
+        for file in files {
+            if existing.verified.iter().any(|name| name == file.name) {
+                continue;
+            }
+            write_verified(&dir.join(file.name), file)?;
+            existing.verified.push(file.name.to_owned());
+        }
+        Ok(dir)
+    }
+
+    fn sha256_of_file(path: &Path) -> io::Result<Vec<u8>> {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+        Ok(hasher.finalize().to_vec())
+    }
+
+    /// Write `file` to `path` unless the file there already has the expected
+    /// checksum. Files are made executable on unix.
+    fn write_verified(path: &Path, file: &EmbeddedFile<'_>) -> eyre::Result<()> {
+        if sha256_of_file(path).is_ok_and(|digest| digest == file.sha256) {
+            return Ok(());
+        }
+        fs::File::create(path)
+            .and_then(|mut out| io::copy(&mut &file.data[..], &mut out))
+            .with_context(|| format!("Failed to create \"{}\".", file.name))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+                .with_context(|| format!("Failed to make \"{}\" executable.", file.name))?;
+        }
+
+        let digest =
+            sha256_of_file(path).with_context(|| format!("Failed to read \"{}\".", file.name))?;
+        if digest != file.sha256 {
+            bail!(
+                "The SHA-256 checksum of \"{}\" didn't match the data embedded into the program.",
+                file.name
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Parse a line that the runner program wrote to stderr, it reports warnings
+/// from wkhtmltopdf as lines like `warning: Failed to load ...`.
+#[allow(dead_code)] // Unused when linking directly to wkhtml.
+fn parse_runner_diagnostic(line: &str) -> Option<Diagnostic> {
+    if let Some(message) = line.strip_prefix("warning: ") {
+        Some(Diagnostic::warning(message))
+    } else {
+        line.strip_prefix("error: ").map(Diagnostic::error)
+    }
+}
+
+/// Returns warnings that wkhtmltopdf reported. Those are only collected when
+/// using the runner program, when linking directly to wkhtml they are logged
+/// using the `log` crate instead.
+///
+/// The `writer` is flushed at most every `flush_interval` while PDF data is
+/// read from the runner program, which is started without a console window
+/// if `hide_window` is `true`.
+fn convert_reader_with_settings<R, W>(
+    mut html_reader: R,
+    _settings: &Settings,
+    _temp_dir: &extract::TempDirOptions,
+    _flush_interval: Option<Duration>,
+    _hide_window: bool,
+    mut writer: W,
+) -> eyre::Result<Vec<Diagnostic>>
 where
     R: Read,
     W: WriteBuilder + Send,
 {
     is_supported!({
+        let settings = _settings;
         /// This will have 0 size if the program is compiled with a link.
         static WK_HTML_RUNNER: &[u8] =
             include_bytes!(concat!(env!("OUT_DIR"), "/wkhtml_runner.exe"));
+        static WK_HTML_RUNNER_SHA256: &[u8] =
+            include_bytes!(concat!(env!("OUT_DIR"), "/wkhtml_runner.exe.sha256"));
         has_link!({
             let mut html = String::with_capacity(2024);
             html_reader.read_to_string(&mut html)?;
 
-            convert_html_str_to_pdf(html, writer)?;
+            return convert_str_with_settings(html, settings, writer);
         });
         no_link!({
-            use std::borrow::Cow;
-            use std::fs;
+            use std::io::BufRead;
             use std::process::{Command, Stdio};
 
             if WK_HTML_RUNNER.is_empty() {
@@ -116,94 +551,131 @@ where
                 }
             }}
 
-            let tmp_dir = tempfile::Builder::new()
-                .prefix(&format!("wkhtml-{}", WK_HTML_LIBRARY_VERSION))
-                .tempdir()?;
-
-            // Write runner executable:
-            let exe_path = tmp_dir.path().join("wkhtml_runner.exe");
-
-            fs::File::create(&exe_path)
-                .and_then(|mut file| io::copy(&mut &WK_HTML_RUNNER[..], &mut file))
-                .context("Failed to create \"wkhtml_runner.exe\".")?;
-
-            // Write needed dynamic library:
+            // Runner executable and needed dynamic library:
+            #[allow(unused_mut)]
+            let mut files = vec![extract::EmbeddedFile {
+                name: "wkhtml_runner.exe",
+                data: WK_HTML_RUNNER,
+                sha256: WK_HTML_RUNNER_SHA256,
+            }];
             has_dll! {{
                 use wkhtml_link::WK_HTML_TO_PDF_DLL;
 
-                let dll_path = tmp_dir.path().join("wkhtmltox.dll");
-
-                fs::File::create(dll_path).and_then(|mut file| {
-                    io::copy(&mut &WK_HTML_TO_PDF_DLL[..], &mut file)
-                }).context("Failed to create \"wkhtmltox.dll\".")?;
+                files.push(extract::EmbeddedFile {
+                    name: wkhtml_link::WK_HTML_TO_PDF_LIB_NAME,
+                    data: &WK_HTML_TO_PDF_DLL[..],
+                    sha256: wkhtml_link::WK_HTML_TO_PDF_DLL_SHA256,
+                });
             }}
+            // Reused by later conversions:
+            let tmp_dir = extract::extracted_dir(_temp_dir, &files)?;
+            let exe_path = tmp_dir.join("wkhtml_runner.exe");
 
             // Spawn child process:
             let mut process = Command::new(exe_path);
-            #[cfg(all(windows, feature = "windows-gui"))]
-            {
-                use std::os::windows::process::CommandExt;
-
-                // Hide console window:
-                // https://stackoverflow.com/questions/6371149/what-is-the-difference-between-detach-process-and-create-no-window-process-creat
-                // https://learn.microsoft.com/sv-se/windows/win32/procthread/process-creation-flags?redirectedfrom=MSDN
-                // Need "CREATE_NO_WINDOW" if the created process will spawn its own sub-processes,
-                // otherwise DETACHED_PROCESS is enough to prevent a console from being opened.
-                process.creation_flags(/*CREATE_NO_WINDOW*/ 0x08000000);
+            for (name, value) in &settings.global {
+                process.arg("--global").arg(format!("{name}={value}"));
             }
+            for (name, value) in &settings.object {
+                process.arg("--object").arg(format!("{name}={value}"));
+            }
+            // Windows searches the executable's folder for ".dll" files but on
+            // Linux the library folder must be specified:
+            #[cfg(target_os = "linux")]
+            process.env("LD_LIBRARY_PATH", &tmp_dir);
+            html_to_pdf::hide_console_window(&mut process, _hide_window);
             let mut process = process
                 .stdin(Stdio::piped())
                 .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
                 .spawn()
                 .context("Failed to start \"wkhtml_runner.exe\"")?;
+            let mut stdin = process
+                .stdin
+                .take()
+                .context("Failed to open stdin for \"wkhtml_runner.exe\".")?;
             // Redirect child process stdout to writer:
             let mut stdout = process
                 .stdout
                 .take()
                 .context("Failed to open stdout for \"wkhtml_runner.exe\".")?;
+            let stderr = process
+                .stderr
+                .take()
+                .context("Failed to open stderr for \"wkhtml_runner.exe\".")?;
+            let mut process = html_to_pdf::ChildGuard::from(process);
 
-            crossbeam::scope(|s| -> eyre::Result<_> {
+            return crossbeam::scope(|s| -> eyre::Result<_> {
                 let redirect_thread = s.spawn(move |_| -> eyre::Result<_> {
-                    Ok(io::copy(&mut stdout, &mut writer.get_writer()?)?)
+                    Ok(html_to_pdf::copy_with_flush_interval(
+                        &mut stdout,
+                        &mut writer.get_writer()?,
+                        _flush_interval,
+                    )?)
+                });
+                // Collect warnings, anything else (like panic messages) is
+                // forwarded to our stderr and the end of it is kept in case
+                // the program fails:
+                let diagnostics_thread = s.spawn(move |_| {
+                    let mut diagnostics = Vec::new();
+                    let mut stderr_tail = String::new();
+                    for line in io::BufReader::new(stderr).lines() {
+                        let Ok(line) = line else { break };
+                        match parse_runner_diagnostic(&line) {
+                            Some(diagnostic) => diagnostics.push(diagnostic),
+                            None => {
+                                eprintln!("{line}");
+                                stderr_tail.push_str(&line);
+                                stderr_tail.push('\n');
+                                let excess = stderr_tail
+                                    .len()
+                                    .saturating_sub(html_to_pdf::ProcessError::STDERR_TAIL_LEN);
+                                if excess > 0 {
+                                    let start = (excess..=stderr_tail.len())
+                                        .find(|&i| stderr_tail.is_char_boundary(i))
+                                        .unwrap_or(stderr_tail.len());
+                                    stderr_tail.drain(..start);
+                                }
+                            }
+                        }
+                    }
+                    (diagnostics, stderr_tail)
                 });
 
                 // Write to child process stdin:
-                let mut stdin = process
-                    .stdin
-                    .take()
-                    .context("Failed to open stdin for \"wkhtml_runner.exe\".")?;
-                io::copy(&mut html_reader, &mut stdin)
-                    .context("Failed to write html data to stdin for \"wkhtml_runner.exe\".")?;
+                if let Err(e) = io::copy(&mut html_reader, &mut stdin) {
+                    // Stop the program before its stdin is closed, otherwise
+                    // it would convert the partial HTML while the scope waits
+                    // for the other threads:
+                    process.kill().ok();
+                    return Err(e)
+                        .context("Failed to write html data to stdin for \"wkhtml_runner.exe\".");
+                }
                 // Close stdin:
                 drop(stdin);
                 // Wait for child process to exit:
                 let status = process
                     .wait()
                     .context("Failed to wait for \"wkhtml_runner.exe\" to exit.")?;
+                let (diagnostics, stderr_tail) = diagnostics_thread
+                    .join()
+                    .map_err(|payload| html_to_pdf::WorkerPanicked::new(&*payload))
+                    .context(r#"Thread reading from stderr of "wkhtml_runner.exe" failed"#)?;
                 if !status.success() {
-                    bail!(
-                        "\"wkhtml_runner.exe\" exited with an error{}.",
-                        if let Some(code) = status.code() {
-                            Cow::from(format!(" (code: {})", code))
-                        } else {
-                            "".into()
-                        }
-                    );
+                    return Err(html_to_pdf::ProcessError::new("wkhtml_runner.exe", status)
+                        .with_stderr(stderr_tail)
+                        .into());
                 }
                 redirect_thread
                     .join()
-                    .expect(r#"Thread reading from stdin of "wkhtml_runner.exe" panicked"#)
+                    .map_err(|payload| html_to_pdf::WorkerPanicked::new(&*payload))
+                    .context(r#"Thread reading from stdout of "wkhtml_runner.exe" failed"#)?
                     .context(r#"Failed to read pdf data from stdout of "wkhtml_runner.exe"."#)?;
 
-                Ok(())
+                Ok(diagnostics)
             })
-            .unwrap()?;
-
-            tmp_dir
-                .close()
-                .context("failed to delete temporary folder for wkhtml files")?;
+            .unwrap();
         });
-        return Ok(());
     });
     #[allow(unreachable_code)]
     {
@@ -215,21 +687,52 @@ where
 ///
 /// This version is more efficient when linking directly to wkhtml.
 pub fn convert_html_str_to_pdf<R, W>(html: R, writer: W) -> eyre::Result<()>
+where
+    R: AsRef<str>,
+    W: WriteBuilder + Send,
+{
+    convert_str_with_settings(html, &Settings::default(), writer)?;
+    Ok(())
+}
+
+/// Returns warnings that wkhtmltopdf reported, see
+/// [`convert_reader_with_settings`].
+fn convert_str_with_settings<R, W>(
+    html: R,
+    _settings: &Settings,
+    writer: W,
+) -> eyre::Result<Vec<Diagnostic>>
 where
     R: AsRef<str>,
     W: WriteBuilder + Send,
 {
     is_supported!({
+        let settings = _settings;
         has_link!({
             let mut writer = writer;
             let writer = writer.get_writer()?;
-            wkhtml_link::convert_html_to_pdf(html, writer)?;
+            // Safety: only known setting names can be added to `Settings`.
+            unsafe {
+                wkhtml_link::convert_html_to_pdf_with_settings(
+                    html,
+                    &settings.global,
+                    &settings.object,
+                    writer,
+                )?;
+            }
+            return Ok(Vec::new());
         });
         no_link!({
             let html = html.as_ref();
-            convert_html_to_pdf(html.as_bytes(), writer)?;
+            return convert_reader_with_settings(
+                html.as_bytes(),
+                settings,
+                &Default::default(),
+                None,
+                cfg!(feature = "windows-gui"),
+                writer,
+            );
         });
-        return Ok(());
     });
     #[allow(unreachable_code)]
     {
@@ -241,8 +744,232 @@ mod converter {
     use super::*;
 
     /// Use WKHtmlToPdf to convert HTML to a PDF.
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-    pub struct WkHtmlPdfConverter;
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct WkHtmlPdfConverter {
+        settings: Settings,
+        /// Extract the runner program and the wkhtml library into a temporary
+        /// folder inside this folder instead of the system's temp folder. This
+        /// is useful if the system's temp folder doesn't allow executing
+        /// programs (it is mounted with `noexec`).
+        ///
+        /// Not used when linking directly to wkhtml.
+        pub temp_dir: Option<PathBuf>,
+        /// Start the name of the temporary folder with this instead of
+        /// `wkhtml-{version}`, which makes it easier to find, for example to
+        /// clean up after a program that crashed. Ignored if
+        /// [`temp_dir_name`](Self::temp_dir_name) is set.
+        ///
+        /// Not used when linking directly to wkhtml.
+        pub temp_dir_prefix: Option<String>,
+        /// Extract the files into a folder with exactly this name inside
+        /// [`temp_dir`](Self::temp_dir) instead of a folder with a random
+        /// name. The folder is never deleted so that later runs of the program
+        /// can reuse the files, which are only written again if their checksum
+        /// doesn't match.
+        ///
+        /// Not used when linking directly to wkhtml.
+        pub temp_dir_name: Option<String>,
+        /// Don't delete the temporary folder when the process exits. Useful
+        /// to inspect the extracted runner program and wkhtml library when
+        /// debugging errors like "couldn't find runner".
+        ///
+        /// Not used when linking directly to wkhtml.
+        pub keep_temp_dir: bool,
+        /// Resources that the HTML references, see [`Assets`] for how their
+        /// paths are mapped. They are written to a temporary folder and a
+        /// `<base>` tag that points to that folder is inserted at the start of
+        /// the HTML, so it takes precedence over any `<base>` tag in the
+        /// document.
+        ///
+        /// If there are any assets then wkhtmltopdf is only allowed to load
+        /// local files: `load.proxy` is set to a proxy that can't be reached
+        /// and `load.blockLocalFileAccess` is disabled, overriding values from
+        /// [`set`](Self::set).
+        pub assets: Assets,
+        /// Flush the output at most this often while PDF data arrives from
+        /// the runner program, so that clients can start reading the PDF
+        /// before a buffered output fills up. `None` (the default) never
+        /// flushes until the conversion is done, which gives the best
+        /// throughput since each flush can mean a small write, see
+        /// [`copy_with_flush_interval`](html_to_pdf::copy_with_flush_interval).
+        ///
+        /// Not used when linking directly to wkhtml since the PDF is then
+        /// written all at once.
+        pub flush_interval: Option<Duration>,
+        /// Start the runner program without a console window on Windows,
+        /// which otherwise pops up in GUI programs. Disable it to see the
+        /// program's console while debugging. Ignored on other platforms and
+        /// when linking directly to wkhtml.
+        ///
+        /// Defaults to `true` if the `windows-gui` feature is enabled.
+        pub hide_window: bool,
+        /// wkhtmltopdf can't create PDF/A documents, so starting a conversion
+        /// fails with a [`PdfANotSupportedError`] if this is set.
+        pub pdf_a: Option<PdfAConformance>,
+    }
+    // Only derivable if the `windows-gui` feature is disabled:
+    #[allow(clippy::derivable_impls)]
+    impl Default for WkHtmlPdfConverter {
+        fn default() -> Self {
+            Self {
+                settings: Settings::default(),
+                temp_dir: None,
+                temp_dir_prefix: None,
+                temp_dir_name: None,
+                keep_temp_dir: false,
+                assets: Assets::default(),
+                flush_interval: None,
+                hide_window: cfg!(feature = "windows-gui"),
+                pdf_a: None,
+            }
+        }
+    }
+    impl WkHtmlPdfConverter {
+        /// `true` if the PDF is written to the output while it is read from
+        /// the runner program. When wkhtml is linked to directly the library
+        /// returns the whole PDF at once, so it is written to the output in
+        /// [`HtmlSink::complete`](html_to_pdf::HtmlSink::complete).
+        pub const STREAMS_PDF: bool = !PREFER_BUFFER_OVER_READER;
+
+        /// Set a wkhtmltopdf setting, for example `load.jsdelay` to wait
+        /// longer for JavaScript. The name must be one of [`GLOBAL_SETTINGS`]
+        /// or [`OBJECT_SETTINGS`]. Setting the same name again replaces the
+        /// previous value.
+        pub fn set(
+            &mut self,
+            name: &str,
+            value: impl Into<String>,
+        ) -> Result<(), UnknownSettingError> {
+            let (settings, name) = match (
+                GLOBAL_SETTINGS.iter().find(|&&v| v == name),
+                OBJECT_SETTINGS.iter().find(|&&v| v == name),
+            ) {
+                (Some(name), _) => (&mut self.settings.global, *name),
+                (None, Some(name)) => (&mut self.settings.object, *name),
+                (None, None) => return Err(UnknownSettingError(name.to_owned())),
+            };
+            settings.retain(|(existing, _)| *existing != name);
+            settings.push((name, value.into()));
+            Ok(())
+        }
+        /// Scale the page content by `factor`, for example `1.5` to make text
+        /// and images 50% larger. Sets `load.zoomFactor`.
+        pub fn zoom(&mut self, factor: f64) {
+            self.set_known("load.zoomFactor", factor.to_string());
+        }
+        /// The resolution that the page is laid out with, which affects the
+        /// size of text and images in the PDF. Sets `dpi`.
+        pub fn dpi(&mut self, dpi: u32) {
+            self.set_known("dpi", dpi.to_string());
+        }
+        /// Stop wkhtmltopdf from shrinking the content to make more of it fit
+        /// the page width. Pages then use the same scale as a browser, which
+        /// makes layouts that depend on pixel sizes more accurate. Sets
+        /// `web.enableIntelligentShrinking`.
+        ///
+        /// Note that wkhtmltopdf only honors this on some platforms.
+        pub fn disable_smart_shrinking(&mut self, value: bool) {
+            self.set_known("web.enableIntelligentShrinking", (!value).to_string());
+        }
+        /// The paper to lay out the pages on. Sets `size.width` and
+        /// `size.height`, which take precedence over `size.paperSize`.
+        pub fn paper(&mut self, paper: Paper) {
+            let (width, height) = paper.size();
+            self.set_known("size.width", format!("{}mm", width.mm()));
+            self.set_known("size.height", format!("{}mm", height.mm()));
+        }
+        /// Turn the paper to landscape. Sets `orientation`.
+        pub fn orientation(&mut self, orientation: PageOrientation) {
+            let value = match orientation {
+                PageOrientation::Portrait => "Portrait",
+                PageOrientation::Landscape => "Landscape",
+            };
+            self.set_known("orientation", value.to_owned());
+        }
+        /// Space between the edges of the paper and the content. Sets
+        /// `margin.top`, `margin.right`, `margin.bottom` and `margin.left`.
+        pub fn margin(&mut self, margin: Margin) {
+            for (name, length) in [
+                ("margin.top", margin.top),
+                ("margin.right", margin.right),
+                ("margin.bottom", margin.bottom),
+                ("margin.left", margin.left),
+            ] {
+                self.set_known(name, format!("{}mm", length.mm()));
+            }
+        }
+        /// Store an outline (bookmarks) in the PDF that is built from the
+        /// document's `<h1>` to `<h6>` headings, so that
+        /// [`HtmlToPdfConverter::convert_with_outline`](html_to_pdf::HtmlToPdfConverter::convert_with_outline)
+        /// can read it back. Sets `outline`.
+        pub fn outline(&mut self, value: bool) {
+            self.set_known("outline", value.to_string());
+        }
+        /// Treat the HTML as untrusted: JavaScript is disabled, local files
+        /// can't be read and network requests go to a proxy that can't be
+        /// reached. Sets `web.enableJavascript`, `load.blockLocalFileAccess`
+        /// and `load.proxy`.
+        ///
+        /// If there are [`assets`](Self::assets) then local files are still
+        /// allowed so that the assets can be read, but nothing is fetched over
+        /// the network.
+        pub fn sanitize_untrusted(&mut self, value: bool) {
+            self.set_known("web.enableJavascript", (!value).to_string());
+            self.set_known("load.blockLocalFileAccess", value.to_string());
+            if value {
+                // Nothing should listen on the discard port:
+                self.set_known("load.proxy", "http://127.0.0.1:9".to_owned());
+            } else {
+                self.settings
+                    .object
+                    .retain(|(name, _)| *name != "load.proxy");
+            }
+        }
+        fn set_known(&mut self, name: &str, value: String) {
+            self.set(name, value)
+                .expect("wkhtmltopdf should have the setting");
+        }
+
+        /// The settings that have been [`set`](Self::set).
+        pub fn settings(&self) -> impl Iterator<Item = (&str, &str)> {
+            self.settings
+                .global
+                .iter()
+                .chain(&self.settings.object)
+                .map(|(name, value)| (*name, value.as_str()))
+        }
+
+        #[allow(dead_code)] // Unused on platforms that wkhtml doesn't support.
+        fn temp_dir_options(&self) -> extract::TempDirOptions {
+            extract::TempDirOptions {
+                parent: self.temp_dir.clone(),
+                prefix: self.temp_dir_prefix.clone(),
+                name: self.temp_dir_name.clone(),
+                keep: self.keep_temp_dir,
+            }
+        }
+    }
+
+    /// Uses all options, see [`paper`](Self::paper),
+    /// [`orientation`](Self::orientation), [`margin`](Self::margin) and
+    /// [`dpi`](Self::dpi).
+    impl html_to_pdf::WithPageOptions for WkHtmlPdfConverter {
+        fn with_page_options(mut self, options: html_to_pdf::PageOptions) -> Self {
+            if let Some(paper) = options.page_size {
+                self.paper(paper);
+            }
+            if let Some(orientation) = options.orientation {
+                self.orientation(orientation);
+            }
+            if let Some(margin) = options.margins {
+                self.margin(margin);
+            }
+            if let Some(dpi) = options.dpi {
+                self.dpi(dpi);
+            }
+            self
+        }
+    }
 
     // TODO: implement an option to run WKHtml as a child process even if it is
     // linked.
@@ -264,100 +991,158 @@ mod converter {
             _scope: html_to_pdf::PdfScope<'scope, '_>,
             _output: W,
         ) -> Result<Self::HtmlSink, Self::Error> {
+            if let Some(conformance) = self.pdf_a {
+                return Err(PdfANotSupportedError {
+                    engine: "wkhtml",
+                    conformance,
+                }
+                .into());
+            }
             is_supported!({
                 let mut output = _output;
+                let temp_dir_options = self.temp_dir_options();
+                let mut settings = self.settings;
+                let assets = if self.assets.is_empty() {
+                    None
+                } else {
+                    settings.only_load_local_files();
+                    Some(
+                        AssetDir::create(&self.assets)
+                            .context("Failed to write the assets to a temporary folder")?,
+                    )
+                };
                 let state = if PREFER_BUFFER_OVER_READER {
                     HtmlSinkState::Wkhtml {
                         output,
                         buffer: Vec::new(),
+                        settings,
+                        assets,
                     }
                 } else {
                     HtmlSinkState::Streaming(html_to_pdf::WriteStream::stream(
                         _scope,
                         move |html| {
-                            convert_html_to_pdf::<_, &mut W>(html, &mut output)
-                                .context(r#"Failed to convert HTML to PDF using "WKHtmlToPdf""#)?;
-                            Ok(output)
+                            let base_url = assets.as_ref().map(AssetDir::base_url);
+                            let html = with_base_href(html, base_url.as_deref())
+                                .context("Failed to read the start of the HTML")?;
+                            let diagnostics = convert_reader_with_settings::<_, &mut W>(
+                                html,
+                                &settings,
+                                &temp_dir_options,
+                                self.flush_interval,
+                                self.hide_window,
+                                &mut output,
+                            )
+                            .context(r#"Failed to convert HTML to PDF using "WKHtmlToPdf""#)?;
+                            // The assets are no longer needed:
+                            drop(assets);
+                            Ok((output, diagnostics))
                         },
                     ))
                 };
-                return Ok(HtmlSink(Some(state)));
+                return Ok(HtmlSink(state));
             });
             #[allow(unreachable_code)]
             {
                 Err(NotSupportedError.into())
             }
         }
+
+        fn name(&self) -> Cow<'static, str> {
+            Cow::Borrowed("wkhtml")
+        }
+
+        /// The PDF only has an outline if [`outline`](Self::outline) is
+        /// enabled. The HTML is buffered when wkhtml is linked, see
+        /// [`PREFER_BUFFER_OVER_READER`].
+        fn capabilities(&self) -> html_to_pdf::ConverterCapabilities {
+            html_to_pdf::ConverterCapabilities {
+                table_of_contents: self
+                    .settings()
+                    .any(|(name, value)| name == "outline" && value == "true"),
+                clickable_links: true,
+                streaming_input: !PREFER_BUFFER_OVER_READER,
+                embedded_fonts: true,
+            }
+        }
     }
     impl<'scope, W> html_to_pdf::HtmlSink<W, eyre::Error> for HtmlSink<'scope, W>
     where
         W: WriteBuilder + Send + 'scope,
     {
-        fn complete(mut self) -> Result<W, eyre::Error> {
-            self._complete().map(Option::unwrap)
+        fn complete(self) -> Result<W, eyre::Error> {
+            html_to_pdf::HtmlSink::complete_with_diagnostics(self).map(|(output, _)| output)
+        }
+        /// Warnings are only collected when using the runner program, see
+        /// [`WkHtmlPdfConverter::STREAMS_PDF`].
+        fn complete_with_diagnostics(self) -> Result<(W, Vec<Diagnostic>), eyre::Error> {
+            match self.0 {
+                HtmlSinkState::Wkhtml {
+                    mut output,
+                    mut buffer,
+                    settings,
+                    assets,
+                } => {
+                    if let Some(assets) = &assets {
+                        let position = base_href_position(&buffer, true)
+                            .expect("the whole document is available");
+                        let tag = base_href_tag(&assets.base_url());
+                        buffer.splice(position..position, tag.into_bytes());
+                    }
+                    let diagnostics = convert_str_with_settings::<_, &mut W>(
+                        String::from_utf8_lossy(&buffer),
+                        &settings,
+                        &mut output,
+                    )
+                    .context(r#"Failed to convert HTML to PDF using "WKHtmlToPdf""#)?;
+                    Ok((output, diagnostics))
+                }
+                HtmlSinkState::Streaming(mut writer) => {
+                    writer
+                        .flush()
+                        .context("Failed to flush written HTML data to the PDF converter.")?;
+                    // Wait for the thread to stop writing PDF data and return the
+                    // PDF sink:
+                    let joined = writer.join_result();
+                    let result = joined
+                        .result
+                        .context("The thread converting HTML to PDF failed")
+                        .and_then(|result| result);
+                    if joined.reader_closed_early {
+                        result
+                            .context(r#""wkhtml_runner.exe" exited before it read all of the HTML"#)
+                    } else {
+                        result
+                    }
+                }
+            }
         }
     }
 
     enum HtmlSinkState<'scope, W> {
         /// When "WKHtmlToPdf" is linked to directly it needs a string slice to work
         /// with which means that we can't stream data to it.
-        Wkhtml { output: W, buffer: Vec<u8> },
+        Wkhtml {
+            output: W,
+            buffer: Vec<u8>,
+            settings: Settings,
+            /// Deleted once the conversion is done.
+            assets: Option<AssetDir>,
+        },
         /// We shell out to another program and so we can stream the data to it.
-        Streaming(html_to_pdf::WriteStream<'scope, eyre::Result<W>>),
+        Streaming(html_to_pdf::WriteStream<'scope, eyre::Result<(W, Vec<Diagnostic>)>>),
     }
-    pub struct HtmlSink<'scope, W>(Option<HtmlSinkState<'scope, W>>)
+    /// Dropping this without completing it cancels the conversion: buffered
+    /// HTML is discarded and a running "wkhtml_runner.exe" program is killed
+    /// without being told that the HTML ended.
+    pub struct HtmlSink<'scope, W>(HtmlSinkState<'scope, W>)
     where
         W: WriteBuilder + Send + 'scope;
 
-    impl<'scope, W> HtmlSink<'scope, W>
-    where
-        W: WriteBuilder + Send + 'scope,
-    {
-        /// This can be called via `complete` or via the Drop implementation. The
-        /// `Option` is guaranteed to be `Some` if this method isn't called in the
-        /// `Drop` implementation.
-        fn _complete(&mut self) -> eyre::Result<Option<W>> {
-            if let Some(state) = self.0.take() {
-                Ok(Some(match state {
-                    HtmlSinkState::Wkhtml { mut output, buffer } => {
-                        convert_html_str_to_pdf::<_, &mut W>(
-                            String::from_utf8_lossy(&buffer),
-                            &mut output,
-                        )
-                        .context(r#"Failed to convert HTML to PDF using "WKHtmlToPdf""#)?;
-                        output
-                    }
-                    HtmlSinkState::Streaming(mut writer) => {
-                        writer
-                            .flush()
-                            .context("Failed to flush written HTML data to the PDF converter.")?;
-                        // Wait for the thread to stop writing PDF data and return the
-                        // PDF sink:
-                        writer.join().unwrap()?
-                    }
-                }))
-            } else {
-                // Already completed:
-                Ok(None)
-            }
-        }
-    }
-    /// Ensure the PDF conversion happens even if the [`HtmlSink`] is dropped
-    /// without calling [`html_to_pdf::HtmlToPdfConverter::complete`].
-    impl<'scope, W> Drop for HtmlSink<'scope, W>
-    where
-        W: WriteBuilder + Send + 'scope,
-    {
-        fn drop(&mut self) {
-            // This might have been called previously but that is handled by the method:
-            self._complete().ok();
-        }
-    }
-
     /// Forward writing from the HtmlSink to the inner sink.
     macro_rules! get_writer {
         ($this: ident, $name:ident => $($token:tt)*) => {
-            match $this.0.as_mut().unwrap() {
+            match &mut $this.0 {
                 HtmlSinkState::Wkhtml { buffer: $name, .. } => { $($token)* },
                 HtmlSinkState::Streaming($name) => { $($token)* },
             }
@@ -386,3 +1171,474 @@ mod converter {
 }
 #[doc(inline)]
 pub use converter::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use extract::EmbeddedFile;
+    use sha2::{Digest, Sha256};
+
+    /// Returns one byte at a time.
+    struct ByteReader<'a>(&'a [u8]);
+    impl Read for ByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((&byte, rest)) if !buf.is_empty() => {
+                    buf[0] = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_a_is_rejected() {
+        let mut converter = WkHtmlPdfConverter::default();
+        converter.pdf_a = Some(PdfAConformance::PdfA2B);
+        let error = html_to_pdf::HtmlToPdfConverter::start(
+            converter,
+            html_to_pdf::PdfScope::owned(),
+            html_to_pdf::WriteBuilderSimple(Vec::new()),
+        )
+        .err()
+        .expect("wkhtml can't create PDF/A documents");
+
+        let error = html_to_pdf::HtmlToPdfError::from(error);
+        assert!(matches!(error, html_to_pdf::HtmlToPdfError::Engine(_)));
+        assert_eq!(
+            std::error::Error::source(&error).and_then(|source| source.downcast_ref()),
+            Some(&PdfANotSupportedError {
+                engine: "wkhtml",
+                conformance: PdfAConformance::PdfA2B,
+            })
+        );
+    }
+
+    #[test]
+    fn base_href_is_inserted_after_doctype() {
+        let base = r#"<base href="file:///assets/">"#;
+        for (html, expected) in [
+            (
+                "<!DOCTYPE html><img src=a.png>",
+                format!("<!DOCTYPE html>{base}<img src=a.png>"),
+            ),
+            (
+                "\u{feff}\n<!doctype html>\n<p>",
+                format!("\u{feff}\n<!doctype html>{base}\n<p>"),
+            ),
+            ("<p>Hello</p>", format!("{base}<p>Hello</p>")),
+            ("", base.to_owned()),
+        ] {
+            let mut output = String::new();
+            with_base_href(ByteReader(html.as_bytes()), Some("file:///assets/"))
+                .unwrap()
+                .read_to_string(&mut output)
+                .unwrap();
+            assert_eq!(output, expected);
+        }
+
+        let mut output = String::new();
+        with_base_href(ByteReader(b"<p>"), Some(r#"file:///a&b/"><script>"#))
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(
+            output,
+            r#"<base href="file:///a&amp;b/&quot;&gt;&lt;script&gt;"><p>"#
+        );
+
+        let mut output = String::new();
+        with_base_href(ByteReader(b"<p>"), None)
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "<p>");
+    }
+
+    #[test]
+    fn runner_diagnostics_are_parsed() {
+        assert_eq!(
+            parse_runner_diagnostic("warning: Failed to load file:///missing.png"),
+            Some(Diagnostic::warning("Failed to load file:///missing.png"))
+        );
+        assert_eq!(
+            parse_runner_diagnostic("error: Exit with code 1"),
+            Some(Diagnostic::error("Exit with code 1"))
+        );
+        assert_eq!(parse_runner_diagnostic("thread 'main' panicked"), None);
+    }
+
+    #[test]
+    #[cfg(not(feature = "should_link"))]
+    #[ignore = "requires the wkhtml library"]
+    fn missing_image_is_reported_as_warning() {
+        use html_to_pdf::HtmlToPdfConverter as _;
+
+        let mut html_sink = WkHtmlPdfConverter::default()
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                html_to_pdf::WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+        html_sink
+            .write_all(br#"<p>Hello</p><img src="file:///html_to_pdf-missing/image.png">"#)
+            .unwrap();
+        let (output, diagnostics) =
+            html_to_pdf::HtmlSink::complete_with_diagnostics(html_sink).unwrap();
+
+        assert!(output.0.starts_with(b"%PDF-"));
+        assert!(
+            diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.message.contains("image.png")),
+            "{diagnostics:?}"
+        );
+    }
+
+    #[test]
+    #[ignore = "requires the wkhtml library"]
+    fn dropped_sink_writes_no_pdf() {
+        use html_to_pdf::HtmlToPdfConverter as _;
+
+        let mut output = html_to_pdf::WriteBuilderSimple(Vec::new());
+        std::thread::scope(|s| {
+            let mut html_sink = WkHtmlPdfConverter::default()
+                .start(html_to_pdf::PdfScope::scoped(s), &mut output)
+                .unwrap();
+            html_sink.write_all(b"<p>Hello</p>").unwrap();
+            drop(html_sink);
+        });
+        assert!(output.0.is_empty());
+    }
+
+    #[test]
+    fn options_are_mapped_to_settings() {
+        let mut converter = WkHtmlPdfConverter::default();
+        converter.zoom(1.5);
+        converter.dpi(96);
+        converter.disable_smart_shrinking(true);
+        converter.outline(true);
+        assert_eq!(
+            converter.settings().collect::<Vec<_>>(),
+            [
+                ("dpi", "96"),
+                ("outline", "true"),
+                ("load.zoomFactor", "1.5"),
+                ("web.enableIntelligentShrinking", "false")
+            ]
+        );
+    }
+
+    #[test]
+    fn page_setup_is_mapped_to_settings() {
+        let mut converter = WkHtmlPdfConverter::default();
+        converter.paper("100mm x 200mm".parse().unwrap());
+        converter.orientation(PageOrientation::Landscape);
+        converter.margin(Margin::all(html_to_pdf::Length::from_mm(5.0)));
+        let settings = converter
+            .settings()
+            .collect::<std::collections::HashMap<_, _>>();
+        let mm = |name: &str| -> f64 {
+            let value = settings[name].strip_suffix("mm").unwrap();
+            value.parse::<f64>().unwrap().round()
+        };
+        assert_eq!((mm("size.width"), mm("size.height")), (100.0, 200.0));
+        assert_eq!(settings["orientation"], "Landscape");
+        for side in ["top", "right", "bottom", "left"] {
+            assert_eq!(mm(&format!("margin.{side}")), 5.0);
+        }
+    }
+
+    #[test]
+    fn page_options_are_mapped_to_settings() {
+        use html_to_pdf::WithPageOptions;
+
+        let converter = WkHtmlPdfConverter::default().with_page_options(html_to_pdf::PageOptions {
+            orientation: Some(PageOrientation::Landscape),
+            dpi: Some(300),
+            ..Default::default()
+        });
+        assert_eq!(
+            converter.settings().collect::<Vec<_>>(),
+            [("orientation", "Landscape"), ("dpi", "300")]
+        );
+    }
+
+    #[test]
+    #[ignore = "requires the wkhtml library"]
+    fn outline_is_read_back() {
+        let mut converter = WkHtmlPdfConverter::default();
+        converter.outline(true);
+        let html = b"<h1>One</h1><h2>One A</h2><h1>Two</h1>";
+        let (_, outline) = html_to_pdf::convert_with_outline(converter, html).unwrap();
+        let titles = |items: &[html_to_pdf::OutlineItem]| {
+            items
+                .iter()
+                .map(|item| item.title.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(titles(&outline), ["One", "Two"]);
+        assert_eq!(titles(&outline[0].children), ["One A"]);
+        assert!(outline[1].children.is_empty());
+    }
+
+    #[test]
+    #[ignore = "requires the wkhtml library"]
+    fn zoom_changes_content_scale() {
+        use html_to_pdf::HtmlToPdfConverter as _;
+
+        let html = (0..200)
+            .map(|i| format!("<p>Paragraph {i}</p>"))
+            .collect::<String>();
+        let pages = |zoom: f64| {
+            let mut converter = WkHtmlPdfConverter::default();
+            converter.zoom(zoom);
+            converter.estimate_pages(html.as_bytes()).unwrap()
+        };
+
+        let normal = pages(1.0);
+        let zoomed = pages(2.0);
+        assert!(normal > 1);
+        // Larger text means fewer paragraphs fit on each page:
+        assert!(zoomed > normal, "{normal} pages vs {zoomed} zoomed");
+    }
+
+    #[test]
+    fn assets_only_allow_local_files() {
+        let mut settings = Settings::default();
+        settings
+            .object
+            .push(("load.proxy", "http://proxy:8080".to_owned()));
+        settings.only_load_local_files();
+        assert_eq!(
+            settings.object,
+            [
+                ("load.proxy", "http://127.0.0.1:9".to_owned()),
+                ("load.blockLocalFileAccess", "false".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitizing_disables_scripts_and_network() {
+        let mut converter = WkHtmlPdfConverter::default();
+        converter.sanitize_untrusted(true);
+        assert_eq!(
+            converter.settings().collect::<Vec<_>>(),
+            [
+                ("web.enableJavascript", "false"),
+                ("load.blockLocalFileAccess", "true"),
+                ("load.proxy", "http://127.0.0.1:9"),
+            ]
+        );
+
+        converter.sanitize_untrusted(false);
+        assert_eq!(
+            converter.settings().collect::<Vec<_>>(),
+            [
+                ("web.enableJavascript", "true"),
+                ("load.blockLocalFileAccess", "false"),
+            ]
+        );
+    }
+
+    #[test]
+    fn temp_dir_is_created_in_custom_folder() {
+        let parent = std::env::temp_dir().join(format!("wkhtml-custom-{}", std::process::id()));
+        let options = extract::TempDirOptions {
+            parent: Some(parent.clone()),
+            prefix: Some("custom-prefix".to_owned()),
+            ..Default::default()
+        };
+        let tmp_dir = extract::create_temp_dir(&options).unwrap();
+        let path = tmp_dir.path().to_owned();
+        assert_eq!(path.parent(), Some(parent.as_path()));
+        assert!(path.is_dir());
+        let name = path.file_name().unwrap().to_string_lossy();
+        assert!(name.starts_with("custom-prefix"), "{name}");
+
+        drop(tmp_dir);
+        assert!(!path.exists());
+        std::fs::remove_dir(&parent).unwrap();
+    }
+
+    #[test]
+    fn kept_temp_dir_is_not_deleted() {
+        let parent = std::env::temp_dir().join(format!("wkhtml-keep-{}", std::process::id()));
+        let kept = extract::TempDirOptions {
+            parent: Some(parent.clone()),
+            keep: true,
+            ..Default::default()
+        };
+        let named = extract::TempDirOptions {
+            parent: Some(parent.clone()),
+            name: Some("named".to_owned()),
+            ..Default::default()
+        };
+
+        let kept_dir = extract::create_temp_dir(&kept).unwrap();
+        let kept_path = kept_dir.path().to_owned();
+        let named_dir = extract::create_temp_dir(&named).unwrap();
+        assert_eq!(named_dir.path(), parent.join("named"));
+
+        // Dropped when the process exits:
+        drop((kept_dir, named_dir));
+        assert!(kept_path.is_dir());
+        assert!(parent.join("named").is_dir());
+
+        std::fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn extracted_files_are_reused() {
+        let parent = std::env::temp_dir().join(format!("wkhtml-reuse-{}", std::process::id()));
+        let options = extract::TempDirOptions {
+            parent: Some(parent),
+            ..Default::default()
+        };
+        let runner_sha256 = Sha256::digest(b"runner");
+        let library_sha256 = Sha256::digest(b"library");
+        let files = [
+            EmbeddedFile {
+                name: "runner",
+                data: b"runner",
+                sha256: &runner_sha256,
+            },
+            EmbeddedFile {
+                name: "library",
+                data: b"library",
+                sha256: &library_sha256,
+            },
+        ];
+
+        let first = extract::extracted_dir(&options, &files).unwrap();
+        let modified = || {
+            std::fs::metadata(first.join("runner"))
+                .unwrap()
+                .modified()
+                .unwrap()
+        };
+        let first_modified = modified();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        let second = extract::extracted_dir(&options, &files).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first_modified, modified());
+        assert_eq!(std::fs::read(second.join("library")).unwrap(), b"library");
+    }
+
+    #[test]
+    fn corrupted_files_are_extracted_again() {
+        let parent = std::env::temp_dir().join(format!("wkhtml-corrupt-{}", std::process::id()));
+        let options = extract::TempDirOptions {
+            parent: Some(parent.clone()),
+            name: Some("kept".to_owned()),
+            keep: true,
+            ..Default::default()
+        };
+        let runner_sha256 = Sha256::digest(b"runner");
+        let files = [EmbeddedFile {
+            name: "runner",
+            data: b"runner",
+            sha256: &runner_sha256,
+        }];
+
+        // A kept folder from an earlier run is verified when it is reused:
+        std::fs::create_dir_all(parent.join("kept")).unwrap();
+        std::fs::write(parent.join("kept").join("runner"), b"corrupted").unwrap();
+        let dir = extract::extracted_dir(&options, &files).unwrap();
+        assert_eq!(std::fs::read(dir.join("runner")).unwrap(), b"runner");
+
+        // But only once per process:
+        std::fs::write(dir.join("runner"), b"corrupted").unwrap();
+        let dir = extract::extracted_dir(&options, &files).unwrap();
+        assert_eq!(std::fs::read(dir.join("runner")).unwrap(), b"corrupted");
+
+        // Data that doesn't match its checksum is an error:
+        let wrong_checksum = [EmbeddedFile {
+            name: "library",
+            data: b"library",
+            sha256: &runner_sha256,
+        }];
+        let error = extract::extracted_dir(&options, &wrong_checksum).unwrap_err();
+        assert!(error.to_string().contains("SHA-256"), "{error}");
+
+        std::fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86", target_arch = "x86_64"),
+        feature = "should_include_dll"
+    ))]
+    fn embedded_library_is_extracted_with_its_soname() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let parent = std::env::temp_dir().join(format!("wkhtml-so-{}", std::process::id()));
+        let options = extract::TempDirOptions {
+            parent: Some(parent),
+            ..Default::default()
+        };
+        let files = [EmbeddedFile {
+            name: wkhtml_link::WK_HTML_TO_PDF_LIB_NAME,
+            data: &wkhtml_link::WK_HTML_TO_PDF_DLL[..],
+            sha256: wkhtml_link::WK_HTML_TO_PDF_DLL_SHA256,
+        }];
+
+        let dir = extract::extracted_dir(&options, &files).unwrap();
+        // The runner program links to the library's soname:
+        let path = dir.join("libwkhtmltox.so.0");
+        let library = std::fs::read(&path).unwrap();
+        assert!(library.starts_with(b"\x7fELF"));
+        assert_eq!(library, &wkhtml_link::WK_HTML_TO_PDF_DLL[..]);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o755, 0o755);
+    }
+
+    #[test]
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86", target_arch = "x86_64"),
+        feature = "should_include_dll",
+        not(feature = "should_link")
+    ))]
+    #[ignore = "requires the libraries that wkhtml depends on, such as fontconfig"]
+    fn runner_loads_the_extracted_library() {
+        use html_to_pdf::HtmlToPdfConverter as _;
+
+        let parent = std::env::temp_dir().join(format!("wkhtml-load-{}", std::process::id()));
+        let mut converter = WkHtmlPdfConverter::default();
+        converter.temp_dir = Some(parent.clone());
+        converter.temp_dir_name = Some("extracted".to_owned());
+        let mut html_sink = converter
+            .start(
+                html_to_pdf::PdfScope::owned(),
+                html_to_pdf::WriteBuilderSimple(Vec::new()),
+            )
+            .unwrap();
+        html_sink.write_all(b"<p>Hello</p>").unwrap();
+        let output = html_to_pdf::HtmlSink::complete(html_sink).unwrap();
+
+        // The runner found the library through `LD_LIBRARY_PATH`:
+        assert!(output.0.starts_with(b"%PDF-"));
+        let extracted = parent.join("extracted");
+        assert!(extracted.join("wkhtml_runner.exe").is_file());
+        assert!(extracted.join("libwkhtmltox.so.0").is_file());
+
+        std::fs::remove_dir_all(&parent).unwrap();
+    }
+
+    #[test]
+    fn reports_name() {
+        let converter = WkHtmlPdfConverter::default();
+        assert_eq!(
+            html_to_pdf::HtmlToPdfConverter::<html_to_pdf::WriteBuilderSimple<Vec<u8>>>::name(
+                &converter
+            ),
+            "wkhtml"
+        );
+    }
+}
@@ -38,6 +38,29 @@ fn main() {
             );
         }
     };
+    let unpack_tar_xz = |data: &[u8]| {
+        let archive_path = PathBuf::from(&out_dir).join("wkhtmltox-0.12.3.tar.xz");
+        std::fs::write(&archive_path, data).expect("Failed to write downloaded archive to file");
+
+        let status_tar = std::process::Command::new("tar")
+            .arg("-xJf")
+            .arg(archive_path)
+            // Only interested in "wkhtmltox/lib/libwkhtmltox.so*":
+            .arg("--strip-components=2")
+            .arg("--wildcards")
+            .arg("*/lib/libwkhtmltox.so*")
+            .stdin(std::process::Stdio::null())
+            .stdout(std::io::stderr())
+            .current_dir(&out_dir)
+            .status()
+            .expect("Failed to start tar CLI as child process to extract wkhtmltopdf archive");
+        if !status_tar.success() {
+            panic!(
+                "tar failed to extract wkhtmltopdf archive (code: {:?})",
+                status_tar.code()
+            );
+        }
+    };
     println!("cargo::rustc-check-cfg=cfg(supported_target)");
 
     // Download dll from website and verify SHA256 checksum:
@@ -65,15 +88,15 @@ fn main() {
             hex!("6e4613c060eb9e5eb0bd05b0ccd85d09086ef7a1156300e53a9dfba7969b6fc0"),
             None
         ),
-        () if target_arch == "aarch64" => (
+        () if target_os == "linux" && target_arch == "x86_64" => (
             "https://github.com/wkhtmltopdf/wkhtmltopdf/releases/download/0.12.3/wkhtmltox-0.12.3_linux-generic-amd64.tar.xz",
             hex!("40bc014d0754ea44bb90e733f03e7c92862f7445ef581e3599ecc00711dddcaa"),
-            None
+            Some(&unpack_tar_xz)
         ),
-        () if target_arch == "x86" || target_arch == "x86_64" => (
+        () if target_os == "linux" && target_arch == "x86" => (
             "https://github.com/wkhtmltopdf/wkhtmltopdf/releases/download/0.12.3/wkhtmltox-0.12.3_linux-generic-i386.tar.xz",
             hex!("001af3e3030a5418367e5d0ec75cdc1deef6131f0ed51e873c474e4199b8380b"),
-            None
+            Some(&unpack_tar_xz)
         ),
         _ => return, // Unsupported target
     };
@@ -102,6 +125,24 @@ fn main() {
 
     println!("cargo::rustc-cfg=supported_target");
 
+    // The file name of the dynamic library that the runner program needs at
+    // runtime. On Linux this is the library's "soname".
+    let library_name = if is_windows {
+        "wkhtmltox.dll"
+    } else {
+        "libwkhtmltox.so.0"
+    };
+    println!("cargo::rustc-env=WK_HTML_TO_PDF_LIB_NAME={library_name}");
+
+    // Store the library's checksum so that extracted copies can be verified at runtime:
+    let library = fs::read(PathBuf::from(&out_dir).join(library_name))
+        .expect("failed to read unpacked wkhtmltopdf library");
+    fs::write(
+        PathBuf::from(&out_dir).join(format!("{library_name}.sha256")),
+        Sha256::digest(library),
+    )
+    .unwrap();
+
     if env::var_os("CARGO_FEATURE_SHOULD_LINK").is_some() {
         // Copy library files to out_dir so that the out_dir is added to path when run and test commands are used.
         // This ensures that the dll file is found at runtime for those commands.
@@ -111,13 +152,13 @@ fn main() {
         );
     }
 
-    // Generate compressed include macro with path to ".dll" file since the macro can't specify path's relative to env!("OUT_DIR"):
+    // Generate compressed include macro with path to the library file since the macro can't specify path's relative to env!("OUT_DIR"):
     fs::write(
         PathBuf::from(&out_dir).join("compressed.rs"),
         format!(
             r#####"include_flate::flate!(pub static WK_HTML_TO_PDF_DLL: [u8] from r####"{}"####);"#####,
             PathBuf::from(&out_dir)
-                .join("wkhtmltox.dll")
+                .join(library_name)
                 .to_str()
                 .expect("the OUT_DIR path should be valid UTF-8")
         ),
@@ -6,16 +6,36 @@ pub const WK_HTML_LIBRARY_VERSION: &str = "v0.12.3";
 mod stuff {
     pub use wkhtmltopdf::*;
 
-    pub fn convert_html_to_pdf<W: std::io::Write>(
+    pub fn convert_html_to_pdf<W: std::io::Write>(html: impl AsRef<str>, writer: W) -> Result<()> {
+        // Safety: there are no extra settings.
+        unsafe { convert_html_to_pdf_with_settings(html, &[], &[], writer) }
+    }
+
+    /// Convert HTML to PDF and apply extra wkhtmltopdf settings, see
+    /// <https://wkhtmltopdf.org/libwkhtmltox/pagesettings.html> for the
+    /// available global and object settings.
+    ///
+    /// # Safety
+    ///
+    /// wkhtmltopdf doesn't validate the settings, so the caller must ensure
+    /// that the names and values are valid.
+    pub unsafe fn convert_html_to_pdf_with_settings<W: std::io::Write>(
         html: impl AsRef<str>,
+        global_settings: &[(&'static str, String)],
+        object_settings: &[(&'static str, String)],
         mut writer: W,
     ) -> Result<()> {
         let mut pdf_app = PdfApplication::new().expect("Failed to init PDF application");
         let mut builder = pdf_app.builder();
         builder.orientation(Orientation::Portrait);
         // builder.margin(Size::Inches(2));
-        // builder.dpi(72);
         builder.page_size(PageSize::A6);
+        for (name, value) in global_settings {
+            builder.global_setting(*name, value.clone());
+        }
+        for (name, value) in object_settings {
+            builder.object_setting(*name, value.clone());
+        }
         let mut pdf_out = builder
             .build_from_html(html.as_ref())
             .expect("Failed to build pdf");
@@ -31,13 +51,31 @@ mod stuff {
     include!(concat!(env!("OUT_DIR"), "/compressed.rs"));
 
     #[cfg(not(feature = "compression"))]
-    pub static WK_HTML_TO_PDF_DLL: &[u8] =
-        include_bytes!(concat!(env!("OUT_DIR"), "/wkhtmltox.dll"));
+    pub static WK_HTML_TO_PDF_DLL: &[u8] = include_bytes!(concat!(
+        env!("OUT_DIR"),
+        "/",
+        env!("WK_HTML_TO_PDF_LIB_NAME")
+    ));
+
+    /// The file name that [`WK_HTML_TO_PDF_DLL`] should be written to so that
+    /// it is found by the runner program, for example `wkhtmltox.dll` on
+    /// Windows and `libwkhtmltox.so.0` on Linux.
+    pub const WK_HTML_TO_PDF_LIB_NAME: &str = env!("WK_HTML_TO_PDF_LIB_NAME");
+
+    /// SHA-256 digest of [`WK_HTML_TO_PDF_DLL`].
+    pub static WK_HTML_TO_PDF_DLL_SHA256: &[u8] = include_bytes!(concat!(
+        env!("OUT_DIR"),
+        "/",
+        env!("WK_HTML_TO_PDF_LIB_NAME"),
+        ".sha256"
+    ));
 }
 
 #[cfg(not(supported_target))]
 mod stuff {
     pub static WK_HTML_TO_PDF_DLL: &[u8] = &[];
+    pub const WK_HTML_TO_PDF_LIB_NAME: &str = "";
+    pub static WK_HTML_TO_PDF_DLL_SHA256: &[u8] = &[];
 
     pub fn convert_html_to_pdf<W: std::io::Write>(
         html: impl AsRef<str>,
@@ -48,6 +86,19 @@ mod stuff {
             "wkhtmltopdf doesn't support this target",
         ))
     }
+
+    /// # Safety
+    ///
+    /// Always safe to call since this target isn't supported and the
+    /// settings are never used.
+    pub unsafe fn convert_html_to_pdf_with_settings<W: std::io::Write>(
+        html: impl AsRef<str>,
+        _global_settings: &[(&'static str, String)],
+        _object_settings: &[(&'static str, String)],
+        writer: W,
+    ) -> Result<()> {
+        convert_html_to_pdf(html, writer)
+    }
 }
 
 #[doc(inline)]
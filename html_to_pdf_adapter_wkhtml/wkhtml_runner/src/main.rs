@@ -1,11 +1,65 @@
 use std::io::{self, Read};
 
+/// Writes warnings and errors that wkhtmltopdf logs to stderr, one per line,
+/// so that the main program can report them as diagnostics.
+struct StderrLogger;
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let level = if record.level() == log::Level::Error {
+            "error"
+        } else {
+            "warning"
+        };
+        let message = record.args().to_string().replace(['\r', '\n'], " ");
+        eprintln!("{}: {}", level, message);
+    }
+    fn flush(&self) {}
+}
+
 fn main() {
+    log::set_logger(&StderrLogger).expect("Failed to set logger.");
+    log::set_max_level(log::LevelFilter::Warn);
+
+    // Extra wkhtmltopdf settings are passed as "--global name=value" or
+    // "--object name=value":
+    let mut global_settings = Vec::new();
+    let mut object_settings = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let settings = match arg.as_str() {
+            "--global" => &mut global_settings,
+            "--object" => &mut object_settings,
+            _ => panic!("Unknown argument: {}", arg),
+        };
+        let setting = args.next().expect("Missing setting after argument.");
+        let (name, value) = setting
+            .split_once('=')
+            .expect("Settings should be specified as name=value.");
+        // wkhtmltopdf wants static setting names and they are needed until
+        // the program exits anyway:
+        let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+        settings.push((name, value.to_owned()));
+    }
+
     let mut html = String::with_capacity(2048);
     io::stdin().lock().read_to_string(&mut html)
         .expect("Failed to read HTML from stdin.");
 
     let stdout = std::io::stdout();
-    wkhtml_link::convert_html_to_pdf(html, &mut io::BufWriter::new(stdout.lock()))
-        .expect("Failed to convert HTML to PDF.");
+    // Safety: the main program only forwards known setting names.
+    unsafe {
+        wkhtml_link::convert_html_to_pdf_with_settings(
+            html,
+            &global_settings,
+            &object_settings,
+            &mut io::BufWriter::new(stdout.lock()),
+        )
+    }
+    .expect("Failed to convert HTML to PDF.");
 }
\ No newline at end of file
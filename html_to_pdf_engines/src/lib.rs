@@ -0,0 +1,280 @@
+//! Pick one of the `html_to_pdf` adapters at runtime, for example from a
+//! setting or a command line argument. Each adapter is only included if its
+//! feature is enabled.
+
+#[allow(unused_imports)] // `WithPageOptions` is only used if an engine is compiled in.
+use html_to_pdf::{
+    ConverterCapabilities, HtmlSink, HtmlToPdfConverter, HtmlToPdfError, PageOptions, PdfScope,
+    WithPageOptions, WriteBuilder, WriteBuilderSimple,
+};
+use std::{borrow::Cow, io::Write};
+
+/// An engine that can convert HTML to PDF. Only engines whose features were
+/// enabled when this crate was compiled are available, see [`Engine::ALL`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Engine {
+    /// A C# program that uses the iText .Net Framework library. Only works on
+    /// Windows.
+    #[cfg(feature = "dotnet_framework_conversion")]
+    DotNetItextFramework,
+    /// A C# program that uses the iText .Net library.
+    #[cfg(feature = "dotnet_conversion")]
+    DotNetItext,
+    /// The wkhtmltopdf library.
+    #[cfg(feature = "wk_html_to_pdf")]
+    Wkhtml,
+    /// The minimal `pdf-min` Rust library, which doesn't support many HTML
+    /// tags.
+    #[cfg(feature = "pdf_min_conversion")]
+    PdfMin,
+    /// A headless Chrome browser that "prints" the page.
+    #[cfg(feature = "chromiumoxide_conversion")]
+    Chromiumoxide,
+}
+impl Engine {
+    /// All engines that were compiled in.
+    pub const ALL: &'static [Engine] = &[
+        #[cfg(feature = "dotnet_framework_conversion")]
+        Engine::DotNetItextFramework,
+        #[cfg(feature = "dotnet_conversion")]
+        Engine::DotNetItext,
+        #[cfg(feature = "wk_html_to_pdf")]
+        Engine::Wkhtml,
+        #[cfg(feature = "pdf_min_conversion")]
+        Engine::PdfMin,
+        #[cfg(feature = "chromiumoxide_conversion")]
+        Engine::Chromiumoxide,
+    ];
+
+    /// A short name for the engine, the same as the CLI's subcommand for it.
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "dotnet_framework_conversion")]
+            Engine::DotNetItextFramework => "dot-net-itext-framework",
+            #[cfg(feature = "dotnet_conversion")]
+            Engine::DotNetItext => "dot-net-itext",
+            #[cfg(feature = "wk_html_to_pdf")]
+            Engine::Wkhtml => "wkhtml",
+            #[cfg(feature = "pdf_min_conversion")]
+            Engine::PdfMin => "pdf-min",
+            #[cfg(feature = "chromiumoxide_conversion")]
+            Engine::Chromiumoxide => "chromiumoxide",
+        }
+    }
+}
+
+/// Configure the iText .Net Framework converter with the page setup from
+/// `options`.
+#[cfg(feature = "dotnet_framework_conversion")]
+pub fn dotnet_framework_itext_converter(
+    mode: html_to_pdf_adapter_dotnet_framework_itext::DotNetFrameworkPdfConverterMode,
+    options: &PageOptions,
+) -> html_to_pdf_adapter_dotnet_framework_itext::DotNetFrameworkPdfConverter {
+    html_to_pdf_adapter_dotnet_framework_itext::DotNetFrameworkPdfConverter {
+        mode,
+        #[cfg(feature = "dotnet_framework_conversion_include_exe")]
+        extract_included_exe_at: Some(std::env::temp_dir().join("HtmlToPdf_Framework")),
+        #[cfg(not(feature = "dotnet_framework_conversion_include_exe"))]
+        extract_included_exe_at: None,
+        ..Default::default()
+    }
+    .with_page_options(*options)
+}
+
+/// Configure the iText .Net converter with the page setup from `options`,
+/// except for the margins which it doesn't support.
+#[cfg(feature = "dotnet_conversion")]
+pub fn dotnet_itext_converter(
+    options: &PageOptions,
+) -> html_to_pdf_adapter_dotnet_itext::DotNetPdfConverter {
+    html_to_pdf_adapter_dotnet_itext::DotNetPdfConverter {
+        #[cfg(feature = "dotnet_conversion_include_exe")]
+        extract_included_exe_at: Some(std::env::temp_dir().join("HtmlToPdf")),
+        #[cfg(not(feature = "dotnet_conversion_include_exe"))]
+        extract_included_exe_at: None,
+        ..Default::default()
+    }
+    .with_page_options(*options)
+}
+
+/// Configure the wkhtml converter with the page setup from `options`.
+#[cfg(feature = "wk_html_to_pdf")]
+pub fn wkhtml_converter(options: &PageOptions) -> html_to_pdf_adapter_wkhtml::WkHtmlPdfConverter {
+    html_to_pdf_adapter_wkhtml::WkHtmlPdfConverter::default().with_page_options(*options)
+}
+
+/// Configure the `pdf-min` converter with the page setup from `options`,
+/// except for the top and right margins which it doesn't support.
+#[cfg(feature = "pdf_min_conversion")]
+pub fn pdf_min_converter(options: &PageOptions) -> html_to_pdf_adapter_pdf_min::PdfMinConverter {
+    html_to_pdf_adapter_pdf_min::PdfMinConverter::default().with_page_options(*options)
+}
+
+/// Start configuring a Chrome converter with the page setup from `options`.
+#[cfg(feature = "chromiumoxide_conversion")]
+pub fn chromiumoxide_converter(
+    options: &PageOptions,
+) -> html_to_pdf_adapter_chromiumoxide::ChromiumoxideConverterBuilder {
+    html_to_pdf_adapter_chromiumoxide::ChromiumoxideConverter::builder().with_page_options(*options)
+}
+
+/// Converts HTML using an engine that is chosen at runtime. Errors from the
+/// engines are wrapped in [`HtmlToPdfError::Engine`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EngineConverter {
+    pub engine: Engine,
+    pub options: PageOptions,
+}
+impl<'scope, W> HtmlToPdfConverter<'scope, W> for EngineConverter
+where
+    W: WriteBuilder + Send + 'scope,
+{
+    type HtmlSink = Box<dyn HtmlSink<W, HtmlToPdfError> + 'scope>;
+    type Error = HtmlToPdfError;
+
+    // There is nothing to match on if no engines were compiled in:
+    #[allow(unused_variables, unreachable_code)]
+    fn start(self, scope: PdfScope<'scope, '_>, output: W) -> Result<Self::HtmlSink, Self::Error> {
+        let EngineConverter { engine, options } = self;
+        Ok(match engine {
+            #[cfg(feature = "dotnet_framework_conversion")]
+            Engine::DotNetItextFramework => Box::new(
+                dotnet_framework_itext_converter(Default::default(), &options)
+                    .start(scope, output)?
+                    .map_completion_err(HtmlToPdfError::from),
+            ),
+            #[cfg(feature = "dotnet_conversion")]
+            Engine::DotNetItext => Box::new(
+                dotnet_itext_converter(&options)
+                    .start(scope, output)?
+                    .map_completion_err(HtmlToPdfError::from),
+            ),
+            #[cfg(feature = "wk_html_to_pdf")]
+            Engine::Wkhtml => Box::new(
+                wkhtml_converter(&options)
+                    .start(scope, output)?
+                    .map_completion_err(HtmlToPdfError::from),
+            ),
+            #[cfg(feature = "pdf_min_conversion")]
+            Engine::PdfMin => Box::new(
+                pdf_min_converter(&options)
+                    .start(scope, output)?
+                    .map_completion_err(HtmlToPdfError::from),
+            ),
+            #[cfg(feature = "chromiumoxide_conversion")]
+            Engine::Chromiumoxide => Box::new(
+                chromiumoxide_converter(&options)
+                    .build()
+                    .start(scope, output)?
+                    .map_completion_err(HtmlToPdfError::from),
+            ),
+        })
+    }
+
+    /// The same name as the adapter for the engine uses.
+    #[allow(unreachable_code)]
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed(match self.engine {
+            #[cfg(feature = "dotnet_framework_conversion")]
+            Engine::DotNetItextFramework => "dotnet-itext-framework",
+            #[cfg(feature = "dotnet_conversion")]
+            Engine::DotNetItext => "dotnet-itext",
+            #[cfg(feature = "wk_html_to_pdf")]
+            Engine::Wkhtml => "wkhtml",
+            #[cfg(feature = "pdf_min_conversion")]
+            Engine::PdfMin => "pdf-min",
+            #[cfg(feature = "chromiumoxide_conversion")]
+            Engine::Chromiumoxide => "chromiumoxide",
+        })
+    }
+
+    /// The capabilities of the adapter for the engine with the same options
+    /// that [`start`](Self::start) uses.
+    #[allow(unreachable_code)]
+    fn capabilities(&self) -> ConverterCapabilities {
+        match self.engine {
+            #[cfg(feature = "dotnet_framework_conversion")]
+            Engine::DotNetItextFramework => HtmlToPdfConverter::<W>::capabilities(
+                &dotnet_framework_itext_converter(Default::default(), &self.options),
+            ),
+            #[cfg(feature = "dotnet_conversion")]
+            Engine::DotNetItext => {
+                HtmlToPdfConverter::<W>::capabilities(&dotnet_itext_converter(&self.options))
+            }
+            #[cfg(feature = "wk_html_to_pdf")]
+            Engine::Wkhtml => {
+                HtmlToPdfConverter::<W>::capabilities(&wkhtml_converter(&self.options))
+            }
+            #[cfg(feature = "pdf_min_conversion")]
+            Engine::PdfMin => {
+                HtmlToPdfConverter::<W>::capabilities(&pdf_min_converter(&self.options))
+            }
+            #[cfg(feature = "chromiumoxide_conversion")]
+            Engine::Chromiumoxide => HtmlToPdfConverter::<W>::capabilities(
+                &chromiumoxide_converter(&self.options).build(),
+            ),
+        }
+    }
+}
+
+/// Convert `html` with `engine` and return the whole PDF.
+pub fn render_to_vec(
+    engine: Engine,
+    html: &str,
+    options: PageOptions,
+) -> Result<Vec<u8>, HtmlToPdfError> {
+    let mut html_sink = EngineConverter { engine, options }
+        .start(PdfScope::owned(), WriteBuilderSimple(Vec::new()))?;
+    html_sink.write_all(html.as_bytes())?;
+    Ok(html_sink.complete()?.0)
+}
+
+#[cfg(all(test, feature = "pdf_min_conversion"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pdf_min_engine_returns_pdf() {
+        let pdf = render_to_vec(
+            Engine::PdfMin,
+            "<h1>Title</h1><p>Hello</p>",
+            PageOptions::default(),
+        )
+        .unwrap();
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(pdf.trim_ascii_end().ends_with(b"%%EOF"));
+        assert_eq!(Engine::PdfMin.name(), "pdf-min");
+        assert!(Engine::ALL.contains(&Engine::PdfMin));
+    }
+
+    #[test]
+    fn pdf_min_engine_can_be_benchmarked() {
+        let converter = EngineConverter {
+            engine: Engine::PdfMin,
+            options: PageOptions::default(),
+        };
+        let report = html_to_pdf::run_benchmark(b"<h1>Title</h1><p>Hello</p>", &[converter], 3);
+
+        let [result] = &report.converters[..] else {
+            panic!("expected one engine: {report:?}");
+        };
+        assert_eq!(result.name, "pdf-min");
+        assert_eq!(result.error, None);
+        assert_eq!(result.durations.len(), 3);
+        assert!(result.mean_duration().is_some());
+        assert!(result.mean_size().unwrap() > 0);
+    }
+
+    #[test]
+    fn engine_converter_uses_adapter_name() {
+        let converter = EngineConverter {
+            engine: Engine::PdfMin,
+            options: PageOptions::default(),
+        };
+        assert_eq!(
+            HtmlToPdfConverter::<WriteBuilderSimple<Vec<u8>>>::name(&converter),
+            "pdf-min"
+        );
+    }
+}